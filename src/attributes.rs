@@ -0,0 +1,241 @@
+use crate::config::Config;
+use crate::error::GitError;
+use crate::ignore::wildmatch;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+/// The state an attribute is given for a path.
+#[derive(Clone, Debug, PartialEq)]
+pub enum State {
+    Set,
+    Unset,
+    Value(String),
+}
+/// Whether and how line endings are normalised on the way in.
+#[derive(Clone, Copy, PartialEq)]
+enum Text {
+    On,
+    Off,
+    Auto,
+}
+/// Whether `pattern` from an attributes file in `base` applies to `path`.
+fn matches(pattern: &str, base: &str, path: &str) -> bool {
+    let relative = match base.is_empty() {
+        true => path,
+        false => match path
+            .strip_prefix(base)
+            .and_then(|rest| rest.strip_prefix('/'))
+        {
+            Some(rest) => rest,
+            None => return false,
+        },
+    };
+    if pattern.contains('/') {
+        wildmatch(
+            pattern.trim_start_matches('/').as_bytes(),
+            relative.as_bytes(),
+        )
+    } else {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        wildmatch(pattern.as_bytes(), name.as_bytes())
+    }
+}
+fn apply(word: &str, found: &mut HashMap<String, State>) {
+    if word == "binary" {
+        for name in ["diff", "merge", "text"] {
+            found.insert(name.to_string(), State::Unset);
+        }
+    } else if let Some(name) = word.strip_prefix('-') {
+        found.insert(name.to_string(), State::Unset);
+    } else if let Some(name) = word.strip_prefix('!') {
+        found.remove(name);
+    } else if let Some((name, value)) = word.split_once('=') {
+        found.insert(name.to_string(), State::Value(value.to_string()));
+    } else {
+        found.insert(word.to_string(), State::Set);
+    }
+}
+/// The attributes given to `path` by `core.attributesFile`, the
+/// `.gitattributes` files from the top down and `.git/info/attributes`,
+/// later files overriding earlier ones.
+pub fn attributes(path: &str) -> Result<HashMap<String, State>, GitError> {
+    let global = Config::load()?
+        .get_path("core.attributesFile")
+        .or_else(|| Config::xdg_config_dir().map(|dir| dir.join("attributes")));
+    let mut files: Vec<(String, PathBuf)> = global
+        .into_iter()
+        .map(|file| (String::new(), file))
+        .collect();
+    files.push((String::new(), PathBuf::from(".gitattributes")));
+    let mut dir = String::new();
+    if let Some((parents, _)) = path.rsplit_once('/') {
+        for component in parents.split('/') {
+            if !dir.is_empty() {
+                dir.push('/');
+            }
+            dir.push_str(component);
+            files.push((dir.clone(), PathBuf::from(&dir).join(".gitattributes")));
+        }
+    }
+    files.push((String::new(), PathBuf::from(".git/info/attributes")));
+    let mut found = HashMap::new();
+    for (base, file) in files {
+        let Ok(text) = fs::read_to_string(file) else {
+            continue;
+        };
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some(pattern) if !pattern.starts_with('#') && matches(pattern, &base, path) => {
+                    words.for_each(|word| apply(word, &mut found))
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(found)
+}
+/// Whether `data` looks binary: it has a NUL, a lone carriage return or
+/// too many other control characters.
+fn is_binary(data: &[u8]) -> bool {
+    let data = data.strip_suffix(b"\x1a").unwrap_or(data);
+    let (mut printable, mut nonprintable) = (0, 0);
+    for (i, &byte) in data.iter().enumerate() {
+        match byte {
+            0 => return true,
+            b'\r' if data.get(i + 1) != Some(&b'\n') => return true,
+            b'\r' | b'\n' => {}
+            b'\x08' | b'\t' | b'\x1b' | b'\x0c' => printable += 1,
+            127 => nonprintable += 1,
+            byte if byte < 32 => nonprintable += 1,
+            _ => printable += 1,
+        }
+    }
+    (printable >> 7) < nonprintable
+}
+/// Pipes `data` through the `filter.<driver>.clean` command, with `%f`
+/// standing for the path being cleaned.
+fn run_filter(command: &str, path: &str, data: &[u8]) -> Result<Vec<u8>, GitError> {
+    let quoted = format!("'{}'", path.replace('\'', "'\\''"));
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command.replace("%f", &quoted))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = data.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    let fed = writer.join().map(|result| result.is_ok()).unwrap_or(false);
+    match output.status.success() && fed {
+        true => Ok(output.stdout),
+        false => Err(GitError::any(format!("external filter '{command}' failed"))),
+    }
+}
+/// Collapses every `$Id: ...$` keyword back to `$Id$`.
+fn collapse_ident(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while let Some(start) = rest.windows(4).position(|w| w == b"$Id:") {
+        let after = &rest[start + 4..];
+        match after.iter().position(|&b| b == b'$' || b == b'\n') {
+            Some(end) if after[end] == b'$' => {
+                out.extend_from_slice(&rest[..start]);
+                out.extend_from_slice(b"$Id$");
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.extend_from_slice(&rest[..start + 4]);
+                rest = after;
+            }
+        }
+    }
+    out.extend_from_slice(rest);
+    out
+}
+/// Converts working tree `data` for `path` into what gets stored, as its
+/// attributes ask: the `filter` driver's clean command, then line ending
+/// normalisation from `text`, `eol` or `core.autocrlf`, then `ident`.
+pub fn clean(path: &str, mut data: Vec<u8>) -> Result<Vec<u8>, GitError> {
+    let attributes = attributes(path)?;
+    let config = Config::load()?;
+    if let Some(State::Value(driver)) = attributes.get("filter") {
+        if let Some(command) = config.get(&format!("filter.{driver}.clean")) {
+            match run_filter(command, path, &data) {
+                Ok(filtered) => data = filtered,
+                Err(_) if config.get(&format!("filter.{driver}.required")) == Some("true") => {
+                    return Err(GitError::any(format!(
+                        "{path}: clean filter '{driver}' failed"
+                    )))
+                }
+                Err(err) => eprintln!("error: {err}"),
+            }
+        }
+    }
+    let text = match attributes.get("text") {
+        Some(State::Set) => Text::On,
+        Some(State::Unset) => Text::Off,
+        Some(State::Value(value)) if value == "auto" => Text::Auto,
+        _ if matches!(attributes.get("eol"), Some(State::Value(_))) => Text::On,
+        _ => match config.get("core.autocrlf") {
+            Some("true" | "input") => Text::Auto,
+            _ => Text::Off,
+        },
+    };
+    let crlf = data.windows(2).any(|w| w == b"\r\n");
+    if text == Text::On || (text == Text::Auto && crlf && !is_binary(&data)) {
+        let mut converted = Vec::with_capacity(data.len());
+        for (i, &byte) in data.iter().enumerate() {
+            if byte != b'\r' || data.get(i + 1) != Some(&b'\n') {
+                converted.push(byte);
+            }
+        }
+        data = converted;
+    }
+    if attributes.get("ident") == Some(&State::Set) {
+        data = collapse_ident(&data);
+    }
+    Ok(data)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn patterns_match_names_anywhere_and_paths_below_their_file() {
+        assert!(matches("*.txt", "", "a/b.txt"));
+        assert!(matches("b.txt", "a", "a/c/b.txt"));
+        assert!(!matches("*.txt", "a", "b/c.txt"));
+        assert!(matches("/c/*.txt", "a", "a/c/b.txt"));
+        assert!(!matches("c/*.txt", "", "a/c/b.txt"));
+    }
+    #[test]
+    fn words_set_unset_unspecify_and_give_values() {
+        let mut found = HashMap::new();
+        for word in ["text", "eol=crlf", "-diff", "ident", "!ident", "binary"] {
+            apply(word, &mut found);
+        }
+        assert_eq!(found.get("eol"), Some(&State::Value("crlf".into())));
+        assert_eq!(found.get("text"), Some(&State::Unset));
+        assert_eq!(found.get("merge"), Some(&State::Unset));
+        assert_eq!(found.get("diff"), Some(&State::Unset));
+        assert_eq!(found.get("ident"), None);
+    }
+    #[test]
+    fn binary_data_has_nuls_lone_carriage_returns_or_many_controls() {
+        assert!(!is_binary(b"line\r\nline\r\n\x1a"));
+        assert!(is_binary(b"a\0b\r\n"));
+        assert!(is_binary(b"a\rb\r\n"));
+        assert!(is_binary(b"\x01\x02\r\n"));
+        assert!(!is_binary(&[b"\x01".as_slice(), &[b'a'; 128]].concat()));
+    }
+    #[test]
+    fn ident_keywords_collapse_only_when_closed_on_the_line() {
+        assert_eq!(
+            collapse_ident(b"$Id: abc $ and $Id$ and $Id: open\n$"),
+            b"$Id$ and $Id$ and $Id: open\n$"
+        );
+    }
+}
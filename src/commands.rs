@@ -1,14 +1,68 @@
+mod add;
+mod branch;
 mod cat_file;
+mod checkout;
+mod cherry_pick;
+mod clean;
 mod clone;
+mod commit;
 mod commit_tree;
+mod diff;
+mod for_each_ref;
 mod hash_object;
 mod init;
+mod log;
 mod ls_tree;
+mod merge;
+mod merge_base;
+mod mktag;
+mod rebase;
+mod reflog;
+mod reset;
+mod restore;
+mod rev_list;
+mod rev_parse;
+mod revert;
+mod show_ref;
+mod stash;
+mod status;
+mod submodule;
+mod switch;
+mod symbolic_ref;
+mod tag;
+mod update_ref;
 mod write_tree;
+pub use add::*;
+pub use branch::*;
 pub use cat_file::*;
+pub use checkout::*;
+pub use cherry_pick::*;
+pub use clean::*;
 pub use clone::*;
+pub use commit::*;
 pub use commit_tree::*;
+pub use diff::*;
+pub use for_each_ref::*;
 pub use hash_object::*;
 pub use init::*;
+pub use log::*;
 pub use ls_tree::*;
-pub use write_tree::*;
\ No newline at end of file
+pub use merge::*;
+pub use merge_base::*;
+pub use mktag::*;
+pub use rebase::*;
+pub use reflog::*;
+pub use reset::*;
+pub use restore::*;
+pub use rev_list::*;
+pub use rev_parse::*;
+pub use revert::*;
+pub use show_ref::*;
+pub use stash::*;
+pub use status::*;
+pub use submodule::*;
+pub use switch::*;
+pub use symbolic_ref::*;
+pub use tag::*;
+pub use update_ref::*;
+pub use write_tree::*;
@@ -0,0 +1,91 @@
+use crate::error::GitError;
+use crate::ignore::Ignore;
+use crate::index::{Index, IndexEntry};
+use crate::objects::GitObject;
+use crate::submodule;
+use std::fs;
+use std::path::Path;
+pub fn add(args: Vec<String>) -> Result<(), GitError> {
+    let force = args.iter().any(|a| a == "-f" || a == "--force");
+    let all = args.iter().any(|a| a == "-A" || a == "--all");
+    let mut pathspecs: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+    let dot = String::from(".");
+    if pathspecs.is_empty() {
+        if !all {
+            return Err(GitError::any("Nothing specified, nothing added."));
+        }
+        pathspecs.push(&dot);
+    }
+    let mut index = Index::load()?;
+    let mut ignore = Ignore::load()?;
+    let mut ignored_paths = Vec::new();
+    for pathspec in pathspecs {
+        let pathspec = pathspec.trim_start_matches("./").trim_end_matches('/');
+        let pathspec = if pathspec == "." { "" } else { pathspec };
+        let path = Path::new(if pathspec.is_empty() { "." } else { pathspec });
+        let is_dir = path.is_dir() && !path.is_symlink();
+        if fs::symlink_metadata(path).is_ok() {
+            // The superproject itself is never a gitlink, only repositories
+            // nested inside it.
+            let files = if is_dir && !pathspec.is_empty() && submodule::is_repo(path) {
+                vec![pathspec.to_string()]
+            } else if is_dir {
+                ignore.walk(pathspec)?
+            } else if force || !ignore.is_ignored(pathspec, false)? {
+                vec![pathspec.to_string()]
+            } else {
+                ignored_paths.push(pathspec.to_string());
+                continue;
+            };
+            for file in files {
+                add_file(&mut index, file)?;
+            }
+        }
+        let vanished: Vec<String> = index
+            .entries()
+            .iter()
+            .map(|e| e.path())
+            .filter(|p| is_under(p, pathspec) && fs::symlink_metadata(p).is_err())
+            .cloned()
+            .collect();
+        if vanished.is_empty() && fs::symlink_metadata(path).is_err() {
+            return Err(GitError::any(format!(
+                "pathspec '{pathspec}' did not match any files"
+            )));
+        }
+        for path in vanished {
+            index.remove(&path);
+        }
+    }
+    index.write()?;
+    if !ignored_paths.is_empty() {
+        return Err(GitError::any(format!(
+            "The following paths are ignored by one of your .gitignore files:\n{}\nUse -f if you really want to add them.",
+            ignored_paths.join("\n")
+        )));
+    }
+    Ok(())
+}
+fn add_file(index: &mut Index, path: String) -> Result<(), GitError> {
+    if let Some(entry) = index.get(&path) {
+        if entry.matches_stat(&fs::symlink_metadata(&path)?) {
+            return Ok(());
+        }
+    }
+    if let Some(head) = submodule::head(&path)? {
+        let mut entry = IndexEntry::new(path, "160000", hex::decode(head)?, 0)?;
+        entry.refresh_stat()?;
+        index.add(entry);
+        return Ok(());
+    }
+    let git_object = GitObject::from_path(&path, true)?;
+    index.add(IndexEntry::from_file(path, git_object.hash().clone())?);
+    Ok(())
+}
+pub fn is_under(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
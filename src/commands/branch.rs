@@ -0,0 +1,525 @@
+use crate::commit::Commit;
+use crate::config::Config;
+use crate::error::GitError;
+use crate::ignore::wildmatch;
+use crate::objects::short_hash;
+use crate::reflog;
+use crate::refs::{self, Target};
+use crate::revision::{dwim_ref, resolve, resolve_commit, upstream};
+use crate::revwalk::ancestors;
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    List,
+    Delete,
+    Move,
+    Copy,
+    SetUpstream,
+    UnsetUpstream,
+    ShowCurrent,
+}
+#[derive(Default)]
+struct Options {
+    verbose: usize,
+    all: bool,
+    remotes: bool,
+    force: bool,
+    track: Option<bool>,
+    upstream: Option<String>,
+    merged: Option<(bool, String)>,
+    contains: Option<(bool, String)>,
+}
+struct Item {
+    full: String,
+    label: String,
+    hash: String,
+    current: bool,
+    symref: Option<String>,
+}
+/// Splits clusters of short flags such as `-vv` or `-av` into single flags.
+fn expand_flags(args: Vec<String>) -> Vec<String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let cluster = arg.strip_prefix('-').filter(|flags| {
+            flags.len() > 1
+                && !flags.starts_with('-')
+                && flags.chars().all(|c| "vardDmMcCfqlt".contains(c))
+        });
+        match cluster {
+            Some(flags) => expanded.extend(flags.chars().map(|c| format!("-{c}"))),
+            None => expanded.push(arg),
+        }
+    }
+    expanded
+}
+fn local(name: &str) -> String {
+    format!("refs/heads/{name}")
+}
+fn is_current(full: &str) -> Result<bool, GitError> {
+    Ok(matches!(refs::read("HEAD")?, Some(Target::Symbolic(head)) if head == full))
+}
+fn checked_out(name: &str) -> Result<GitError, GitError> {
+    Ok(GitError::any(format!(
+        "cannot force update the branch '{name}' checked out at '{}'",
+        std::env::current_dir()?.display()
+    )))
+}
+fn is_ancestor(commit: &str, of: &str) -> Result<bool, GitError> {
+    Ok(ancestors(of)?.contains(commit))
+}
+/// Describes a detached HEAD like git does, from the last checkout recorded
+/// in the HEAD reflog.
+fn detached_label(head: &str) -> Result<String, GitError> {
+    let last_checkout = reflog::read("HEAD")?.iter().rev().find_map(|entry| {
+        let (_, to) = entry
+            .message()
+            .strip_prefix("checkout: moving from ")?
+            .split_once(" to ")?;
+        Some(to.to_string())
+    });
+    let Some(to) = last_checkout else {
+        return Ok(format!("(HEAD detached at {})", short_hash(head)));
+    };
+    let at = resolve(&to).is_ok_and(|hash| hash == head);
+    let to = match to.len() == 40 && to.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => short_hash(&to).to_string(),
+        false => to,
+    };
+    Ok(match at {
+        true => format!("(HEAD detached at {to})"),
+        false => format!("(HEAD detached from {to})"),
+    })
+}
+/// The `[upstream: ahead 1, behind 2]` part of `-v` output; the upstream
+/// name is only shown with `-vv`.
+fn tracking(branch: &str, hash: &str, verbose: usize) -> Result<String, GitError> {
+    let Ok(upstream) = upstream(Some(branch)) else {
+        return Ok(String::new());
+    };
+    let name = refs::shorten(&upstream);
+    let Some(upstream_hash) = refs::resolve(&upstream)? else {
+        return Ok(match verbose {
+            1 => "[gone] ".into(),
+            _ => format!("[{name}: gone] "),
+        });
+    };
+    let (ours, theirs) = (ancestors(hash)?, ancestors(&upstream_hash)?);
+    let ahead = ours.difference(&theirs).count();
+    let behind = theirs.difference(&ours).count();
+    let mut counts = Vec::new();
+    if ahead > 0 {
+        counts.push(format!("ahead {ahead}"));
+    }
+    if behind > 0 {
+        counts.push(format!("behind {behind}"));
+    }
+    Ok(match (verbose, counts.is_empty()) {
+        (1, true) => String::new(),
+        (1, false) => format!("[{}] ", counts.join(", ")),
+        (_, true) => format!("[{name}] "),
+        (_, false) => format!("[{name}: {}] ", counts.join(", ")),
+    })
+}
+fn list(options: &Options, patterns: &[String]) -> Result<(), GitError> {
+    let mut prefixes = Vec::new();
+    if !options.remotes || options.all {
+        prefixes.push("refs/heads/");
+    }
+    if options.remotes || options.all {
+        prefixes.push("refs/remotes/");
+    }
+    let mut items = Vec::new();
+    let head = refs::resolve("HEAD")?;
+    if let (None, Some(head), true) = (refs::head_branch()?, &head, patterns.is_empty()) {
+        items.push(Item {
+            full: "HEAD".into(),
+            label: detached_label(head)?,
+            hash: head.clone(),
+            current: true,
+            symref: None,
+        });
+    }
+    for prefix in prefixes {
+        for (full, hash) in refs::list(prefix)? {
+            let short = refs::shorten(&full);
+            if !patterns.is_empty()
+                && !patterns
+                    .iter()
+                    .any(|p| wildmatch(p.as_bytes(), short.as_bytes()))
+            {
+                continue;
+            }
+            let label = match options.all && prefix == "refs/remotes/" {
+                true => format!("remotes/{short}"),
+                false => short.to_string(),
+            };
+            let symref = match refs::read(&full)? {
+                Some(Target::Symbolic(target)) => Some(refs::shorten(&target).to_string()),
+                _ => None,
+            };
+            items.push(Item {
+                current: is_current(&full)?,
+                full,
+                label,
+                hash,
+                symref,
+            });
+        }
+    }
+    let merged = match &options.merged {
+        Some((want, commit)) => Some((*want, ancestors(&resolve_commit(commit)?)?)),
+        None => None,
+    };
+    let contains = match &options.contains {
+        Some((want, commit)) => Some((*want, resolve_commit(commit)?)),
+        None => None,
+    };
+    let mut shown = Vec::new();
+    for item in items {
+        if let Some((want, reachable)) = &merged {
+            if reachable.contains(&item.hash) != *want {
+                continue;
+            }
+        }
+        if let Some((want, commit)) = &contains {
+            if is_ancestor(commit, &item.hash)? != *want {
+                continue;
+            }
+        }
+        shown.push(item);
+    }
+    let width = shown
+        .iter()
+        .map(|item| item.label.chars().count())
+        .max()
+        .unwrap_or(0);
+    for item in shown {
+        let marker = if item.current { '*' } else { ' ' };
+        match (&item.symref, options.verbose) {
+            (Some(target), 0) => println!("{marker} {} -> {target}", item.label),
+            (Some(target), _) => println!("{marker} {:<width$} -> {target}", item.label),
+            (None, 0) => println!("{marker} {}", item.label),
+            (None, verbose) => {
+                let track = match item.full.strip_prefix("refs/heads/") {
+                    Some(branch) => tracking(branch, &item.hash, verbose)?,
+                    None => String::new(),
+                };
+                let subject = Commit::from_hex_string(&item.hash)?.subject();
+                println!(
+                    "{marker} {:<width$} {} {track}{subject}",
+                    item.label,
+                    short_hash(&item.hash)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+/// Records `upstream` (a full ref name) as the branch `branch` tracks.
+fn set_tracking(branch: &str, upstream: &str) -> Result<(), GitError> {
+    let (remote, merge) = match upstream
+        .strip_prefix("refs/remotes/")
+        .and_then(|rest| rest.split_once('/'))
+    {
+        Some((remote, name)) => (remote.to_string(), local(name)),
+        None => (".".to_string(), upstream.to_string()),
+    };
+    Config::set(&format!("branch.{branch}.remote"), Some(&remote))?;
+    Config::set(&format!("branch.{branch}.merge"), Some(&merge))?;
+    println!(
+        "branch '{branch}' set up to track '{}'.",
+        refs::shorten(upstream)
+    );
+    Ok(())
+}
+fn create(options: &Options, name: &str, start: Option<&str>) -> Result<(), GitError> {
+    let full = local(name);
+    if options.force && refs::resolve(&full)?.is_some() && is_current(&full)? {
+        return Err(checked_out(name)?);
+    }
+    create_branch(name, start.unwrap_or("HEAD"), options.force, options.track)
+}
+/// Creates branch `name` at `start`, or resets it there with `force`, and
+/// sets up tracking as `track` (or `branch.autoSetupMerge`) asks.
+pub(crate) fn create_branch(
+    name: &str,
+    start: &str,
+    force: bool,
+    track: Option<bool>,
+) -> Result<(), GitError> {
+    let full = local(name);
+    refs::check_name(&full)
+        .map_err(|_| GitError::any(format!("'{name}' is not a valid branch name")))?;
+    let exists = refs::resolve(&full)?.is_some();
+    if exists && !force {
+        return Err(GitError::any(format!(
+            "a branch named '{name}' already exists"
+        )));
+    }
+    let hash = resolve_commit(start)
+        .map_err(|_| GitError::any(format!("not a valid object name: '{start}'")))?;
+    let message = match exists {
+        true => format!("branch: Reset to {start}"),
+        false => format!("branch: Created from {start}"),
+    };
+    let old = if exists { None } else { Some(refs::ZERO_HASH) };
+    refs::update(&full, &hash, old, false, &message)?;
+    let start_ref = dwim_ref(start)?.map(|(full, _)| full);
+    let auto_track = Config::load()?.get("branch.autoSetupMerge") != Some("false");
+    match (start_ref, track) {
+        (Some(start_ref), Some(true)) if start_ref.starts_with("refs/") => {
+            set_tracking(name, &start_ref)
+        }
+        (Some(start_ref), None) if auto_track && start_ref.starts_with("refs/remotes/") => {
+            set_tracking(name, &start_ref)
+        }
+        _ => Ok(()),
+    }
+}
+fn delete(options: &Options, names: &[String]) -> Result<(), GitError> {
+    if names.is_empty() {
+        return Err(GitError::any("branch name required"));
+    }
+    let mut failed = false;
+    for name in names {
+        let full = match options.remotes {
+            true => format!("refs/remotes/{name}"),
+            false => local(name),
+        };
+        let Some(hash) = refs::resolve(&full)? else {
+            let kind = if options.remotes {
+                "remote-tracking branch"
+            } else {
+                "branch"
+            };
+            eprintln!("error: {kind} '{name}' not found.");
+            failed = true;
+            continue;
+        };
+        if !options.remotes && is_current(&full)? {
+            let cwd = std::env::current_dir()?;
+            eprintln!(
+                "error: Cannot delete branch '{name}' checked out at '{}'",
+                cwd.display()
+            );
+            failed = true;
+            continue;
+        }
+        if !options.remotes && !options.force && !merged(name, &hash)? {
+            eprintln!("error: The branch '{name}' is not fully merged.");
+            eprintln!("If you are sure you want to delete it, run 'git branch -D {name}'.");
+            failed = true;
+            continue;
+        }
+        refs::delete(&full, Some(&hash), false)?;
+        if options.remotes {
+            println!(
+                "Deleted remote-tracking branch {name} (was {}).",
+                short_hash(&hash)
+            );
+        } else {
+            Config::rename_section(&format!("branch.{name}"), None)?;
+            println!("Deleted branch {name} (was {}).", short_hash(&hash));
+        }
+    }
+    if failed {
+        return Err(GitError::exit(1));
+    }
+    Ok(())
+}
+/// Whether a branch is safe to delete: merged into its upstream when it has
+/// one, else into HEAD. Disagreements between the two are warned about.
+fn merged(name: &str, hash: &str) -> Result<bool, GitError> {
+    let head = refs::resolve("HEAD")?;
+    let in_head = match &head {
+        Some(head) => is_ancestor(hash, head)?,
+        None => false,
+    };
+    let upstream = upstream(Some(name))
+        .ok()
+        .and_then(|up| Some((refs::resolve(&up).ok()??, up)));
+    let Some((upstream_hash, upstream)) = upstream else {
+        return Ok(in_head);
+    };
+    let in_upstream = is_ancestor(hash, &upstream_hash)?;
+    if in_head && !in_upstream {
+        eprintln!("warning: not deleting branch '{name}' that is not yet merged to");
+        eprintln!("         '{upstream}', even though it is merged to HEAD.");
+    } else if in_upstream && !in_head {
+        eprintln!("warning: deleting branch '{name}' that has been merged to");
+        eprintln!("         '{upstream}', but not yet merged to HEAD.");
+    }
+    Ok(in_upstream)
+}
+fn rename(options: &Options, names: &[String], copy: bool) -> Result<(), GitError> {
+    let (old, new) = match names {
+        [new] => (
+            refs::head_branch()?.ok_or(GitError::any(
+                "cannot rename the current branch while not on any",
+            ))?,
+            new.clone(),
+        ),
+        [old, new] => (old.clone(), new.clone()),
+        _ => return Err(GitError::any("too many arguments for a rename operation")),
+    };
+    let (old_full, new_full) = (local(&old), local(&new));
+    if refs::resolve(&old_full)?.is_none() {
+        return Err(GitError::any(format!("No branch named '{old}'.")));
+    }
+    refs::check_name(&new_full)
+        .map_err(|_| GitError::any(format!("'{new}' is not a valid branch name")))?;
+    if old != new && !options.force && refs::resolve(&new_full)?.is_some() {
+        return Err(GitError::any(format!(
+            "a branch named '{new}' already exists"
+        )));
+    }
+    if old != new && refs::resolve(&new_full)?.is_some() && is_current(&new_full)? {
+        return Err(checked_out(&new)?);
+    }
+    if old != new {
+        let skip = Some(old_full.as_str()).filter(|_| !copy);
+        refs::check_available(&new_full, skip)?;
+    }
+    let action = if copy { "copied" } else { "renamed" };
+    let message = format!("Branch: {action} {old_full} to {new_full}");
+    refs::rename(&old_full, &new_full, &message, copy)?;
+    let (old_section, new_section) = (format!("branch.{old}"), format!("branch.{new}"));
+    if old != new {
+        Config::rename_section(&new_section, None)?;
+    }
+    match copy {
+        true => Config::copy_section(&old_section, &new_section),
+        false => Config::rename_section(&old_section, Some(&new_section)),
+    }
+}
+fn set_upstream(options: &Options, names: &[String]) -> Result<(), GitError> {
+    let upstream_name = options.upstream.as_deref().unwrap_or_default();
+    let branch = match names {
+        [] => refs::head_branch()?.ok_or_else(|| {
+            GitError::any(format!(
+                "could not set upstream of HEAD to {upstream_name} when it does not point to any branch."
+            ))
+        })?,
+        [branch] => branch.clone(),
+        _ => return Err(GitError::any("too many arguments to set new upstream")),
+    };
+    if refs::resolve(&local(&branch))?.is_none() {
+        return Err(GitError::any(format!("branch '{branch}' does not exist")));
+    }
+    let upstream = dwim_ref(upstream_name)?
+        .map(|(full, _)| full)
+        .filter(|full| full.starts_with("refs/heads/") || full.starts_with("refs/remotes/"))
+        .ok_or_else(|| {
+            GitError::any(format!(
+                "the requested upstream branch '{upstream_name}' does not exist"
+            ))
+        })?;
+    set_tracking(&branch, &upstream)
+}
+fn unset_upstream(names: &[String]) -> Result<(), GitError> {
+    let branch = match names {
+        [] => refs::head_branch()?.ok_or(GitError::any("HEAD does not point to a branch"))?,
+        [branch] => branch.clone(),
+        _ => return Err(GitError::any("too many arguments to unset upstream")),
+    };
+    let merge = format!("branch.{branch}.merge");
+    if Config::load()?.get(&merge).is_none() {
+        return Err(GitError::any(format!(
+            "Branch '{branch}' has no upstream information"
+        )));
+    }
+    Config::set(&format!("branch.{branch}.remote"), None)?;
+    Config::set(&merge, None)
+}
+pub fn branch(args: Vec<String>) -> Result<(), GitError> {
+    let mut mode = Mode::List;
+    let mut options = Options::default();
+    let mut list_requested = false;
+    let mut names = Vec::new();
+    let args = expand_flags(args);
+    let mut args_iter = args.iter().peekable();
+    while let Some(arg) = args_iter.next() {
+        let mut commit_arg = || match args_iter.next_if(|next| !next.starts_with('-')) {
+            Some(commit) => commit.clone(),
+            None => "HEAD".to_string(),
+        };
+        match arg.as_str() {
+            "-v" | "--verbose" => options.verbose += 1,
+            "-a" | "--all" => options.all = true,
+            "-r" | "--remotes" => options.remotes = true,
+            "-l" | "--list" => list_requested = true,
+            "-f" | "--force" => options.force = true,
+            "-q" | "--quiet" => {}
+            "-t" | "--track" => options.track = Some(true),
+            "--no-track" => options.track = Some(false),
+            "-d" | "--delete" => mode = Mode::Delete,
+            "-D" => {
+                mode = Mode::Delete;
+                options.force = true;
+            }
+            "-m" | "--move" => mode = Mode::Move,
+            "-M" => {
+                mode = Mode::Move;
+                options.force = true;
+            }
+            "-c" | "--copy" => mode = Mode::Copy,
+            "-C" => {
+                mode = Mode::Copy;
+                options.force = true;
+            }
+            "-u" => {
+                mode = Mode::SetUpstream;
+                options.upstream = Some(
+                    args_iter
+                        .next()
+                        .cloned()
+                        .ok_or(GitError::any("option -u requires a value"))?,
+                );
+            }
+            "--unset-upstream" => mode = Mode::UnsetUpstream,
+            "--show-current" => mode = Mode::ShowCurrent,
+            "--merged" => options.merged = Some((true, commit_arg())),
+            "--no-merged" => options.merged = Some((false, commit_arg())),
+            "--contains" => options.contains = Some((true, commit_arg())),
+            "--no-contains" => options.contains = Some((false, commit_arg())),
+            arg => {
+                let value = |option: &str| arg.strip_prefix(option).map(str::to_string);
+                if let Some(upstream) = value("--set-upstream-to=") {
+                    mode = Mode::SetUpstream;
+                    options.upstream = Some(upstream);
+                } else if let Some(commit) = value("--merged=") {
+                    options.merged = Some((true, commit));
+                } else if let Some(commit) = value("--no-merged=") {
+                    options.merged = Some((false, commit));
+                } else if let Some(commit) = value("--contains=") {
+                    options.contains = Some((true, commit));
+                } else if let Some(commit) = value("--no-contains=") {
+                    options.contains = Some((false, commit));
+                } else if arg.starts_with('-') && arg.len() > 1 {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else {
+                    names.push(arg.to_string());
+                }
+            }
+        }
+    }
+    let filtering = options.merged.is_some() || options.contains.is_some();
+    match mode {
+        Mode::List if names.is_empty() || list_requested || filtering => list(&options, &names),
+        Mode::List => match names.as_slice() {
+            [name] => create(&options, name, None),
+            [name, start] => create(&options, name, Some(start)),
+            _ => Err(GitError::any("too many arguments")),
+        },
+        Mode::Delete => delete(&options, &names),
+        Mode::Move => rename(&options, &names, false),
+        Mode::Copy => rename(&options, &names, true),
+        Mode::SetUpstream => set_upstream(&options, &names),
+        Mode::UnsetUpstream => unset_upstream(&names),
+        Mode::ShowCurrent => {
+            if let Some(branch) = refs::head_branch()? {
+                println!("{branch}");
+            }
+            Ok(())
+        }
+    }
+}
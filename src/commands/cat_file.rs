@@ -1,20 +1,241 @@
 use crate::error::GitError;
 use crate::objects::{GitObject, Kind};
+use crate::refs::ZERO_HASH;
+use crate::revision::{peel, resolve};
+use std::fs;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+const OBJECTS: &str = ".git/objects";
+const BATCH_CHECK_FORMAT: &str = "%(objectname) %(objecttype) %(objectsize)";
+const BATCH_ATOMS: [&str; 6] = [
+    "objectname",
+    "objecttype",
+    "objectsize",
+    "objectsize:disk",
+    "deltabase",
+    "rest",
+];
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Type,
+    Size,
+    Exists,
+    Pretty,
+}
+/// How objects named on stdin, or every object, are reported.
+struct Batch {
+    format: String,
+    contents: bool,
+    all_objects: bool,
+    buffer: bool,
+}
+fn not_valid(rev: &str) -> GitError {
+    GitError::any(format!("Not a valid object name {rev}"))
+}
+fn loose_path(hash: &str) -> std::path::PathBuf {
+    Path::new(OBJECTS).join(&hash[..2]).join(&hash[2..])
+}
+/// Writes `object` the way `-p` shows it: trees as `ls-tree` lists them,
+/// everything else as it is stored.
+fn pretty_print(object: &GitObject, out: &mut impl Write) -> Result<(), GitError> {
+    match object.kind() {
+        Kind::Tree => {
+            for entry in object.tree_entries()? {
+                writeln!(
+                    out,
+                    "{:0>6} {} {}\t{}",
+                    entry.mode(),
+                    entry.kind(),
+                    entry.hex_string(),
+                    entry.filename()
+                )?;
+            }
+        }
+        _ => out.write_all(object.contents())?,
+    }
+    Ok(())
+}
+/// Every loose object in the repository, in hash order.
+fn all_objects() -> Result<Vec<String>, GitError> {
+    let mut hashes = Vec::new();
+    for dir in fs::read_dir(OBJECTS)? {
+        let dir = dir?;
+        let prefix = dir.file_name().to_string_lossy().to_string();
+        if prefix.len() != 2 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        for file in fs::read_dir(dir.path())? {
+            let name = file?.file_name().to_string_lossy().to_string();
+            if name.len() == 38 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+                hashes.push(format!("{prefix}{name}"));
+            }
+        }
+    }
+    hashes.sort();
+    Ok(hashes)
+}
+impl Batch {
+    /// Checks that the format only uses atoms we know, before any input is
+    /// read.
+    fn check_format(&self) -> Result<(), GitError> {
+        let mut rest = self.format.as_str();
+        while let Some(start) = rest.find("%(") {
+            let end = rest[start..]
+                .find(')')
+                .ok_or_else(|| GitError::any(format!("malformed format string {}", self.format)))?;
+            let atom = &rest[start + 2..start + end];
+            if !BATCH_ATOMS.contains(&atom) {
+                return Err(GitError::any(format!("unknown format element: {atom}")));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        Ok(())
+    }
+    fn header(&self, object: &GitObject, rest: &str) -> Result<String, GitError> {
+        let hash = object.hex_string();
+        let mut header = self.format.clone();
+        for (atom, value) in [
+            ("objectname", hash.clone()),
+            ("objecttype", object.kind().to_string()),
+            (
+                "objectsize:disk",
+                fs::metadata(loose_path(&hash))?.len().to_string(),
+            ),
+            ("objectsize", object.contents().len().to_string()),
+            ("deltabase", ZERO_HASH.to_string()),
+            ("rest", rest.to_string()),
+        ] {
+            header = header.replace(&format!("%({atom})"), &value);
+        }
+        Ok(header)
+    }
+    fn show(&self, name: &str, rest: &str, out: &mut impl Write) -> Result<(), GitError> {
+        let object = resolve(name).and_then(GitObject::from_hex_string);
+        match object {
+            Ok(object) => {
+                writeln!(out, "{}", self.header(&object, rest)?)?;
+                if self.contents {
+                    out.write_all(object.contents())?;
+                    writeln!(out)?;
+                }
+            }
+            Err(_) => writeln!(out, "{name} missing")?,
+        }
+        if !self.buffer {
+            out.flush()?;
+        }
+        Ok(())
+    }
+    fn run(&self) -> Result<(), GitError> {
+        self.check_format()?;
+        let mut out = BufWriter::new(std::io::stdout().lock());
+        if self.all_objects {
+            for hash in all_objects()? {
+                self.show(&hash, "", &mut out)?;
+            }
+            return Ok(out.flush()?);
+        }
+        // With `%(rest)` in the format, only the first word names the object.
+        let split = self.format.contains("%(rest)");
+        for line in std::io::stdin().lock().lines() {
+            let line = line?;
+            let (name, rest) = match line.split_once(char::is_whitespace) {
+                Some((name, rest)) if split => (name, rest.trim_start()),
+                _ => (line.as_str(), ""),
+            };
+            self.show(name, rest, &mut out)?;
+        }
+        Ok(out.flush()?)
+    }
+}
 pub fn cat_file(args: Vec<String>) -> Result<(), GitError> {
-    let hex_string = args.last().ok_or(GitError::any("missing object hash"))?;
-    if hex_string.len() != 40 {
-        return Err(GitError::any("invalid object hash"));
+    let mut mode = None;
+    let mut batch: Option<Batch> = None;
+    let mut all_objects = false;
+    let mut buffer = false;
+    let mut operands = Vec::new();
+    for arg in args {
+        let batch_format = |format: Option<&str>| Batch {
+            format: format.unwrap_or(BATCH_CHECK_FORMAT).to_string(),
+            contents: false,
+            all_objects: false,
+            buffer: false,
+        };
+        match arg.as_str() {
+            "-t" => mode = Some(Mode::Type),
+            "-s" => mode = Some(Mode::Size),
+            "-e" => mode = Some(Mode::Exists),
+            "-p" => mode = Some(Mode::Pretty),
+            "--batch-all-objects" => all_objects = true,
+            "--buffer" => buffer = true,
+            "--unordered" => {}
+            arg if arg == "--batch" || arg.starts_with("--batch=") => {
+                if batch.is_some() {
+                    return Err(GitError::any("only one batch option may be specified"));
+                }
+                batch = Some(Batch {
+                    contents: true,
+                    ..batch_format(arg.strip_prefix("--batch="))
+                });
+            }
+            arg if arg == "--batch-check" || arg.starts_with("--batch-check=") => {
+                if batch.is_some() {
+                    return Err(GitError::any("only one batch option may be specified"));
+                }
+                batch = Some(batch_format(arg.strip_prefix("--batch-check=")));
+            }
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => operands.push(arg),
+        }
+    }
+    if let Some(mut batch) = batch {
+        if mode.is_some() || !operands.is_empty() {
+            return Err(GitError::any("batch modes take no arguments"));
+        }
+        batch.all_objects = all_objects;
+        batch.buffer = buffer;
+        return batch.run();
+    }
+    if all_objects {
+        return Err(GitError::any("'--batch-all-objects' requires a batch mode"));
+    }
+    if buffer {
+        return Err(GitError::any("'--buffer' requires a batch mode"));
+    }
+    match (mode, operands.as_slice()) {
+        (Some(mode), [rev]) => show(mode, rev),
+        (None, [kind, rev]) => show_as(kind, rev),
+        (Some(Mode::Type), []) => Err(GitError::any("<object> required with '-t'")),
+        (Some(Mode::Size), []) => Err(GitError::any("<object> required with '-s'")),
+        (Some(Mode::Exists), []) => Err(GitError::any("<object> required with '-e'")),
+        (Some(Mode::Pretty), []) => Err(GitError::any("<object> required with '-p'")),
+        (None, [] | [_]) => Err(GitError::any("usage: git cat-file <type> <object>")),
+        _ => Err(GitError::any("too many arguments")),
+    }
+}
+fn show(mode: Mode, rev: &str) -> Result<(), GitError> {
+    let hash = resolve(rev).map_err(|_| not_valid(rev))?;
+    let object = match GitObject::from_hex_string(&hash) {
+        Ok(object) => object,
+        Err(_) if mode == Mode::Exists => return Err(GitError::exit(1)),
+        Err(_) => return Err(not_valid(rev)),
     };
-    let git_object = GitObject::from_hex_string(hex_string)?;
-    match git_object.kind() {
-        Kind::Blob => {
-            let content = std::str::from_utf8(git_object.contents())?;
-            print!("{content}");
-            Ok(())
-        }
-        kind => Err(GitError::any(format!(
-            "support for {} git object not implemented",
-            kind,
-        ))),
-    }
-}
\ No newline at end of file
+    match mode {
+        Mode::Type => println!("{}", object.kind()),
+        Mode::Size => println!("{}", object.contents().len()),
+        Mode::Exists => {}
+        Mode::Pretty => pretty_print(&object, &mut std::io::stdout().lock())?,
+    }
+    Ok(())
+}
+/// Prints the raw contents of `rev` as an object of type `kind`, peeling
+/// tags and commits to get there.
+fn show_as(kind: &str, rev: &str) -> Result<(), GitError> {
+    let hash = resolve(rev).map_err(|_| not_valid(rev))?;
+    let object = peel(&hash, Some(kind))
+        .and_then(GitObject::from_hex_string)
+        .map_err(|_| GitError::any(format!("git cat-file {rev}: bad file")))?;
+    Ok(std::io::stdout().write_all(object.contents())?)
+}
@@ -0,0 +1,156 @@
+use super::is_under;
+use super::switch::{expand_previous, guess_remote, switch_to, Destination};
+use crate::diff::{index_snapshot, tree_snapshot, Snapshot};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::refs;
+use crate::revision::{resolve_commit, resolve_tree};
+use crate::worktree::{checkout_entry, is_clean};
+pub(crate) fn normalize(pathspec: &str) -> &str {
+    match pathspec.trim_start_matches("./").trim_end_matches('/') {
+        "." => "",
+        pathspec => pathspec,
+    }
+}
+/// Picks the entries of `snapshot` under `pathspecs`, failing on a pathspec
+/// that matches nothing there or only unmerged entries of `index`.
+pub(crate) fn select(
+    snapshot: &Snapshot,
+    index: &Index,
+    pathspecs: &[String],
+) -> Result<Snapshot, GitError> {
+    for pathspec in pathspecs {
+        let pathspec = normalize(pathspec);
+        let unmerged = index
+            .entries()
+            .iter()
+            .find(|entry| entry.stage() > 0 && is_under(entry.path(), pathspec));
+        if let Some(entry) = unmerged {
+            return Err(GitError::any(format!(
+                "path '{}' is unmerged",
+                entry.path()
+            )));
+        }
+        if !snapshot.keys().any(|path| is_under(path, pathspec)) {
+            return Err(GitError::any(format!(
+                "pathspec '{pathspec}' did not match any file(s) known to git"
+            )));
+        }
+    }
+    Ok(snapshot
+        .iter()
+        .filter(|(path, _)| pathspecs.iter().any(|spec| is_under(path, normalize(spec))))
+        .map(|(path, entry)| (path.clone(), entry.clone()))
+        .collect())
+}
+/// Overwrites the paths under `pathspecs` with their staged version, or
+/// with the version in `source`, which is staged as well.
+fn checkout_paths(source: Option<&str>, pathspecs: &[String]) -> Result<(), GitError> {
+    let mut index = Index::load()?;
+    let snapshot = match source {
+        Some(rev) => tree_snapshot(&resolve_tree(rev)?, true)?,
+        None => index_snapshot(&index),
+    };
+    for (path, (mode, hash)) in select(&snapshot, &index, pathspecs)? {
+        let staged = index
+            .get(&path)
+            .is_some_and(|entry| entry.mode() == mode && *entry.hash() == hash);
+        if !staged || !is_clean(&index, &path)? {
+            checkout_entry(&mut index, &path, &mode, &hash)?;
+        }
+    }
+    index.write()
+}
+pub fn checkout(args: Vec<String>) -> Result<(), GitError> {
+    let mut force = false;
+    let mut detach = false;
+    let mut guess = true;
+    let mut track = None;
+    let mut create: Option<(String, bool)> = None;
+    let mut positional = Vec::new();
+    let mut pathspecs = None;
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--" => {
+                pathspecs = Some(args_iter.by_ref().collect::<Vec<_>>());
+            }
+            "-f" | "--force" => force = true,
+            "--detach" => detach = true,
+            "-q" | "--quiet" => {}
+            "--guess" => guess = true,
+            "--no-guess" => guess = false,
+            "-t" | "--track" => track = Some(true),
+            "--no-track" => track = Some(false),
+            "-b" | "-B" => {
+                let name = args_iter.next().ok_or_else(|| {
+                    GitError::any(format!("switch `{}' requires a value", &arg[1..]))
+                })?;
+                create = Some((name, arg == "-B"));
+            }
+            arg if arg.starts_with('-') && arg != "-" => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if let Some(pathspecs) = pathspecs {
+        if create.is_some() || detach || positional.len() > 1 {
+            return Err(GitError::any(
+                "cannot switch branches and update paths at once",
+            ));
+        }
+        let source = positional
+            .first()
+            .map(|rev| expand_previous(rev))
+            .transpose()?;
+        return checkout_paths(source.as_deref(), &pathspecs);
+    }
+    if let Some((name, reset)) = create {
+        if positional.len() > 1 {
+            return Err(GitError::any(
+                "cannot switch branches and update paths at once",
+            ));
+        }
+        let start = expand_previous(positional.first().map_or("HEAD", |start| start))?;
+        return switch_to(
+            Destination::NewBranch {
+                name,
+                start,
+                reset,
+                track,
+            },
+            force,
+            true,
+        );
+    }
+    let Some(first) = positional.first() else {
+        return match detach {
+            true => switch_to(Destination::Detached("HEAD".to_string()), force, false),
+            false => Ok(()),
+        };
+    };
+    let target = expand_previous(first)?;
+    let is_branch = refs::resolve(&format!("refs/heads/{target}"))?.is_some();
+    let remote = match is_branch || detach || !guess || positional.len() > 1 {
+        true => None,
+        false => guess_remote(&target)?,
+    };
+    if !is_branch && remote.is_none() && resolve_commit(&target).is_err() {
+        return checkout_paths(None, &positional);
+    }
+    if positional.len() > 1 {
+        return checkout_paths(Some(&target), &positional[1..]);
+    }
+    let destination = match (is_branch && !detach, remote) {
+        (true, _) => Destination::Branch(target),
+        (false, Some(start)) => Destination::NewBranch {
+            name: target,
+            start,
+            reset: false,
+            track,
+        },
+        (false, None) => Destination::Detached(target),
+    };
+    switch_to(destination, force, !detach)
+}
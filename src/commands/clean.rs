@@ -0,0 +1,169 @@
+use super::add::is_under;
+use super::checkout::normalize;
+use crate::config::Config;
+use crate::error::GitError;
+use crate::ignore::Ignore;
+use crate::index::Index;
+use crate::submodule;
+use std::fs;
+#[derive(Clone, Copy, PartialEq)]
+enum Ignored {
+    Keep,
+    Remove,
+    Only,
+}
+struct Cleaner {
+    index: Index,
+    ignore: Ignore,
+    directories: bool,
+    ignored: Ignored,
+}
+impl Cleaner {
+    /// Whether `dir` is tracked as a gitlink or holds tracked files.
+    fn tracks(&self, dir: &str) -> bool {
+        self.index.contains(dir) || self.index.tracks_dir(dir)
+    }
+    /// Collects the removable paths under `dir`, untracked directories as
+    /// `dir/`, and returns whether everything in `dir` is removable.
+    fn collect(&mut self, dir: &str, found: &mut Vec<String>) -> Result<bool, GitError> {
+        let mut entries =
+            fs::read_dir(if dir.is_empty() { "." } else { dir })?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        let mut complete = true;
+        for entry in entries {
+            let Ok(filename) = entry.file_name().into_string() else {
+                continue;
+            };
+            if filename == ".git" {
+                continue;
+            }
+            let path = match dir {
+                "" => filename,
+                dir => format!("{dir}/{filename}"),
+            };
+            let is_dir = entry.file_type()?.is_dir();
+            let ignored = self.ignore.is_ignored(&path, is_dir)?;
+            if !is_dir {
+                let wanted = match self.ignored {
+                    Ignored::Keep => !ignored,
+                    Ignored::Remove => true,
+                    Ignored::Only => ignored,
+                };
+                match wanted && !self.index.contains(&path) {
+                    true => found.push(path),
+                    false => complete = false,
+                }
+            } else if self.tracks(&path) {
+                self.collect(&path, found)?;
+                complete = false;
+            } else if submodule::is_repo(&path) {
+                complete = false;
+            } else if !self.directories {
+                // Only ignored files are still looked for in untracked
+                // directories that -d leaves alone.
+                if self.ignored == Ignored::Only && !ignored {
+                    self.collect(&path, found)?;
+                }
+                complete = false;
+            } else if ignored {
+                match self.ignored {
+                    Ignored::Keep => complete = false,
+                    _ => found.push(format!("{path}/")),
+                }
+            } else {
+                let mut inner = Vec::new();
+                if self.collect(&path, &mut inner)? && self.ignored != Ignored::Only {
+                    found.push(format!("{path}/"));
+                } else {
+                    complete = false;
+                    found.extend(inner);
+                }
+            }
+        }
+        Ok(complete)
+    }
+}
+pub fn clean(args: Vec<String>) -> Result<(), GitError> {
+    let mut dry_run = false;
+    let mut force = false;
+    let mut quiet = false;
+    let mut directories = false;
+    let (mut remove_ignored, mut only_ignored) = (false, false);
+    let mut pathspecs = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--" => pathspecs.extend(args_iter.by_ref()),
+            "--dry-run" => dry_run = true,
+            "--force" => force = true,
+            "--quiet" => quiet = true,
+            flags if flags.starts_with('-') && !flags.starts_with("--") && flags.len() > 1 => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'n' => dry_run = true,
+                        'f' => force = true,
+                        'q' => quiet = true,
+                        'd' => directories = true,
+                        'x' => remove_ignored = true,
+                        'X' => only_ignored = true,
+                        flag => return Err(GitError::any(format!("unknown switch `{flag}'"))),
+                    }
+                }
+            }
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => pathspecs.push(arg),
+        }
+    }
+    if remove_ignored && only_ignored {
+        return Err(GitError::any("-x and -X cannot be used together"));
+    }
+    if !dry_run && !force {
+        match Config::load()?.get("clean.requireForce") {
+            Some("false") => {}
+            Some(_) => {
+                return Err(GitError::any(
+                    "clean.requireForce set to true and neither -i, -n, nor -f given; refusing to clean",
+                ))
+            }
+            None => {
+                return Err(GitError::any(
+                    "clean.requireForce defaults to true and neither -i, -n, nor -f given; refusing to clean",
+                ))
+            }
+        }
+    }
+    let mut cleaner = Cleaner {
+        index: Index::load()?,
+        ignore: Ignore::load()?,
+        directories,
+        ignored: match (remove_ignored, only_ignored) {
+            (true, _) => Ignored::Remove,
+            (_, true) => Ignored::Only,
+            _ => Ignored::Keep,
+        },
+    };
+    let mut found = Vec::new();
+    cleaner.collect("", &mut found)?;
+    let selected = found.iter().filter(|path| {
+        pathspecs.is_empty()
+            || pathspecs
+                .iter()
+                .any(|spec| is_under(path.trim_end_matches('/'), normalize(spec)))
+    });
+    for path in selected {
+        if dry_run {
+            println!("Would remove {path}");
+            continue;
+        }
+        if !quiet {
+            println!("Removing {path}");
+        }
+        match path.strip_suffix('/') {
+            Some(dir) => fs::remove_dir_all(dir)?,
+            None => fs::remove_file(path)?,
+        }
+    }
+    Ok(())
+}
@@ -1,31 +1,140 @@
-use std::fs;
-use std::io::{Cursor, Read};
-use flate2::bufread::ZlibDecoder;
-use reqwest::Url;
-use reqwest::blocking::Client;
+use crate::config::Config;
 use crate::error::GitError;
 use crate::objects::{GitObject, Kind};
+use crate::refs::{self, Target};
+use crate::submodule::{git_dir, within};
+use crate::worktree::stage_tree;
+use flate2::bufread::ZlibDecoder;
+use reqwest::blocking::Client;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
 pub fn clone(args: Vec<String>) -> Result<(), GitError> {
-    let [repo_url, rest @ ..] = args.as_slice() else {
+    let recurse = args
+        .iter()
+        .any(|a| a == "--recurse-submodules" || a == "--recursive");
+    let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+    let [repo_url, rest @ ..] = positional.as_slice() else {
         return Err(GitError::any("repo url missing"));
     };
     let [clone_dir, ..] = rest else {
         return Err(GitError::any("clone dir missing"));
     };
-    let git_client = GitClient::new();
-    let head_rev = git_client.get_head_rev(repo_url)?;
-    let pack_data = git_client.fetch_pack(repo_url, &head_rev)?;
-    fs::create_dir(clone_dir)?;
-    std::env::set_current_dir(clone_dir)?;
+    let source = Path::new(repo_url)
+        .is_dir()
+        .then(|| fs::canonicalize(repo_url))
+        .transpose()?;
+    let repo_url = match &source {
+        Some(source) => source.to_string_lossy().into_owned(),
+        None => repo_url.to_string(),
+    };
+    if Path::new(clone_dir).exists() {
+        if fs::read_dir(clone_dir)?.next().is_some() {
+            return Err(GitError::any(format!(
+                "destination path '{clone_dir}' already exists and is not an empty directory."
+            )));
+        }
+    } else {
+        fs::create_dir(clone_dir)?;
+    }
+    let (head_rev, head_ref, branches, tags) = match &source {
+        Some(source) => {
+            let (head_rev, head_ref, branches, tags) = within(source, || {
+                let head_ref = match refs::read("HEAD")? {
+                    Some(Target::Symbolic(head_ref)) => head_ref,
+                    _ => format!("refs/heads/{}", refs::default_branch()?),
+                };
+                let head_rev = refs::resolve("HEAD")?
+                    .ok_or(GitError::any("remote HEAD refers to nonexistent ref"))?;
+                let branches = refs::list("refs/heads/")?;
+                Ok((head_rev, head_ref, branches, refs::list("refs/tags/")?))
+            })?;
+            let objects = git_dir(source)
+                .unwrap_or_else(|| source.clone())
+                .join("objects");
+            std::env::set_current_dir(clone_dir)?;
+            create_layout()?;
+            copy_objects(&objects)?;
+            (head_rev, head_ref, branches, tags)
+        }
+        None => {
+            let git_client = GitClient::new();
+            let (head_rev, head_ref) = git_client.get_head_ref(&repo_url)?;
+            let pack_data = git_client.fetch_pack(&repo_url, &head_rev)?;
+            std::env::set_current_dir(clone_dir)?;
+            create_layout()?;
+            unpack(pack_data)?;
+            let branches = vec![(head_ref.clone(), head_rev.clone())];
+            (head_rev, head_ref, branches, Vec::new())
+        }
+    };
+    let message = format!("clone: from {repo_url}");
+    Config::set("remote.origin.url", Some(&repo_url))?;
+    Config::set(
+        "remote.origin.fetch",
+        Some("+refs/heads/*:refs/remotes/origin/*"),
+    )?;
+    for (branch, hash) in &branches {
+        let name = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+        let tracking = format!("refs/remotes/origin/{name}");
+        refs::update(&tracking, hash, Some(refs::ZERO_HASH), false, &message)?;
+    }
+    for (tag, hash) in &tags {
+        refs::update(tag, hash, Some(refs::ZERO_HASH), false, &message)?;
+    }
+    let head_name = head_ref.strip_prefix("refs/heads/").unwrap_or(&head_ref);
+    let remote_head = format!("refs/remotes/origin/{head_name}");
+    refs::set_symbolic("refs/remotes/origin/HEAD", &remote_head, None)?;
+    refs::update(&head_ref, &head_rev, Some(refs::ZERO_HASH), false, &message)?;
+    refs::set_symbolic("HEAD", &head_ref, Some(&message))?;
+    Config::set(&format!("branch.{head_name}.remote"), Some("origin"))?;
+    Config::set(&format!("branch.{head_name}.merge"), Some(&head_ref))?;
+    let commit = GitObject::from_hex_string(head_rev)?;
+    commit.restore(".")?;
+    stage_tree(&commit.tree_hex_string()?)?;
+    if recurse {
+        super::submodule(
+            ["update", "--init", "--recursive"]
+                .map(String::from)
+                .to_vec(),
+        )?;
+    }
+    Ok(())
+}
+fn create_layout() -> Result<(), GitError> {
     fs::create_dir(".git")?;
     fs::create_dir(".git/objects")?;
     fs::create_dir(".git/refs")?;
     fs::create_dir(".git/refs/heads")?;
-    fs::write(".git/HEAD", "ref: refs/heads/master\n")?;
-    fs::write(".git/refs/heads/master", &head_rev)?;
-    unpack(pack_data)?;
-    let commit = GitObject::from_hex_string(head_rev)?;
-    commit.restore(".")?;
+    fs::create_dir(".git/refs/tags")?;
+    Ok(())
+}
+/// Copies the loose objects of a local repository and unpacks its packs.
+fn copy_objects(objects: &Path) -> Result<(), GitError> {
+    for dir in fs::read_dir(objects)? {
+        let dir = dir?;
+        let name = dir.file_name().to_string_lossy().into_owned();
+        if name.len() != 2 || !dir.file_type()?.is_dir() {
+            continue;
+        }
+        let target = Path::new(".git/objects").join(&name);
+        fs::create_dir_all(&target)?;
+        for object in fs::read_dir(dir.path())? {
+            let object = object?;
+            fs::copy(object.path(), target.join(object.file_name()))?;
+        }
+    }
+    let pack_dir = objects.join("pack");
+    if pack_dir.is_dir() {
+        for pack in fs::read_dir(pack_dir)? {
+            let path = pack?.path();
+            if path.extension().is_some_and(|ext| ext == "pack") {
+                unpack(fs::read(path)?)?;
+            }
+        }
+    }
     Ok(())
 }
 struct GitClient {
@@ -37,19 +146,32 @@ impl GitClient {
             inner: Client::new(),
         }
     }
-    fn get_head_rev(&self, repo_url: impl AsRef<str>) -> Result<String, GitError> {
+    fn get_head_ref(&self, repo_url: impl AsRef<str>) -> Result<(String, String), GitError> {
         let repo_url = repo_url.as_ref();
         let refs_url = Url::parse(&format!("{repo_url}.git/info/refs?service=git-upload-pack"))?;
         let response = self.inner.get(refs_url).send()?;
         let body = response.bytes()?;
-        let head_rev = String::from_utf8_lossy(
-            body.strip_prefix(b"001e# service=git-upload-pack\n0000")
-                .and_then(|s| s.split(|&b| b == b' ').next())
-                .and_then(|s| s.split_at_checked(4))
-                .ok_or(GitError::any("cannot parse head ref"))?
-                .1,
-        );
-        Ok(head_rev.into())
+        let first_line = body
+            .strip_prefix(b"001e# service=git-upload-pack\n0000")
+            .and_then(|s| {
+                let len = usize::from_str_radix(std::str::from_utf8(s.get(..4)?).ok()?, 16).ok()?;
+                s.get(4..len)
+            })
+            .ok_or(GitError::any("cannot parse head ref"))?;
+        let first_line = String::from_utf8_lossy(first_line);
+        let (head, capabilities) = first_line.split_once('\0').unwrap_or((&first_line, ""));
+        let head_rev = head
+            .split(' ')
+            .next()
+            .ok_or(GitError::any("cannot parse head ref"))?;
+        let head_ref = match capabilities
+            .split_whitespace()
+            .find_map(|c| c.strip_prefix("symref=HEAD:"))
+        {
+            Some(head_ref) => head_ref.to_string(),
+            None => format!("refs/heads/{}", refs::default_branch()?),
+        };
+        Ok((head_rev.to_string(), head_ref))
     }
     fn fetch_pack(
         &self,
@@ -84,8 +206,10 @@ fn unpack(data: impl AsRef<Vec<u8>>) -> Result<(), GitError> {
     let mut num_objects = [0u8; 4];
     reader.read_exact(&mut num_objects)?;
     let num_objects = u32::from_be_bytes(num_objects);
+    let mut offsets = HashMap::new();
     let mut buf = [0u8; 1];
     for _ in 1..=num_objects {
+        let start = reader.position();
         reader.read_exact(&mut buf)?;
         let object_type = buf[0] >> 4 & 0b111;
         let mut object_size: u32 = (buf[0] & 0b1111) as u32;
@@ -95,82 +219,32 @@ fn unpack(data: impl AsRef<Vec<u8>>) -> Result<(), GitError> {
             object_size |= ((buf[0] & 0b01111111) as u32) << (7 * iter + 4);
             iter += 1;
         }
-        match object_type {
-            object_type @ 1..=3 => {
-                let mut object_data = vec![0u8; object_size as usize];
-                if object_size == 0 {
-                    reader.read_exact(&mut [0u8; 8])?;
-                } else {
-                    let mut zlib_reader = ZlibDecoder::new(&mut reader);
-                    zlib_reader.read_exact(&mut object_data)?;
-                }
+        let git_object = match object_type {
+            object_type @ 1..=4 => {
+                let object_data = inflate(&mut reader, object_size)?;
                 let kind = match object_type {
                     1 => Kind::Commit,
                     2 => Kind::Tree,
                     3 => Kind::Blob,
+                    4 => Kind::Tag,
                     _ => unreachable!(),
                 };
-                let git_object = GitObject::build(kind, object_data)?;
-                git_object.write()?;
+                GitObject::build(kind, object_data)?
+            }
+            6 => {
+                let offset = read_offset(&mut reader)?;
+                let base_hex_string = start
+                    .checked_sub(offset)
+                    .and_then(|base| offsets.get(&base))
+                    .ok_or(GitError::any("bad delta base offset in pack"))?;
+                let base_object = GitObject::from_hex_string(base_hex_string)?;
+                apply_delta(&base_object, inflate(&mut reader, object_size)?)?
             }
             7 => {
                 let mut base_hash = vec![0u8; 20];
                 reader.read_exact(&mut base_hash)?;
-                let mut delta_data = vec![0u8; object_size as usize];
-                let mut zlib_reader = ZlibDecoder::new(&mut reader);
-                zlib_reader.read_exact(&mut delta_data)?;
-                let base_hex_string = hex::encode(base_hash);
-                let base_object = GitObject::from_hex_string(&base_hex_string)?;
-                let mut base_object_data = Cursor::new(base_object.contents());
-                let mut delta_data = Cursor::new(delta_data);
-                let _base_object_size = read_varint(&mut delta_data)?;
-                let target_object_size = read_varint(&mut delta_data)?;
-                let mut target_object_data = Cursor::new(vec![0u8; target_object_size as usize]);
-                loop {
-                    let mut instruction_buf = vec![0u8; 1];
-                    if delta_data.read_exact(&mut instruction_buf).is_err() {
-                        break;
-                    };
-                    if instruction_buf[0] >> 7 == 0 {
-                        let size = instruction_buf[0] & 0b01111111;
-                        if size == 0 {
-                            continue;
-                        }
-                        std::io::copy(
-                            &mut (&mut delta_data).take(size as u64),
-                            &mut target_object_data,
-                        )?;
-                    } else {
-                        let mut offset = [0u8; 4];
-                        let mut size = [0u8; 4];
-                        let mut buf = [0u8; 1];
-                        for (i, b) in offset.iter_mut().enumerate() {
-                            if (instruction_buf[0] & 1 << i) > 0 {
-                                delta_data.read_exact(&mut buf)?;
-                                *b = buf[0];
-                            }
-                        }
-                        for (i, b) in size.iter_mut().enumerate().take(3) {
-                            if (instruction_buf[0] & 1 << (i + 4)) > 0 {
-                                delta_data.read_exact(&mut buf)?;
-                                *b = buf[0];
-                            }
-                        }
-                        let offset = u32::from_ne_bytes(offset);
-                        let mut size = u32::from_ne_bytes(size);
-                        if size == 0 {
-                            size = 0x10000;
-                        }
-                        base_object_data.set_position(offset as u64);
-                        std::io::copy(
-                            &mut (&mut base_object_data).take(size as u64),
-                            &mut target_object_data,
-                        )?;
-                    };
-                }
-                let target_object =
-                    GitObject::build(base_object.kind().clone(), target_object_data.into_inner())?;
-                target_object.write()?;
+                let base_object = GitObject::from_hex_string(hex::encode(base_hash))?;
+                apply_delta(&base_object, inflate(&mut reader, object_size)?)?
             }
             object_type => {
                 return Err(GitError::any(format!(
@@ -178,10 +252,82 @@ fn unpack(data: impl AsRef<Vec<u8>>) -> Result<(), GitError> {
                     object_type
                 )));
             }
-        }
+        };
+        git_object.write()?;
+        offsets.insert(start, git_object.hex_string());
     }
     Ok(())
 }
+/// Inflates one zlib stream of `size` bytes, consuming it entirely.
+fn inflate(reader: &mut Cursor<&Vec<u8>>, size: u32) -> Result<Vec<u8>, GitError> {
+    let mut data = Vec::with_capacity(size as usize);
+    ZlibDecoder::new(reader).read_to_end(&mut data)?;
+    if data.len() != size as usize {
+        return Err(GitError::any("pack object has the wrong size"));
+    }
+    Ok(data)
+}
+fn apply_delta(base_object: &GitObject, delta_data: Vec<u8>) -> Result<GitObject, GitError> {
+    let mut base_object_data = Cursor::new(base_object.contents());
+    let mut delta_data = Cursor::new(delta_data);
+    let _base_object_size = read_varint(&mut delta_data)?;
+    let target_object_size = read_varint(&mut delta_data)?;
+    let mut target_object_data = Cursor::new(vec![0u8; target_object_size as usize]);
+    loop {
+        let mut instruction_buf = vec![0u8; 1];
+        if delta_data.read_exact(&mut instruction_buf).is_err() {
+            break;
+        };
+        if instruction_buf[0] >> 7 == 0 {
+            let size = instruction_buf[0] & 0b01111111;
+            if size == 0 {
+                continue;
+            }
+            std::io::copy(
+                &mut (&mut delta_data).take(size as u64),
+                &mut target_object_data,
+            )?;
+        } else {
+            let mut offset = [0u8; 4];
+            let mut size = [0u8; 4];
+            let mut buf = [0u8; 1];
+            for (i, b) in offset.iter_mut().enumerate() {
+                if (instruction_buf[0] & 1 << i) > 0 {
+                    delta_data.read_exact(&mut buf)?;
+                    *b = buf[0];
+                }
+            }
+            for (i, b) in size.iter_mut().enumerate().take(3) {
+                if (instruction_buf[0] & 1 << (i + 4)) > 0 {
+                    delta_data.read_exact(&mut buf)?;
+                    *b = buf[0];
+                }
+            }
+            let offset = u32::from_le_bytes(offset);
+            let mut size = u32::from_le_bytes(size);
+            if size == 0 {
+                size = 0x10000;
+            }
+            base_object_data.set_position(offset as u64);
+            std::io::copy(
+                &mut (&mut base_object_data).take(size as u64),
+                &mut target_object_data,
+            )?;
+        };
+    }
+    GitObject::build(base_object.kind().clone(), target_object_data.into_inner())
+}
+/// Reads the negative base offset of an `OFS_DELTA` entry.
+fn read_offset(r: &mut impl Read) -> Result<u64, GitError> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    let mut offset = (buf[0] & 0b01111111) as u64;
+    while buf[0] >> 7 != 0 {
+        r.read_exact(&mut buf)?;
+        offset = ((offset + 1) << 7) | (buf[0] & 0b01111111) as u64;
+    }
+    Ok(offset)
+}
 fn read_varint<R: Read>(mut r: R) -> Result<u32, GitError> {
     let mut buf = [0u8; 1];
     let mut iter = 1;
@@ -193,4 +339,4 @@ fn read_varint<R: Read>(mut r: R) -> Result<u32, GitError> {
         iter += 1;
     }
     Ok(varint)
-}
\ No newline at end of file
+}
@@ -0,0 +1,301 @@
+use super::status;
+use crate::commit::{Commit, CommitBuilder, Signature};
+use crate::config::Config;
+use crate::date::parse_date;
+use crate::editor::{add_trailers, edit_message_with, save_message, Cleanup};
+use crate::error::GitError;
+use crate::index::{Index, IndexEntry};
+use crate::objects::GitObject;
+use crate::refs;
+use crate::sequencer::{check_merged, print_summary};
+use crate::state::{self, BRANCH_STATE, CHERRY_PICK_HEAD, MERGE_HEAD, MERGE_MSG, SQUASH_MSG};
+use std::fs;
+use std::io::Read;
+#[derive(Default)]
+struct Options {
+    messages: Vec<String>,
+    file: Option<String>,
+    edit: Option<bool>,
+    all: bool,
+    amend: bool,
+    allow_empty: bool,
+    allow_empty_message: bool,
+    author: Option<String>,
+    date: Option<String>,
+    cleanup: Option<String>,
+    trailers: Vec<String>,
+    signoff: bool,
+    quiet: bool,
+}
+/// Parses `--author`, which must be in the `Name <email>` form.
+fn parse_author(text: &str) -> Result<(String, String), GitError> {
+    text.split_once('<')
+        .and_then(|(name, rest)| Some((name.trim(), rest.strip_suffix('>')?)))
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, email)| (name.to_string(), email.to_string()))
+        .ok_or_else(|| {
+            GitError::any(format!(
+                "--author '{text}' is not 'Name <email>' and matches no existing author"
+            ))
+        })
+}
+/// A trailer given as `key=value` or `key: value`, in the latter form.
+fn parse_trailer(text: &str) -> String {
+    match text.split_once(['=', ':']) {
+        Some((key, value)) => format!("{}: {}", key.trim(), value.trim()),
+        None => text.trim().to_string(),
+    }
+}
+/// Stages the changes to tracked files, as `-a` does; new files are left
+/// alone.
+fn stage_tracked() -> Result<(), GitError> {
+    let mut index = Index::load()?;
+    let mut updates = Vec::new();
+    for entry in index.entries().iter().filter(|entry| entry.stage() == 0) {
+        if entry.mode() == "160000" || !entry.is_modified()? {
+            continue;
+        }
+        updates.push(entry.path().clone());
+    }
+    for path in updates {
+        match fs::symlink_metadata(&path) {
+            Ok(_) => {
+                let blob = GitObject::from_path(&path, true)?;
+                index.add(IndexEntry::from_file(path, blob.hash().clone())?);
+            }
+            Err(_) => index.remove(&path),
+        }
+    }
+    index.write()
+}
+/// The message to start from when none is given: the amended commit's, or
+/// the one a merge, squash or stopped cherry-pick left behind.
+fn template(amended: Option<&Commit>) -> Result<String, GitError> {
+    if let Some(commit) = amended {
+        return Ok(commit.message().clone());
+    }
+    for path in [MERGE_MSG, SQUASH_MSG] {
+        if let Some(message) = state::read(path)? {
+            return Ok(message);
+        }
+    }
+    Ok(String::new())
+}
+fn commit_message(options: &Options, amended: Option<&Commit>) -> Result<String, GitError> {
+    let given = match &options.file {
+        Some(path) if path == "-" => {
+            let mut message = String::new();
+            std::io::stdin().read_to_string(&mut message)?;
+            Some(message)
+        }
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .map_err(|_| GitError::any(format!("could not read log file '{path}'")))?,
+        ),
+        None if options.messages.is_empty() => None,
+        None => Some(options.messages.join("\n\n") + "\n"),
+    };
+    let mode = match options
+        .cleanup
+        .as_deref()
+        .or(Config::load()?.get("commit.cleanup"))
+    {
+        Some(name) => Cleanup::parse(name)?,
+        None => Cleanup::Default,
+    };
+    let edit = options.edit.unwrap_or(given.is_none());
+    let source = match given {
+        Some(message) => message,
+        None => template(amended)?,
+    };
+    let mut message = match edit {
+        true => edit_message_with(&source, mode)?,
+        false => mode.apply(&source, false),
+    };
+    let mut trailers: Vec<String> = options.trailers.iter().map(|t| parse_trailer(t)).collect();
+    if options.signoff {
+        let committer = Signature::committer()?;
+        trailers.push(format!(
+            "Signed-off-by: {} <{}>",
+            committer.name(),
+            committer.email()
+        ));
+    }
+    if !trailers.is_empty() {
+        message = add_trailers(&message, &trailers);
+    }
+    if message.trim().is_empty() && !options.allow_empty_message {
+        return Err(GitError::any(
+            "Aborting commit due to empty commit message.",
+        ));
+    }
+    Ok(message)
+}
+fn run(options: Options) -> Result<(), GitError> {
+    if options.all {
+        stage_tracked()?;
+    }
+    let index = Index::load()?;
+    check_merged(&index)?;
+    let head = refs::resolve("HEAD")?;
+    let head_commit = head.as_deref().map(Commit::from_hex_string).transpose()?;
+    let amended = match (options.amend, &head_commit) {
+        (true, None) => return Err(GitError::any("You have nothing to amend.")),
+        (true, Some(commit)) => Some(commit),
+        (false, _) => None,
+    };
+    let merge_heads: Vec<String> = match state::read(MERGE_HEAD)? {
+        Some(heads) if amended.is_none() => heads.lines().map(str::to_string).collect(),
+        _ => Vec::new(),
+    };
+    let parents: Vec<String> = match amended {
+        Some(commit) => commit.parents().clone(),
+        None => head.iter().cloned().chain(merge_heads.clone()).collect(),
+    };
+    let tree = index.write_tree()?;
+    let unchanged = match &head_commit {
+        Some(commit) => commit.tree() == &tree,
+        None => index.entries().is_empty(),
+    };
+    if unchanged && amended.is_none() && merge_heads.is_empty() && !options.allow_empty {
+        status(Vec::new())?;
+        return Err(GitError::exit(1));
+    }
+    let message = commit_message(&options, amended)?;
+    let picked = match state::read(CHERRY_PICK_HEAD)? {
+        Some(hash) => Some(Commit::from_hex_string(hash.trim())?),
+        None => None,
+    };
+    let mut author = match (amended, &picked) {
+        (Some(commit), _) | (None, Some(commit)) => commit.author().clone(),
+        (None, None) => Signature::author()?,
+    };
+    if let Some(text) = &options.author {
+        let (name, email) = parse_author(text)?;
+        author = Signature::new(name, email, author.time(), author.offset());
+    }
+    if let Some(text) = &options.date {
+        let (time, offset) = parse_date(text)?;
+        author = Signature::new(author.name().clone(), author.email().clone(), time, &offset);
+    }
+    let mut builder = CommitBuilder::new(&tree)
+        .parents(&parents)
+        .author(author)
+        .message(&message);
+    // An amended commit keeps its extra headers, but not its signature.
+    for (name, value) in amended.iter().flat_map(|commit| commit.extra_headers()) {
+        if !matches!(name.as_str(), "encoding" | "gpgsig" | "gpgsig-sha256") {
+            builder = builder.header(name, value);
+        }
+    }
+    let commit = builder.build()?;
+    commit.write()?;
+    let hash = commit.hex_string();
+    let kind = match (amended, head.as_ref(), merge_heads.is_empty()) {
+        (Some(_), _, _) => " (amend)",
+        (None, None, _) => " (initial)",
+        (None, _, false) => " (merge)",
+        _ => "",
+    };
+    let subject = message.lines().next().unwrap_or_default();
+    let old = head.as_deref().unwrap_or(refs::ZERO_HASH);
+    refs::update(
+        "HEAD",
+        &hash,
+        Some(old),
+        true,
+        &format!("commit{kind}: {subject}"),
+    )?;
+    save_message(&message)?;
+    state::remove_all(&BRANCH_STATE)?;
+    if !options.quiet {
+        print_summary(&hash, amended.is_some() || options.date.is_some())?;
+    }
+    Ok(())
+}
+pub fn commit(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = Options::default();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or_else(|| GitError::any(format!("option `{name}' requires a value")))
+        };
+        match arg.as_str() {
+            "-m" | "--message" => {
+                let message = value("message")?;
+                options.messages.push(message)
+            }
+            "-F" | "--file" => options.file = Some(value("file")?),
+            "-e" | "--edit" => options.edit = Some(true),
+            "--no-edit" => options.edit = Some(false),
+            "-a" | "--all" => options.all = true,
+            "--amend" => options.amend = true,
+            "--allow-empty" => options.allow_empty = true,
+            "--allow-empty-message" => options.allow_empty_message = true,
+            "--author" => options.author = Some(value("author")?),
+            "--date" => options.date = Some(value("date")?),
+            "--cleanup" => options.cleanup = Some(value("cleanup")?),
+            "--trailer" => {
+                let trailer = value("trailer")?;
+                options.trailers.push(trailer)
+            }
+            "-s" | "--signoff" => options.signoff = true,
+            "-q" | "--quiet" => options.quiet = true,
+            "-n" | "--no-verify" | "--verify" => {}
+            arg => {
+                let long = |name: &str| arg.strip_prefix(name).map(str::to_string);
+                if let Some(message) = long("--message=") {
+                    options.messages.push(message);
+                } else if let Some(file) = long("--file=") {
+                    options.file = Some(file);
+                } else if let Some(author) = long("--author=") {
+                    options.author = Some(author);
+                } else if let Some(date) = long("--date=") {
+                    options.date = Some(date);
+                } else if let Some(mode) = long("--cleanup=") {
+                    options.cleanup = Some(mode);
+                } else if let Some(trailer) = long("--trailer=") {
+                    options.trailers.push(trailer);
+                } else if let Some(flags) = arg.strip_prefix('-').filter(|f| !f.starts_with('-')) {
+                    for (i, flag) in flags.char_indices() {
+                        let rest = &flags[i + 1..];
+                        let mut argument = || match rest.is_empty() {
+                            true => value(&flag.to_string()),
+                            false => Ok(rest.to_string()),
+                        };
+                        match flag {
+                            'a' => options.all = true,
+                            'q' => options.quiet = true,
+                            's' => options.signoff = true,
+                            'e' => options.edit = Some(true),
+                            'n' => {}
+                            'm' => {
+                                options.messages.push(argument()?);
+                                break;
+                            }
+                            'F' => {
+                                options.file = Some(argument()?);
+                                break;
+                            }
+                            flag => return Err(GitError::any(format!("unknown switch `{flag}'"))),
+                        }
+                    }
+                } else if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else {
+                    return Err(GitError::any(format!(
+                        "committing only some paths is not supported: {arg}"
+                    )));
+                }
+            }
+        }
+    }
+    if options.file.is_some() && !options.messages.is_empty() {
+        return Err(GitError::any(
+            "options '-m' and '-F' cannot be used together",
+        ));
+    }
+    run(options)
+}
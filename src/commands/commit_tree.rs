@@ -1,31 +1,102 @@
-use std::collections::HashMap;
+use crate::commit::CommitBuilder;
+use crate::config::Config;
 use crate::error::GitError;
-use crate::objects::GitObject;
+use crate::revision::{resolve_commit, resolve_tree};
+use std::fs;
+use std::io::Read;
+/// Appends a `-m` paragraph, separated from what came before by a blank
+/// line.
+fn add_paragraph(message: &mut String, paragraph: &str) {
+    if !message.is_empty() {
+        message.push('\n');
+    }
+    message.push_str(paragraph);
+    if !message.ends_with('\n') {
+        message.push('\n');
+    }
+}
+/// Appends the contents of a `-F` file, or standard input for `-`, as is.
+fn add_file(message: &mut String, path: &str) -> Result<(), GitError> {
+    if !message.is_empty() {
+        message.push('\n');
+    }
+    if path == "-" {
+        std::io::stdin()
+            .read_to_string(message)
+            .map_err(|_| GitError::any("git commit-tree: failed to read '-'"))?;
+        return Ok(());
+    }
+    let contents = fs::read_to_string(path)
+        .map_err(|_| GitError::any(format!("could not open '{path}' for reading")))?;
+    message.push_str(&contents);
+    Ok(())
+}
+fn add_parent(parents: &mut Vec<String>, rev: &str) -> Result<(), GitError> {
+    let hash = resolve_commit(rev)?;
+    match parents.contains(&hash) {
+        true => eprintln!("error: duplicate parent {hash} ignored"),
+        false => parents.push(hash),
+    }
+    Ok(())
+}
 pub fn commit_tree(args: Vec<String>) -> Result<(), GitError> {
-    let mut args_iter = args.iter();
-    let mut args = Vec::new();
-    let mut options_map = HashMap::new();
+    let mut trees = Vec::new();
+    let mut parents = Vec::new();
+    let mut message = String::new();
+    let mut signing_key = match Config::load()?.get("commit.gpgSign") {
+        Some("true") => Some(String::new()),
+        _ => None,
+    };
+    let mut args_iter = args.into_iter();
     while let Some(arg) = args_iter.next() {
-        if arg.starts_with('-') {
-            if let Some(value) = args_iter.next() {
-                options_map.insert(arg, value);
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or_else(|| GitError::any(format!("switch `{name}' requires a value")))
+        };
+        match arg.as_str() {
+            "-p" => add_parent(&mut parents, &value("p")?)?,
+            "-m" => add_paragraph(&mut message, &value("m")?),
+            "-F" => add_file(&mut message, &value("F")?)?,
+            "-S" | "--gpg-sign" => signing_key = Some(String::new()),
+            "--no-gpg-sign" => signing_key = None,
+            arg => {
+                if let Some(key) = arg
+                    .strip_prefix("--gpg-sign=")
+                    .or_else(|| arg.strip_prefix("-S"))
+                {
+                    signing_key = Some(key.to_string());
+                } else if let Some(rev) = arg.strip_prefix("-p") {
+                    add_parent(&mut parents, rev)?;
+                } else if let Some(paragraph) = arg.strip_prefix("-m") {
+                    add_paragraph(&mut message, paragraph);
+                } else if let Some(path) = arg.strip_prefix("-F") {
+                    add_file(&mut message, path)?;
+                } else if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else {
+                    trees.push(arg.to_string());
+                }
             }
-        } else {
-            args.push(arg);
         }
     }
-    let msg_option = String::from("-m");
-    let msg = options_map
-        .get(&msg_option)
-        .ok_or(GitError::any("missing msg"))?;
-    let tree_hash = args.pop().ok_or(GitError::any("missing tree hash"))?;
-    if tree_hash.len() != 40 {
-        return Err(GitError::any("invalid tree hash"));
+    let [tree] = trees.as_slice() else {
+        return Err(GitError::any("must give exactly one tree"));
     };
-    let parent_hash_option = String::from("-p");
-    let parent_hash = options_map.get(&parent_hash_option);
-    let git_object = GitObject::build_commit(msg, tree_hash, parent_hash)?;
+    let tree_hash = resolve_tree(tree)?;
+    if message.is_empty() {
+        std::io::stdin()
+            .read_to_string(&mut message)
+            .map_err(|_| GitError::any("git commit-tree: failed to read"))?;
+    }
+    let mut builder = CommitBuilder::new(tree_hash)
+        .parents(&parents)
+        .message(message);
+    if let Some(key) = signing_key {
+        builder = builder.sign(key);
+    }
+    let git_object = builder.build()?;
     git_object.write()?;
     println!("{}", git_object.hex_string());
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,248 @@
+use crate::commit::Commit;
+use crate::diff::{
+    compare, detect_renames, index_snapshot, limit_snapshot, print_diffs, tree_snapshot,
+    worktree_snapshot, DiffOptions, FileDiff, Format, Snapshot, DEFAULT_SIMILARITY,
+};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::objects::{file_mode, GitObject, Kind};
+use crate::refs;
+use crate::revision::{resolve, resolve_commit, resolve_tree};
+use crate::revwalk::merge_bases;
+use std::fs;
+pub fn diff(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = DiffOptions {
+        renames: Some(DEFAULT_SIMILARITY),
+        ..DiffOptions::default()
+    };
+    options.configure_rename_limit(&["diff.renameLimit"])?;
+    let mut no_index = false;
+    let mut cached = false;
+    let mut revs = Vec::new();
+    let mut pathspecs = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--" => pathspecs.extend(args_iter.by_ref()),
+            "--no-index" => no_index = true,
+            "--cached" | "--staged" => cached = true,
+            _ if options.parse_arg(arg)? => {}
+            _ if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")));
+            }
+            _ => revs.push(arg),
+        }
+    }
+    if no_index {
+        let [old, new] = revs.as_slice() else {
+            return Err(GitError::any("usage: diff --no-index <path> <path>"));
+        };
+        return print_diffs(&file_diff(old, new)?, &options);
+    }
+    if let [old, new] = revs.as_slice() {
+        let old_object = resolve(old).and_then(GitObject::from_hex_string).ok();
+        if old_object.is_some_and(|o| matches!(o.kind(), Kind::Blob)) {
+            return print_diffs(&blob_diff(old, new)?, &options);
+        }
+    }
+    let revs: Vec<String> = match revs.as_slice() {
+        [range] if range.contains("..") => {
+            let symmetric = range.contains("...");
+            let (old, new) = match symmetric {
+                true => range.split_once("..."),
+                false => range.split_once(".."),
+            }
+            .unwrap_or_default();
+            let side = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+            let (old, new) = (side(old), side(new));
+            if symmetric {
+                let base = merge_bases(&resolve_commit(&old)?, &resolve_commit(&new)?)?
+                    .into_iter()
+                    .next()
+                    .ok_or(GitError::any(format!("{old} and {new} have no merge base")))?;
+                vec![base, new]
+            } else {
+                vec![old, new]
+            }
+        }
+        revs => revs.iter().map(|r| r.to_string()).collect(),
+    };
+    let index = Index::load()?;
+    let (old, new, worktree) = match (revs.as_slice(), cached) {
+        ([], false) => {
+            let old = index_snapshot(&index);
+            let new = worktree_snapshot(old.keys(), &index)?;
+            (old, new, true)
+        }
+        ([], true) => {
+            let old = match refs::resolve("HEAD")? {
+                Some(_) => tree_snapshot(&resolve_tree("HEAD")?, true)?,
+                None => Snapshot::new(),
+            };
+            (old, index_snapshot(&index), false)
+        }
+        ([rev], true) => (
+            tree_snapshot(&resolve_tree(rev)?, true)?,
+            index_snapshot(&index),
+            false,
+        ),
+        ([rev], false) => {
+            let old = tree_snapshot(&resolve_tree(rev)?, true)?;
+            let tracked = index_snapshot(&index);
+            let new = worktree_snapshot(old.keys().chain(tracked.keys()), &index)?;
+            (old, new, true)
+        }
+        ([old, new], false) => (
+            tree_snapshot(&resolve_tree(old)?, true)?,
+            tree_snapshot(&resolve_tree(new)?, true)?,
+            false,
+        ),
+        _ => {
+            return Err(GitError::any(
+                "usage: diff [<options>] [--cached] [<commit> [<commit>]] [-- <path>...]",
+            ))
+        }
+    };
+    let (old, new) = (
+        limit_snapshot(old, &pathspecs),
+        limit_snapshot(new, &pathspecs),
+    );
+    let diffs = detect_renames(compare(&old, &new, worktree)?, &old, &options)?;
+    print_diffs(&diffs, &options)?;
+    options.warn_rename_limit("diff.renameLimit");
+    Ok(())
+}
+pub fn diff_tree(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = DiffOptions {
+        format: Format::Raw,
+        ..DiffOptions::default()
+    };
+    options.configure_rename_limit(&["diff.renameLimit"])?;
+    let mut recursive = false;
+    let mut separate_merges = false;
+    let mut revs = Vec::new();
+    let mut pathspecs = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--" => pathspecs.extend(args_iter.by_ref()),
+            "-r" => recursive = true,
+            "-m" => separate_merges = true,
+            "-c" | "--cc" => {
+                return Err(GitError::any(format!(
+                    "combined diffs are not supported: {arg}"
+                )));
+            }
+            "-p" | "--patch" | "--stat" | "--numstat" => {
+                recursive = true;
+                options.parse_arg(arg)?;
+            }
+            _ if options.parse_arg(arg)? => {}
+            _ if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")));
+            }
+            _ => revs.push(arg),
+        }
+    }
+    let snapshot = |tree: &str| -> Result<Snapshot, GitError> {
+        Ok(limit_snapshot(tree_snapshot(tree, recursive)?, &pathspecs))
+    };
+    let diff = |old: &Snapshot, new: &Snapshot| -> Result<Vec<FileDiff>, GitError> {
+        detect_renames(compare(old, new, false)?, old, &options)
+    };
+    match revs.as_slice() {
+        [old, new] => {
+            let old = snapshot(&resolve_tree(old)?)?;
+            print_diffs(&diff(&old, &snapshot(&resolve_tree(new)?)?)?, &options)?;
+        }
+        [rev] => {
+            // Like git, merges are only diffed when asked to diff each
+            // parent in turn, and each diff that shows anything is headed
+            // by the commit.
+            let commit = Commit::from_hex_string(resolve_commit(rev)?)?;
+            let parents = match commit.parents().as_slice() {
+                [_, _, ..] if !separate_merges => &[],
+                parents => parents,
+            };
+            let new = snapshot(commit.tree())?;
+            for parent in parents {
+                let diffs = diff(&snapshot(&resolve_tree(parent)?)?, &new)?;
+                if !diffs.is_empty() {
+                    println!("{}", commit.hash());
+                    print_diffs(&diffs, &options)?;
+                }
+            }
+        }
+        _ => {
+            return Err(GitError::any(
+                "usage: diff-tree [-r] [-m] [-p] <tree-ish> [<tree-ish>] [-- <path>...]",
+            ))
+        }
+    }
+    options.warn_rename_limit("diff.renameLimit");
+    Ok(())
+}
+fn file_diff(old: &str, new: &str) -> Result<Vec<FileDiff>, GitError> {
+    let read = |path: &str| -> Result<(String, GitObject), GitError> {
+        let mode = file_mode(&fs::symlink_metadata(path)?)
+            .ok_or(GitError::any(format!("unsupported file type: {path}")))?;
+        if mode == "40000" {
+            return Err(GitError::any(format!("{path} is a directory")));
+        }
+        Ok((mode, GitObject::build(Kind::Blob, fs::read(path)?)?))
+    };
+    let (old_mode, old_blob) = read(old)?;
+    let (new_mode, new_blob) = read(new)?;
+    if old_blob.hash() == new_blob.hash() && old_mode == new_mode {
+        return Ok(Vec::new());
+    }
+    Ok(vec![FileDiff {
+        old_path: Some(old.trim_start_matches("./").to_string()),
+        new_path: Some(new.trim_start_matches("./").to_string()),
+        old_mode: Some(old_mode),
+        new_mode: Some(new_mode),
+        old_hash: old_blob.hash().clone(),
+        new_hash: new_blob.hash().clone(),
+        old_data: old_blob.contents().clone().into(),
+        new_data: new_blob.contents().clone().into(),
+        ..FileDiff::default()
+    }])
+}
+fn blob_diff(old: &str, new: &str) -> Result<Vec<FileDiff>, GitError> {
+    let read = |rev: &str| -> Result<GitObject, GitError> {
+        let git_object = GitObject::from_hex_string(resolve(rev)?)?;
+        match git_object.kind() {
+            Kind::Blob => Ok(git_object),
+            kind => Err(GitError::any(format!("{rev} is a {kind}, not a blob"))),
+        }
+    };
+    let tree_entry = |rev: &str| -> Result<(String, Option<String>), GitError> {
+        match rev.split_once(':') {
+            Some((tree, path)) if !tree.is_empty() && !path.starts_with('/') => {
+                let snapshot = tree_snapshot(&resolve_tree(tree)?, true)?;
+                Ok((
+                    path.to_string(),
+                    snapshot.get(path).map(|(mode, _)| mode.clone()),
+                ))
+            }
+            _ => Ok((rev.to_string(), None)),
+        }
+    };
+    let (old_blob, new_blob) = (read(old)?, read(new)?);
+    let ((old_path, old_mode), (new_path, new_mode)) = (tree_entry(old)?, tree_entry(new)?);
+    let (old_mode, new_mode) = (old_mode.clone().or(new_mode.clone()), new_mode.or(old_mode));
+    if old_blob.hash() == new_blob.hash() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![FileDiff {
+        old_path: Some(old_path),
+        new_path: Some(new_path),
+        old_mode,
+        new_mode,
+        old_hash: old_blob.hash().clone(),
+        new_hash: new_blob.hash().clone(),
+        old_data: old_blob.contents().clone().into(),
+        new_data: new_blob.contents().clone().into(),
+        ..FileDiff::default()
+    }])
+}
@@ -0,0 +1,268 @@
+use crate::commit::{body, subject, Commit, Signature};
+use crate::date::DateFormat;
+use crate::error::GitError;
+use crate::ignore::wildmatch;
+use crate::objects::{short_hash, GitObject, Kind};
+use crate::refs::{self, Target};
+use crate::revision::{peel, upstream};
+use crate::tag::Tag;
+use std::cmp::Ordering;
+use std::io::Write;
+const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+struct RefInfo {
+    name: String,
+    hash: String,
+    kind: Kind,
+    size: usize,
+    commit: Option<Commit>,
+    tag: Option<Tag>,
+}
+impl RefInfo {
+    fn load(name: String, hash: String) -> Result<Self, GitError> {
+        let object = GitObject::from_hex_string(&hash)?;
+        let commit = match object.kind() {
+            Kind::Commit => Some(Commit::parse(&hash, object.contents())?),
+            _ => None,
+        };
+        let tag = match object.kind() {
+            Kind::Tag => Some(Tag::parse(object.contents())?),
+            _ => None,
+        };
+        Ok(Self {
+            name,
+            kind: object.kind().clone(),
+            size: object.contents().len(),
+            hash,
+            commit,
+            tag,
+        })
+    }
+    /// The author, committer or tagger; the creator is the committer of a
+    /// commit or the tagger of a tag.
+    fn signature(&self, who: &str) -> Option<&Signature> {
+        match (who, &self.commit, &self.tag) {
+            ("author", Some(commit), _) => Some(commit.author()),
+            ("committer" | "creator", Some(commit), _) => Some(commit.committer()),
+            ("tagger" | "creator", _, Some(tag)) => tag.tagger(),
+            _ => None,
+        }
+    }
+    /// The message of a commit or tag, without a tag's signature.
+    fn message(&self) -> String {
+        match (&self.commit, &self.tag) {
+            (Some(commit), _) => commit.message().clone(),
+            (_, Some(tag)) => tag.message().clone(),
+            _ => String::new(),
+        }
+    }
+    fn signature_text(&self) -> String {
+        self.tag
+            .as_ref()
+            .and_then(Tag::signature)
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn timestamp(&self, who: &str) -> i64 {
+        self.signature(who).map(Signature::time).unwrap_or(0)
+    }
+    fn atom(&self, atom: &str) -> Result<String, GitError> {
+        if let Some(atom) = atom.strip_prefix('*') {
+            return match &self.tag {
+                Some(tag) => {
+                    let peeled = peel(tag.object(), None)?;
+                    Self::load(self.name.clone(), peeled)?.atom(atom)
+                }
+                None => Ok(String::new()),
+            };
+        }
+        let (name, modifier) = match atom.split_once(':') {
+            Some((name, modifier)) => (name, Some(modifier)),
+            None => (atom, None),
+        };
+        Ok(match (name, modifier) {
+            ("refname", None) => self.name.clone(),
+            ("refname", Some("short")) => refs::shorten(&self.name).to_string(),
+            ("objectname", None) => self.hash.clone(),
+            ("objectname", Some("short")) => short_hash(&self.hash).to_string(),
+            ("objecttype", None) => self.kind.to_string(),
+            ("objectsize", None) => self.size.to_string(),
+            ("HEAD", None) => {
+                let head = refs::head_branch()?.map(|b| format!("refs/heads/{b}"));
+                if head.as_deref() == Some(self.name.as_str()) {
+                    "*".into()
+                } else {
+                    " ".into()
+                }
+            }
+            ("symref", None) => match refs::read(&self.name)? {
+                Some(Target::Symbolic(target)) => target,
+                _ => String::new(),
+            },
+            ("upstream", modifier) => {
+                let branch = self.name.strip_prefix("refs/heads/");
+                match branch.map(|b| upstream(Some(b))) {
+                    Some(Ok(name)) if modifier == Some("short") => refs::shorten(&name).into(),
+                    Some(Ok(name)) => name,
+                    _ => String::new(),
+                }
+            }
+            ("subject" | "contents", Some("subject")) => subject(&self.message()),
+            ("body" | "contents", Some("body")) => body(&self.message()).to_string(),
+            ("subject", None) => subject(&self.message()),
+            ("body", None) => body(&self.message()).to_string(),
+            ("contents", None) => self.message() + &self.signature_text(),
+            ("contents", Some("signature")) => self.signature_text(),
+            ("object", None) => self
+                .tag
+                .as_ref()
+                .map(|t| t.object().clone())
+                .unwrap_or_default(),
+            ("type", None) => self
+                .tag
+                .as_ref()
+                .map(|t| t.kind().clone())
+                .unwrap_or_default(),
+            ("tag", None) => self
+                .tag
+                .as_ref()
+                .map(|t| t.name().clone())
+                .unwrap_or_default(),
+            (who @ ("author" | "committer" | "tagger" | "creator"), None) => self
+                .signature(who)
+                .map(|s| format!("{} <{}> {}", s.name(), s.email(), s.date(DateFormat::Raw)))
+                .unwrap_or_default(),
+            (field, modifier) => {
+                let Some((who, part)) = ["author", "committer", "tagger", "creator"]
+                    .iter()
+                    .find_map(|who| Some((*who, field.strip_prefix(who)?)))
+                else {
+                    return Err(GitError::any(format!("unknown field name: {atom}")));
+                };
+                let Some(signature) = self.signature(who) else {
+                    return Ok(String::new());
+                };
+                match (part, modifier) {
+                    ("name", None) => signature.name().clone(),
+                    ("email", None) => format!("<{}>", signature.email()),
+                    ("email", Some("trim")) => signature.email().clone(),
+                    ("date", modifier) => {
+                        signature.date(DateFormat::parse(modifier.unwrap_or("default"))?)
+                    }
+                    _ => return Err(GitError::any(format!("unknown field name: {atom}"))),
+                }
+            }
+        })
+    }
+    /// Expands `format` as bytes, so `%xx` can write any byte.
+    fn format(&self, format: &str) -> Result<Vec<u8>, GitError> {
+        let mut out = Vec::new();
+        let mut rest = format;
+        while let Some(i) = rest.find('%') {
+            out.extend(&rest.as_bytes()[..i]);
+            rest = &rest[i + 1..];
+            if let Some(after) = rest.strip_prefix('%') {
+                out.push(b'%');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('(') {
+                let end = after
+                    .find(')')
+                    .ok_or_else(|| GitError::any(format!("malformed format string {format}")))?;
+                out.extend(self.atom(&after[..end])?.into_bytes());
+                rest = &after[end + 1..];
+            } else if let Some(byte) = rest.get(..2).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                rest = &rest[2..];
+            } else {
+                out.push(b'%');
+            }
+        }
+        out.extend(rest.as_bytes());
+        Ok(out)
+    }
+    fn compare(&self, other: &Self, key: &str) -> Result<Ordering, GitError> {
+        Ok(match key {
+            "authordate" => self.timestamp("author").cmp(&other.timestamp("author")),
+            "committerdate" => self
+                .timestamp("committer")
+                .cmp(&other.timestamp("committer")),
+            "taggerdate" => self.timestamp("tagger").cmp(&other.timestamp("tagger")),
+            "creatordate" => self.timestamp("creator").cmp(&other.timestamp("creator")),
+            "objectsize" => self.size.cmp(&other.size),
+            key => self.atom(key)?.cmp(&other.atom(key)?),
+        })
+    }
+}
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    if pattern.contains(['*', '?', '[']) {
+        return wildmatch(pattern.as_bytes(), name.as_bytes());
+    }
+    let pattern = pattern.trim_end_matches('/');
+    name == pattern
+        || name
+            .strip_prefix(pattern)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+pub fn for_each_ref(args: Vec<String>) -> Result<(), GitError> {
+    let mut format = DEFAULT_FORMAT.to_string();
+    let mut sort_keys = Vec::new();
+    let mut count = None;
+    let mut patterns = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |option: &str| -> Result<Option<String>, GitError> {
+            if arg == option {
+                return args_iter
+                    .next()
+                    .cloned()
+                    .map(Some)
+                    .ok_or_else(|| GitError::any(format!("option {option} requires a value")));
+            }
+            Ok(arg.strip_prefix(&format!("{option}=")).map(str::to_string))
+        };
+        if let Some(value) = value("--format")? {
+            format = value;
+        } else if let Some(value) = value("--sort")? {
+            sort_keys.push(value);
+        } else if let Some(value) = value("--count")? {
+            count = Some(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| GitError::any(format!("invalid count: {value}")))?,
+            );
+        } else if arg.starts_with('-') {
+            return Err(GitError::any(format!("unknown option: {arg}")));
+        } else {
+            patterns.push(arg.clone());
+        }
+    }
+    let mut infos = Vec::new();
+    for (name, hash) in refs::list("refs/")? {
+        if patterns.is_empty() || patterns.iter().any(|p| matches_pattern(&name, p)) {
+            infos.push(RefInfo::load(name, hash)?);
+        }
+    }
+    let mut error = None;
+    for key in sort_keys.iter().rev() {
+        let (key, reverse) = match key.strip_prefix('-') {
+            Some(key) => (key, true),
+            None => (key.as_str(), false),
+        };
+        infos.sort_by(|a, b| match a.compare(b, key) {
+            Ok(ordering) if reverse => ordering.reverse(),
+            Ok(ordering) => ordering,
+            Err(e) => {
+                error.get_or_insert(e);
+                Ordering::Equal
+            }
+        });
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    let mut stdout = std::io::stdout().lock();
+    for info in infos.iter().take(count.unwrap_or(usize::MAX)) {
+        stdout.write_all(&info.format(&format)?)?;
+        stdout.write_all(b"\n")?;
+    }
+    Ok(())
+}
@@ -1,11 +1,161 @@
+use crate::attributes;
 use crate::error::GitError;
-use crate::objects::GitObject;
+use crate::fsck;
+use crate::objects::{self, GitObject, Kind};
+use std::fs;
+use std::io::{BufRead, Read};
+use std::path::Path;
+/// How each payload is turned into an object.
+struct Hasher {
+    /// The type name, which only `--literally` lets be one git does not
+    /// know.
+    kind: String,
+    write: bool,
+    literally: bool,
+    filters: bool,
+}
+/// Reports a commit or tag that fails the fsck checks.
+fn malformed(message: String) -> GitError {
+    eprintln!("error: object fails fsck: {message}");
+    GitError::any("refusing to create malformed object")
+}
+/// Checks that a tree payload is a run of well-formed entries.
+fn check_tree(mut data: &[u8]) -> Result<(), GitError> {
+    while !data.is_empty() {
+        if data.len() < 23 || data[data.len() - 21] != 0 {
+            return Err(GitError::any("too-short tree object"));
+        }
+        let space = data.iter().position(|&b| b == b' ').unwrap_or(data.len());
+        if !data[..space].iter().all(|b| (b'0'..=b'7').contains(b)) || space == data.len() {
+            return Err(GitError::any("malformed mode in tree entry"));
+        }
+        let nul = space + 1 + data[space + 1..].iter().position(|&b| b == 0).unwrap_or(0);
+        if nul == space + 1 {
+            return Err(GitError::any("empty filename in tree entry"));
+        }
+        if data.len() < nul + 21 {
+            return Err(GitError::any("too-short tree file"));
+        }
+        data = &data[nul + 21..];
+    }
+    Ok(())
+}
+impl Hasher {
+    /// Hashes `data`, cleaned as the attributes of `path` ask when it is a
+    /// blob with a path and filters are on. With `--literally` it is hashed
+    /// as is, whatever its type.
+    fn hash(&self, data: Vec<u8>, path: Option<&str>) -> Result<(), GitError> {
+        if self.literally {
+            println!(
+                "{}",
+                objects::hash_literally(&self.kind, &data, self.write)?
+            );
+            return Ok(());
+        }
+        let kind = Kind::parse(&self.kind)?;
+        let data = match (&kind, path) {
+            (Kind::Blob, Some(path)) if self.filters => {
+                attributes::clean(path.trim_start_matches("./"), data)?
+            }
+            _ => data,
+        };
+        match kind {
+            Kind::Blob => {}
+            Kind::Tree => check_tree(&data)?,
+            Kind::Commit => fsck::check_commit(&data).map_err(malformed)?,
+            Kind::Tag => {
+                fsck::check_tag(&data, false).map_err(malformed)?;
+            }
+        }
+        let git_object = GitObject::build(kind, data)?;
+        if self.write {
+            git_object.write()?;
+        }
+        println!("{}", git_object.hex_string());
+        Ok(())
+    }
+    fn hash_file(&self, file: &str, path: Option<&str>) -> Result<(), GitError> {
+        if Path::new(file).is_dir() {
+            let git_object = GitObject::from_path(file, self.write)?;
+            println!("{}", git_object.hex_string());
+            return Ok(());
+        }
+        let data = fs::read(file)
+            .map_err(|_| GitError::any(format!("could not open '{file}' for reading")))?;
+        self.hash(data, Some(path.unwrap_or(file)))
+    }
+}
 pub fn hash_object(args: Vec<String>) -> Result<(), GitError> {
-    let filename = args
-        .last()
-        .ok_or(GitError::any("missing filename to hash"))?;
-    let write = args.contains(&String::from("-w"));
-    let git_object = GitObject::from_path(filename, write)?;
-    println!("{}", git_object.hex_string());
+    let mut kind = String::from("blob");
+    let mut write = false;
+    let mut stdin = false;
+    let mut stdin_paths = false;
+    let mut no_filters = false;
+    let mut literally = false;
+    let mut path = None;
+    let mut files = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or_else(|| GitError::any(format!("switch `{name}' requires a value")))
+        };
+        match arg.as_str() {
+            "-t" => kind = value("t")?,
+            "-w" => write = true,
+            "--stdin" => stdin = true,
+            "--stdin-paths" => stdin_paths = true,
+            "--no-filters" => no_filters = true,
+            "--literally" => literally = true,
+            "--path" => path = Some(value("path")?),
+            "--" => files.extend(args_iter.by_ref()),
+            arg => {
+                if let Some(name) = arg.strip_prefix("-t") {
+                    kind = name.to_string();
+                } else if let Some(name) = arg.strip_prefix("--path=") {
+                    path = Some(name.to_string());
+                } else if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else {
+                    files.push(arg.to_string());
+                }
+            }
+        }
+    }
+    if stdin_paths && stdin {
+        return Err(GitError::any("Can't use --stdin-paths with --stdin"));
+    }
+    if stdin_paths && path.is_some() {
+        return Err(GitError::any("Can't use --stdin-paths with --path"));
+    }
+    if stdin_paths && !files.is_empty() {
+        return Err(GitError::any("Can't specify files with --stdin-paths"));
+    }
+    if path.is_some() && no_filters {
+        return Err(GitError::any("Can't use --path with --no-filters"));
+    }
+    if !literally {
+        Kind::parse(&kind)?;
+    }
+    let hasher = Hasher {
+        kind,
+        write,
+        literally,
+        filters: !no_filters,
+    };
+    if stdin {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        hasher.hash(data, path.as_deref())?;
+    }
+    for file in &files {
+        hasher.hash_file(file, path.as_deref())?;
+    }
+    if stdin_paths {
+        for line in std::io::stdin().lock().lines() {
+            hasher.hash_file(&line?, path.as_deref())?;
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
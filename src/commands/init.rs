@@ -1,10 +1,77 @@
 use crate::error::GitError;
+use crate::refs;
+use crate::reftable;
 use std::fs;
-pub fn init(_args: Vec<String>) -> Result<(), GitError> {
+/// Writes the repository config, recording the ref storage format.
+fn write_config(ref_format: &str) -> Result<(), GitError> {
+    let version = if ref_format == "reftable" { 1 } else { 0 };
+    let mut config = format!(
+        "[core]\n\trepositoryformatversion = {version}\n\tfilemode = true\n\tbare = false\n\tlogallrefupdates = true\n"
+    );
+    if ref_format == "reftable" {
+        config.push_str("[extensions]\n\trefStorage = reftable\n");
+    }
+    fs::write(".git/config", config)?;
+    Ok(())
+}
+pub fn init(args: Vec<String>) -> Result<(), GitError> {
+    let mut ref_format =
+        std::env::var("GIT_DEFAULT_REF_FORMAT").unwrap_or_else(|_| "files".to_string());
+    let mut initial_branch = None;
+    let mut quiet = false;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "-q" || arg == "--quiet" {
+            quiet = true;
+        } else if arg == "-b" || arg == "--initial-branch" {
+            initial_branch = Some(
+                args_iter
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| GitError::any(format!("option {arg} requires a value")))?,
+            );
+        } else if let Some(branch) = arg.strip_prefix("--initial-branch=") {
+            initial_branch = Some(branch.to_string());
+        } else if arg == "--ref-format" {
+            ref_format = args_iter
+                .next()
+                .cloned()
+                .ok_or(GitError::any("option --ref-format requires a value"))?;
+        } else if let Some(format) = arg.strip_prefix("--ref-format=") {
+            ref_format = format.to_string();
+        } else {
+            return Err(GitError::any(format!("unknown option: {arg}")));
+        }
+    }
+    if !matches!(ref_format.as_str(), "files" | "reftable") {
+        return Err(GitError::any(format!(
+            "unknown ref storage format '{ref_format}'"
+        )));
+    }
+    let branch = match initial_branch {
+        Some(branch) => branch,
+        None => refs::default_branch()?,
+    };
+    let head = format!("refs/heads/{branch}");
+    refs::check_name(&head)
+        .map_err(|_| GitError::any(format!("invalid initial branch name: '{branch}'")))?;
     fs::create_dir(".git")?;
     fs::create_dir(".git/objects")?;
     fs::create_dir(".git/refs")?;
-    fs::write(".git/HEAD", "ref: refs/heads/main\n")?;
-    println!("Initialized git directory");
+    write_config(&ref_format)?;
+    if ref_format == "reftable" {
+        // Keep older tools that look for these from treating .git as a
+        // repository with loose refs.
+        fs::write(".git/HEAD", "ref: refs/heads/.invalid\n")?;
+        fs::write(".git/refs/heads", "")?;
+        reftable::init()?;
+    } else {
+        fs::create_dir(".git/refs/heads")?;
+        fs::create_dir(".git/refs/tags")?;
+    }
+    refs::set_symbolic("HEAD", &head, None)?;
+    if !quiet {
+        println!("Initialized git directory");
+    }
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,611 @@
+use crate::commit::Commit;
+use crate::date::DateFormat;
+use crate::diff::{
+    compare, detect_renames, tree_snapshot, write_diffs, DiffOptions, Snapshot, DEFAULT_SIMILARITY,
+};
+use crate::error::GitError;
+use crate::objects::{short_hash, GitObject};
+use crate::refs;
+use crate::revision::{resolve_range, RevSpec};
+use crate::revwalk::{topo_order, RevWalk};
+use std::io::Write;
+use std::path::Path;
+#[derive(Clone, Debug, PartialEq)]
+enum Pretty {
+    Oneline,
+    Short,
+    Medium,
+    Full,
+    Fuller,
+    Raw,
+    /// `tformat:`, whose entries each end in a newline.
+    Format(String),
+    /// `format:`, whose entries are separated by newlines instead.
+    Separated(String),
+}
+struct LogOptions {
+    pretty: Pretty,
+    abbrev: bool,
+    date: DateFormat,
+    max_count: Option<usize>,
+    graph: bool,
+    topo_order: bool,
+    follow: bool,
+    show_diff: bool,
+    diff: DiffOptions,
+}
+/// Expands the placeholders of a `--format` string. The result is bytes,
+/// since `%x` can insert any byte.
+fn expand(format: &str, commit: &Commit, date: DateFormat) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        let Some(spec) = chars.next() else {
+            out.push(b'%');
+            break;
+        };
+        match spec {
+            '%' => out.push(b'%'),
+            'n' => out.push(b'\n'),
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) => out.push(byte),
+                    Err(_) => out.extend(format!("%x{digits}").as_bytes()),
+                }
+            }
+            'H' => out.extend(commit.hash().as_bytes()),
+            'h' => out.extend(short_hash(commit.hash()).as_bytes()),
+            'T' => out.extend(commit.tree().as_bytes()),
+            't' => out.extend(short_hash(commit.tree()).as_bytes()),
+            'P' => out.extend(commit.parents().join(" ").as_bytes()),
+            'p' => {
+                let parents: Vec<&str> = commit.parents().iter().map(|p| short_hash(p)).collect();
+                out.extend(parents.join(" ").as_bytes());
+            }
+            's' => out.extend(commit.subject().as_bytes()),
+            'b' => out.extend(commit.body().as_bytes()),
+            'B' => out.extend(commit.message().as_bytes()),
+            'a' | 'c' => {
+                let signature = if spec == 'a' {
+                    commit.author()
+                } else {
+                    commit.committer()
+                };
+                match chars.next() {
+                    Some('n') => out.extend(signature.name().as_bytes()),
+                    Some('e') => out.extend(signature.email().as_bytes()),
+                    Some('d') => out.extend(signature.date(date).as_bytes()),
+                    Some('D') => out.extend(signature.date(DateFormat::Rfc2822).as_bytes()),
+                    Some('t') => out.extend(signature.time().to_string().as_bytes()),
+                    Some('r') => out.extend(signature.date(DateFormat::Relative).as_bytes()),
+                    Some('i') => out.extend(signature.date(DateFormat::Iso).as_bytes()),
+                    Some('I') => out.extend(signature.date(DateFormat::IsoStrict).as_bytes()),
+                    Some(other) => out.extend(format!("%{spec}{other}").as_bytes()),
+                    None => out.extend(format!("%{spec}").as_bytes()),
+                }
+            }
+            other => out.extend(format!("%{other}").as_bytes()),
+        }
+    }
+    out
+}
+fn indent_message(message: &str) -> Vec<String> {
+    message
+        .trim_end_matches('\n')
+        .lines()
+        .map(|line| format!("    {line}"))
+        .collect()
+}
+fn render(commit: &Commit, options: &LogOptions) -> Vec<Vec<u8>> {
+    let hash = if options.abbrev {
+        short_hash(commit.hash()).to_string()
+    } else {
+        commit.hash().clone()
+    };
+    let mut lines = Vec::new();
+    let merge = || {
+        let parents: Vec<&str> = commit.parents().iter().map(|p| short_hash(p)).collect();
+        format!("Merge: {}", parents.join(" "))
+    };
+    match &options.pretty {
+        Pretty::Oneline => lines.push(format!("{hash} {}", commit.subject())),
+        Pretty::Format(format) | Pretty::Separated(format) => {
+            return expand(format, commit, options.date)
+                .split(|&b| b == b'\n')
+                .map(<[u8]>::to_vec)
+                .collect();
+        }
+        Pretty::Raw => {
+            lines.push(format!("commit {}", commit.hash()));
+            lines.push(format!("tree {}", commit.tree()));
+            for parent in commit.parents() {
+                lines.push(format!("parent {parent}"));
+            }
+            lines.push(format!("author {}", commit.author()));
+            lines.push(format!("committer {}", commit.committer()));
+            for (key, value) in commit.extra_headers() {
+                lines.push(format!("{key} {}", value.replace('\n', "\n ")));
+            }
+            lines.push(String::new());
+            lines.extend(indent_message(commit.message()));
+        }
+        pretty => {
+            lines.push(format!("commit {hash}"));
+            if commit.parents().len() > 1 {
+                lines.push(merge());
+            }
+            let author = commit.author();
+            let committer = commit.committer();
+            match pretty {
+                Pretty::Short => {
+                    lines.push(format!("Author: {} <{}>", author.name(), author.email()));
+                }
+                Pretty::Full => {
+                    lines.push(format!("Author: {} <{}>", author.name(), author.email()));
+                    lines.push(format!(
+                        "Commit: {} <{}>",
+                        committer.name(),
+                        committer.email()
+                    ));
+                }
+                Pretty::Fuller => {
+                    lines.push(format!(
+                        "Author:     {} <{}>",
+                        author.name(),
+                        author.email()
+                    ));
+                    lines.push(format!("AuthorDate: {}", author.date(options.date)));
+                    lines.push(format!(
+                        "Commit:     {} <{}>",
+                        committer.name(),
+                        committer.email()
+                    ));
+                    lines.push(format!("CommitDate: {}", committer.date(options.date)));
+                }
+                _ => {
+                    lines.push(format!("Author: {} <{}>", author.name(), author.email()));
+                    lines.push(format!("Date:   {}", author.date(options.date)));
+                }
+            }
+            lines.push(String::new());
+            if *pretty == Pretty::Short {
+                lines.push(format!("    {}", commit.subject()));
+            } else {
+                lines.extend(indent_message(commit.message()));
+            }
+        }
+    }
+    lines.into_iter().map(String::into_bytes).collect()
+}
+/// Writes `line` after `prefix`, keeping the bytes of `line` as they are.
+fn write_line(out: &mut impl Write, prefix: &str, line: &[u8]) -> Result<(), GitError> {
+    out.write_all(prefix.as_bytes())?;
+    out.write_all(line)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+struct GraphRows {
+    separator: String,
+    row: String,
+    transitions: Vec<String>,
+    continuation: String,
+}
+#[derive(Default)]
+struct Graph {
+    lanes: Vec<String>,
+}
+impl Graph {
+    fn draw(lanes: usize) -> String {
+        vec!["|"; lanes].join(" ")
+    }
+    fn update(&mut self, commit: &Commit, parents: &[String]) -> GraphRows {
+        let col = match self.lanes.iter().position(|h| h == commit.hash()) {
+            Some(col) => col,
+            None => {
+                self.lanes.push(commit.hash().clone());
+                self.lanes.len() - 1
+            }
+        };
+        let before = self.lanes.len();
+        let separator = Self::draw(before);
+        let mut row: Vec<&str> = vec!["|"; before];
+        row[col] = "*";
+        let row = row.join(" ");
+        let mut transitions = Vec::new();
+        match parents.first() {
+            Some(first) => self.lanes[col] = first.clone(),
+            None => {
+                self.lanes.remove(col);
+            }
+        }
+        let mut inserted = 0;
+        for parent in parents.iter().skip(1) {
+            if !self.lanes.contains(parent) {
+                inserted += 1;
+                self.lanes.insert(col + inserted, parent.clone());
+            }
+        }
+        if inserted > 0 {
+            let mut line = Self::draw(col + 1);
+            line.push_str(&"\\".repeat(inserted));
+            for _ in col + 1..before {
+                line.push_str(" \\");
+            }
+            transitions.push(line);
+        }
+        let mut lane = 0;
+        while lane < self.lanes.len() {
+            if self.lanes[..lane].contains(&self.lanes[lane]) {
+                self.lanes.remove(lane);
+                let mut line = Self::draw(lane);
+                line.push('/');
+                for _ in lane..self.lanes.len() {
+                    line.push_str(" /");
+                }
+                transitions.push(line);
+            } else {
+                lane += 1;
+            }
+        }
+        if parents.is_empty() && col < self.lanes.len() {
+            let mut line = Self::draw(col);
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&vec!["/"; self.lanes.len() - col].join(" "));
+            transitions.push(line);
+        }
+        let width = 2 * before.max(self.lanes.len()).max(1);
+        let pad = |line: String| format!("{line:<width$}");
+        GraphRows {
+            separator: pad(separator),
+            row: pad(row),
+            transitions: transitions.into_iter().map(pad).collect(),
+            continuation: pad(Self::draw(self.lanes.len())),
+        }
+    }
+}
+/// The entries of `tree` that `pathspecs` name, found by reading only the
+/// trees along their paths. Directories are kept whole as tree entries,
+/// so two commits are the same under the pathspecs when these are equal.
+fn path_entries(tree: &str, pathspecs: &[&String]) -> Result<Snapshot, GitError> {
+    let mut entries = Snapshot::new();
+    for spec in pathspecs {
+        let components: Vec<&str> = spec
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect();
+        let mut entry = Some(("40000".to_string(), hex::decode(tree)?));
+        for component in &components {
+            entry = match entry {
+                Some((mode, hash)) if mode == "40000" => {
+                    GitObject::from_hex_string(hex::encode(hash))?
+                        .tree_entries()?
+                        .into_iter()
+                        .find(|e| e.filename() == component)
+                        .map(|e| (e.mode().clone(), e.hash().clone()))
+                }
+                _ => None,
+            };
+        }
+        if let Some(entry) = entry {
+            entries.insert(components.join("/"), entry);
+        }
+    }
+    Ok(entries)
+}
+/// The files of `tree` under `pathspecs`, reading only the trees on the
+/// way to them and below them.
+fn path_snapshot(tree: &str, pathspecs: &[&String]) -> Result<Snapshot, GitError> {
+    if pathspecs.is_empty() {
+        return tree_snapshot(tree, true);
+    }
+    let mut snapshot = Snapshot::new();
+    for (path, (mode, hash)) in path_entries(tree, pathspecs)? {
+        if mode != "40000" {
+            snapshot.insert(path, (mode, hash));
+            continue;
+        }
+        for (file, entry) in tree_snapshot(&hex::encode(hash), true)? {
+            let file = match path.as_str() {
+                "" => file,
+                dir => format!("{dir}/{file}"),
+            };
+            snapshot.insert(file, entry);
+        }
+    }
+    Ok(snapshot)
+}
+fn follow_rename(
+    commit: &Commit,
+    parent: &str,
+    path: &str,
+    options: &DiffOptions,
+) -> Result<Option<String>, GitError> {
+    let parent_tree = Commit::from_hex_string(parent)?.tree().clone();
+    let old = tree_snapshot(&parent_tree, true)?;
+    let new = tree_snapshot(commit.tree(), true)?;
+    let diffs = detect_renames(compare(&old, &new, false)?, &old, options)?;
+    Ok(diffs
+        .into_iter()
+        .find(|d| d.new_path.as_deref() == Some(path) && d.score.is_some())
+        .and_then(|d| d.old_path))
+}
+pub fn log(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = LogOptions {
+        pretty: Pretty::Medium,
+        abbrev: false,
+        date: DateFormat::Default,
+        max_count: None,
+        graph: false,
+        topo_order: false,
+        follow: false,
+        show_diff: false,
+        diff: DiffOptions {
+            renames: Some(DEFAULT_SIMILARITY),
+            ..DiffOptions::default()
+        },
+    };
+    options.diff.configure_rename_limit(&["diff.renameLimit"])?;
+    let mut revs = Vec::new();
+    let mut pathspecs: Vec<String> = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        let arg = arg.as_str();
+        match arg {
+            "--" => pathspecs.extend(args_iter.by_ref().cloned()),
+            "--oneline" => {
+                options.pretty = Pretty::Oneline;
+                options.abbrev = true;
+            }
+            "--abbrev-commit" => options.abbrev = true,
+            "--graph" => options.graph = true,
+            "--topo-order" => options.topo_order = true,
+            "--date-order" => options.topo_order = false,
+            "--follow" => options.follow = true,
+            "-n" => {
+                let count = args_iter
+                    .next()
+                    .ok_or(GitError::any("-n requires a value"))?;
+                options.max_count = Some(
+                    count
+                        .parse()
+                        .map_err(|_| GitError::any(format!("invalid count: {count}")))?,
+                );
+            }
+            "-p" | "-u" | "--patch" | "--stat" | "--numstat" | "--name-status" | "--name-only"
+            | "--raw" => {
+                options.show_diff = true;
+                options
+                    .diff
+                    .parse_arg(if arg == "-u" { "-p" } else { arg })?;
+            }
+            _ => {
+                if let Some(pretty) = arg
+                    .strip_prefix("--pretty=")
+                    .or_else(|| arg.strip_prefix("--format="))
+                {
+                    options.pretty = match pretty {
+                        "oneline" => Pretty::Oneline,
+                        "short" => Pretty::Short,
+                        "medium" => Pretty::Medium,
+                        "full" => Pretty::Full,
+                        "fuller" => Pretty::Fuller,
+                        "raw" => Pretty::Raw,
+                        format => match format.strip_prefix("format:") {
+                            Some(format) => Pretty::Separated(format.to_string()),
+                            None => Pretty::Format(
+                                format
+                                    .strip_prefix("tformat:")
+                                    .unwrap_or(format)
+                                    .to_string(),
+                            ),
+                        },
+                    };
+                } else if let Some(date) = arg.strip_prefix("--date=") {
+                    options.date = DateFormat::parse(date)?;
+                } else if let Some(count) = arg
+                    .strip_prefix("--max-count=")
+                    .or_else(|| arg.strip_prefix("-n"))
+                    .or_else(|| arg.strip_prefix('-').filter(|n| n.parse::<usize>().is_ok()))
+                {
+                    options.max_count = Some(
+                        count
+                            .parse()
+                            .map_err(|_| GitError::any(format!("invalid count: {count}")))?,
+                    );
+                } else if options.diff.parse_arg(arg)? {
+                } else if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else if let Ok(specs) = resolve_range(arg) {
+                    revs.extend(specs);
+                } else if Path::new(arg).exists() {
+                    pathspecs.push(arg.to_string());
+                } else {
+                    return Err(GitError::any(format!(
+                        "ambiguous argument '{arg}': unknown revision or path not in the working tree"
+                    )));
+                }
+            }
+        }
+    }
+    if !revs.iter().any(|spec| matches!(spec, RevSpec::Include(_))) {
+        match refs::resolve("HEAD")? {
+            Some(hash) => revs.push(RevSpec::Include(hash)),
+            None => {
+                return Err(GitError::any(
+                    "your current branch does not have any commits yet",
+                ))
+            }
+        }
+    }
+    if options.follow && pathspecs.len() != 1 {
+        return Err(GitError::any("--follow requires exactly one pathspec"));
+    }
+    let rename_options = DiffOptions {
+        renames: Some(options.diff.renames.unwrap_or(DEFAULT_SIMILARITY)),
+        rename_limit: options.diff.rename_limit,
+        ..DiffOptions::default()
+    };
+    let mut walk = RevWalk::new();
+    for rev in &revs {
+        if let RevSpec::Exclude(hash) = rev {
+            walk.hide(hash)?;
+        }
+    }
+    for rev in &revs {
+        if let RevSpec::Include(hash) = rev {
+            walk.push(hash)?;
+        }
+    }
+    let mut entries = Vec::new();
+    while let Some(commit) = walk.pop()? {
+        let current = pathspecs.clone();
+        let specs: Vec<&String> = current.iter().collect();
+        let mut parents = commit.parents().clone();
+        let mut show = true;
+        if !specs.is_empty() {
+            let own = path_entries(commit.tree(), &specs)?;
+            let mut treesame = None;
+            for parent in commit.parents() {
+                let parent_tree = Commit::from_hex_string(parent)?.tree().clone();
+                if path_entries(&parent_tree, &specs)? == own {
+                    treesame = Some(parent.clone());
+                    break;
+                }
+            }
+            match treesame {
+                Some(parent) => {
+                    show = false;
+                    parents = vec![parent];
+                }
+                None => show = !own.is_empty() || !commit.parents().is_empty(),
+            }
+            if options.follow && show {
+                if let Some(parent) = commit.parents().first() {
+                    let path = pathspecs[0].clone();
+                    let parent_tree = Commit::from_hex_string(parent)?.tree().clone();
+                    if path_entries(&parent_tree, &specs)?.is_empty() {
+                        if let Some(old) = follow_rename(&commit, parent, &path, &rename_options)? {
+                            pathspecs[0] = old;
+                        }
+                    }
+                }
+            }
+        }
+        for parent in &parents {
+            walk.push(parent)?;
+        }
+        // A followed rename shows in the diff as the file's old name.
+        let specs = (pathspecs.clone(), current);
+        entries.push((commit, parents, show, specs));
+        if !options.graph && !options.topo_order {
+            let shown = entries.iter().filter(|(_, _, show, _)| *show).count();
+            if options.max_count.is_some_and(|max| shown >= max) {
+                break;
+            }
+        }
+    }
+    if options.graph || options.topo_order {
+        entries = topo_order(entries, |(commit, parents, _, _)| (commit.hash(), parents));
+    }
+    let mut graph = Graph::default();
+    let mut shown = 0;
+    let mut stdout = std::io::stdout().lock();
+    let separator = !matches!(
+        options.pretty,
+        Pretty::Oneline | Pretty::Format(_) | Pretty::Separated(_)
+    );
+    // `format:` leaves each entry's last line open, to be ended by the
+    // separator before the next entry or by the diff after it.
+    let open_ended = matches!(options.pretty, Pretty::Separated(_));
+    for (commit, parents, show, (old_specs, new_specs)) in entries {
+        if options.max_count.is_some_and(|max| shown >= max) {
+            break;
+        }
+        if !show {
+            if options.graph {
+                graph.update(&commit, &parents);
+            }
+            continue;
+        }
+        let mut lines = render(&commit, &options);
+        let diff = if options.show_diff && commit.parents().len() <= 1 {
+            let old_specs: Vec<&String> = old_specs.iter().collect();
+            let new_specs: Vec<&String> = new_specs.iter().collect();
+            let old = match commit.parents().first() {
+                Some(parent) => path_snapshot(Commit::from_hex_string(parent)?.tree(), &old_specs)?,
+                None => Snapshot::new(),
+            };
+            let new = path_snapshot(commit.tree(), &new_specs)?;
+            Some(detect_renames(
+                compare(&old, &new, false)?,
+                &old,
+                &options.diff,
+            )?)
+        } else {
+            None
+        };
+        let mut prefix = String::new();
+        let mut entry = Vec::new();
+        if open_ended && shown > 0 {
+            stdout.write_all(b"\n")?;
+        }
+        if options.graph {
+            let rows = graph.update(&commit, &parents);
+            if separator && shown > 0 {
+                write_line(&mut stdout, &rows.separator, b"")?;
+            }
+            let mut rest = lines.split_off(1.min(lines.len()));
+            write_line(
+                &mut entry,
+                &rows.row,
+                lines.first().map(Vec::as_slice).unwrap_or_default(),
+            )?;
+            for transition in rows.transitions {
+                let text = if rest.is_empty() {
+                    Vec::new()
+                } else {
+                    rest.remove(0)
+                };
+                write_line(&mut entry, &transition, &text)?;
+            }
+            for line in rest {
+                write_line(&mut entry, &rows.continuation, &line)?;
+            }
+            prefix = rows.continuation;
+        } else {
+            if separator && shown > 0 {
+                write_line(&mut stdout, "", b"")?;
+            }
+            for line in lines {
+                write_line(&mut entry, "", &line)?;
+            }
+        }
+        if open_ended {
+            entry.pop();
+        }
+        stdout.write_all(&entry)?;
+        shown += 1;
+        if let Some(diffs) = diff.filter(|diffs| !diffs.is_empty()) {
+            let mut out = Vec::new();
+            write_diffs(&mut out, &diffs, &options.diff)?;
+            if open_ended {
+                stdout.write_all(b"\n")?;
+            } else if options.pretty != Pretty::Oneline {
+                write_line(&mut stdout, &prefix, b"")?;
+            }
+            for line in out.split_inclusive(|&b| b == b'\n') {
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                write_line(&mut stdout, &prefix, line)?;
+            }
+        }
+    }
+    options.diff.warn_rename_limit("diff.renameLimit");
+    Ok(())
+}
@@ -1,12 +1,10 @@
 use crate::error::GitError;
 use crate::objects::GitObject;
+use crate::revision::resolve_tree;
 pub fn ls_tree(args: Vec<String>) -> Result<(), GitError> {
-    let object_hash = args.last().ok_or(GitError::any("missing tree hash"))?;
-    if object_hash.len() != 40 {
-        return Err(GitError::any("invalid object hash"));
-    };
+    let rev = args.last().ok_or(GitError::any("missing tree hash"))?;
     let name_only = args.contains(&String::from("--name-only"));
-    let git_object = GitObject::from_hex_string(object_hash)?;
+    let git_object = GitObject::from_hex_string(resolve_tree(rev)?)?;
     let tree_entries = git_object.tree_entries()?;
     for e in tree_entries {
         if name_only {
@@ -22,4 +20,4 @@ pub fn ls_tree(args: Vec<String>) -> Result<(), GitError> {
         };
     }
     Ok(())
-}
\ No newline at end of file
+}
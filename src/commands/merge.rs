@@ -0,0 +1,319 @@
+use crate::commit::Commit;
+use crate::date::DateFormat;
+use crate::diff::{index_snapshot, print_stat, tree_snapshot, Snapshot};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::merge::{merge_commits, ConflictStyle, Labels};
+use crate::objects::{short_hash, GitObject};
+use crate::refs;
+use crate::revision::{dwim_ref, resolve_commit};
+use crate::revwalk::{merge_bases, RevWalk};
+use crate::state::{self, MERGE_HEAD, MERGE_MODE, MERGE_MSG, ORIG_HEAD, SQUASH_MSG};
+use crate::worktree::{checkout_entry, checkout_tree, remove_file, update_tree};
+use std::collections::BTreeSet;
+use std::fs;
+const FAILED: &str = "Merge with strategy ort failed.";
+#[derive(Clone, Copy, PartialEq)]
+enum FastForward {
+    Allow,
+    Never,
+    Only,
+}
+struct Options {
+    fast_forward: FastForward,
+    squash: bool,
+    commit: bool,
+    stat: bool,
+    message: Option<String>,
+}
+/// The default merge commit message, naming what was merged the way git's
+/// `fmt-merge-msg` does.
+fn merge_message(name: &str) -> Result<String, GitError> {
+    let refname = dwim_ref(name)?.map(|(refname, _)| refname);
+    let prefixes = [
+        ("refs/heads/", "branch"),
+        ("refs/remotes/", "remote-tracking branch"),
+        ("refs/tags/", "tag"),
+    ];
+    let what = prefixes
+        .iter()
+        .find_map(|(prefix, kind)| {
+            let short = refname.as_deref()?.strip_prefix(prefix)?;
+            Some(format!("{kind} '{short}'"))
+        })
+        .unwrap_or_else(|| format!("commit '{name}'"));
+    Ok(match refs::head_branch()? {
+        Some(branch) if branch != "main" && branch != "master" => {
+            format!("Merge {what} into {branch}")
+        }
+        _ => format!("Merge {what}"),
+    })
+}
+/// The message `--squash` leaves behind: the log of every commit merged.
+fn squash_message(head: &str, theirs: &str) -> Result<String, GitError> {
+    let mut message = String::from("Squashed commit of the following:\n");
+    let mut walk = RevWalk::new();
+    walk.hide(head)?;
+    walk.push(theirs)?;
+    while let Some(commit) = walk.pop()? {
+        let author = commit.author();
+        message.push_str(&format!(
+            "\ncommit {}\nAuthor: {} <{}>\nDate:   {}\n\n",
+            commit.hash(),
+            author.name(),
+            author.email(),
+            author.date(DateFormat::Default)
+        ));
+        for line in commit.message().trim_end_matches('\n').lines() {
+            message.push_str(&format!("    {line}\n"));
+        }
+        for parent in commit.parents() {
+            walk.push(parent)?;
+        }
+    }
+    Ok(message)
+}
+/// Refuses to merge over changes staged relative to HEAD.
+fn check_index(index: &Index, head: &Snapshot) -> Result<(), GitError> {
+    let staged = index_snapshot(index);
+    let changed: BTreeSet<&String> = staged
+        .keys()
+        .chain(head.keys())
+        .filter(|path| staged.get(*path) != head.get(*path))
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let paths: String = changed.iter().map(|path| format!("  {path}\n")).collect();
+    Err(GitError::any(format!(
+        "Your local changes to the following files would be overwritten by merge:\n{paths}{FAILED}"
+    )))
+}
+fn fast_forward(name: &str, head: &str, theirs: &str, options: &Options) -> Result<(), GitError> {
+    let old = tree_snapshot(Commit::from_hex_string(head)?.tree(), true)?;
+    let new = tree_snapshot(Commit::from_hex_string(theirs)?.tree(), true)?;
+    println!("Updating {}..{}", short_hash(head), short_hash(theirs));
+    update_tree(&old, &new, false, "merge")?;
+    println!("Fast-forward");
+    if options.squash {
+        fs::write(SQUASH_MSG, squash_message(head, theirs)?)?;
+        println!("Squash commit -- not updating HEAD");
+    } else {
+        fs::write(ORIG_HEAD, format!("{head}\n"))?;
+        let message = format!("merge {name}: Fast-forward");
+        refs::update("HEAD", theirs, Some(head), true, &message)?;
+    }
+    if options.stat {
+        print_stat(&old, &new, false)?;
+    }
+    Ok(())
+}
+fn true_merge(name: &str, head: &str, theirs: &str, options: &Options) -> Result<(), GitError> {
+    let head_snapshot = tree_snapshot(Commit::from_hex_string(head)?.tree(), true)?;
+    check_index(&Index::load()?, &head_snapshot)?;
+    let mut labels = Labels {
+        base: String::new(),
+        ours: "HEAD".to_string(),
+        theirs: name.to_string(),
+    };
+    let merge = merge_commits(head, theirs, &mut labels, ConflictStyle::from_config()?)?;
+    update_tree(&head_snapshot, &merge.result, false, "merge")
+        .map_err(|e| GitError::any(format!("{e}\n{FAILED}")))?;
+    for message in merge.messages() {
+        println!("{message}");
+    }
+    merge.stage_conflicts()?;
+    fs::write(ORIG_HEAD, format!("{head}\n"))?;
+    let mut message = match &options.message {
+        Some(message) => message.clone(),
+        None => merge_message(name)?,
+    };
+    if !message.ends_with('\n') {
+        message.push('\n');
+    }
+    if options.squash {
+        fs::write(SQUASH_MSG, squash_message(head, theirs)?)?;
+    }
+    if !merge.is_clean() {
+        message.push_str("\n# Conflicts:\n");
+        for path in merge.conflicts.keys() {
+            message.push_str(&format!("#\t{path}\n"));
+        }
+        if !options.squash {
+            write_merge_state(theirs, &message, options)?;
+        }
+        println!("Automatic merge failed; fix conflicts and then commit the result.");
+        return Err(GitError::exit(1));
+    }
+    if options.squash || !options.commit {
+        if !options.squash {
+            write_merge_state(theirs, &message, options)?;
+        }
+        println!("Automatic merge went well; stopped before committing as requested");
+        if options.squash {
+            println!("Squash commit -- not updating HEAD");
+        }
+        return Ok(());
+    }
+    let tree = Index::load()?.write_tree()?;
+    let commit = GitObject::build_commit(message.trim_end(), &tree, [head, theirs])?;
+    commit.write()?;
+    let reflog = format!("merge {name}: Merge made by the 'ort' strategy.");
+    refs::update("HEAD", &commit.hex_string(), Some(head), true, &reflog)?;
+    println!("Merge made by the 'ort' strategy.");
+    if options.stat {
+        print_stat(&head_snapshot, &tree_snapshot(&tree, true)?, false)?;
+    }
+    Ok(())
+}
+/// Records an unfinished merge for the commit that concludes it.
+fn write_merge_state(theirs: &str, message: &str, options: &Options) -> Result<(), GitError> {
+    fs::write(MERGE_HEAD, format!("{theirs}\n"))?;
+    fs::write(MERGE_MSG, message)?;
+    let mode = match options.fast_forward {
+        FastForward::Never => "no-ff",
+        _ => "",
+    };
+    fs::write(MERGE_MODE, mode)?;
+    Ok(())
+}
+/// Throws away an unfinished merge: paths whose index entries differ from
+/// HEAD, conflicted ones included, go back to HEAD's version, while other
+/// local changes are kept.
+fn abort() -> Result<(), GitError> {
+    if fs::metadata(MERGE_HEAD).is_err() {
+        return Err(GitError::any(
+            "There is no merge to abort (MERGE_HEAD missing).",
+        ));
+    }
+    let head = refs::resolve("HEAD")?.ok_or(GitError::any("HEAD does not point to a commit"))?;
+    let head = tree_snapshot(Commit::from_hex_string(head)?.tree(), true)?;
+    let mut index = Index::load()?;
+    let staged = index_snapshot(&index);
+    let paths: BTreeSet<String> = head
+        .keys()
+        .chain(index.entries().iter().map(|entry| entry.path()))
+        .cloned()
+        .collect();
+    let changed: Vec<&String> = paths
+        .iter()
+        .filter(|path| {
+            let unmerged = !staged.contains_key(*path) && index.contains(path);
+            staged.get(*path) != head.get(*path) || unmerged
+        })
+        .collect();
+    // Removals go first so that a directory the merge put where HEAD has a
+    // file is out of the way before the file comes back.
+    for path in changed.iter().filter(|path| !head.contains_key(**path)) {
+        index.remove(path);
+        remove_file(path)?;
+    }
+    for path in changed {
+        if let Some((mode, hash)) = head.get(path) {
+            checkout_entry(&mut index, path, mode, hash)?;
+        }
+    }
+    index.write()?;
+    state::remove_all(&[MERGE_HEAD, MERGE_MSG, MERGE_MODE])?;
+    Ok(())
+}
+pub fn merge(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = Options {
+        fast_forward: FastForward::Allow,
+        squash: false,
+        commit: true,
+        stat: true,
+        message: None,
+    };
+    let mut heads = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--abort" => return abort(),
+            "--ff" => options.fast_forward = FastForward::Allow,
+            "--no-ff" => options.fast_forward = FastForward::Never,
+            "--ff-only" => options.fast_forward = FastForward::Only,
+            "--squash" => options.squash = true,
+            "--no-squash" => options.squash = false,
+            "--commit" => options.commit = true,
+            "--no-commit" => options.commit = false,
+            "--stat" => options.stat = true,
+            "-n" | "--no-stat" => options.stat = false,
+            "--no-edit" | "-q" | "--quiet" => {}
+            "-m" => {
+                let message = args_iter
+                    .next()
+                    .ok_or(GitError::any("switch `m' requires a value"))?;
+                options.message = Some(match options.message.take() {
+                    Some(previous) => format!("{previous}\n\n{message}"),
+                    None => message,
+                });
+            }
+            "-s" | "--strategy" => match args_iter.next().as_deref() {
+                Some("ort" | "recursive") => {}
+                Some(strategy) => {
+                    return Err(GitError::any(format!(
+                        "Could not find merge strategy '{strategy}'."
+                    )))
+                }
+                None => return Err(GitError::any("switch `s' requires a value")),
+            },
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => heads.push(arg),
+        }
+    }
+    if options.squash && options.fast_forward == FastForward::Never {
+        return Err(GitError::any(
+            "options '--squash' and '--no-ff.' cannot be used together",
+        ));
+    }
+    if fs::metadata(MERGE_HEAD).is_ok() {
+        return Err(GitError::any(
+            "You have not concluded your merge (MERGE_HEAD exists).\nPlease, commit your changes before you merge.",
+        ));
+    }
+    if Index::load()?
+        .entries()
+        .iter()
+        .any(|entry| entry.stage() > 0)
+    {
+        return Err(GitError::any(
+            "Merging is not possible because you have unmerged files.\nhint: Fix them up in the work tree, and then use 'git add/rm <file>'\nhint: as appropriate to mark resolution and make a commit.",
+        ));
+    }
+    let name = match heads.as_slice() {
+        [] => {
+            return Err(GitError::any(
+                "No commit specified and merge.defaultToUpstream not set.",
+            ))
+        }
+        [name] => name,
+        _ => {
+            return Err(GitError::any(
+                "merging more than one commit is not supported",
+            ))
+        }
+    };
+    let theirs = resolve_commit(name)
+        .map_err(|_| GitError::any(format!("merge: {name} - not something we can merge")))?;
+    let Some(head) = refs::resolve("HEAD")? else {
+        let tree = Commit::from_hex_string(&theirs)?.tree().clone();
+        checkout_tree(None, &tree, false)?;
+        return refs::update("HEAD", &theirs, None, true, "initial pull");
+    };
+    let bases = merge_bases(&head, &theirs)?;
+    if bases.contains(&theirs) {
+        println!("Already up to date.");
+        return Ok(());
+    }
+    let fast_forwards = bases == [head.clone()];
+    match options.fast_forward {
+        FastForward::Allow | FastForward::Only if fast_forwards => {
+            fast_forward(name, &head, &theirs, &options)
+        }
+        FastForward::Only => Err(GitError::any("Not possible to fast-forward, aborting.")),
+        _ => true_merge(name, &head, &theirs, &options),
+    }
+}
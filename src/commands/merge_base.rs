@@ -0,0 +1,106 @@
+use crate::commit::Commit;
+use crate::error::GitError;
+use crate::reflog;
+use crate::refs;
+use crate::revision::{dwim_ref, resolve_commit};
+use crate::revwalk::{independent, is_ancestor, merge_bases, merge_bases_many};
+/// Sorts commits newest first, the order git reports merge bases in.
+fn by_date(commits: Vec<String>) -> Result<Vec<String>, GitError> {
+    let mut dated = Vec::new();
+    for hash in commits {
+        dated.push((Commit::from_hex_string(&hash)?.committer().time(), hash));
+    }
+    dated.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+    Ok(dated.into_iter().map(|(_, hash)| hash).collect())
+}
+/// The commits merged with `--octopus`: the common ancestors of all of them.
+fn octopus(commits: &[String]) -> Result<Vec<String>, GitError> {
+    let mut bases = vec![commits[0].clone()];
+    for commit in &commits[1..] {
+        let mut next = Vec::new();
+        for base in &bases {
+            next.extend(merge_bases(base, commit)?);
+        }
+        bases = independent(&next)?;
+    }
+    Ok(bases)
+}
+/// Finds where `commit` forked from `refname`, using the ref's reflog to
+/// see past rewrites of its history.
+fn fork_point(refname: &str, commit: &str) -> Result<Option<String>, GitError> {
+    let (full, tip) = dwim_ref(refname)?
+        .ok_or_else(|| GitError::any(format!("Not a valid object name: '{refname}'")))?;
+    let mut history: Vec<String> = reflog::read(&full)?
+        .iter()
+        .map(|entry| entry.new_hash().clone())
+        .filter(|hash| *hash != refs::ZERO_HASH)
+        .collect();
+    history.push(tip);
+    let bases = by_date(merge_bases_many(commit, &history)?)?;
+    Ok(bases.into_iter().find(|base| history.contains(base)))
+}
+pub fn merge_base(args: Vec<String>) -> Result<(), GitError> {
+    let mut mode = None;
+    let mut all = false;
+    let mut revs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-a" | "--all" => all = true,
+            "--octopus" | "--is-ancestor" | "--fork-point" | "--independent" => mode = Some(arg),
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => revs.push(arg),
+        }
+    }
+    let usage = |expected: &str| GitError::any(format!("usage: git merge-base {expected}"));
+    match mode.as_deref() {
+        Some("--is-ancestor") => {
+            let [ancestor, descendant] = revs.as_slice() else {
+                return Err(usage("--is-ancestor <commit> <commit>"));
+            };
+            if !is_ancestor(&resolve_commit(ancestor)?, &resolve_commit(descendant)?)? {
+                return Err(GitError::exit(1));
+            }
+            return Ok(());
+        }
+        Some("--fork-point") => {
+            let (refname, commit) = match revs.as_slice() {
+                [refname] => (refname, "HEAD"),
+                [refname, commit] => (refname, commit.as_str()),
+                _ => return Err(usage("--fork-point <ref> [<commit>]")),
+            };
+            match fork_point(refname, &resolve_commit(commit)?)? {
+                Some(hash) => println!("{hash}"),
+                None => return Err(GitError::exit(1)),
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+    let commits = revs
+        .iter()
+        .map(|rev| resolve_commit(rev))
+        .collect::<Result<Vec<_>, _>>()?;
+    let bases = match mode.as_deref() {
+        Some("--independent") => {
+            let heads = independent(&commits)?;
+            for hash in by_date(heads)? {
+                println!("{hash}");
+            }
+            return Ok(());
+        }
+        Some(_) if commits.is_empty() => return Err(usage("--octopus <commit>...")),
+        Some(_) => octopus(&commits)?,
+        None if commits.len() < 2 => return Err(usage("[-a | --all] <commit> <commit>...")),
+        None => merge_bases_many(&commits[0], &commits[1..])?,
+    };
+    let bases = by_date(bases)?;
+    if bases.is_empty() {
+        return Err(GitError::exit(1));
+    }
+    for hash in bases.iter().take(if all { bases.len() } else { 1 }) {
+        println!("{hash}");
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use crate::error::GitError;
+use crate::fsck;
+use crate::objects::{GitObject, Kind};
+use std::io::Read;
+pub fn mktag(args: Vec<String>) -> Result<(), GitError> {
+    if let Some(arg) = args.iter().find(|arg| *arg != "--strict") {
+        return Err(GitError::any(format!("unknown option: {arg}")));
+    }
+    let mut data = Vec::new();
+    std::io::stdin().read_to_end(&mut data)?;
+    let (object, kind) = fsck::check_tag(&data, true).map_err(|message| {
+        eprintln!("error: tag input does not pass fsck: {message}");
+        GitError::any("tag on stdin did not pass our strict fsck check")
+    })?;
+    let tagged = GitObject::from_hex_string(&object)
+        .map_err(|_| GitError::any(format!("could not read tagged object '{object}'")))?;
+    if tagged.kind().to_string() != kind {
+        return Err(GitError::any(format!(
+            "object '{object}' tagged as '{kind}', but is a '{}' type",
+            tagged.kind()
+        )));
+    }
+    let tag = GitObject::build(Kind::Tag, data)?;
+    tag.write()?;
+    println!("{}", tag.hex_string());
+    Ok(())
+}
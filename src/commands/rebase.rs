@@ -0,0 +1,702 @@
+use crate::commit::Commit;
+use crate::config::Config;
+use crate::diff::{index_snapshot, Snapshot};
+use crate::editor::{cleanup, edit, edit_message, sequence_editor};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::merge::{commit_snapshot, Labels};
+use crate::objects::{short_hash, GitObject};
+use crate::refs;
+use crate::revision::{resolve_commit, upstream};
+use crate::revwalk::{list, merge_bases, WalkOptions};
+use crate::sequencer::{apply, describe, print_summary, Action, Step};
+use crate::state::{self, ORIG_HEAD, REBASE_DIR};
+use crate::worktree::{is_clean, update_tree};
+use std::collections::BTreeSet;
+use std::fs;
+use std::process::Command;
+const TODO_HELP: &str = "#
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup [-C] <commit> = like \"squash\" but keep only the previous
+#                    commit's log message, unless -C is used, in which case
+#                    keep only this commit's message
+# x, exec <command> = run command (the rest of the line) using shell
+# b, break = stop here (continue rebase later with 'git rebase --continue')
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+#
+";
+const IN_PROGRESS: &str = "It seems that there is already a rebase-merge directory, and
+I wonder if you are in the middle of another rebase.  If that is the
+case, please try
+\tgit rebase (--continue | --abort | --skip)
+If that is not the case, please
+\trm -fr \".git/rebase-merge\"
+and run me again.  I am stopping in case you still have something
+valuable there.
+";
+fn state_path(name: &str) -> String {
+    format!("{REBASE_DIR}/{name}")
+}
+fn read_state(name: &str) -> Result<Option<String>, GitError> {
+    state::read(&state_path(name))
+}
+fn write_state(name: &str, contents: &str) -> Result<(), GitError> {
+    Ok(fs::write(state_path(name), contents)?)
+}
+fn remove_state(name: &str) -> Result<(), GitError> {
+    state::remove(&state_path(name))
+}
+fn head() -> Result<String, GitError> {
+    refs::resolve("HEAD")?.ok_or(GitError::any("HEAD does not point to a commit"))
+}
+fn parse_todo(text: &str) -> Result<Vec<Step>, GitError> {
+    let mut steps = Vec::new();
+    for (n, line) in (1..).zip(text.lines()) {
+        let invalid = || GitError::any(format!("invalid line {n}: {}", line.trim()));
+        match Step::parse(line).map_err(|_| invalid())? {
+            Some(step) if step.action == Action::Revert => return Err(invalid()),
+            step => steps.extend(step),
+        }
+    }
+    Ok(steps)
+}
+fn format_todo(steps: &[Step], abbreviate: bool) -> String {
+    steps
+        .iter()
+        .map(|step| step.format(abbreviate) + "\n")
+        .collect()
+}
+/// A rebase in progress, as recorded in `.git/rebase-merge`.
+struct Rebase {
+    /// The branch being rebased, or `detached HEAD`.
+    head_name: String,
+    onto: String,
+    orig_head: String,
+    interactive: bool,
+    force: bool,
+    todo: Vec<Step>,
+    done: Vec<Step>,
+}
+impl Rebase {
+    fn load() -> Result<Self, GitError> {
+        let read = |name| -> Result<String, GitError> {
+            let value = read_state(name)?.ok_or(GitError::any("No rebase in progress?"))?;
+            Ok(value.trim_end().to_string())
+        };
+        Ok(Self {
+            head_name: read("head-name")?,
+            onto: read("onto")?,
+            orig_head: read("orig-head")?,
+            interactive: read_state("interactive")?.is_some(),
+            force: read_state("force")?.is_some(),
+            todo: parse_todo(&read("git-rebase-todo")?)?,
+            done: parse_todo(&read_state("done")?.unwrap_or_default())?,
+        })
+    }
+    fn save(&self) -> Result<(), GitError> {
+        fs::create_dir_all(REBASE_DIR)?;
+        write_state("head-name", &format!("{}\n", self.head_name))?;
+        write_state("onto", &format!("{}\n", self.onto))?;
+        write_state("orig-head", &format!("{}\n", self.orig_head))?;
+        if self.interactive {
+            write_state("interactive", "")?;
+        }
+        if self.force {
+            write_state("force", "")?;
+        }
+        write_state("git-rebase-todo", &format_todo(&self.todo, false))?;
+        write_state("done", &format_todo(&self.done, false))?;
+        write_state("msgnum", &format!("{}\n", self.done.len()))?;
+        write_state("end", &format!("{}\n", self.done.len() + self.todo.len()))
+    }
+    fn reflog(&self, action: &str, subject: &str) -> String {
+        format!("rebase ({action}): {subject}")
+    }
+    /// Whether the squash or fixup just done ends a run of them.
+    fn ends_squash(&self) -> bool {
+        !matches!(
+            self.todo.first().map(|step| step.action),
+            Some(Action::Squash | Action::Fixup | Action::FixupMessage)
+        )
+    }
+}
+/// Refuses to start over local changes, staged or not.
+fn check_clean(head: &str) -> Result<(), GitError> {
+    let index = Index::load()?;
+    for entry in index.entries() {
+        if entry.stage() > 0 || !is_clean(&index, entry.path())? {
+            return Err(GitError::any(
+                "cannot rebase: You have unstaged changes.\nerror: Please commit or stash them.",
+            ));
+        }
+    }
+    if index_snapshot(&index) != commit_snapshot(head)? {
+        return Err(GitError::any(
+            "cannot rebase: Your index contains uncommitted changes.\nerror: Please commit or stash them.",
+        ));
+    }
+    Ok(())
+}
+/// Moves the todo's `fixup!`, `amend!` and `squash!` commits right after
+/// the commits they name, turning them into fixups and squashes.
+fn rearrange_squashes(steps: Vec<Step>) -> Vec<Step> {
+    let mut ordered: Vec<(Step, Vec<Step>)> = Vec::new();
+    for mut step in steps {
+        let mut target = step.subject.as_str();
+        let mut action = None;
+        while let Some((rest, kind)) = target
+            .strip_prefix("fixup! ")
+            .map(|rest| (rest, Action::Fixup))
+            .or_else(|| {
+                target
+                    .strip_prefix("amend! ")
+                    .map(|rest| (rest, Action::FixupMessage))
+            })
+            .or_else(|| {
+                target
+                    .strip_prefix("squash! ")
+                    .map(|rest| (rest, Action::Squash))
+            })
+        {
+            action.get_or_insert(kind);
+            target = rest;
+        }
+        let found = action.and_then(|_| {
+            ordered.iter().position(|(picked, _)| {
+                picked.subject == target || (target.len() >= 4 && picked.arg.starts_with(target))
+            })
+        });
+        match (found, action) {
+            (Some(index), Some(action)) => {
+                step.action = action;
+                ordered[index].1.push(step);
+            }
+            _ => ordered.push((step, Vec::new())),
+        }
+    }
+    ordered
+        .into_iter()
+        .flat_map(|(step, fixups)| std::iter::once(step).chain(fixups))
+        .collect()
+}
+/// Adds the message of `commit` to the combined message of the squash run
+/// in progress, started by `head`. Fixup messages are commented out.
+fn squash_message(head: &Commit, commit: &Commit, action: Action) -> Result<String, GitError> {
+    let (count, body) = match read_state("message-squash")? {
+        Some(text) => {
+            let (first, body) = text.split_once('\n').unwrap_or((&text, ""));
+            let count: usize = first
+                .trim_start_matches("# This is a combination of ")
+                .trim_end_matches(" commits.")
+                .parse()
+                .unwrap_or(1);
+            (count + 1, body.to_string())
+        }
+        None => (
+            2,
+            format!(
+                "# This is the 1st commit message:\n\n{}\n",
+                head.message().trim_end()
+            ),
+        ),
+    };
+    let message = commit.message().trim_end();
+    let (body, added) = match action {
+        Action::Squash => (
+            body,
+            format!("\n# This is the commit message #{count}:\n\n{message}\n"),
+        ),
+        Action::FixupMessage => {
+            let message = match message.strip_prefix("amend! ") {
+                Some(rest) => rest.split_once('\n').map_or("", |(_, rest)| rest),
+                None => message,
+            };
+            let message = message.trim_start_matches('\n');
+            (
+                skip_messages(&body),
+                format!("\n# This is the commit message #{count}:\n\n{message}\n"),
+            )
+        }
+        _ => {
+            let commented: String = message.lines().map(|line| format!("# {line}\n")).collect();
+            (
+                body,
+                format!("\n# The commit message #{count} will be skipped:\n\n{commented}"),
+            )
+        }
+    };
+    Ok(format!(
+        "# This is a combination of {count} commits.\n{body}{added}"
+    ))
+}
+/// Comments out the messages of a squash run so far, for a `fixup -C`
+/// whose message replaces them.
+fn skip_messages(body: &str) -> String {
+    body.lines()
+        .map(|line| {
+            let header = line
+                .strip_prefix("# This is the ")
+                .and_then(|rest| rest.strip_suffix(":"));
+            match header {
+                Some(header) => format!("# The {header} will be skipped:\n"),
+                None if line.is_empty() || line.starts_with('#') => format!("{line}\n"),
+                None => format!("# {line}\n"),
+            }
+        })
+        .collect()
+}
+/// Melds the index into HEAD for a squash or fixup of `commit`. The
+/// editor is opened on the combined message at the end of a run that
+/// squashed anything.
+fn squash(
+    state: &Rebase,
+    step: &Step,
+    commit: &Commit,
+    tree: &str,
+    continued: bool,
+) -> Result<(), GitError> {
+    let head = head()?;
+    let head_commit = Commit::from_hex_string(&head)?;
+    let combined = squash_message(&head_commit, commit, step.action)?;
+    let mut fixups = read_state("current-fixups")?.unwrap_or_default();
+    fixups.push_str(&format!("{} {}\n", step.action.name(), commit.hash()));
+    let last = state.ends_squash();
+    let edit = last && fixups.lines().any(|line| line.starts_with("squash"));
+    let message = match edit {
+        true => edit_message(&combined)?,
+        false => cleanup(&combined),
+    };
+    let amended = GitObject::build_commit_by(
+        head_commit.author(),
+        message.trim_end(),
+        tree,
+        head_commit.parents(),
+    )?;
+    amended.write()?;
+    let action = match continued {
+        true => "continue",
+        false => step.action.name(),
+    };
+    let subject = message.lines().next().unwrap_or_default();
+    let reflog = state.reflog(action, subject);
+    refs::update("HEAD", &amended.hex_string(), Some(&head), false, &reflog)?;
+    if last {
+        remove_state("message-squash")?;
+        remove_state("current-fixups")?;
+        if edit || continued {
+            print_summary(&amended.hex_string(), true)?;
+        }
+    } else {
+        write_state("message-squash", &combined)?;
+        write_state("current-fixups", &fixups)?;
+    }
+    Ok(())
+}
+/// Commits the index as the result of applying `commit` for `step`. A
+/// commit that became empty is dropped. After a conflict, `continued`
+/// takes the message the user may have edited in the state directory.
+fn record(state: &Rebase, step: &Step, commit: &Commit, continued: bool) -> Result<(), GitError> {
+    let head = head()?;
+    let tree = Index::load()?.write_tree()?;
+    if matches!(
+        step.action,
+        Action::Squash | Action::Fixup | Action::FixupMessage
+    ) {
+        return squash(state, step, commit, &tree, continued);
+    }
+    let head_tree = Commit::from_hex_string(&head)?.tree().clone();
+    let was_empty = match commit.parents().first() {
+        Some(parent) => Commit::from_hex_string(parent)?.tree() == commit.tree(),
+        None => false,
+    };
+    if tree == head_tree && !was_empty {
+        return Ok(());
+    }
+    let mut message = match continued {
+        true => read_state("message")?.unwrap_or_else(|| commit.message().clone()),
+        false => commit.message().clone(),
+    };
+    if continued || step.action == Action::Reword {
+        message = edit_message(&message)?;
+    }
+    let new = GitObject::build_commit_by(commit.author(), message.trim_end(), &tree, [&head])?;
+    new.write()?;
+    let action = match continued {
+        true => "continue",
+        false => step.action.name(),
+    };
+    let reflog = state.reflog(action, &step.subject);
+    refs::update("HEAD", &new.hex_string(), Some(&head), false, &reflog)?;
+    if continued || step.action == Action::Reword {
+        print_summary(&new.hex_string(), !continued)?;
+    }
+    Ok(())
+}
+/// Stops at an edit, leaving HEAD for the user to amend.
+fn stop_for_edit(commit: &Commit) -> Result<(), GitError> {
+    let head = head()?;
+    write_state("amend", &format!("{head}\n"))?;
+    write_state("stopped-sha", &format!("{}\n", commit.hash()))?;
+    eprint!("\r\x1b[K");
+    eprintln!(
+        "Stopped at {}...  {}\nYou can amend the commit now, with\n\n  git commit --amend \n\nOnce you are satisfied with your changes, run\n\n  git rebase --continue",
+        short_hash(commit.hash()),
+        commit.subject()
+    );
+    Ok(())
+}
+/// Applies the commit of a pick-like `step` on HEAD. Returns false when
+/// the rebase stops there for an edit; conflicts end the process.
+fn pick(state: &Rebase, step: &Step) -> Result<bool, GitError> {
+    let commit = Commit::from_hex_string(&step.arg)?;
+    let head = head()?;
+    let parent = commit.parents().first();
+    let fast_forward =
+        matches!(step.action, Action::Pick | Action::Edit) && !state.force && parent == Some(&head);
+    if fast_forward {
+        update_tree(
+            &commit_snapshot(&head)?,
+            &commit_snapshot(commit.hash())?,
+            false,
+            "merge",
+        )?;
+        let reflog = state.reflog(step.action.name(), &step.subject);
+        refs::update("HEAD", commit.hash(), Some(&head), false, &reflog)?;
+    } else {
+        let base = match parent {
+            Some(parent) => commit_snapshot(parent)?,
+            None => Snapshot::new(),
+        };
+        let labels = Labels {
+            base: format!("parent of {}", describe(&commit)),
+            ours: "HEAD".to_string(),
+            theirs: describe(&commit),
+        };
+        let ours = commit_snapshot(&head)?;
+        let merge = apply(&base, &ours, &commit_snapshot(commit.hash())?, &labels)?;
+        if !merge.is_clean() {
+            for message in merge.messages() {
+                println!("{message}");
+            }
+            write_state("message", commit.message())?;
+            write_state("stopped-sha", &format!("{}\n", commit.hash()))?;
+            let what = format!("{}... {}", short_hash(commit.hash()), commit.subject());
+            eprintln!(
+                "error: could not apply {what}
+hint: Resolve all conflicts manually, mark them as resolved with
+hint: \"git add/rm <conflicted_files>\", then run \"git rebase --continue\".
+hint: You can instead skip this commit: run \"git rebase --skip\".
+hint: To abort and get back to the state before \"git rebase\", run \"git rebase --abort\".
+Could not apply {what}"
+            );
+            return Err(GitError::exit(1));
+        }
+        record(state, step, &commit, false)?;
+    }
+    if step.action == Action::Edit {
+        stop_for_edit(&commit)?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+/// Works through the todo list until it is done or a step stops.
+fn run(mut state: Rebase) -> Result<(), GitError> {
+    let mut leading = state.done.is_empty();
+    while !state.todo.is_empty() {
+        let step = state.todo.remove(0);
+        state.done.push(step.clone());
+        state.save()?;
+        if step.action == Action::Drop {
+            continue;
+        }
+        // Like git, picks that merely fast-forward at the start of an
+        // interactive rebase go by without progress.
+        leading = leading
+            && state.interactive
+            && step.action == Action::Pick
+            && !state.force
+            && Commit::from_hex_string(&step.arg)?.parents().first() == Some(&head()?);
+        if !leading {
+            let total = state.done.len() + state.todo.len();
+            eprint!("Rebasing ({}/{total})\r", state.done.len());
+        }
+        match step.action {
+            Action::Break => {
+                let head = Commit::from_hex_string(head()?)?;
+                eprint!("\r\x1b[K");
+                eprintln!("Stopped at {}", describe(&head));
+                return Ok(());
+            }
+            Action::Exec => {
+                eprint!("\r\x1b[K");
+                eprintln!("Executing: {}", step.arg);
+                let status = Command::new("sh").arg("-c").arg(&step.arg).status()?;
+                if !status.success() {
+                    eprintln!(
+                        "warning: execution failed: {}\nYou can fix the problem, and then run\n\n  git rebase --continue\n\n",
+                        step.arg
+                    );
+                    return Err(GitError::exit(1));
+                }
+            }
+            _ if !pick(&state, &step)? => return Ok(()),
+            _ => {}
+        }
+    }
+    finish(&state)
+}
+/// Points the rebased branch at the new history and checks it out again.
+fn finish(state: &Rebase) -> Result<(), GitError> {
+    let head = head()?;
+    if state.head_name.starts_with("refs/") {
+        let message = format!("rebase (finish): {} onto {}", state.head_name, state.onto);
+        refs::update(&state.head_name, &head, None, false, &message)?;
+        let message = format!("rebase (finish): returning to {}", state.head_name);
+        refs::set_symbolic("HEAD", &state.head_name, Some(&message))?;
+    }
+    fs::write(ORIG_HEAD, format!("{}\n", state.orig_head))?;
+    fs::remove_dir_all(REBASE_DIR)?;
+    eprint!("\r\x1b[K");
+    eprintln!("Successfully rebased and updated {}.", state.head_name);
+    Ok(())
+}
+/// Resumes after a stop, committing what the user resolved or amended.
+fn resume() -> Result<(), GitError> {
+    let state = Rebase::load()?;
+    let index = Index::load()?;
+    let unmerged: BTreeSet<&String> = index
+        .entries()
+        .iter()
+        .filter(|entry| entry.stage() > 0)
+        .map(|entry| entry.path())
+        .collect();
+    if !unmerged.is_empty() {
+        for path in unmerged {
+            eprintln!("{path}: needs merge");
+        }
+        eprintln!(
+            "You must edit all merge conflicts and then\nmark them as resolved using git add"
+        );
+        return Err(GitError::exit(1));
+    }
+    let head = head()?;
+    if let Some(amend) = read_state("amend")? {
+        let staged = index_snapshot(&index) != commit_snapshot(&head)?;
+        if staged && amend.trim_end() != head {
+            eprintln!(
+                "error: \nYou have uncommitted changes in your working tree. Please, commit them\nfirst and then run 'git rebase --continue' again."
+            );
+            return Err(GitError::exit(1));
+        }
+        if staged {
+            let commit = Commit::from_hex_string(&head)?;
+            let amended = GitObject::build_commit_by(
+                commit.author(),
+                commit.message().trim_end(),
+                index.write_tree()?,
+                commit.parents(),
+            )?;
+            amended.write()?;
+            let reflog = state.reflog("continue", &commit.subject());
+            refs::update("HEAD", &amended.hex_string(), Some(&head), false, &reflog)?;
+        }
+    } else if let Some(stopped) = read_state("stopped-sha")? {
+        let commit = Commit::from_hex_string(stopped.trim_end())?;
+        let step = state
+            .done
+            .last()
+            .cloned()
+            .unwrap_or_else(|| Step::new(Action::Pick, &commit));
+        record(&state, &step, &commit, true)?;
+    }
+    for name in ["amend", "stopped-sha", "message"] {
+        remove_state(name)?;
+    }
+    run(state)
+}
+/// Drops the commit the rebase stopped at, resetting to HEAD.
+fn skip() -> Result<(), GitError> {
+    let state = Rebase::load()?;
+    let head = commit_snapshot(&head()?)?;
+    update_tree(&head, &head, true, "reset")?;
+    for name in ["amend", "stopped-sha", "message"] {
+        remove_state(name)?;
+    }
+    run(state)
+}
+/// Gives up, returning to the branch as it was before the rebase.
+fn abort() -> Result<(), GitError> {
+    let state = Rebase::load()?;
+    let head = head()?;
+    update_tree(
+        &commit_snapshot(&head)?,
+        &commit_snapshot(&state.orig_head)?,
+        true,
+        "reset",
+    )?;
+    let message = format!("rebase (abort): returning to {}", state.head_name);
+    match state.head_name.starts_with("refs/") {
+        true => refs::set_symbolic("HEAD", &state.head_name, Some(&message))?,
+        false => refs::update("HEAD", &state.orig_head, None, false, &message)?,
+    }
+    fs::remove_dir_all(REBASE_DIR)?;
+    Ok(())
+}
+pub fn rebase(args: Vec<String>) -> Result<(), GitError> {
+    let (mut interactive, mut force) = (false, false);
+    let mut autosquash = None;
+    let mut onto = None;
+    let mut revs = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--continue" => return resume(),
+            "--skip" => return skip(),
+            "--abort" => return abort(),
+            "-i" | "--interactive" => interactive = true,
+            "--autosquash" => autosquash = Some(true),
+            "--no-autosquash" => autosquash = Some(false),
+            "-f" | "--force-rebase" | "--no-ff" => force = true,
+            "-q" | "--quiet" | "--merge" | "-m" => {}
+            "--onto" => {
+                onto = Some(
+                    args_iter
+                        .next()
+                        .ok_or(GitError::any("option `onto' requires a value"))?,
+                )
+            }
+            arg if arg.starts_with("--onto=") => onto = Some(arg["--onto=".len()..].to_string()),
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => revs.push(arg),
+        }
+    }
+    if fs::metadata(REBASE_DIR).is_ok() {
+        return Err(GitError::any(IN_PROGRESS));
+    }
+    let (upstream_name, branch) = match revs.as_slice() {
+        [] => (upstream(None)?, None),
+        [upstream] => (upstream.clone(), None),
+        [upstream, branch] => (upstream.clone(), Some(branch.clone())),
+        _ => return Err(GitError::any("too many arguments")),
+    };
+    if let Some(branch) = &branch {
+        let target = resolve_commit(branch)?;
+        let old = commit_snapshot(&head()?)?;
+        update_tree(&old, &commit_snapshot(&target)?, false, "checkout")?;
+        let message = format!("rebase (start): checkout {branch}");
+        let refname = format!("refs/heads/{branch}");
+        match refs::resolve(&refname)? {
+            Some(_) => refs::set_symbolic("HEAD", &refname, Some(&message))?,
+            None => refs::update("HEAD", &target, None, false, &message)?,
+        }
+    }
+    let upstream_commit = resolve_commit(&upstream_name)
+        .map_err(|_| GitError::any(format!("invalid upstream '{upstream_name}'")))?;
+    let (onto_name, onto) = match onto {
+        Some(name) => {
+            let hash = resolve_commit(&name)
+                .map_err(|_| GitError::any(format!("Does not point to a valid commit '{name}'")))?;
+            (name, hash)
+        }
+        None => (upstream_name.clone(), upstream_commit.clone()),
+    };
+    let head = head()?;
+    check_clean(&head)?;
+    let head_name = match refs::head_branch()? {
+        Some(branch) => format!("refs/heads/{branch}"),
+        None => "detached HEAD".to_string(),
+    };
+    let current = match refs::head_branch()? {
+        Some(branch) => format!("Current branch {branch}"),
+        None => "HEAD".to_string(),
+    };
+    let up_to_date = merge_bases(&onto, &head)? == [onto.clone()]
+        && (onto == upstream_commit || merge_bases(&upstream_commit, &head)? == [onto.clone()]);
+    if up_to_date && !interactive {
+        match force {
+            true => println!("{current} is up to date, rebase forced."),
+            false => {
+                println!("{current} is up to date.");
+                return Ok(());
+            }
+        }
+    }
+    let options = WalkOptions {
+        topo_order: true,
+        ..WalkOptions::default()
+    };
+    let mut commits = list(
+        std::slice::from_ref(&head),
+        std::slice::from_ref(&upstream_commit),
+        &options,
+    )?;
+    commits.reverse();
+    let mut todo: Vec<Step> = commits
+        .iter()
+        .filter(|commit| commit.parents().len() < 2)
+        .map(|commit| Step::new(Action::Pick, commit))
+        .collect();
+    let config_autosquash = interactive
+        && Config::load()?
+            .get("rebase.autoSquash")
+            .is_some_and(|value| value == "true");
+    if autosquash.unwrap_or(config_autosquash) {
+        todo = rearrange_squashes(todo);
+    }
+    let mut state = Rebase {
+        head_name,
+        onto: onto.clone(),
+        orig_head: head.clone(),
+        interactive,
+        force,
+        todo,
+        done: Vec::new(),
+    };
+    state.save()?;
+    if interactive {
+        let count = state.todo.len();
+        let help = format!(
+            "\n# Rebase {}..{} onto {} ({count} command{})\n{TODO_HELP}",
+            short_hash(&upstream_commit),
+            short_hash(&head),
+            short_hash(&onto),
+            if count == 1 { "" } else { "s" }
+        );
+        let path = state_path("git-rebase-todo");
+        fs::write(&path, format_todo(&state.todo, true) + &help)?;
+        let edited =
+            edit(&sequence_editor()?, &path).and_then(|_| parse_todo(&fs::read_to_string(&path)?));
+        match edited {
+            Ok(todo) if !todo.is_empty() => state.todo = todo,
+            Ok(_) => {
+                fs::remove_dir_all(REBASE_DIR)?;
+                return Err(GitError::any("nothing to do"));
+            }
+            Err(e) => {
+                fs::remove_dir_all(REBASE_DIR)?;
+                return Err(e);
+            }
+        }
+    }
+    update_tree(
+        &commit_snapshot(&head)?,
+        &commit_snapshot(&onto)?,
+        false,
+        "checkout",
+    )?;
+    let message = format!("rebase (start): checkout {onto_name}");
+    refs::update("HEAD", &onto, None, false, &message)?;
+    run(state)
+}
@@ -0,0 +1,219 @@
+use crate::config::Config;
+use crate::date::approxidate;
+use crate::error::GitError;
+use crate::objects::short_hash;
+use crate::reflog::{self, ReflogEntry};
+use crate::refs;
+use crate::revision::dwim_ref;
+use crate::revwalk::ancestors;
+use std::collections::{BTreeSet, HashSet};
+const DEFAULT_EXPIRE: &str = "90.days.ago";
+const DEFAULT_EXPIRE_UNREACHABLE: &str = "30.days.ago";
+fn full_name(name: &str) -> Result<String, GitError> {
+    if name == "HEAD" || reflog::exists(name) {
+        return Ok(name.to_string());
+    }
+    dwim_ref(name)?
+        .map(|(full, _)| full)
+        .ok_or_else(|| GitError::any(format!("no reflog for '{name}'")))
+}
+/// Parses an expiry time, where `never` and `false` mean entries are kept.
+fn expiry(value: &str) -> Result<Option<i64>, GitError> {
+    match value {
+        "never" | "false" => Ok(None),
+        "all" => Ok(Some(i64::MAX)),
+        value => approxidate(value).map(Some),
+    }
+}
+fn show(args: &[String]) -> Result<(), GitError> {
+    let mut max_count = None;
+    let mut name = None;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        if arg == "-n" {
+            let count = args_iter
+                .next()
+                .ok_or(GitError::any("option -n requires a value"))?;
+            max_count = Some(count.parse().map_err(|_| GitError::any("invalid count"))?);
+        } else if let Some(count) = arg.strip_prefix("--max-count=").or(arg.strip_prefix("-n")) {
+            max_count = Some(count.parse().map_err(|_| GitError::any("invalid count"))?);
+        } else if arg.starts_with('-') {
+            return Err(GitError::any(format!("unknown option: {arg}")));
+        } else {
+            name = Some(arg.as_str());
+        }
+    }
+    let name = name.unwrap_or("HEAD");
+    let entries = reflog::read(&full_name(name)?)?;
+    let count = max_count.unwrap_or(usize::MAX);
+    for (n, entry) in entries.iter().rev().take(count).enumerate() {
+        println!(
+            "{} {name}@{{{n}}}: {}",
+            short_hash(entry.new_hash()),
+            entry.message()
+        );
+    }
+    Ok(())
+}
+fn expire(args: &[String]) -> Result<(), GitError> {
+    let config = Config::load()?;
+    let mut expire = config
+        .get("gc.reflogExpire")
+        .unwrap_or(DEFAULT_EXPIRE)
+        .to_string();
+    let mut expire_unreachable = config
+        .get("gc.reflogExpireUnreachable")
+        .unwrap_or(DEFAULT_EXPIRE_UNREACHABLE)
+        .to_string();
+    let mut all = false;
+    let mut dry_run = false;
+    let mut rewrite = false;
+    let mut updateref = false;
+    let mut names = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--all" => all = true,
+            "-n" | "--dry-run" => dry_run = true,
+            "--rewrite" => rewrite = true,
+            "--updateref" => updateref = true,
+            "--verbose" | "--stale-fix" => {}
+            arg => {
+                if let Some(value) = arg.strip_prefix("--expire=") {
+                    expire = value.to_string();
+                } else if let Some(value) = arg.strip_prefix("--expire-unreachable=") {
+                    expire_unreachable = value.to_string();
+                } else if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else {
+                    names.push(full_name(arg)?);
+                }
+            }
+        }
+    }
+    if all {
+        names = reflog::list()?;
+    }
+    let (expire, expire_unreachable) = (expiry(&expire)?, expiry(&expire_unreachable)?);
+    for name in names {
+        let reachable = match refs::resolve(&name)? {
+            Some(tip) => ancestors(&tip).unwrap_or_default(),
+            None => HashSet::new(),
+        };
+        let entries = reflog::read(&name)?;
+        let keep: Vec<bool> = entries
+            .iter()
+            .map(|entry| {
+                let time = entry.identity().time();
+                let expired = |threshold: Option<i64>| threshold.is_some_and(|t| time <= t);
+                let unreachable = !reachable.contains(entry.new_hash())
+                    || entry.old_hash() != refs::ZERO_HASH && !reachable.contains(entry.old_hash());
+                !(expired(expire) || unreachable && expired(expire_unreachable))
+            })
+            .collect();
+        if dry_run {
+            for (entry, _) in entries.iter().zip(&keep).filter(|(_, keep)| !**keep) {
+                println!("would prune {}", entry.message());
+            }
+            continue;
+        }
+        let kept = prune(entries, &keep, rewrite);
+        finish(&name, &kept, updateref)?;
+    }
+    Ok(())
+}
+/// Drops the entries not marked in `keep`; with `rewrite`, each kept entry's
+/// old value is patched to the new value of the entry now preceding it.
+pub(crate) fn prune(entries: Vec<ReflogEntry>, keep: &[bool], rewrite: bool) -> Vec<ReflogEntry> {
+    let mut kept: Vec<ReflogEntry> = Vec::new();
+    let mut dropped = false;
+    for (mut entry, keep) in entries.into_iter().zip(keep) {
+        if !keep {
+            dropped = true;
+            continue;
+        }
+        if rewrite && dropped {
+            let previous = kept.last().map(|e| e.new_hash().clone());
+            entry.set_old_hash(previous.unwrap_or_else(|| refs::ZERO_HASH.to_string()));
+        }
+        dropped = false;
+        kept.push(entry);
+    }
+    kept
+}
+/// Writes `kept` as the log of `name`; with `updateref`, the ref is moved to
+/// the newest kept entry.
+pub(crate) fn finish(name: &str, kept: &[ReflogEntry], updateref: bool) -> Result<(), GitError> {
+    if let Some(last) = kept.last().filter(|_| updateref) {
+        let current = refs::resolve(name)?;
+        if current.as_ref() != Some(last.new_hash()) {
+            refs::update(name, last.new_hash(), current.as_deref(), false, "")?;
+        }
+    }
+    reflog::write(name, kept)
+}
+fn delete(args: &[String]) -> Result<(), GitError> {
+    let mut rewrite = false;
+    let mut updateref = false;
+    let mut dry_run = false;
+    let mut selectors: Vec<(String, usize)> = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--rewrite" => rewrite = true,
+            "--updateref" => updateref = true,
+            "-n" | "--dry-run" => dry_run = true,
+            "--verbose" => {}
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")));
+            }
+            arg => {
+                let invalid = || GitError::any(format!("not a reflog: {arg}"));
+                let (name, n) = arg.split_once("@{").ok_or_else(invalid)?;
+                let n = n
+                    .strip_suffix('}')
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(invalid)?;
+                let name = if name.is_empty() { "HEAD" } else { name };
+                selectors.push((full_name(name)?, n));
+            }
+        }
+    }
+    if selectors.is_empty() {
+        return Err(GitError::any("no reflog specified to delete"));
+    }
+    let names: BTreeSet<String> = selectors.iter().map(|(name, _)| name.clone()).collect();
+    for name in &names {
+        let entries = reflog::read(name)?;
+        let mut keep = vec![true; entries.len()];
+        for (_, n) in selectors.iter().filter(|(other, _)| other == name) {
+            let index = entries
+                .len()
+                .checked_sub(n + 1)
+                .ok_or_else(|| GitError::any(format!("reflog entry {name}@{{{n}}} not found")))?;
+            keep[index] = false;
+        }
+        if dry_run {
+            continue;
+        }
+        finish(name, &prune(entries, &keep, rewrite), updateref)?;
+    }
+    Ok(())
+}
+pub fn reflog(args: Vec<String>) -> Result<(), GitError> {
+    match args.first().map(String::as_str) {
+        Some("show") => show(&args[1..]),
+        Some("expire") => expire(&args[1..]),
+        Some("delete") => delete(&args[1..]),
+        Some("exists") => match args.get(1) {
+            Some(name) if reflog::exists(&full_name(name).unwrap_or_default()) => Ok(()),
+            Some(_) => Err(GitError::exit(1)),
+            None => Err(GitError::any("usage: reflog exists <ref>")),
+        },
+        Some("list") => {
+            for name in reflog::list()? {
+                println!("{name}");
+            }
+            Ok(())
+        }
+        _ => show(&args),
+    }
+}
@@ -0,0 +1,222 @@
+use super::add::is_under;
+use super::checkout::normalize;
+use crate::commit::Commit;
+use crate::diff::{index_snapshot, tree_snapshot, Snapshot};
+use crate::error::GitError;
+use crate::index::{Index, IndexEntry};
+use crate::objects::short_hash;
+use crate::refs;
+use crate::revision::{resolve_commit, resolve_tree};
+use crate::state::{self, BRANCH_STATE, MERGE_HEAD, ORIG_HEAD};
+use crate::worktree::{checkout_entry, remove_file, update_tree};
+use std::fs;
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Soft,
+    Mixed,
+    Hard,
+    Merge,
+    Keep,
+}
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Soft => "soft",
+            Mode::Mixed => "mixed",
+            Mode::Hard => "hard",
+            Mode::Merge => "merge",
+            Mode::Keep => "keep",
+        }
+    }
+}
+fn head_snapshot(head: Option<&str>) -> Result<Snapshot, GitError> {
+    match head {
+        Some(head) => tree_snapshot(Commit::from_hex_string(head)?.tree(), true),
+        None => Ok(Snapshot::new()),
+    }
+}
+/// Prints the stage-0 paths whose working tree file no longer matches the
+/// index, as `git reset` does after a mixed reset.
+fn print_unstaged(index: &Index) -> Result<(), GitError> {
+    let mut header = false;
+    for entry in index.entries().iter().filter(|entry| entry.stage() == 0) {
+        if !entry.is_modified()? {
+            continue;
+        }
+        if !header {
+            println!("Unstaged changes after reset:");
+            header = true;
+        }
+        let status = match fs::symlink_metadata(entry.path()) {
+            Ok(_) => 'M',
+            Err(_) => 'D',
+        };
+        println!("{status}\t{}", entry.path());
+    }
+    Ok(())
+}
+/// Sets the index entries under `pathspecs` to their version in `wanted`,
+/// dropping those it does not have; HEAD and the working tree stay put.
+fn reset_paths(wanted: &Snapshot, pathspecs: &[String], quiet: bool) -> Result<(), GitError> {
+    let mut index = Index::load()?;
+    let matches = |path: &String| pathspecs.iter().any(|spec| is_under(path, normalize(spec)));
+    let stale: Vec<String> = index
+        .entries()
+        .iter()
+        .map(|entry| entry.path())
+        .filter(|path| matches(path) && !wanted.contains_key(*path))
+        .cloned()
+        .collect();
+    for path in &stale {
+        index.remove(path);
+    }
+    let current = index_snapshot(&index);
+    for (path, (mode, hash)) in wanted.iter().filter(|(path, _)| matches(path)) {
+        let unmerged = !current.contains_key(path) && index.contains(path);
+        if current.get(path) != Some(&(mode.clone(), hash.clone())) || unmerged {
+            index.add(IndexEntry::new(path.as_str(), mode, hash.clone(), 0)?);
+        }
+    }
+    index.write()?;
+    if !quiet {
+        print_unstaged(&index)?;
+    }
+    Ok(())
+}
+/// Rebuilds the index from `tree`, keeping the stat data of entries that
+/// did not change so untouched files are not reported as modified.
+fn reset_index(tree: &str) -> Result<Index, GitError> {
+    let old = Index::load()?;
+    let mut index = Index::default();
+    for entry in Index::from_tree(tree)?.entries() {
+        match old.get(entry.path()) {
+            Some(kept)
+                if kept.stage() == 0
+                    && kept.mode() == entry.mode()
+                    && kept.hash() == entry.hash() =>
+            {
+                index.add(kept.clone())
+            }
+            _ => {
+                let mut entry = entry.clone();
+                if !entry.is_modified()? {
+                    entry.refresh_stat()?;
+                }
+                index.add(entry);
+            }
+        }
+    }
+    index.write()?;
+    Ok(index)
+}
+/// Resets the index to `target` like `--hard`, but keeps local changes to
+/// files that are the same in the index and `target`; conflicted paths are
+/// always overwritten.
+fn reset_merge(target: &Snapshot) -> Result<(), GitError> {
+    let mut index = Index::load()?;
+    let current = index_snapshot(&index);
+    let unmerged: Vec<String> = index
+        .entries()
+        .iter()
+        .filter(|entry| entry.stage() > 0 && !current.contains_key(entry.path()))
+        .map(|entry| entry.path().clone())
+        .collect();
+    for path in &unmerged {
+        match target.get(path) {
+            Some((mode, hash)) => checkout_entry(&mut index, path, mode, hash)?,
+            None => {
+                index.remove(path);
+                remove_file(path)?;
+            }
+        }
+    }
+    index.write()?;
+    update_tree(&index_snapshot(&index), target, false, "reset")
+}
+fn reset_commit(mode: Mode, rev: &str, quiet: bool) -> Result<(), GitError> {
+    let head = refs::resolve("HEAD")?;
+    let target = resolve_commit(rev)?;
+    let commit = Commit::from_hex_string(&target)?;
+    if mode == Mode::Soft && fs::metadata(MERGE_HEAD).is_ok() {
+        return Err(GitError::any(
+            "Cannot do a soft reset in the middle of a merge.",
+        ));
+    }
+    let wanted = tree_snapshot(commit.tree(), true)?;
+    match mode {
+        Mode::Soft => {}
+        Mode::Mixed => {
+            let index = reset_index(commit.tree())?;
+            if !quiet {
+                print_unstaged(&index)?;
+            }
+        }
+        Mode::Hard => update_tree(&head_snapshot(head.as_deref())?, &wanted, true, "reset")?,
+        Mode::Merge => reset_merge(&wanted)?,
+        Mode::Keep => update_tree(&head_snapshot(head.as_deref())?, &wanted, false, "reset")?,
+    }
+    if let Some(head) = &head {
+        fs::write(ORIG_HEAD, format!("{head}\n"))?;
+    }
+    let message = format!("reset: moving to {rev}");
+    refs::update("HEAD", &target, None, true, &message)?;
+    state::remove_all(&BRANCH_STATE)?;
+    if mode == Mode::Hard && !quiet {
+        println!(
+            "HEAD is now at {} {}",
+            short_hash(&target),
+            commit.subject()
+        );
+    }
+    Ok(())
+}
+pub fn reset(args: Vec<String>) -> Result<(), GitError> {
+    let mut mode = None;
+    let mut quiet = false;
+    let mut revs = Vec::new();
+    let mut pathspecs = Vec::new();
+    let mut separated = false;
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--soft" => mode = Some(Mode::Soft),
+            "--mixed" => mode = Some(Mode::Mixed),
+            "--hard" => mode = Some(Mode::Hard),
+            "--merge" => mode = Some(Mode::Merge),
+            "--keep" => mode = Some(Mode::Keep),
+            "-q" | "--quiet" => quiet = true,
+            "--" => {
+                pathspecs.extend(args_iter.by_ref());
+                separated = true;
+            }
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => revs.push(arg),
+        }
+    }
+    // Without `--`, the first argument is a revision only if it resolves.
+    if !separated && revs.first().is_some_and(|rev| resolve_tree(rev).is_err()) {
+        pathspecs = std::mem::take(&mut revs);
+    } else if revs.len() > 1 {
+        pathspecs.splice(0..0, revs.drain(1..));
+    }
+    let rev = revs.pop().unwrap_or_else(|| "HEAD".to_string());
+    if pathspecs.is_empty() {
+        return reset_commit(mode.unwrap_or(Mode::Mixed), &rev, quiet);
+    }
+    match mode {
+        None | Some(Mode::Mixed) => {}
+        Some(mode) => {
+            return Err(GitError::any(format!(
+                "Cannot do {} reset with paths.",
+                mode.name()
+            )))
+        }
+    }
+    let wanted = match refs::resolve("HEAD")? {
+        None if rev == "HEAD" => Snapshot::new(),
+        _ => tree_snapshot(&resolve_tree(&rev)?, true)?,
+    };
+    reset_paths(&wanted, &pathspecs, quiet)
+}
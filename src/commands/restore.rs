@@ -0,0 +1,87 @@
+use super::checkout::select;
+use crate::commit::Commit;
+use crate::diff::{index_snapshot, tree_snapshot, Snapshot};
+use crate::error::GitError;
+use crate::index::{Index, IndexEntry};
+use crate::refs;
+use crate::revision::resolve_tree;
+use crate::worktree::{checkout_entry, is_clean, remove_file, write_file};
+pub fn restore(args: Vec<String>) -> Result<(), GitError> {
+    let mut source = None;
+    let mut staged = false;
+    let mut worktree = false;
+    let mut overlay = false;
+    let mut pathspecs = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-s" | "--source" => {
+                source = Some(
+                    args_iter
+                        .next()
+                        .ok_or_else(|| GitError::any(format!("option {arg} requires a value")))?,
+                )
+            }
+            "-S" | "--staged" => staged = true,
+            "-W" | "--worktree" => worktree = true,
+            "--overlay" => overlay = true,
+            "--no-overlay" => overlay = false,
+            "-q" | "--quiet" => {}
+            "--" => pathspecs.extend(args_iter.by_ref()),
+            arg => match arg.strip_prefix("--source=") {
+                Some(rev) => source = Some(rev.to_string()),
+                None if arg.starts_with('-') => {
+                    return Err(GitError::any(format!("unknown option: {arg}")))
+                }
+                None => pathspecs.push(arg.to_string()),
+            },
+        }
+    }
+    if pathspecs.is_empty() {
+        return Err(GitError::any("you must specify path(s) to restore"));
+    }
+    worktree |= !staged;
+    let mut index = Index::load()?;
+    let current = index_snapshot(&index);
+    let wanted = match (&source, staged) {
+        (Some(rev), _) => tree_snapshot(&resolve_tree(rev)?, true)?,
+        (None, true) => match refs::resolve("HEAD")? {
+            Some(head) => tree_snapshot(Commit::from_hex_string(head)?.tree(), true)?,
+            None => Snapshot::new(),
+        },
+        (None, false) => current.clone(),
+    };
+    let mut known = current.clone();
+    known.extend(wanted.clone());
+    let selected = select(&known, &index, &pathspecs)?;
+    let vanished = selected
+        .keys()
+        .filter(|path| !wanted.contains_key(*path) && !overlay);
+    for path in vanished {
+        if staged {
+            index.remove(path);
+        }
+        if worktree && current.contains_key(path) {
+            remove_file(path)?;
+        }
+    }
+    for (path, entry) in selected
+        .keys()
+        .filter_map(|path| wanted.get_key_value(path))
+    {
+        let (mode, hash) = entry;
+        let unchanged = current.get(path) == Some(entry);
+        match (staged, worktree) {
+            (true, true) => checkout_entry(&mut index, path, mode, hash)?,
+            (true, false) if !unchanged => {
+                index.add(IndexEntry::new(path.as_str(), mode, hash.clone(), 0)?)
+            }
+            (false, _) if source.is_none() && !is_clean(&index, path)? => {
+                checkout_entry(&mut index, path, mode, hash)?
+            }
+            (false, _) if source.is_some() => write_file(path, mode, hash)?,
+            _ => {}
+        }
+    }
+    index.write()
+}
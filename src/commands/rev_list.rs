@@ -0,0 +1,163 @@
+use crate::commit::Commit;
+use crate::error::GitError;
+use crate::objects::{GitObject, Kind};
+use crate::refs;
+use crate::revision::{peel, resolve_commit, resolve_range, RevSpec};
+use crate::revwalk::{ancestors, list, WalkOptions};
+use std::collections::HashSet;
+/// Collects the objects of `tree` not yet `seen`, the tree itself first and
+/// then its entries in tree order, each named by its path.
+fn tree_objects(
+    tree: &str,
+    path: &str,
+    seen: &mut HashSet<String>,
+    out: &mut Vec<(String, String)>,
+) -> Result<(), GitError> {
+    if !seen.insert(tree.to_string()) {
+        return Ok(());
+    }
+    out.push((tree.to_string(), path.to_string()));
+    for entry in GitObject::from_hex_string(tree)?.tree_entries()? {
+        let name = match path {
+            "" => entry.filename().clone(),
+            path => format!("{path}/{}", entry.filename()),
+        };
+        match entry.kind() {
+            Kind::Tree => tree_objects(&entry.hex_string(), &name, seen, out)?,
+            Kind::Blob if seen.insert(entry.hex_string()) => out.push((entry.hex_string(), name)),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+pub fn rev_list(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = WalkOptions::default();
+    let (mut count, mut objects, mut left_right, mut reverse) = (false, false, false, false);
+    let mut max_count = None;
+    let mut negated = false;
+    let (mut include, mut exclude) = (Vec::new(), Vec::new());
+    let mut left = HashSet::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--count" => count = true,
+            "--objects" => objects = true,
+            "--topo-order" => options.topo_order = true,
+            "--date-order" => options.topo_order = false,
+            "--ancestry-path" => options.ancestry_path = true,
+            "--first-parent" => options.first_parent = true,
+            "--left-right" => left_right = true,
+            "--reverse" => reverse = true,
+            "--not" => negated = !negated,
+            "--all" => {
+                let mut tips: Vec<String> = refs::list("refs/")?
+                    .into_iter()
+                    .map(|(_, hash)| hash)
+                    .collect();
+                tips.extend(refs::resolve("HEAD")?);
+                for tip in tips {
+                    if let Ok(commit) = peel(&tip, Some("commit")) {
+                        match negated {
+                            true => exclude.push(commit),
+                            false => include.push(commit),
+                        }
+                    }
+                }
+            }
+            "-n" => {
+                let value = args_iter
+                    .next()
+                    .ok_or(GitError::any("-n requires a value"))?;
+                max_count = Some(
+                    value
+                        .parse()
+                        .map_err(|_| GitError::any(format!("invalid count: {value}")))?,
+                );
+            }
+            arg => {
+                if let Some(n) = arg
+                    .strip_prefix("--max-count=")
+                    .or_else(|| arg.strip_prefix("-n"))
+                    .or_else(|| arg.strip_prefix('-').filter(|n| n.parse::<usize>().is_ok()))
+                {
+                    max_count = Some(
+                        n.parse::<usize>()
+                            .map_err(|_| GitError::any(format!("invalid count: {n}")))?,
+                    );
+                    continue;
+                }
+                if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                }
+                if let Some((side, _)) = arg.split_once("...") {
+                    let side = if side.is_empty() { "HEAD" } else { side };
+                    left.extend(ancestors(&resolve_commit(side)?)?);
+                }
+                for spec in resolve_range(arg)? {
+                    match (spec, negated) {
+                        (RevSpec::Include(hash), false) | (RevSpec::Exclude(hash), true) => {
+                            include.push(resolve_commit(&hash)?)
+                        }
+                        (RevSpec::Include(hash), true) | (RevSpec::Exclude(hash), false) => {
+                            exclude.push(resolve_commit(&hash)?)
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if include.is_empty() {
+        return Err(GitError::any("usage: git rev-list [<options>] <commit>..."));
+    }
+    let mut commits = list(&include, &exclude, &options)?;
+    if let Some(max) = max_count {
+        commits.truncate(max);
+    }
+    if reverse {
+        commits.reverse();
+    }
+    let side = |commit: &Commit| match left.contains(commit.hash()) {
+        true => "<",
+        false => ">",
+    };
+    if count {
+        match left_right {
+            true => {
+                let lefts = commits.iter().filter(|c| side(c) == "<").count();
+                println!("{lefts}\t{}", commits.len() - lefts);
+            }
+            false => println!("{}", commits.len()),
+        }
+        return Ok(());
+    }
+    for commit in &commits {
+        match left_right {
+            true => println!("{}{}", side(commit), commit.hash()),
+            false => println!("{}", commit.hash()),
+        }
+    }
+    if objects {
+        let mut hidden = HashSet::new();
+        for hash in &exclude {
+            hidden.extend(ancestors(hash)?);
+        }
+        let mut boundary: Vec<String> = exclude.clone();
+        for commit in &commits {
+            let parents = commit.parents().iter();
+            boundary.extend(parents.filter(|parent| hidden.contains(*parent)).cloned());
+        }
+        let mut seen = HashSet::new();
+        for hash in boundary {
+            let tree = Commit::from_hex_string(&hash)?.tree().clone();
+            tree_objects(&tree, "", &mut seen, &mut Vec::new())?;
+        }
+        let mut out = Vec::new();
+        for commit in &commits {
+            tree_objects(commit.tree(), "", &mut seen, &mut out)?;
+        }
+        for (hash, path) in out {
+            println!("{hash} {path}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use crate::error::GitError;
+use crate::refs::{head_branch, shorten};
+use crate::revision::{dwim_ref, is_range, resolve, resolve_range, RevSpec};
+pub fn rev_parse(args: Vec<String>) -> Result<(), GitError> {
+    let mut verify = false;
+    let mut quiet = false;
+    let mut short = None;
+    let mut abbrev_ref = false;
+    let mut symbolic_full_name = false;
+    let mut revs = Vec::new();
+    for arg in &args {
+        match arg.as_str() {
+            "--verify" => verify = true,
+            "-q" | "--quiet" => quiet = true,
+            "--short" => short = Some(7),
+            "--abbrev-ref" => abbrev_ref = true,
+            "--symbolic-full-name" => symbolic_full_name = true,
+            "--git-dir" => println!(".git"),
+            "--" => {}
+            arg => match arg.strip_prefix("--short=") {
+                Some(len) => {
+                    let len: usize = len
+                        .parse()
+                        .map_err(|_| GitError::any(format!("invalid --short length: {len}")))?;
+                    short = Some(len.clamp(4, 40));
+                }
+                None if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                }
+                None => revs.push(arg),
+            },
+        }
+    }
+    if verify && revs.len() != 1 {
+        return Err(GitError::any("Needed a single revision"));
+    }
+    for rev in revs {
+        if abbrev_ref || symbolic_full_name {
+            let name = if rev == "@" { "HEAD" } else { rev };
+            let full = match name {
+                "HEAD" => head_branch()?
+                    .map(|branch| format!("refs/heads/{branch}"))
+                    .unwrap_or_else(|| "HEAD".into()),
+                _ => match dwim_ref(name)? {
+                    Some((full, _)) => full,
+                    None => return Err(GitError::any(format!("unknown revision: {rev}"))),
+                },
+            };
+            if symbolic_full_name {
+                println!("{full}");
+            } else {
+                println!("{}", shorten(&full));
+            }
+            continue;
+        }
+        let specs = if !verify && is_range(rev) {
+            resolve_range(rev)
+        } else {
+            resolve(rev).map(|hash| vec![RevSpec::Include(hash)])
+        };
+        let specs = match specs {
+            Ok(specs) => specs,
+            Err(_) if quiet && verify => return Err(GitError::exit(1)),
+            Err(e) => return Err(e),
+        };
+        let abbreviate = |hash: &str| match short {
+            Some(len) => hash[..len.min(hash.len())].to_string(),
+            None => hash.to_string(),
+        };
+        for spec in specs {
+            match spec {
+                RevSpec::Include(hash) => println!("{}", abbreviate(&hash)),
+                RevSpec::Exclude(hash) => println!("^{}", abbreviate(&hash)),
+            }
+        }
+    }
+    Ok(())
+}
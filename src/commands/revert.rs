@@ -0,0 +1,34 @@
+use crate::error::GitError;
+use crate::sequencer::{
+    replay, replay_abort, replay_commits, replay_continue, replay_skip, Action, ReplayOptions,
+};
+pub fn revert(args: Vec<String>) -> Result<(), GitError> {
+    let mut options = ReplayOptions::default();
+    let mut revs = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--continue" => return replay_continue("revert"),
+            "--skip" => return replay_skip("revert"),
+            "--abort" => return replay_abort("revert", true),
+            "--quit" => return replay_abort("revert", false),
+            "-n" | "--no-commit" => options.no_commit = true,
+            "-m" | "--mainline" => {
+                let value = args_iter.next().unwrap_or_default();
+                options.mainline = Some(
+                    value
+                        .parse()
+                        .map_err(|_| GitError::any("switch `m' expects a numerical value"))?,
+                );
+            }
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => revs.push(arg),
+        }
+    }
+    if revs.is_empty() {
+        return Err(GitError::any("empty commit set passed"));
+    }
+    replay(Action::Revert, &replay_commits(&revs, false)?, options)
+}
@@ -0,0 +1,106 @@
+use crate::error::GitError;
+use crate::objects::{GitObject, Kind};
+use crate::refs;
+use crate::revision::peel;
+pub fn show_ref(args: Vec<String>) -> Result<(), GitError> {
+    let mut head = false;
+    let mut heads = false;
+    let mut tags = false;
+    let mut dereference = false;
+    let mut hash_only = None;
+    let mut verify = false;
+    let mut quiet = false;
+    let mut patterns = Vec::new();
+    for arg in &args {
+        match arg.as_str() {
+            "--head" => head = true,
+            "--heads" | "--branches" => heads = true,
+            "--tags" => tags = true,
+            "-d" | "--dereference" => dereference = true,
+            "-s" | "--hash" => hash_only = Some(40),
+            "--verify" => verify = true,
+            "-q" | "--quiet" => quiet = true,
+            arg => match arg.strip_prefix("--hash=") {
+                Some(len) => {
+                    let len: usize = len
+                        .parse()
+                        .map_err(|_| GitError::any(format!("invalid --hash length: {len}")))?;
+                    hash_only = Some(len.clamp(4, 40));
+                }
+                None if arg.starts_with('-') => {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                }
+                None => patterns.push(arg),
+            },
+        }
+    }
+    let show = |name: &str, hash: &str| {
+        if quiet {
+            return;
+        }
+        match hash_only {
+            Some(len) => println!("{}", &hash[..len]),
+            None => println!("{hash} {name}"),
+        }
+    };
+    // Tags are peeled from `packed-refs` when recorded there, or else by
+    // reading the tag objects.
+    let show_peeled = |name: &str, hash: &str| -> Result<(), GitError> {
+        if !dereference {
+            return Ok(());
+        }
+        let peeled = match refs::peeled(name)? {
+            Some(peeled) => Some(peeled),
+            None if matches!(GitObject::from_hex_string(hash)?.kind(), Kind::Tag) => {
+                Some(peel(hash, None)?)
+            }
+            None => None,
+        };
+        if let Some(peeled) = peeled {
+            show(&format!("{name}^{{}}"), &peeled);
+        }
+        Ok(())
+    };
+    if verify {
+        for pattern in &patterns {
+            let hash = match pattern.starts_with("refs/") || *pattern == "HEAD" {
+                true => refs::resolve(pattern)?,
+                false => None,
+            };
+            match hash {
+                Some(hash) => {
+                    show(pattern, &hash);
+                    show_peeled(pattern, &hash)?;
+                }
+                None if quiet => return Err(GitError::exit(1)),
+                None => return Err(GitError::any(format!("'{pattern}' - not a valid ref"))),
+            }
+        }
+        return Ok(());
+    }
+    let mut found = false;
+    if head {
+        if let Some(hash) = refs::resolve("HEAD")? {
+            found = true;
+            show("HEAD", &hash);
+        }
+    }
+    for (name, hash) in refs::list("refs/")? {
+        let kind_matches = (!heads && !tags)
+            || (heads && name.starts_with("refs/heads/"))
+            || (tags && name.starts_with("refs/tags/"));
+        let pattern_matches = patterns.is_empty()
+            || patterns
+                .iter()
+                .any(|p| name == **p || name.ends_with(&format!("/{p}")));
+        if kind_matches && pattern_matches {
+            found = true;
+            show(&name, &hash);
+            show_peeled(&name, &hash)?;
+        }
+    }
+    match found {
+        true => Ok(()),
+        false => Err(GitError::exit(1)),
+    }
+}
@@ -0,0 +1,389 @@
+use super::reflog::{finish, prune};
+use super::status;
+use super::switch::{switch_to, Destination};
+use crate::commit::Commit;
+use crate::diff::{
+    compare, detect_renames, index_snapshot, print_diffs, tree_snapshot, DiffOptions, Format,
+    Snapshot, DEFAULT_SIMILARITY,
+};
+use crate::error::GitError;
+use crate::ignore::Ignore;
+use crate::index::{Index, IndexEntry};
+use crate::merge::{commit_snapshot, merge_trees, ConflictStyle, Labels};
+use crate::objects::{short_hash, GitObject};
+use crate::reflog;
+use crate::refs;
+use crate::revision::resolve_commit;
+use crate::sequencer;
+use crate::worktree::{remove_file, update_tree, write_file};
+use std::collections::BTreeSet;
+use std::fs;
+const STASH: &str = "refs/stash";
+const KEPT: &str = "The stash entry is kept in case you need it again.";
+/// Names the stash entry `arg` refers to, where a bare number `n` means
+/// `stash@{n}` and nothing means the latest entry.
+fn stash_name(arg: Option<&String>) -> Result<String, GitError> {
+    if reflog::read(STASH)?.is_empty() {
+        return Err(GitError::any("No stash entries found."));
+    }
+    Ok(match arg {
+        None => format!("{STASH}@{{0}}"),
+        Some(n) if n.chars().all(|c| c.is_ascii_digit()) => format!("{STASH}@{{{n}}}"),
+        Some(name) => name.clone(),
+    })
+}
+/// Loads the working tree commit of a stash entry. Its parents are the
+/// commit it was based on and the index commit, with the untracked files
+/// commit as an optional third.
+fn stash_commit(name: &str) -> Result<Commit, GitError> {
+    let commit = Commit::from_hex_string(resolve_commit(name)?)?;
+    if commit.parents().len() < 2 {
+        return Err(GitError::any(format!(
+            "'{name}' is not a stash-like commit"
+        )));
+    }
+    Ok(commit)
+}
+/// Hashes the modified tracked files into a copy of the index and writes
+/// it as a tree, the working tree state a stash records.
+fn worktree_tree(index: &Index) -> Result<String, GitError> {
+    let mut worktree = Index::default();
+    for entry in index.entries() {
+        if entry.mode() == "160000" || !entry.is_modified()? {
+            worktree.add(entry.clone());
+        } else if fs::symlink_metadata(entry.path()).is_ok() {
+            let blob = GitObject::from_path(entry.path(), true)?;
+            worktree.add(IndexEntry::from_file(
+                entry.path().as_str(),
+                blob.hash().clone(),
+            )?);
+        }
+    }
+    worktree.write_tree()
+}
+/// Writes the untracked files at `paths` into a tree of their own.
+fn untracked_tree(paths: &[String]) -> Result<String, GitError> {
+    let mut untracked = Index::default();
+    for path in paths {
+        let blob = GitObject::from_path(path, true)?;
+        untracked.add(IndexEntry::from_file(path.as_str(), blob.hash().clone())?);
+    }
+    untracked.write_tree()
+}
+fn write_commit(message: &str, tree: &str, parents: &[&str]) -> Result<String, GitError> {
+    let commit = GitObject::build_commit(message, tree, parents)?;
+    commit.write()?;
+    Ok(commit.hex_string())
+}
+/// Replaces the index with `target`, keeping the stat data of entries that
+/// stay the same.
+fn restage(target: &Snapshot) -> Result<(), GitError> {
+    let old = Index::load()?;
+    let mut index = Index::default();
+    for (path, (mode, hash)) in target {
+        match old.get(path) {
+            Some(kept) if kept.stage() == 0 && &kept.mode() == mode && kept.hash() == hash => {
+                index.add(kept.clone())
+            }
+            _ => index.add(IndexEntry::new(path.as_str(), mode, hash.clone(), 0)?),
+        }
+    }
+    index.write()
+}
+fn push(args: &[String]) -> Result<(), GitError> {
+    let mut message = None;
+    let mut keep_index = false;
+    let mut include_untracked = false;
+    let mut quiet = false;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-m" | "--message" => {
+                message = Some(
+                    args_iter
+                        .next()
+                        .ok_or_else(|| GitError::any(format!("option {arg} requires a value")))?
+                        .clone(),
+                )
+            }
+            "-k" | "--keep-index" => keep_index = true,
+            "--no-keep-index" => keep_index = false,
+            "-u" | "--include-untracked" => include_untracked = true,
+            "--no-include-untracked" => include_untracked = false,
+            "-q" | "--quiet" => quiet = true,
+            arg => match arg.strip_prefix("--message=") {
+                Some(value) => message = Some(value.to_string()),
+                None => return Err(GitError::any(format!("unknown option: {arg}"))),
+            },
+        }
+    }
+    let head =
+        refs::resolve("HEAD")?.ok_or(GitError::any("You do not have the initial commit yet"))?;
+    let head_commit = Commit::from_hex_string(&head)?;
+    let branch = refs::head_branch()?.unwrap_or_else(|| "(no branch)".to_string());
+    let summary = format!("{branch}: {} {}", short_hash(&head), head_commit.subject());
+    let index = Index::load()?;
+    let unmerged: BTreeSet<&String> = index
+        .entries()
+        .iter()
+        .filter(|entry| entry.stage() > 0)
+        .map(|entry| entry.path())
+        .collect();
+    if !unmerged.is_empty() {
+        for path in unmerged {
+            eprintln!("{path}: needs merge");
+        }
+        return Err(GitError::exit(1));
+    }
+    let index_tree = index.write_tree()?;
+    let worktree = worktree_tree(&index)?;
+    let untracked: Vec<String> = match include_untracked {
+        true => Ignore::load()?
+            .walk("")?
+            .into_iter()
+            .filter(|path| !index.contains(path))
+            .collect(),
+        false => Vec::new(),
+    };
+    let unchanged = |tree: &String| tree == head_commit.tree();
+    if unchanged(&index_tree) && unchanged(&worktree) && untracked.is_empty() {
+        println!("No local changes to save");
+        return Ok(());
+    }
+    let index_commit = write_commit(&format!("index on {summary}"), &index_tree, &[&head])?;
+    let mut parents = vec![head.as_str(), index_commit.as_str()];
+    let untracked_commit = match untracked.is_empty() {
+        true => None,
+        false => Some(write_commit(
+            &format!("untracked files on {summary}"),
+            &untracked_tree(&untracked)?,
+            &[],
+        )?),
+    };
+    parents.extend(untracked_commit.as_deref());
+    let message = match message {
+        Some(message) => format!("On {branch}: {message}"),
+        None => format!("WIP on {summary}"),
+    };
+    let stash = write_commit(&message, &worktree, &parents)?;
+    refs::update(STASH, &stash, None, false, &message)?;
+    if !quiet {
+        println!("Saved working directory and index state {message}");
+    }
+    let kept = match keep_index {
+        true => &index_tree,
+        false => head_commit.tree(),
+    };
+    let head_snapshot = tree_snapshot(head_commit.tree(), true)?;
+    update_tree(&head_snapshot, &tree_snapshot(kept, true)?, true, "reset")?;
+    for path in &untracked {
+        remove_file(path)?;
+    }
+    Ok(())
+}
+/// Applies the changes recorded in `stash` to the working tree, and to the
+/// index as well with `restore_index`; otherwise only files the stash added
+/// are staged. Returns whether the changes merged without conflicts.
+fn apply_stash(stash: &Commit, restore_index: bool, quiet: bool) -> Result<bool, GitError> {
+    let index = Index::load()?;
+    if index.entries().iter().any(|entry| entry.stage() > 0) {
+        return Err(GitError::any(
+            "Cannot apply a stash in the middle of a merge",
+        ));
+    }
+    let current = index_snapshot(&index);
+    let base = commit_snapshot(&stash.parents()[0])?;
+    let stashed = tree_snapshot(stash.tree(), true)?;
+    let labels = Labels {
+        base: "Version stash was based on".to_string(),
+        ours: "Updated upstream".to_string(),
+        theirs: "Stashed changes".to_string(),
+    };
+    let staged = commit_snapshot(&stash.parents()[1])?;
+    let index_result = match restore_index && staged != base {
+        true => {
+            let merge = merge_trees(
+                &base,
+                &current,
+                &staged,
+                &labels,
+                ConflictStyle::from_config()?,
+            )?;
+            if !merge.is_clean() {
+                return Err(GitError::any("Conflicts in index. Try without --index."));
+            }
+            merge.result
+        }
+        false => current.clone(),
+    };
+    let untracked = match stash.parents().get(2) {
+        Some(commit) => commit_snapshot(commit)?,
+        None => Snapshot::new(),
+    };
+    let existing: Vec<&String> = untracked
+        .keys()
+        .filter(|path| fs::symlink_metadata(path).is_ok())
+        .collect();
+    if !existing.is_empty() {
+        let lines: String = existing
+            .iter()
+            .map(|path| format!("{path} already exists, no checkout\n"))
+            .collect();
+        return Err(GitError::any(format!(
+            "{lines}could not restore untracked files from stash"
+        )));
+    }
+    let merge = sequencer::apply(&base, &current, &stashed, &labels)?;
+    if merge.is_clean() {
+        let mut wanted = index_result;
+        if !restore_index {
+            let added = merge
+                .result
+                .iter()
+                .filter(|(path, _)| !current.contains_key(*path) && !base.contains_key(*path));
+            wanted.extend(added.map(|(path, entry)| (path.clone(), entry.clone())));
+        }
+        restage(&wanted)?;
+    } else {
+        for message in merge.messages() {
+            println!("{message}");
+        }
+        if restore_index {
+            eprintln!("Index was not unstashed.");
+        }
+    }
+    for (path, (mode, hash)) in &untracked {
+        write_file(path, mode, hash)?;
+    }
+    if !quiet {
+        status(Vec::new())?;
+    }
+    Ok(merge.is_clean())
+}
+/// Removes entry `name` from the stash reflog, deleting `refs/stash` along
+/// with the last entry.
+fn drop_stash(name: &str, quiet: bool) -> Result<(), GitError> {
+    let invalid = || GitError::any(format!("{name} is not a valid reference"));
+    let n: usize = name
+        .strip_prefix(STASH)
+        .or_else(|| name.strip_prefix("stash"))
+        .and_then(|rest| rest.strip_prefix("@{")?.strip_suffix('}'))
+        .ok_or_else(|| GitError::any(format!("'{name}' is not a stash reference")))?
+        .parse()
+        .map_err(|_| invalid())?;
+    let entries = reflog::read(STASH)?;
+    let position = entries.len().checked_sub(n + 1).ok_or_else(invalid)?;
+    let hash = entries[position].new_hash().clone();
+    if entries.len() == 1 {
+        refs::delete(STASH, None, false)?;
+    } else {
+        let mut keep = vec![true; entries.len()];
+        keep[position] = false;
+        finish(STASH, &prune(entries, &keep, true), true)?;
+    }
+    if !quiet {
+        println!("Dropped {name} ({hash})");
+    }
+    Ok(())
+}
+/// Splits the arguments of `apply`, `pop`, `drop` and `show` into flags and
+/// the stash entry they name.
+fn entry_args(args: &[String]) -> (Vec<&String>, Option<&String>) {
+    let (flags, names): (Vec<&String>, Vec<&String>) =
+        args.iter().partition(|arg| arg.starts_with('-'));
+    (flags, names.first().copied())
+}
+fn apply(args: &[String], pop: bool) -> Result<(), GitError> {
+    let (flags, name) = entry_args(args);
+    let (mut restore_index, mut quiet) = (false, false);
+    for flag in flags {
+        match flag.as_str() {
+            "--index" => restore_index = true,
+            "-q" | "--quiet" => quiet = true,
+            flag => return Err(GitError::any(format!("unknown option: {flag}"))),
+        }
+    }
+    let name = stash_name(name)?;
+    let stash = stash_commit(&name)?;
+    if !apply_stash(&stash, restore_index, quiet)? {
+        if pop {
+            println!("{KEPT}");
+        }
+        return Err(GitError::exit(1));
+    }
+    match pop {
+        true => drop_stash(&name, quiet),
+        false => Ok(()),
+    }
+}
+fn show(args: &[String]) -> Result<(), GitError> {
+    let (flags, name) = entry_args(args);
+    let mut options = DiffOptions {
+        renames: Some(DEFAULT_SIMILARITY),
+        format: Format::Stat,
+        ..DiffOptions::default()
+    };
+    options.configure_rename_limit(&["diff.renameLimit"])?;
+    for flag in flags {
+        match flag.as_str() {
+            "-p" | "-u" | "--patch" => options.format = Format::Patch,
+            flag if options.parse_arg(flag)? => {}
+            flag => return Err(GitError::any(format!("unknown option: {flag}"))),
+        }
+    }
+    let stash = stash_commit(&stash_name(name)?)?;
+    let base = commit_snapshot(&stash.parents()[0])?;
+    let stashed = tree_snapshot(stash.tree(), true)?;
+    let diffs = detect_renames(compare(&base, &stashed, false)?, &base, &options)?;
+    print_diffs(&diffs, &options)?;
+    options.warn_rename_limit("diff.renameLimit");
+    Ok(())
+}
+fn branch(args: &[String]) -> Result<(), GitError> {
+    let name = args
+        .first()
+        .ok_or(GitError::any("No branch name specified"))?;
+    let stash_name = stash_name(args.get(1))?;
+    let stash = stash_commit(&stash_name)?;
+    let destination = Destination::NewBranch {
+        name: name.clone(),
+        start: stash.parents()[0].clone(),
+        reset: false,
+        track: None,
+    };
+    switch_to(destination, false, false)?;
+    if !apply_stash(&stash, true, false)? {
+        return Err(GitError::exit(1));
+    }
+    drop_stash(&stash_name, false)
+}
+pub fn stash(args: Vec<String>) -> Result<(), GitError> {
+    let rest = args.get(1..).unwrap_or_default();
+    match args.first().map(String::as_str) {
+        None => push(&[]),
+        Some("push") => push(rest),
+        Some("list") => {
+            for (n, entry) in reflog::read(STASH)?.iter().rev().enumerate() {
+                println!("stash@{{{n}}}: {}", entry.message());
+            }
+            Ok(())
+        }
+        Some("show") => show(rest),
+        Some("apply") => apply(rest, false),
+        Some("pop") => apply(rest, true),
+        Some("drop") => {
+            let (flags, name) = entry_args(rest);
+            let quiet = flags.iter().any(|flag| *flag == "-q" || *flag == "--quiet");
+            drop_stash(&stash_name(name)?, quiet)
+        }
+        Some("branch") => branch(rest),
+        Some("clear") => match refs::resolve(STASH)? {
+            Some(_) => refs::delete(STASH, None, false),
+            None => Ok(()),
+        },
+        Some(arg) if arg.starts_with('-') => push(&args),
+        Some(command) => Err(GitError::any(format!(
+            "subcommand wasn't specified; 'push' can't be assumed due to unexpected token '{command}'"
+        ))),
+    }
+}
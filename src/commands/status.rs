@@ -0,0 +1,156 @@
+use crate::diff::{
+    compare, detect_renames, index_snapshot, tree_snapshot, DiffOptions, Snapshot,
+    DEFAULT_SIMILARITY,
+};
+use crate::error::GitError;
+use crate::ignore::Ignore;
+use crate::index::Index;
+use crate::objects::{short_hash, GitObject};
+use crate::refs;
+use crate::submodule;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+/// The short status and description of an unmerged path, indexed by which
+/// of the base (1), our (2) and their (4) stages the index holds.
+const UNMERGED: [(&str, &str); 8] = [
+    ("", ""),
+    ("DD", "both deleted"),
+    ("AU", "added by us"),
+    ("UD", "deleted by them"),
+    ("UA", "added by them"),
+    ("DU", "deleted by us"),
+    ("AA", "both added"),
+    ("UU", "both modified"),
+];
+/// How an untracked `path` is listed: as its outermost directory that holds
+/// no tracked files, or as itself.
+fn untracked_entry(index: &Index, path: String) -> String {
+    let dirs = path.match_indices('/').map(|(i, _)| &path[..i]);
+    let dir = dirs
+        .chain(submodule::is_repo(&path).then_some(path.as_str()))
+        .find(|dir| !index.tracks_dir(dir));
+    match dir {
+        Some(dir) => format!("{dir}/"),
+        None => path,
+    }
+}
+pub fn status(args: Vec<String>) -> Result<(), GitError> {
+    let short = args
+        .iter()
+        .any(|a| a == "-s" || a == "--short" || a == "--porcelain");
+    let (branch, head_rev) = (refs::head_branch()?, refs::resolve("HEAD")?);
+    let head_snapshot = match &head_rev {
+        Some(rev) => tree_snapshot(&GitObject::from_hex_string(rev)?.tree_hex_string()?, true)?,
+        None => Snapshot::new(),
+    };
+    let index = Index::load()?;
+    let mut options = DiffOptions {
+        renames: Some(DEFAULT_SIMILARITY),
+        ..DiffOptions::default()
+    };
+    options.configure_rename_limit(&["status.renameLimit", "diff.renameLimit"])?;
+    let staged = compare(&head_snapshot, &index_snapshot(&index), false)?;
+    let mut changes: BTreeMap<String, (char, char)> = BTreeMap::new();
+    let mut renamed_from: HashMap<String, String> = HashMap::new();
+    for diff in detect_renames(staged, &head_snapshot, &options)? {
+        let path = diff
+            .new_path
+            .clone()
+            .or(diff.old_path.clone())
+            .unwrap_or_default();
+        if diff.status() == 'R' {
+            renamed_from.insert(path.clone(), diff.old_path.clone().unwrap_or_default());
+        }
+        changes.insert(path, (diff.status(), ' '));
+    }
+    for entry in index.entries().iter().filter(|e| e.stage() == 0) {
+        let unstaged = if fs::symlink_metadata(entry.path()).is_err() {
+            'D'
+        } else if entry.is_modified()? {
+            'M'
+        } else {
+            continue;
+        };
+        changes.entry(entry.path().clone()).or_insert((' ', ' ')).1 = unstaged;
+    }
+    let mut unmerged: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in index.entries().iter().filter(|e| e.stage() > 0) {
+        *unmerged.entry(entry.path().clone()).or_default() |= 1 << (entry.stage() - 1);
+    }
+    for (path, stages) in &unmerged {
+        let mut code = UNMERGED[*stages].0.chars();
+        let (x, y) = (code.next().unwrap_or(' '), code.next().unwrap_or(' '));
+        changes.insert(path.clone(), (x, y));
+    }
+    let display = |path: &String| match renamed_from.get(path) {
+        Some(old) => format!("{old} -> {path}"),
+        None => path.clone(),
+    };
+    let mut ignore = Ignore::load()?;
+    let untracked: BTreeSet<String> = ignore
+        .walk("")?
+        .into_iter()
+        .filter(|path| !index.contains(path))
+        .map(|path| untracked_entry(&index, path))
+        .collect();
+    if short {
+        for (path, (x, y)) in &changes {
+            println!("{x}{y} {}", display(path));
+        }
+        for path in &untracked {
+            println!("?? {path}");
+        }
+        return Ok(());
+    }
+    match (&branch, &head_rev) {
+        (Some(branch), _) => println!("On branch {branch}"),
+        (None, Some(rev)) => println!("HEAD detached at {}", short_hash(rev)),
+        (None, None) => println!("Not currently on any branch."),
+    }
+    if head_rev.is_none() {
+        println!("\nNo commits yet");
+    }
+    let describe = |c: char| match c {
+        'A' => "new file",
+        'M' => "modified",
+        'D' => "deleted",
+        'R' => "renamed",
+        _ => "typechange",
+    };
+    if !unmerged.is_empty() {
+        println!("\nUnmerged paths:");
+        for (path, stages) in &unmerged {
+            println!("\t{:<17}{path}", format!("{}:", UNMERGED[*stages].1));
+        }
+    }
+    let staged: Vec<_> = changes
+        .iter()
+        .filter(|(path, (x, _))| *x != ' ' && !unmerged.contains_key(*path))
+        .collect();
+    if !staged.is_empty() {
+        println!("\nChanges to be committed:");
+        for (path, (x, _)) in staged {
+            println!("\t{:<12}{}", format!("{}:", describe(*x)), display(path));
+        }
+    }
+    let unstaged: Vec<_> = changes
+        .iter()
+        .filter(|(path, (_, y))| *y != ' ' && !unmerged.contains_key(*path))
+        .collect();
+    if !unstaged.is_empty() {
+        println!("\nChanges not staged for commit:");
+        for (path, (_, y)) in unstaged {
+            println!("\t{:<12}{path}", format!("{}:", describe(*y)));
+        }
+    }
+    if !untracked.is_empty() {
+        println!("\nUntracked files:");
+        for path in &untracked {
+            println!("\t{path}");
+        }
+    }
+    if changes.is_empty() && untracked.is_empty() {
+        println!("nothing to commit, working tree clean");
+    }
+    Ok(())
+}
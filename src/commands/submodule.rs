@@ -0,0 +1,228 @@
+use super::{clone, is_under};
+use crate::commit::Commit;
+use crate::config::Config;
+use crate::error::GitError;
+use crate::index::Index;
+use crate::objects::{short_hash, GitObject};
+use crate::refs;
+use crate::submodule::{self, head, is_repo, resolve_url, within, Submodule};
+use crate::worktree::checkout_tree;
+use std::fs;
+#[derive(Default)]
+struct Options {
+    init: bool,
+    recursive: bool,
+    cached: bool,
+    paths: Vec<String>,
+    prefix: String,
+}
+impl Options {
+    /// The options for recursing into the submodule at `path`.
+    fn nested(&self, path: &str) -> Self {
+        Self {
+            init: self.init,
+            recursive: true,
+            prefix: format!("{}{path}/", self.prefix),
+            ..Self::default()
+        }
+    }
+}
+/// The submodules recorded as gitlinks in the index and selected by
+/// `paths`, with the commit the superproject expects in each.
+fn selected(paths: &[String]) -> Result<Vec<(Submodule, String)>, GitError> {
+    let mut modules = submodule::list()?;
+    let mut selected = Vec::new();
+    for entry in Index::load()?.entries() {
+        let path = entry.path();
+        if entry.mode() != "160000" || entry.stage() != 0 {
+            continue;
+        }
+        if !paths.is_empty()
+            && !paths
+                .iter()
+                .any(|p| is_under(path, p.trim_end_matches('/')))
+        {
+            continue;
+        }
+        let Some(position) = modules.iter().position(|m| m.path == *path) else {
+            return Err(GitError::any(format!(
+                "no submodule mapping found in .gitmodules for path '{path}'"
+            )));
+        };
+        selected.push((modules.remove(position), entry.hex_string()));
+    }
+    Ok(selected)
+}
+/// Names a submodule commit like `git describe --all --always`: a tag or
+/// branch pointing at it, or its abbreviated hash.
+fn describe(path: &str, hash: &str) -> Result<String, GitError> {
+    let label = within(path, || {
+        let refs = refs::list("refs/")?;
+        Ok(["refs/tags/", "refs/heads/", "refs/remotes/"]
+            .iter()
+            .find_map(|prefix| {
+                refs.iter()
+                    .find(|(name, target)| name.starts_with(prefix) && target == hash)
+                    .map(|(name, _)| name.strip_prefix("refs/").unwrap_or(name).to_string())
+            }))
+    });
+    Ok(match label {
+        Ok(Some(label)) => label,
+        _ => short_hash(hash).to_string(),
+    })
+}
+fn status(options: &Options) -> Result<(), GitError> {
+    for (module, commit) in selected(&options.paths)? {
+        let path = &module.path;
+        let display = format!("{}{path}", options.prefix);
+        let Some(head) = head(path)?.filter(|_| is_repo(path)) else {
+            println!("-{commit} {display}");
+            continue;
+        };
+        let (flag, hash) = match head == commit {
+            true => (' ', commit),
+            false if options.cached => ('+', commit),
+            false => ('+', head),
+        };
+        println!("{flag}{hash} {display} ({})", describe(path, &hash)?);
+        if options.recursive {
+            within(path, || status(&options.nested(path)))?;
+        }
+    }
+    Ok(())
+}
+fn init(options: &Options) -> Result<(), GitError> {
+    let config = Config::load()?;
+    for (module, _) in selected(&options.paths)? {
+        let name = &module.name;
+        if config.get(&format!("submodule.{name}.url")).is_some() {
+            continue;
+        }
+        let url = module.url.as_deref().ok_or_else(|| {
+            GitError::any(format!(
+                "No url found for submodule path '{}' in .gitmodules",
+                module.path
+            ))
+        })?;
+        let url = resolve_url(url)?;
+        Config::set(&format!("submodule.{name}.active"), Some("true"))?;
+        Config::set(&format!("submodule.{name}.url"), Some(&url))?;
+        eprintln!(
+            "Submodule '{name}' ({url}) registered for path '{}{}'",
+            options.prefix, module.path
+        );
+    }
+    Ok(())
+}
+/// Detaches HEAD of the current repository at `commit`, updating its
+/// working tree without clobbering local changes.
+fn checkout_commit(commit: &str) -> Result<(), GitError> {
+    let head = refs::resolve("HEAD")?;
+    let old_tree = match &head {
+        Some(head) => Some(Commit::from_hex_string(head)?.tree().clone()),
+        None => None,
+    };
+    let new_tree = Commit::from_hex_string(commit)?.tree().clone();
+    checkout_tree(old_tree.as_deref(), &new_tree, false)?;
+    let from = match (refs::head_branch()?, head) {
+        (Some(branch), _) => branch,
+        (None, Some(head)) => head,
+        (None, None) => "HEAD".to_string(),
+    };
+    let message = format!("checkout: moving from {from} to {commit}");
+    refs::update("HEAD", commit, None, false, &message)
+}
+fn update(options: &Options) -> Result<(), GitError> {
+    if options.init {
+        init(options)?;
+    }
+    let config = Config::load()?;
+    for (module, commit) in selected(&options.paths)? {
+        let path = &module.path;
+        let Some(url) = config.get(&format!("submodule.{}.url", module.name)) else {
+            continue;
+        };
+        let cloned = !is_repo(path);
+        if cloned {
+            fs::create_dir_all(path)?;
+            let destination = fs::canonicalize(path)?;
+            eprintln!("Cloning into '{}'...", destination.display());
+            within(".", || {
+                clone(vec![url.to_string(), destination.to_string_lossy().into()])
+            })?;
+        }
+        if cloned || head(path)?.as_deref() != Some(&commit) {
+            if !within(path, || Ok(GitObject::exists(&commit)))? {
+                return Err(GitError::any(format!(
+                    "Unable to find current revision in submodule path '{path}'"
+                )));
+            }
+            within(path, || checkout_commit(&commit))?;
+            println!(
+                "Submodule path '{}{path}': checked out '{commit}'",
+                options.prefix
+            );
+        }
+        if options.recursive {
+            within(path, || update(&options.nested(path)))?;
+        }
+    }
+    Ok(())
+}
+fn sync(options: &Options) -> Result<(), GitError> {
+    let config = Config::load()?;
+    for (module, _) in selected(&options.paths)? {
+        let Some(url) = module.url.as_deref() else {
+            continue;
+        };
+        let url = resolve_url(url)?;
+        let key = format!("submodule.{}.url", module.name);
+        if config.get(&key).is_none() {
+            continue;
+        }
+        println!(
+            "Synchronizing submodule url for '{}{}'",
+            options.prefix, module.path
+        );
+        Config::set(&key, Some(&url))?;
+        if !is_repo(&module.path) {
+            continue;
+        }
+        within(&module.path, || {
+            Config::set("remote.origin.url", Some(&url))?;
+            match options.recursive {
+                true => sync(&options.nested(&module.path)),
+                false => Ok(()),
+            }
+        })?;
+    }
+    Ok(())
+}
+pub fn submodule(args: Vec<String>) -> Result<(), GitError> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) if !command.starts_with('-') => (command.as_str(), rest),
+        _ => ("status", args.as_slice()),
+    };
+    let mut options = Options::default();
+    for arg in rest {
+        match arg.as_str() {
+            "--init" => options.init = true,
+            "--recursive" => options.recursive = true,
+            "--cached" => options.cached = true,
+            "-q" | "--quiet" | "--" => {}
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            path => options
+                .paths
+                .push(path.trim_start_matches("./").to_string()),
+        }
+    }
+    match command {
+        "status" => status(&options),
+        "init" => init(&options),
+        "update" => update(&options),
+        "sync" => sync(&options),
+        command => Err(GitError::any(format!("unknown subcommand: {command}"))),
+    }
+}
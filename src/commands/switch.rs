@@ -0,0 +1,312 @@
+use super::branch::create_branch;
+use crate::commit::Commit;
+use crate::config::Config;
+use crate::diff::{index_snapshot, tree_snapshot};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::objects::short_hash;
+use crate::refs;
+use crate::revision::{previous_branch, resolve_commit, upstream};
+use crate::revwalk::ancestors;
+use crate::worktree::checkout_tree;
+use std::collections::BTreeSet;
+const DETACHED_ADVICE: &str =
+    "You are in 'detached HEAD' state. You can look around, make experimental
+changes and commit them, and you can discard any commits you make in this
+state without impacting any branches by switching back to a branch.
+
+If you want to create a new branch to retain commits you create, you may
+do so (now or later) by using -c with the switch command. Example:
+
+  git switch -c <new-branch-name>
+
+Or undo this operation with:
+
+  git switch -
+
+Turn off this advice by setting config variable advice.detachedHead to false
+";
+/// Where HEAD ends up after switching.
+pub(crate) enum Destination {
+    Branch(String),
+    NewBranch {
+        name: String,
+        start: String,
+        reset: bool,
+        track: Option<bool>,
+    },
+    Detached(String),
+}
+/// Expands `-` to the previously checked out branch.
+pub(crate) fn expand_previous(name: &str) -> Result<String, GitError> {
+    match name {
+        "-" => previous_branch(1)?.ok_or(GitError::any("invalid reference: @{-1}")),
+        name => Ok(name.to_string()),
+    }
+}
+/// Finds the single remote-tracking branch called `name`, which switching to
+/// a missing local branch of that name creates it from.
+pub(crate) fn guess_remote(name: &str) -> Result<Option<String>, GitError> {
+    let matches: Vec<String> = refs::list("refs/remotes/")?
+        .into_iter()
+        .filter(|(full, _)| {
+            full.strip_prefix("refs/remotes/")
+                .and_then(|rest| rest.split_once('/'))
+                .is_some_and(|(_, branch)| branch == name)
+        })
+        .map(|(full, _)| refs::shorten(&full).to_string())
+        .collect();
+    Ok(match matches.as_slice() {
+        [remote] => Some(remote.clone()),
+        _ => None,
+    })
+}
+fn one_line(hash: &str) -> Result<String, GitError> {
+    Ok(format!(
+        "{} {}",
+        short_hash(hash),
+        Commit::from_hex_string(hash)?.subject()
+    ))
+}
+fn plural(n: usize) -> &'static str {
+    if n == 1 {
+        "commit"
+    } else {
+        "commits"
+    }
+}
+/// Prints how `branch` relates to its upstream, like git does after a
+/// switch.
+fn report_tracking(branch: &str, hash: &str) -> Result<(), GitError> {
+    let Ok(upstream) = upstream(Some(branch)) else {
+        return Ok(());
+    };
+    let name = refs::shorten(&upstream);
+    let Some(upstream_hash) = refs::resolve(&upstream)? else {
+        println!("Your branch is based on '{name}', but the upstream is gone.");
+        println!("  (use \"git branch --unset-upstream\" to fixup)");
+        return Ok(());
+    };
+    let (ours, theirs) = (ancestors(hash)?, ancestors(&upstream_hash)?);
+    let ahead = ours.difference(&theirs).count();
+    let behind = theirs.difference(&ours).count();
+    match (ahead, behind) {
+        (0, 0) => println!("Your branch is up to date with '{name}'."),
+        (ahead, 0) => {
+            println!(
+                "Your branch is ahead of '{name}' by {ahead} {}.",
+                plural(ahead)
+            );
+            println!("  (use \"git push\" to publish your local commits)");
+        }
+        (0, behind) => {
+            println!(
+                "Your branch is behind '{name}' by {behind} {}, and can be fast-forwarded.",
+                plural(behind)
+            );
+            println!("  (use \"git pull\" to update your local branch)");
+        }
+        (ahead, behind) => {
+            println!("Your branch and '{name}' have diverged,");
+            println!("and have {ahead} and {behind} different commits each, respectively.");
+            println!("  (use \"git pull\" to merge the remote branch into yours)");
+        }
+    }
+    Ok(())
+}
+/// Lists paths whose index or working tree differs from the new HEAD.
+fn show_local_changes(tree: &str) -> Result<(), GitError> {
+    let index = Index::load()?;
+    let (head, staged) = (tree_snapshot(tree, true)?, index_snapshot(&index));
+    let paths: BTreeSet<&String> = head.keys().chain(staged.keys()).collect();
+    for path in paths {
+        let status = match (head.get(path), staged.get(path)) {
+            (Some(_), None) => 'D',
+            (None, Some(_)) => 'A',
+            (old, new) if old != new => 'M',
+            _ => match index.get(path) {
+                Some(_) if std::fs::symlink_metadata(path).is_err() => 'D',
+                Some(entry) if entry.is_modified()? => 'M',
+                _ => continue,
+            },
+        };
+        println!("{status}\t{path}");
+    }
+    Ok(())
+}
+/// Updates the index and working tree for `destination` and points HEAD at
+/// it, recording the move in HEAD's reflog. `advise` explains a newly
+/// detached HEAD unless `advice.detachedHead` is false.
+pub(crate) fn switch_to(
+    destination: Destination,
+    force: bool,
+    advise: bool,
+) -> Result<(), GitError> {
+    let head = refs::resolve("HEAD")?;
+    let current = refs::head_branch()?;
+    let (hash, label) = match &destination {
+        Destination::Branch(name) => (
+            refs::resolve(&format!("refs/heads/{name}"))?
+                .ok_or_else(|| GitError::any(format!("invalid reference: {name}")))?,
+            name.clone(),
+        ),
+        Destination::NewBranch { name, start, .. } => (
+            resolve_commit(start).map_err(|_| {
+                GitError::any(format!(
+                    "'{start}' is not a commit and a branch '{name}' cannot be created from it"
+                ))
+            })?,
+            name.clone(),
+        ),
+        Destination::Detached(rev) => (
+            resolve_commit(rev).map_err(|_| GitError::any(format!("invalid reference: {rev}")))?,
+            rev.clone(),
+        ),
+    };
+    let old_tree = match &head {
+        Some(head) => Some(Commit::from_hex_string(head)?.tree().clone()),
+        None => None,
+    };
+    let new_tree = Commit::from_hex_string(&hash)?.tree().clone();
+    let existed = refs::resolve(&format!("refs/heads/{label}"))?.is_some();
+    // The new branch must be creatable before the working tree is touched.
+    if let Destination::NewBranch { reset, .. } = destination {
+        let full = format!("refs/heads/{label}");
+        refs::check_name(&full)
+            .map_err(|_| GitError::any(format!("'{label}' is not a valid branch name")))?;
+        if existed && !reset {
+            return Err(GitError::any(format!(
+                "a branch named '{label}' already exists"
+            )));
+        }
+        refs::check_available(&full, None)
+            .map_err(|e| GitError::any(format!("cannot lock ref '{full}': {e}")))?;
+    }
+    checkout_tree(old_tree.as_deref(), &new_tree, force)?;
+    if let Destination::NewBranch {
+        name,
+        start,
+        reset,
+        track,
+    } = &destination
+    {
+        create_branch(name, start, *reset, *track)?;
+    }
+    let from = match (&current, &head) {
+        (Some(branch), _) => branch.clone(),
+        (None, Some(head)) => head.clone(),
+        (None, None) => "HEAD".to_string(),
+    };
+    let message = format!("checkout: moving from {from} to {label}");
+    if !force {
+        show_local_changes(&new_tree)?;
+    }
+    let was_detached = current.is_none() && head.is_some();
+    if let (true, Some(head)) = (was_detached, &head) {
+        if *head != hash {
+            eprintln!("Previous HEAD position was {}", one_line(head)?);
+        }
+    }
+    if let Destination::Detached(rev) = &destination {
+        refs::update("HEAD", &hash, None, false, &message)?;
+        let advice = Config::load()?.get("advice.detachedHead") != Some("false");
+        if advise && advice && current.is_some() {
+            eprintln!("Note: switching to '{rev}'.\n");
+            eprintln!("{DETACHED_ADVICE}");
+        }
+        if current.is_some() || head.as_ref() != Some(&hash) {
+            eprintln!("HEAD is now at {}", one_line(&hash)?);
+        }
+        return Ok(());
+    }
+    refs::set_symbolic("HEAD", &format!("refs/heads/{label}"), Some(&message))?;
+    let on_label = current.as_deref() == Some(&label);
+    match destination {
+        Destination::NewBranch { .. } if existed && on_label => {
+            eprintln!("Reset branch '{label}'");
+            return Ok(());
+        }
+        Destination::NewBranch { .. } if existed => {
+            eprintln!("Switched to and reset branch '{label}'");
+            return Ok(());
+        }
+        Destination::NewBranch { .. } => {
+            eprintln!("Switched to a new branch '{label}'");
+            return Ok(());
+        }
+        _ if on_label => eprintln!("Already on '{label}'"),
+        _ => eprintln!("Switched to branch '{label}'"),
+    }
+    report_tracking(&label, &hash)
+}
+pub fn switch(args: Vec<String>) -> Result<(), GitError> {
+    let mut force = false;
+    let mut detach = false;
+    let mut guess = true;
+    let mut track = None;
+    let mut create: Option<(String, bool)> = None;
+    let mut names = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-f" | "--force" | "--discard-changes" => force = true,
+            "-d" | "--detach" => detach = true,
+            "-q" | "--quiet" => {}
+            "--guess" => guess = true,
+            "--no-guess" => guess = false,
+            "-t" | "--track" => track = Some(true),
+            "--no-track" => track = Some(false),
+            "-c" | "--create" | "-C" | "--force-create" => {
+                let name = args_iter
+                    .next()
+                    .ok_or_else(|| GitError::any(format!("option {arg} requires a value")))?;
+                create = Some((name, arg == "-C" || arg == "--force-create"));
+            }
+            arg if arg.starts_with('-') && arg != "-" => {
+                return Err(GitError::any(format!("unknown option: {arg}")))
+            }
+            _ => names.push(arg),
+        }
+    }
+    if names.len() > 1 {
+        return Err(GitError::any("only one reference expected"));
+    }
+    let target = names.pop();
+    let destination = match (create, detach) {
+        (Some(_), true) => {
+            return Err(GitError::any(
+                "options '--create' and '--detach' cannot be used together",
+            ))
+        }
+        (Some((name, reset)), false) => Destination::NewBranch {
+            name,
+            start: expand_previous(target.as_deref().unwrap_or("HEAD"))?,
+            reset,
+            track,
+        },
+        (None, true) => {
+            Destination::Detached(expand_previous(target.as_deref().unwrap_or("HEAD"))?)
+        }
+        (None, false) => {
+            let target = target.ok_or(GitError::any("missing branch or commit argument"))?;
+            let name = expand_previous(&target)?;
+            if refs::resolve(&format!("refs/heads/{name}"))?.is_some() {
+                Destination::Branch(name)
+            } else if let Some(remote) = guess.then(|| guess_remote(&name)).transpose()?.flatten() {
+                Destination::NewBranch {
+                    name,
+                    start: remote,
+                    reset: false,
+                    track,
+                }
+            } else if resolve_commit(&name).is_ok() {
+                return Err(GitError::any(format!(
+                    "a branch is expected, got commit '{name}'\nhint: If you want to detach HEAD at the commit, try again with the --detach option."
+                )));
+            } else {
+                return Err(GitError::any(format!("invalid reference: {name}")));
+            }
+        }
+    };
+    switch_to(destination, force, false)
+}
@@ -0,0 +1,58 @@
+use crate::error::GitError;
+use crate::refs::{self, Target};
+pub fn symbolic_ref(args: Vec<String>) -> Result<(), GitError> {
+    let mut short = false;
+    let mut quiet = false;
+    let mut delete = false;
+    let mut message = None;
+    let mut positional = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--short" => short = true,
+            "-q" | "--quiet" => quiet = true,
+            "-d" | "--delete" => delete = true,
+            "-m" => {
+                message = Some(
+                    args_iter
+                        .next()
+                        .ok_or(GitError::any("option -m requires a value"))?,
+                );
+            }
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")));
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    match (delete, positional.as_slice()) {
+        (true, [name]) => match refs::read(name)? {
+            Some(Target::Symbolic(_)) => refs::delete(name, None, false),
+            _ if quiet => Err(GitError::exit(1)),
+            _ => Err(GitError::any(format!("ref {name} is not a symbolic ref"))),
+        },
+        (false, [name]) => match refs::read(name)? {
+            Some(Target::Symbolic(target)) => {
+                let target = match short {
+                    true => refs::shorten(&target),
+                    false => &target,
+                };
+                println!("{target}");
+                Ok(())
+            }
+            _ if quiet => Err(GitError::exit(1)),
+            _ => Err(GitError::any(format!("ref {name} is not a symbolic ref"))),
+        },
+        (false, [name, target]) => {
+            if *name == "HEAD" && !target.starts_with("refs/") {
+                return Err(GitError::any(format!(
+                    "refusing to point HEAD outside of refs/: {target}"
+                )));
+            }
+            refs::set_symbolic(name, target, message.map(String::as_str))
+        }
+        _ => Err(GitError::any(
+            "usage: symbolic-ref [-q] [--short] [-d] <name> [<ref>]",
+        )),
+    }
+}
@@ -0,0 +1,239 @@
+use crate::commit::{sign, Commit, Signature};
+use crate::config::Config;
+use crate::date::DateFormat;
+use crate::editor::{edit, editor, Cleanup};
+use crate::error::GitError;
+use crate::ignore::wildmatch;
+use crate::objects::{short_hash, GitObject, Kind};
+use crate::refs;
+use crate::revision::resolve;
+use crate::tag::Tag;
+use std::fs;
+use std::io::Read;
+const TAGS: &str = "refs/tags/";
+const TAG_EDITMSG: &str = ".git/TAG_EDITMSG";
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    List,
+    Delete,
+    Create,
+}
+#[derive(Default)]
+struct Options {
+    annotate: bool,
+    force: bool,
+    edit: bool,
+    messages: Vec<String>,
+    file: Option<String>,
+    cleanup: Option<String>,
+    signing_key: Option<String>,
+}
+fn list(patterns: &[String]) -> Result<(), GitError> {
+    for (name, _) in refs::list(TAGS)? {
+        let short = &name[TAGS.len()..];
+        if patterns.is_empty()
+            || patterns
+                .iter()
+                .any(|pattern| wildmatch(pattern.as_bytes(), short.as_bytes()))
+        {
+            println!("{short}");
+        }
+    }
+    Ok(())
+}
+fn delete(names: &[String]) -> Result<(), GitError> {
+    let mut failed = false;
+    for name in names {
+        let full = format!("{TAGS}{name}");
+        let Some(hash) = refs::resolve(&full)? else {
+            eprintln!("error: tag '{name}' not found.");
+            failed = true;
+            continue;
+        };
+        refs::delete(&full, Some(&hash), false)?;
+        println!("Deleted tag '{name}' (was {})", short_hash(&hash));
+    }
+    if failed {
+        return Err(GitError::exit(1));
+    }
+    Ok(())
+}
+/// The reflog message for tagging `object`, naming what it is.
+fn reflog_message(object: &GitObject) -> Result<String, GitError> {
+    let hash = object.hex_string();
+    let what = match object.kind() {
+        Kind::Commit => {
+            let commit = Commit::parse(&hash, object.contents())?;
+            format!(
+                "{}, {}",
+                commit.subject(),
+                commit.committer().date(DateFormat::Short)
+            )
+        }
+        Kind::Tree => "tree object".to_string(),
+        Kind::Blob => "blob object".to_string(),
+        Kind::Tag => "other tag object".to_string(),
+    };
+    Ok(format!("tag: tagging {} ({what})", short_hash(&hash)))
+}
+/// The message for an annotated tag from `-m` or `-F`, or from the editor,
+/// cleaned up as `--cleanup` asks.
+fn tag_message(options: &Options, name: &str, previous: Option<&Tag>) -> Result<String, GitError> {
+    let mode = match options.cleanup.as_deref() {
+        None => Cleanup::Strip,
+        Some(name) => match Cleanup::parse(name)? {
+            mode @ (Cleanup::Verbatim | Cleanup::Whitespace | Cleanup::Strip) => mode,
+            _ => return Err(GitError::any(format!("Invalid cleanup mode {name}"))),
+        },
+    };
+    let given = match &options.file {
+        Some(path) if path == "-" => {
+            let mut message = String::new();
+            std::io::stdin().read_to_string(&mut message)?;
+            Some(message)
+        }
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .map_err(|_| GitError::any(format!("could not open or read '{path}'")))?,
+        ),
+        None if options.messages.is_empty() => None,
+        None => Some(options.messages.join("\n\n") + "\n"),
+    };
+    let mut message = given.clone().unwrap_or_default();
+    if given.is_none() || options.edit {
+        let template = match (&given, previous) {
+            (Some(message), _) => message.clone(),
+            (None, Some(tag)) => tag.message().clone(),
+            (None, None) => {
+                let help = match mode {
+                    Cleanup::Strip => "Lines starting with '#' will be ignored.",
+                    _ => "Lines starting with '#' will be kept; you may remove them yourself if you want to.",
+                };
+                format!("\n#\n# Write a message for tag:\n#   {name}\n# {help}\n")
+            }
+        };
+        fs::write(TAG_EDITMSG, template)?;
+        if edit(&editor()?, TAG_EDITMSG).is_err() {
+            eprintln!("Please supply the message using either -m or -F option.");
+            return Err(GitError::exit(1));
+        }
+        message = fs::read_to_string(TAG_EDITMSG)?;
+    }
+    let message = mode.apply(&message, true);
+    if given.is_none() && message.is_empty() {
+        return Err(GitError::any("no tag message?"));
+    }
+    Ok(message)
+}
+fn create(options: &Options, name: &str, rev: &str) -> Result<(), GitError> {
+    let full = format!("{TAGS}{name}");
+    refs::check_name(&full)
+        .map_err(|_| GitError::any(format!("'{name}' is not a valid tag name.")))?;
+    let hash = resolve(rev)
+        .map_err(|_| GitError::any(format!("Failed to resolve '{rev}' as a valid ref.")))?;
+    let object = GitObject::from_hex_string(&hash)?;
+    let previous = refs::resolve(&full)?;
+    if previous.is_some() && !options.force {
+        return Err(GitError::any(format!("tag '{name}' already exists")));
+    }
+    let annotated = options.annotate
+        || options.signing_key.is_some()
+        || options.file.is_some()
+        || !options.messages.is_empty();
+    let target = match annotated {
+        true => {
+            let previous_tag = previous
+                .as_deref()
+                .and_then(|hash| Tag::from_hex_string(hash).ok());
+            let mut message = tag_message(options, name, previous_tag.as_ref())?;
+            let tagger = Signature::committer()?;
+            if let Some(key) = &options.signing_key {
+                let unsigned = Tag::build(&hash, object.kind(), name, &tagger, &message)?;
+                let payload = String::from_utf8_lossy(unsigned.contents()).to_string();
+                message.push_str(&sign(&payload, key, &tagger, &Config::load()?)?);
+            }
+            let tag = Tag::build(&hash, object.kind(), name, &tagger, &message)?;
+            tag.write()?;
+            let advice = Config::load()?.get("advice.nestedTag") != Some("false");
+            if matches!(object.kind(), Kind::Tag) && advice {
+                eprintln!(
+                    "hint: You have created a nested tag. The object referred to by your new tag is
+hint: already a tag. If you meant to tag the object that it points to, use:
+hint: 
+hint: \tgit tag -f {name} {rev}^{{}}
+hint: Disable this message with \"git config advice.nestedTag false\""
+                );
+            }
+            let _ = fs::remove_file(TAG_EDITMSG);
+            tag.hex_string()
+        }
+        false => hash.clone(),
+    };
+    let old = previous.as_deref().unwrap_or(refs::ZERO_HASH);
+    refs::update(&full, &target, Some(old), false, &reflog_message(&object)?)?;
+    if let Some(previous) = previous.filter(|previous| *previous != target) {
+        println!("Updated tag '{name}' (was {})", short_hash(&previous));
+    }
+    Ok(())
+}
+pub fn tag(args: Vec<String>) -> Result<(), GitError> {
+    let mut mode = None;
+    let mut options = Options::default();
+    let mut names = Vec::new();
+    let mut args_iter = args.into_iter();
+    while let Some(arg) = args_iter.next() {
+        let mut value = |name: &str| {
+            args_iter
+                .next()
+                .ok_or_else(|| GitError::any(format!("switch `{name}' requires a value")))
+        };
+        match arg.as_str() {
+            "-l" | "--list" => mode = Some(Mode::List),
+            "-d" | "--delete" => mode = Some(Mode::Delete),
+            "-a" | "--annotate" => options.annotate = true,
+            "-f" | "--force" => options.force = true,
+            "-e" | "--edit" => options.edit = true,
+            "-s" | "--sign" => options.signing_key = Some(String::new()),
+            "-u" | "--local-user" => options.signing_key = Some(value("u")?),
+            "-m" | "--message" => {
+                let message = value("m")?;
+                options.messages.push(message)
+            }
+            "-F" | "--file" => options.file = Some(value("F")?),
+            "--" => names.extend(args_iter.by_ref()),
+            arg => {
+                if let Some(message) = arg.strip_prefix("--message=") {
+                    options.messages.push(message.to_string());
+                } else if let Some(path) = arg.strip_prefix("--file=") {
+                    options.file = Some(path.to_string());
+                } else if let Some(mode) = arg.strip_prefix("--cleanup=") {
+                    options.cleanup = Some(mode.to_string());
+                } else if let Some(key) = arg.strip_prefix("--local-user=") {
+                    options.signing_key = Some(key.to_string());
+                } else if arg.starts_with('-') {
+                    return Err(GitError::any(format!("unknown option: {arg}")));
+                } else {
+                    names.push(arg.to_string());
+                }
+            }
+        }
+    }
+    if options.file.is_some() && !options.messages.is_empty() {
+        return Err(GitError::any(
+            "options '-F' and '-m' cannot be used together",
+        ));
+    }
+    let mode = mode.unwrap_or(match names.is_empty() {
+        true => Mode::List,
+        false => Mode::Create,
+    });
+    match mode {
+        Mode::List => list(&names),
+        Mode::Delete => delete(&names),
+        Mode::Create => match names.as_slice() {
+            [name] => create(&options, name, "HEAD"),
+            [name, rev] => create(&options, name, rev),
+            _ => Err(GitError::any("too many arguments")),
+        },
+    }
+}
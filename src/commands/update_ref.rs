@@ -0,0 +1,46 @@
+use crate::error::GitError;
+use crate::refs;
+use crate::revision::resolve;
+pub fn update_ref(args: Vec<String>) -> Result<(), GitError> {
+    let mut delete = false;
+    let mut deref = true;
+    let mut message = None;
+    let mut positional = Vec::new();
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-d" => delete = true,
+            "--no-deref" => deref = false,
+            "-m" => {
+                message = Some(
+                    args_iter
+                        .next()
+                        .ok_or(GitError::any("option -m requires a value"))?,
+                );
+            }
+            arg if arg.starts_with('-') => {
+                return Err(GitError::any(format!("unknown option: {arg}")));
+            }
+            _ => positional.push(arg.as_str()),
+        }
+    }
+    let old = |rev: &str| -> Result<String, GitError> {
+        match rev {
+            "" => Ok(refs::ZERO_HASH.to_string()),
+            rev if rev == refs::ZERO_HASH => Ok(rev.to_string()),
+            rev => resolve(rev),
+        }
+    };
+    let message = message.map(String::as_str).unwrap_or_default();
+    match (delete, positional.as_slice()) {
+        (true, [name]) => refs::delete(name, None, deref),
+        (true, [name, expected]) => refs::delete(name, Some(&old(expected)?), deref),
+        (false, [name, new]) => refs::update(name, &resolve(new)?, None, deref, message),
+        (false, [name, new, expected]) => {
+            refs::update(name, &resolve(new)?, Some(&old(expected)?), deref, message)
+        }
+        _ => Err(GitError::any(
+            "usage: update-ref [--no-deref] (-d <ref> [<old>] | <ref> <new> [<old>])",
+        )),
+    }
+}
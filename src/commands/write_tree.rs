@@ -4,4 +4,4 @@ pub fn write_tree(_args: Vec<String>) -> Result<(), GitError> {
     let git_object = GitObject::from_path(".", true)?;
     println!("{}", git_object.hex_string());
     Ok(())
-}
\ No newline at end of file
+}
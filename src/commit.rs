@@ -0,0 +1,336 @@
+use crate::config::Config;
+use crate::date::{format_date, local_offset, now, parse_date, DateFormat};
+use crate::error::GitError;
+use crate::objects::{GitObject, Kind};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    name: String,
+    email: String,
+    time: i64,
+    offset: String,
+}
+impl Signature {
+    pub fn new(name: impl Into<String>, email: impl Into<String>, time: i64, offset: &str) -> Self {
+        Self {
+            name: name.into(),
+            email: email.into(),
+            time,
+            offset: offset.to_string(),
+        }
+    }
+    /// The identity for `role`, `author` or `committer`: the name from
+    /// `GIT_<ROLE>_NAME`, `<role>.name` or `user.name`, and the email from
+    /// `GIT_<ROLE>_EMAIL`, `<role>.email`, `user.email` or `EMAIL`, falling
+    /// back to the login name and `<login>@<hostname>`. It is stamped with
+    /// `GIT_<ROLE>_DATE`, or else the current time in the local zone.
+    fn ident(role: &str) -> Result<Self, GitError> {
+        let config = Config::load()?;
+        let var = |name: &str| std::env::var(format!("GIT_{}_{name}", role.to_uppercase())).ok();
+        let lookup = |name: &str| {
+            var(&name.to_uppercase())
+                .or_else(|| config.get(&format!("{role}.{name}")).map(str::to_string))
+                .or_else(|| config.get(&format!("user.{name}")).map(str::to_string))
+        };
+        let login = std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        let name = lookup("name").unwrap_or_else(|| login.clone());
+        let email = lookup("email")
+            .or_else(|| std::env::var("EMAIL").ok())
+            .unwrap_or_else(|| {
+                let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+                    .or_else(|_| fs::read_to_string("/etc/hostname"))
+                    .unwrap_or_else(|_| "localhost".to_string());
+                format!("{login}@{}", hostname.trim())
+            });
+        let (time, offset) = match var("DATE") {
+            Some(date) => parse_date(&date)?,
+            None => (now(), local_offset(now())),
+        };
+        Ok(Self::new(name, email, time, &offset))
+    }
+    /// The author of new commits.
+    pub fn author() -> Result<Self, GitError> {
+        Self::ident("author")
+    }
+    /// The committer of new commits, also recorded for ref updates.
+    pub fn committer() -> Result<Self, GitError> {
+        Self::ident("committer")
+    }
+    pub fn parse(line: &str) -> Result<Self, GitError> {
+        let invalid = || GitError::invalid_object_format(format!("malformed signature: {line}"));
+        let (name, rest) = line.split_once('<').ok_or_else(invalid)?;
+        let (email, rest) = rest.split_once('>').ok_or_else(invalid)?;
+        let mut parts = rest.split_whitespace();
+        let time = parts
+            .next()
+            .and_then(|t| t.parse().ok())
+            .ok_or_else(invalid)?;
+        let offset = parts.next().unwrap_or("+0000").to_string();
+        Ok(Self {
+            name: name.trim().to_string(),
+            email: email.to_string(),
+            time,
+            offset,
+        })
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    pub fn email(&self) -> &String {
+        &self.email
+    }
+    pub fn time(&self) -> i64 {
+        self.time
+    }
+    pub fn offset(&self) -> &str {
+        &self.offset
+    }
+    pub fn date(&self, format: DateFormat) -> String {
+        format_date(self.time, &self.offset, format)
+    }
+}
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name, self.email, self.time, self.offset
+        )
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Commit {
+    hash: String,
+    tree: String,
+    parents: Vec<String>,
+    author: Signature,
+    committer: Signature,
+    extra_headers: Vec<(String, String)>,
+    message: String,
+}
+impl Commit {
+    pub fn parse(hash: impl Into<String>, data: &[u8]) -> Result<Self, GitError> {
+        let text = String::from_utf8_lossy(data);
+        let (headers, message) = match text.split_once("\n\n") {
+            Some((headers, message)) => (headers, message),
+            None => (text.trim_end_matches('\n'), ""),
+        };
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for line in headers.lines() {
+            if let Some(continuation) = line.strip_prefix(' ') {
+                let (_, value) = fields.last_mut().ok_or(GitError::invalid_object_format(
+                    "commit header continuation without header",
+                ))?;
+                value.push('\n');
+                value.push_str(continuation);
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            fields.push((key.to_string(), value.to_string()));
+        }
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        let mut extra_headers = Vec::new();
+        for (key, value) in fields {
+            match key.as_str() {
+                "tree" if tree.is_none() => tree = Some(value),
+                "parent" => parents.push(value),
+                "author" if author.is_none() => author = Some(Signature::parse(&value)?),
+                "committer" if committer.is_none() => committer = Some(Signature::parse(&value)?),
+                _ => extra_headers.push((key, value)),
+            }
+        }
+        let tree = tree.ok_or(GitError::invalid_object_format("commit is missing tree"))?;
+        let author = author.ok_or(GitError::invalid_object_format("commit is missing author"))?;
+        Ok(Self {
+            hash: hash.into(),
+            tree,
+            parents,
+            committer: committer.unwrap_or_else(|| author.clone()),
+            author,
+            extra_headers,
+            message: message.to_string(),
+        })
+    }
+    pub fn from_hex_string(hex_string: impl AsRef<str>) -> Result<Self, GitError> {
+        let git_object = GitObject::from_hex_string(hex_string.as_ref())?;
+        match git_object.kind() {
+            Kind::Commit => Self::parse(hex_string.as_ref(), git_object.contents()),
+            kind => Err(GitError::any(format!(
+                "object {} is a {kind}, not a commit",
+                hex_string.as_ref()
+            ))),
+        }
+    }
+    pub fn hash(&self) -> &String {
+        &self.hash
+    }
+    pub fn tree(&self) -> &String {
+        &self.tree
+    }
+    pub fn parents(&self) -> &Vec<String> {
+        &self.parents
+    }
+    pub fn author(&self) -> &Signature {
+        &self.author
+    }
+    pub fn committer(&self) -> &Signature {
+        &self.committer
+    }
+    pub fn extra_headers(&self) -> &Vec<(String, String)> {
+        &self.extra_headers
+    }
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+    pub fn subject(&self) -> String {
+        subject(&self.message)
+    }
+    pub fn body(&self) -> &str {
+        body(&self.message)
+    }
+}
+/// The first paragraph of a commit or tag message, joined into one line.
+pub fn subject(message: &str) -> String {
+    message
+        .trim_start_matches('\n')
+        .split("\n\n")
+        .next()
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+/// Everything in a commit or tag message after the subject.
+pub fn body(message: &str) -> &str {
+    message
+        .trim_start_matches('\n')
+        .split_once("\n\n")
+        .map(|(_, body)| body.trim_start_matches('\n'))
+        .unwrap_or_default()
+}
+/// Assembles a commit object header by header; the author defaults to the
+/// current identity.
+pub struct CommitBuilder {
+    tree: String,
+    parents: Vec<String>,
+    author: Option<Signature>,
+    headers: Vec<(String, String)>,
+    signing_key: Option<String>,
+    message: String,
+}
+impl CommitBuilder {
+    pub fn new(tree: impl Into<String>) -> Self {
+        Self {
+            tree: tree.into(),
+            parents: Vec::new(),
+            author: None,
+            headers: Vec::new(),
+            signing_key: None,
+            message: String::new(),
+        }
+    }
+    pub fn parents(mut self, hashes: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.parents
+            .extend(hashes.into_iter().map(|hash| hash.as_ref().to_string()));
+        self
+    }
+    pub fn author(mut self, author: Signature) -> Self {
+        self.author = Some(author);
+        self
+    }
+    /// Adds a header after the committer; a value spanning several lines is
+    /// written with continuation lines.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+    /// Signs the commit with gpg into a `gpgsig` header; an empty key uses
+    /// `user.signingKey` or the committer's identity.
+    pub fn sign(mut self, key: impl Into<String>) -> Self {
+        self.signing_key = Some(key.into());
+        self
+    }
+    /// The message, recorded exactly as given.
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+    pub fn build(self) -> Result<GitObject, GitError> {
+        let config = Config::load()?;
+        let author = match self.author {
+            Some(author) => author,
+            None => Signature::author()?,
+        };
+        let committer = Signature::committer()?;
+        let mut contents = String::new();
+        writeln!(contents, "tree {}", self.tree)?;
+        for parent in &self.parents {
+            writeln!(contents, "parent {parent}")?;
+        }
+        writeln!(contents, "author {author}")?;
+        writeln!(contents, "committer {committer}")?;
+        if let Some(encoding) = config.get("i18n.commitEncoding") {
+            if !encoding.eq_ignore_ascii_case("utf-8") && !encoding.eq_ignore_ascii_case("utf8") {
+                writeln!(contents, "encoding {encoding}")?;
+            }
+        }
+        for (name, value) in &self.headers {
+            writeln!(contents, "{name} {}", value.replace('\n', "\n "))?;
+        }
+        if let Some(key) = &self.signing_key {
+            let payload = format!("{contents}\n{}", self.message);
+            let signature = sign(&payload, key, &committer, &config)?;
+            let signature = signature.trim_end_matches('\n').replace('\n', "\n ");
+            writeln!(contents, "gpgsig {signature}")?;
+        }
+        contents.push('\n');
+        contents.push_str(&self.message);
+        GitObject::build(Kind::Commit, contents.into())
+    }
+}
+/// Makes a detached, armored signature of `payload` with `gpg.program`;
+/// an empty key uses `user.signingKey` or the signer's identity.
+pub fn sign(
+    payload: &str,
+    key: &str,
+    signer: &Signature,
+    config: &Config,
+) -> Result<String, GitError> {
+    let key = match key {
+        "" => config
+            .get("user.signingKey")
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{} <{}>", signer.name(), signer.email())),
+        key => key.to_string(),
+    };
+    let program = config.get("gpg.program").unwrap_or("gpg");
+    let mut child = Command::new(program)
+        .args(["--status-fd=2", "-bsau", &key])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| GitError::any(format!("cannot run {program}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(payload.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    let status = String::from_utf8_lossy(&output.stderr);
+    let created = status
+        .lines()
+        .any(|line| line.starts_with("[GNUPG:] SIG_CREATED "));
+    if !output.status.success() || !created {
+        eprint!("{status}");
+        return Err(GitError::any("gpg failed to sign the data"));
+    }
+    String::from_utf8(output.stdout).map_err(|_| GitError::any("gpg failed to sign the data"))
+}
@@ -0,0 +1,325 @@
+use crate::error::GitError;
+use crate::lockfile::LockFile;
+use std::fs;
+use std::path::PathBuf;
+#[derive(Clone, Debug)]
+struct ConfigEntry {
+    section: String,
+    subsection: Option<String>,
+    name: String,
+    value: String,
+}
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+impl Config {
+    fn home_dir() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(PathBuf::from)
+    }
+    pub fn xdg_config_dir() -> Option<PathBuf> {
+        match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("git")),
+            _ => Self::home_dir().map(|home| home.join(".config").join("git")),
+        }
+    }
+    fn global_paths() -> Vec<PathBuf> {
+        if let Some(path) = std::env::var_os("GIT_CONFIG_GLOBAL") {
+            return vec![PathBuf::from(path)];
+        }
+        let mut paths = Vec::new();
+        if let Some(dir) = Self::xdg_config_dir() {
+            paths.push(dir.join("config"));
+        }
+        if let Some(home) = Self::home_dir() {
+            paths.push(home.join(".gitconfig"));
+        }
+        paths
+    }
+    pub fn load() -> Result<Self, GitError> {
+        let mut entries = Vec::new();
+        let mut paths = Self::global_paths();
+        paths.push([".git", "config"].iter().collect());
+        for path in paths {
+            if path.is_file() {
+                entries.extend(Self::parse(&fs::read_to_string(&path)?)?);
+            }
+        }
+        Ok(Self { entries })
+    }
+    /// Reads a single config-format file such as `.gitmodules`.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, GitError> {
+        let path = path.as_ref();
+        let entries = match path.is_file() {
+            true => Self::parse(&fs::read_to_string(path)?)?,
+            false => Vec::new(),
+        };
+        Ok(Self { entries })
+    }
+    /// The distinct subsections of `section`, in the order they appear.
+    pub fn subsections(&self, section: &str) -> Vec<&str> {
+        let mut subsections: Vec<&str> = Vec::new();
+        for entry in self.entries.iter().filter(|e| e.section == section) {
+            if let Some(sub) = entry.subsection.as_deref() {
+                if !subsections.contains(&sub) {
+                    subsections.push(sub);
+                }
+            }
+        }
+        subsections
+    }
+    fn parse(text: &str) -> Result<Vec<ConfigEntry>, GitError> {
+        let mut entries = Vec::new();
+        let mut section: Option<(String, Option<String>)> = None;
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .split_once(']')
+                    .map(|(header, _)| header)
+                    .ok_or(GitError::any(format!("bad config line: {line}")))?;
+                section = Some(Self::parse_header(header));
+                continue;
+            }
+            let Some((section, subsection)) = section.clone() else {
+                return Err(GitError::any(format!("config key outside section: {line}")));
+            };
+            let (name, raw) = match line.split_once('=') {
+                Some((name, raw)) => (name.trim(), Some(raw.trim().to_string())),
+                None => (line, None),
+            };
+            let value = match raw {
+                None => "true".to_string(),
+                Some(mut raw) => {
+                    while raw.ends_with('\\') && !raw.ends_with("\\\\") {
+                        raw.pop();
+                        raw.push_str(lines.next().unwrap_or_default().trim());
+                    }
+                    Self::unquote(&raw)
+                }
+            };
+            entries.push(ConfigEntry {
+                section,
+                subsection,
+                name: name.to_lowercase(),
+                value,
+            });
+        }
+        Ok(entries)
+    }
+    fn parse_header(header: &str) -> (String, Option<String>) {
+        match header.split_once(|c: char| c.is_whitespace()) {
+            Some((name, sub)) => {
+                let sub = sub.trim().trim_matches('"').replace("\\\"", "\"");
+                (name.to_lowercase(), Some(sub))
+            }
+            None => match header.split_once('.') {
+                Some((name, sub)) => (name.to_lowercase(), Some(sub.to_lowercase())),
+                None => (header.to_lowercase(), None),
+            },
+        }
+    }
+    fn unquote(raw: &str) -> String {
+        let mut value = String::new();
+        let mut quoted = false;
+        let mut chars = raw.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '#' | ';' if !quoted => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('b') => {
+                        value.pop();
+                    }
+                    Some(c) => value.push(c),
+                    None => {}
+                },
+                c => value.push(c),
+            }
+        }
+        if quoted {
+            value
+        } else {
+            value.trim_end().to_string()
+        }
+    }
+    fn split_key(key: &str) -> Option<(String, Option<&str>, String)> {
+        let (section, rest) = key.split_once('.')?;
+        match rest.rsplit_once('.') {
+            Some((sub, name)) => Some((section.to_lowercase(), Some(sub), name.to_lowercase())),
+            None => Some((section.to_lowercase(), None, rest.to_lowercase())),
+        }
+    }
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let (section, subsection, name) = Self::split_key(key)?;
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| {
+                e.section == section && e.subsection.as_deref() == subsection && e.name == name
+            })
+            .map(|e| e.value.as_str())
+    }
+    pub fn get_path(&self, key: &str) -> Option<PathBuf> {
+        let value = self.get(key)?;
+        match value.strip_prefix("~/") {
+            Some(rest) => Some(Self::home_dir()?.join(rest)),
+            None => Some(PathBuf::from(value)),
+        }
+    }
+    fn local_path() -> PathBuf {
+        [".git", "config"].iter().collect()
+    }
+    fn local_lines() -> Result<Vec<String>, GitError> {
+        let path = Self::local_path();
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+    fn write_local(lines: &[String]) -> Result<(), GitError> {
+        let mut lock = LockFile::acquire(Self::local_path())?;
+        let contents: String = lines.iter().map(|line| format!("{line}\n")).collect();
+        lock.write_all(contents.as_bytes())?;
+        lock.commit()
+    }
+    /// The section a header line opens, if `line` is one.
+    fn header_of(line: &str) -> Option<(String, Option<String>)> {
+        let header = line.trim().strip_prefix('[')?.split_once(']')?.0;
+        Some(Self::parse_header(header))
+    }
+    /// Splits a section name like `branch.topic` into section and subsection.
+    fn split_section(name: &str) -> (String, Option<&str>) {
+        match name.split_once('.') {
+            Some((section, sub)) => (section.to_lowercase(), Some(sub)),
+            None => (name.to_lowercase(), None),
+        }
+    }
+    fn format_header(name: &str) -> String {
+        match name.split_once('.') {
+            Some((section, sub)) => {
+                let sub = sub.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("[{section} \"{sub}\"]")
+            }
+            None => format!("[{name}]"),
+        }
+    }
+    fn quote(value: &str) -> String {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+            .replace('\t', "\\t");
+        if value.starts_with(' ') || value.ends_with(' ') || value.contains(['#', ';']) {
+            format!("\"{escaped}\"")
+        } else {
+            escaped
+        }
+    }
+    /// Sets `key` in the repository's `.git/config`, replacing an existing
+    /// value or adding it to the end of its section; `None` removes it.
+    pub fn set(key: &str, value: Option<&str>) -> Result<(), GitError> {
+        let (section, subsection, name) = Self::split_key(key)
+            .ok_or_else(|| GitError::any(format!("key does not contain a section: {key}")))?;
+        let mut lines = Self::local_lines()?;
+        let mut in_section = false;
+        let mut section_end = None;
+        let mut matches = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if let Some((s, sub)) = Self::header_of(line) {
+                in_section = s == section && sub.as_deref() == subsection;
+            } else if in_section {
+                let line_name = line.trim().split(['=', ' ', '\t']).next().unwrap_or("");
+                if line_name.to_lowercase() == name {
+                    matches.push(i);
+                }
+            }
+            if in_section {
+                section_end = Some(i);
+            }
+        }
+        let Some(value) = value else {
+            for i in matches.into_iter().rev() {
+                lines.remove(i);
+            }
+            let emptied: Vec<usize> = (0..lines.len())
+                .filter(|&i| {
+                    Self::header_of(&lines[i])
+                        .is_some_and(|(s, sub)| s == section && sub.as_deref() == subsection)
+                        && lines
+                            .get(i + 1)
+                            .map_or(true, |next| Self::header_of(next).is_some())
+                })
+                .collect();
+            for i in emptied.into_iter().rev() {
+                lines.remove(i);
+            }
+            return Self::write_local(&lines);
+        };
+        let key_name = key.rsplit('.').next().unwrap_or(key);
+        let line = format!("\t{key_name} = {}", Self::quote(value));
+        match (matches.last(), section_end) {
+            (Some(&i), _) => lines[i] = line,
+            (None, Some(i)) => lines.insert(i + 1, line),
+            (None, None) => {
+                lines.push(Self::format_header(&key[..key.len() - key_name.len() - 1]));
+                lines.push(line);
+            }
+        }
+        Self::write_local(&lines)
+    }
+    /// Renames every `[section "sub"]` block named `name` (like
+    /// `branch.topic`) to `new`, or removes them along with their entries.
+    pub fn rename_section(name: &str, new: Option<&str>) -> Result<(), GitError> {
+        let (section, subsection) = Self::split_section(name);
+        let mut lines = Vec::new();
+        let mut in_section = false;
+        for line in Self::local_lines()? {
+            if let Some((s, sub)) = Self::header_of(&line) {
+                in_section = s == section && sub.as_deref() == subsection;
+                if in_section {
+                    if let Some(new) = new {
+                        lines.push(Self::format_header(new));
+                    }
+                    continue;
+                }
+            }
+            if !in_section || new.is_some() {
+                lines.push(line);
+            }
+        }
+        Self::write_local(&lines)
+    }
+    /// Copies the entries of section `name` into a new section `new`,
+    /// placed right after the original.
+    pub fn copy_section(name: &str, new: &str) -> Result<(), GitError> {
+        let (section, subsection) = Self::split_section(name);
+        let mut lines = Self::local_lines()?;
+        let mut copied = vec![Self::format_header(new)];
+        let mut in_section = false;
+        let mut section_end = None;
+        for (i, line) in lines.iter().enumerate() {
+            if let Some((s, sub)) = Self::header_of(line) {
+                in_section = s == section && sub.as_deref() == subsection;
+            } else if in_section {
+                copied.push(line.clone());
+            }
+            if in_section {
+                section_end = Some(i);
+            }
+        }
+        let Some(end) = section_end.filter(|_| copied.len() > 1) else {
+            return Ok(());
+        };
+        lines.splice(end + 1..end + 1, copied);
+        Self::write_local(&lines)
+    }
+}
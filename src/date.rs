@@ -0,0 +1,703 @@
+use crate::error::GitError;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateFormat {
+    Default,
+    Iso,
+    IsoStrict,
+    Rfc2822,
+    Short,
+    Unix,
+    Raw,
+    Relative,
+}
+impl DateFormat {
+    pub fn parse(name: &str) -> Result<Self, GitError> {
+        match name {
+            "default" => Ok(Self::Default),
+            "iso" | "iso8601" => Ok(Self::Iso),
+            "iso-strict" | "iso8601-strict" => Ok(Self::IsoStrict),
+            "rfc" | "rfc2822" => Ok(Self::Rfc2822),
+            "short" => Ok(Self::Short),
+            "unix" => Ok(Self::Unix),
+            "raw" => Ok(Self::Raw),
+            "relative" => Ok(Self::Relative),
+            _ => Err(GitError::any(format!("unknown date format: {name}"))),
+        }
+    }
+}
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+/// A daylight saving time rule of a POSIX `TZ` string.
+struct DstRule {
+    offset: i64,
+    start: (TransitionDate, i64),
+    end: (TransitionDate, i64),
+}
+enum TransitionDate {
+    /// `Jn`: day 1 to 365, never counting February 29.
+    Julian(i64),
+    /// `n`: day 0 to 365, counting February 29.
+    Day(i64),
+    /// `Mm.w.d`: weekday `d` of week `w` (5 meaning the last) of month `m`.
+    Weekday(u32, i64, i64),
+}
+impl TransitionDate {
+    /// The day of `year` this rule falls on, counted from the epoch.
+    fn day(&self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        match *self {
+            TransitionDate::Julian(n) => jan1 + n - 1 + i64::from(leap && n > 59),
+            TransitionDate::Day(n) => jan1 + n,
+            TransitionDate::Weekday(month, week, weekday) => {
+                let first = days_from_civil(year, month, 1);
+                let next_month = match month {
+                    12 => days_from_civil(year + 1, 1, 1),
+                    month => days_from_civil(year, month + 1, 1),
+                };
+                let mut day = first + (weekday - (first + 4)).rem_euclid(7) + (week - 1) * 7;
+                while day >= next_month {
+                    day -= 7;
+                }
+                day
+            }
+        }
+    }
+}
+/// A POSIX `TZ` string such as `CET-1CEST,M3.5.0,M10.5.0/3`, reduced to
+/// UTC offsets in seconds east.
+struct PosixZone {
+    offset: i64,
+    dst: Option<DstRule>,
+}
+impl PosixZone {
+    fn parse(text: &str) -> Option<Self> {
+        let mut rest = text;
+        let name = |rest: &mut &str| -> Option<()> {
+            let len = match rest.strip_prefix('<') {
+                Some(quoted) => quoted.find('>')? + 2,
+                None => rest
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len()),
+            };
+            (len >= 3).then(|| *rest = &rest[len..])
+        };
+        let clock = |rest: &mut &str| -> Option<i64> {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || "+-:".contains(c)))
+                .unwrap_or(rest.len());
+            let (text, sign) = match &rest[..end] {
+                text if text.starts_with('-') => (&text[1..], -1),
+                text => (text.trim_start_matches('+'), 1),
+            };
+            let mut seconds = 0;
+            for (part, unit) in text.split(':').zip([3600, 60, 1]) {
+                seconds += part.parse::<i64>().ok()? * unit;
+            }
+            *rest = &rest[end..];
+            Some(sign * seconds)
+        };
+        name(&mut rest)?;
+        let offset = -clock(&mut rest)?;
+        if rest.is_empty() {
+            return Some(Self { offset, dst: None });
+        }
+        name(&mut rest)?;
+        let dst_offset = match rest.starts_with(',') || rest.is_empty() {
+            true => offset + 3600,
+            false => -clock(&mut rest)?,
+        };
+        let rules = rest.strip_prefix(',').unwrap_or("M3.2.0,M11.1.0");
+        let transition = |rule: &str| -> Option<(TransitionDate, i64)> {
+            let (date, time) = match rule.split_once('/') {
+                Some((date, time)) => (date, clock(&mut &*time)?),
+                None => (rule, 7200),
+            };
+            let date = if let Some(n) = date.strip_prefix('J') {
+                TransitionDate::Julian(n.parse().ok()?)
+            } else if let Some(m) = date.strip_prefix('M') {
+                let mut parts = m.split('.').map(|n| n.parse::<i64>().ok());
+                let (month, week, weekday) = (parts.next()??, parts.next()??, parts.next()??);
+                TransitionDate::Weekday(u32::try_from(month).ok()?, week, weekday)
+            } else {
+                TransitionDate::Day(date.parse().ok()?)
+            };
+            Some((date, time))
+        };
+        let (start, end) = rules.split_once(',')?;
+        Some(Self {
+            offset,
+            dst: Some(DstRule {
+                offset: dst_offset,
+                start: transition(start)?,
+                end: transition(end)?,
+            }),
+        })
+    }
+    fn offset_at(&self, time: i64) -> i64 {
+        let Some(dst) = &self.dst else {
+            return self.offset;
+        };
+        let (year, _, _) = civil_from_days((time + self.offset).div_euclid(86400));
+        let start = dst.start.0.day(year) * 86400 + dst.start.1 - self.offset;
+        let end = dst.end.0.day(year) * 86400 + dst.end.1 - dst.offset;
+        let in_dst = match start < end {
+            true => start <= time && time < end,
+            false => !(end <= time && time < start),
+        };
+        match in_dst {
+            true => dst.offset,
+            false => self.offset,
+        }
+    }
+}
+/// Reads the UTC offset at `time` from a TZif zone file, using its
+/// 64-bit transitions and footer rule when it has them.
+fn tzfile_offset(data: &[u8], time: i64) -> Option<i64> {
+    let be32 = |at: usize| -> Option<i64> {
+        Some(i64::from(i32::from_be_bytes(
+            data.get(at..at + 4)?.try_into().ok()?,
+        )))
+    };
+    let be64 = |at: usize| -> Option<i64> {
+        Some(i64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+    };
+    if !data.starts_with(b"TZif") {
+        return None;
+    }
+    let counts = |header: usize| -> Option<[usize; 6]> {
+        let mut counts = [0; 6];
+        for (i, count) in counts.iter_mut().enumerate() {
+            *count = usize::try_from(be32(header + 20 + i * 4)?).ok()?;
+        }
+        Some(counts)
+    };
+    let [isut, isstd, leap, count, types, chars] = counts(0)?;
+    let mut header = 0;
+    let mut time_size = 4;
+    if data[4] >= b'2' {
+        header = 44 + count * 5 + types * 6 + chars + leap * 8 + isstd + isut;
+        time_size = 8;
+    }
+    let [isut, isstd, leap, count, types, chars] = counts(header)?;
+    let times = header + 44;
+    let indices = times + count * time_size;
+    let infos = indices + count;
+    let transition = |i: usize| match time_size {
+        8 => be64(times + i * 8),
+        _ => be32(times + i * 4),
+    };
+    let utoff = |ty: usize| be32(infos + ty * 6);
+    let footer = infos + types * 6 + chars + leap * (time_size + 4) + isstd + isut;
+    let rule = match time_size {
+        8 => std::str::from_utf8(data.get(footer..)?)
+            .ok()
+            .and_then(|text| text.trim_matches('\n').split('\n').next())
+            .and_then(PosixZone::parse),
+        _ => None,
+    };
+    let mut last = None;
+    for i in 0..count {
+        if transition(i)? > time {
+            break;
+        }
+        last = Some(i);
+    }
+    match (last, rule) {
+        (Some(i), Some(rule)) if i + 1 == count => Some(rule.offset_at(time)),
+        (None, Some(rule)) if count == 0 => Some(rule.offset_at(time)),
+        (Some(i), _) => utoff(usize::from(*data.get(indices + i)?)),
+        (None, _) => utoff(0),
+    }
+}
+/// The local UTC offset at `time`, in seconds east, from `TZ` or else
+/// `/etc/localtime`. Unknown zones count as UTC.
+fn local_offset_seconds(time: i64) -> i64 {
+    let zone_file = |name: &str| -> Option<i64> {
+        let path = match name.starts_with('/') {
+            true => name.to_string(),
+            false => format!("{ZONEINFO}/{name}"),
+        };
+        tzfile_offset(&fs::read(path).ok()?, time)
+    };
+    match std::env::var("TZ") {
+        Ok(tz) if tz.is_empty() => 0,
+        Ok(tz) => {
+            let name = tz.trim_start_matches(':');
+            zone_file(name)
+                .or_else(|| PosixZone::parse(name).map(|zone| zone.offset_at(time)))
+                .unwrap_or(0)
+        }
+        Err(_) => zone_file("/etc/localtime").unwrap_or(0),
+    }
+}
+/// The local UTC offset at `time` in git's `+hhmm` form.
+pub fn local_offset(time: i64) -> String {
+    format_offset(local_offset_seconds(time))
+}
+fn format_offset(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("{sign}{:02}{:02}", minutes / 60, minutes % 60)
+}
+pub fn offset_seconds(offset: &str) -> i64 {
+    let (sign, digits) = match offset.split_at_checked(1) {
+        Some(("-", digits)) => (-1, digits),
+        Some(("+", digits)) => (1, digits),
+        _ => (1, offset),
+    };
+    let value = i64::from(digits.parse::<i32>().unwrap_or(0));
+    sign * ((value / 100) * 3600 + (value % 100) * 60)
+}
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+/// The time on the day `days` after the epoch at the local time of day it
+/// is at `now`. Like git, which hands `mktime` the current daylight saving
+/// flag, the day is converted with the offset in effect at `now`.
+fn at_time_of_day(days: i64, now: i64, offset: i64) -> i64 {
+    days * 86400 + (now + offset).rem_euclid(86400) - offset
+}
+/// Parses the loose dates accepted in `@{<date>}` and `--expire`: `now`,
+/// `yesterday`, `<n> <unit>s ago` (dots may replace spaces), `@<seconds>`
+/// and the absolute dates [`strict_date`] reads, in local time unless they
+/// name a zone. A date without a time keeps the current time of day.
+pub fn approxidate(text: &str) -> Result<i64, GitError> {
+    let invalid = || GitError::any(format!("invalid date: {text}"));
+    let text = text.trim().to_lowercase();
+    match text.as_str() {
+        "now" => return Ok(now()),
+        "yesterday" => return Ok(now() - 86400),
+        _ => {}
+    }
+    if let Some(seconds) = text.strip_prefix('@') {
+        return seconds.parse().map_err(|_| invalid());
+    }
+    let words: Vec<&str> = text.split(['.', ' ']).filter(|w| !w.is_empty()).collect();
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        let seconds = match unit.trim_end_matches('s') {
+            "second" | "sec" => 1,
+            "minute" | "min" => 60,
+            "hour" => 3600,
+            "day" => 86400,
+            "week" => 7 * 86400,
+            "month" => 30 * 86400,
+            "year" => 365 * 86400,
+            _ => return Err(invalid()),
+        };
+        return Ok(now() - count * seconds);
+    }
+    if let Some((time, _)) = absolute_date(&text) {
+        return Ok(time);
+    }
+    let days = DateParts::read(&text).and_then(|parts| parts.days());
+    let now = now();
+    Ok(at_time_of_day(
+        days.ok_or_else(invalid)?,
+        now,
+        local_offset_seconds(now),
+    ))
+}
+/// Reads `+hhmm`, `-hh:mm`, `Z`, `UTC` or `GMT` as seconds east of UTC.
+fn parse_offset(text: &str) -> Option<i64> {
+    if matches!(text.to_lowercase().as_str(), "z" | "utc" | "gmt") {
+        return Some(0);
+    }
+    let sign = match text.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = text[1..].replace(':', "");
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes): (i64, i64) = (digits[..2].parse().ok()?, digits[2..].parse().ok()?);
+    (minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+/// The parts of a date read by [`strict_date`]; the zone stays unknown
+/// when the text does not give one.
+#[derive(Default)]
+struct DateParts {
+    year: Option<i64>,
+    month: Option<u32>,
+    day: Option<u32>,
+    seconds: Option<i64>,
+    offset: Option<i64>,
+}
+impl DateParts {
+    fn time(&mut self, text: &str) -> Option<()> {
+        let (text, offset) = match text.find(['+', '-', 'Z', 'z']) {
+            Some(at) => (&text[..at], Some(parse_offset(&text[at..])?)),
+            None => (text, None),
+        };
+        let mut seconds = 0;
+        let parts: Vec<&str> = text.split(':').collect();
+        if !(2..=3).contains(&parts.len()) {
+            return None;
+        }
+        for (part, (unit, limit)) in parts.iter().zip([(3600, 24), (60, 60), (1, 61)]) {
+            let whole = part.split('.').next()?;
+            let value: i64 = whole.parse().ok()?;
+            if value >= limit {
+                return None;
+            }
+            seconds += value * unit;
+        }
+        self.seconds = Some(seconds);
+        self.offset = offset.or(self.offset);
+        Some(())
+    }
+    fn date(&mut self, text: &str) -> Option<()> {
+        let parts: Vec<i64> = text
+            .split(['-', '.', '/'])
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        let [a, b, c] = parts[..] else {
+            return None;
+        };
+        let (year, month, day) = match (text.contains('/'), a > 31) {
+            (_, true) => (a, b, c),
+            (true, false) => (c, a, b),
+            (false, false) => (c, b, a),
+        };
+        self.year = Some(year);
+        self.month = Some(u32::try_from(month).ok()?);
+        self.day = Some(u32::try_from(day).ok()?);
+        Some(())
+    }
+    /// The day these parts name, counted from the epoch.
+    fn days(&self) -> Option<i64> {
+        let (year, month, day) = (self.year?, self.month?, self.day?);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        Some(days_from_civil(year, month, day))
+    }
+    /// Reads the parts of an absolute date made of a weekday, a month
+    /// name, numbers for the day and year, `YYYY-MM-DD`, `HH:MM[:SS]` and a
+    /// zone in any order.
+    fn read(text: &str) -> Option<Self> {
+        let mut parts = Self::default();
+        for token in text.split([' ', ',']).filter(|token| !token.is_empty()) {
+            let (date, time) = match token.split_once(['T', 't']) {
+                Some((date, time)) if date.contains('-') => (Some(date), Some(time)),
+                _ if token.contains(':') && !token.starts_with(['+', '-']) => (None, Some(token)),
+                _ if token.contains(['-', '.', '/']) && !token.starts_with(['+', '-']) => {
+                    (Some(token), None)
+                }
+                _ => (None, None),
+            };
+            if date.is_some() || time.is_some() {
+                date.map_or(Some(()), |date| parts.date(date))?;
+                time.map_or(Some(()), |time| parts.time(time))?;
+            } else if token.chars().all(|c| c.is_ascii_digit()) {
+                let number: i64 = token.parse().ok()?;
+                match (token.len(), parts.day) {
+                    (1 | 2, None) => parts.day = Some(u32::try_from(number).ok()?),
+                    (4, _) => parts.year = Some(number),
+                    _ => return None,
+                }
+            } else {
+                parts.word(token)?;
+            }
+        }
+        Some(parts)
+    }
+    fn word(&mut self, word: &str) -> Option<()> {
+        if let Some(offset) = parse_offset(word) {
+            self.offset = Some(offset);
+            return Some(());
+        }
+        let prefix = word.get(..3)?.to_lowercase();
+        if WEEKDAYS.iter().any(|day| day.to_lowercase() == prefix) {
+            return Some(());
+        }
+        let month = MONTHS
+            .iter()
+            .position(|month| month.to_lowercase() == prefix)?;
+        self.month = Some(month as u32 + 1);
+        Some(())
+    }
+}
+/// Reads an absolute date with a time, which covers RFC 2822, ISO 8601
+/// and git's default format. Returns the local time and the zone's
+/// offset, if it names one.
+fn strict_date(text: &str) -> Option<(i64, Option<i64>)> {
+    let parts = DateParts::read(text)?;
+    Some((parts.days()? * 86400 + parts.seconds?, parts.offset))
+}
+/// Reads a [`strict_date`], taking it to be in local time when it names no
+/// zone. Returns the time and its offset.
+fn absolute_date(text: &str) -> Option<(i64, i64)> {
+    let (local, offset) = strict_date(text)?;
+    let offset = offset.unwrap_or_else(|| {
+        let guess = local_offset_seconds(local);
+        local_offset_seconds(local - guess)
+    });
+    Some((local - offset, offset))
+}
+/// Parses a date as `GIT_AUTHOR_DATE`, `GIT_COMMITTER_DATE` and `--date`
+/// take it: git's raw `<seconds> <offset>`, `@<seconds>`, RFC 2822,
+/// ISO 8601 or anything [`approxidate`] understands. Dates without a zone
+/// are in local time. Returns the time and its `+hhmm` offset.
+pub fn parse_date(text: &str) -> Result<(i64, String), GitError> {
+    let invalid = || GitError::any(format!("invalid date format: {text}"));
+    let text = text.trim();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let raw = match words[..] {
+        [seconds] => seconds.strip_prefix('@').map(|seconds| (seconds, "+0000")),
+        [seconds, offset] => Some((seconds.trim_start_matches('@'), offset)),
+        _ => None,
+    };
+    let raw = raw.map(|(seconds, offset)| (seconds.parse::<i64>(), parse_offset(offset)));
+    if let Some((Ok(seconds), Some(offset))) = raw {
+        return Ok((seconds, format_offset(offset)));
+    }
+    if let Some((time, offset)) = absolute_date(text) {
+        return Ok((time, format_offset(offset)));
+    }
+    let time = approxidate(text).map_err(|_| invalid())?;
+    Ok((time, local_offset(time)))
+}
+struct Broken {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    weekday: usize,
+}
+fn broken_down(time: i64, offset: &str) -> Broken {
+    let local = time + offset_seconds(offset);
+    let days = local.div_euclid(86400);
+    let secs = local.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    Broken {
+        year,
+        month,
+        day,
+        hour: secs / 3600,
+        minute: secs % 3600 / 60,
+        second: secs % 60,
+        weekday: (days + 4).rem_euclid(7) as usize,
+    }
+}
+fn relative(time: i64) -> String {
+    let diff = now() - time;
+    if diff < 0 {
+        return "in the future".into();
+    }
+    let plural = |n: i64, unit: &str| {
+        if n == 1 {
+            format!("{n} {unit} ago")
+        } else {
+            format!("{n} {unit}s ago")
+        }
+    };
+    match diff {
+        d if d < 90 => plural(d, "second"),
+        d if d < 90 * 60 => plural((d + 30) / 60, "minute"),
+        d if d < 36 * 3600 => plural((d + 1800) / 3600, "hour"),
+        d if d < 14 * 86400 => plural((d + 43200) / 86400, "day"),
+        d if d < 70 * 86400 => plural((d + 3 * 86400) / (7 * 86400), "week"),
+        d if d < 365 * 86400 => plural((d + 15 * 86400) / (30 * 86400), "month"),
+        d => plural((d + 183 * 86400) / (365 * 86400), "year"),
+    }
+}
+pub fn format_date(time: i64, offset: &str, format: DateFormat) -> String {
+    let b = broken_down(time, offset);
+    // Offsets come from commit headers, so they may be short or not ASCII.
+    let colon_offset = match (offset.get(..3), offset.get(3..)) {
+        (Some(hours), Some(minutes)) => format!("{hours}:{minutes}"),
+        _ => offset.to_string(),
+    };
+    match format {
+        DateFormat::Default => format!(
+            "{} {} {} {:02}:{:02}:{:02} {} {offset}",
+            WEEKDAYS[b.weekday],
+            MONTHS[b.month as usize - 1],
+            b.day,
+            b.hour,
+            b.minute,
+            b.second,
+            b.year
+        ),
+        DateFormat::Iso => format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02} {offset}",
+            b.year, b.month, b.day, b.hour, b.minute, b.second
+        ),
+        DateFormat::IsoStrict => {
+            format!(
+                "{}-{:02}-{:02}T{:02}:{:02}:{:02}{colon_offset}",
+                b.year, b.month, b.day, b.hour, b.minute, b.second
+            )
+        }
+        DateFormat::Rfc2822 => format!(
+            "{}, {} {} {} {:02}:{:02}:{:02} {offset}",
+            WEEKDAYS[b.weekday],
+            b.day,
+            MONTHS[b.month as usize - 1],
+            b.year,
+            b.hour,
+            b.minute,
+            b.second
+        ),
+        DateFormat::Short => format!("{}-{:02}-{:02}", b.year, b.month, b.day),
+        DateFormat::Unix => time.to_string(),
+        DateFormat::Raw => format!("{time} {offset}"),
+        DateFormat::Relative => relative(time),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn offsets(zone: &str, times: &[i64]) -> Vec<i64> {
+        let zone = PosixZone::parse(zone).unwrap();
+        times.iter().map(|&time| zone.offset_at(time)).collect()
+    }
+    #[test]
+    fn posix_zones_switch_at_their_dst_transitions() {
+        // 2024-03-10 07:00 and 2024-11-03 06:00 UTC.
+        assert_eq!(
+            offsets(
+                "EST5EDT,M3.2.0,M11.1.0",
+                &[1710053999, 1710054000, 1730613599, 1730613600]
+            ),
+            [-5 * 3600, -4 * 3600, -4 * 3600, -5 * 3600]
+        );
+        // Southern zones are on daylight saving time over the new year:
+        // 2024-04-06 16:00 and 2024-10-05 16:00 UTC.
+        assert_eq!(
+            offsets(
+                "AEST-10AEDT,M10.1.0,M4.1.0/3",
+                &[1712419199, 1712419200, 1728143999, 1728144000]
+            ),
+            [11 * 3600, 10 * 3600, 10 * 3600, 11 * 3600]
+        );
+        assert_eq!(
+            offsets("EST5EDT", &[1710053999, 1710054000]),
+            [-5 * 3600, -4 * 3600]
+        );
+    }
+    #[test]
+    fn posix_zones_read_quoted_names_and_minutes() {
+        assert_eq!(offsets("<+0330>-3:30", &[0]), [3 * 3600 + 1800]);
+        assert_eq!(offsets("<-03>3", &[0]), [-3 * 3600]);
+        assert_eq!(offsets("IST-5:30", &[0]), [5 * 3600 + 1800]);
+        assert!(PosixZone::parse("E5").is_none());
+        assert!(PosixZone::parse("EST5EDT,M3.2.0").is_none());
+    }
+    #[test]
+    fn transition_dates_count_days_like_posix() {
+        let day = |year, month, day| days_from_civil(year, month, day);
+        assert_eq!(TransitionDate::Julian(60).day(2024), day(2024, 3, 1));
+        assert_eq!(TransitionDate::Julian(60).day(2023), day(2023, 3, 1));
+        assert_eq!(TransitionDate::Day(59).day(2024), day(2024, 2, 29));
+        assert_eq!(TransitionDate::Weekday(3, 2, 0).day(2024), day(2024, 3, 10));
+        assert_eq!(TransitionDate::Weekday(2, 5, 4).day(2024), day(2024, 2, 29));
+        assert_eq!(TransitionDate::Weekday(2, 5, 4).day(2023), day(2023, 2, 23));
+    }
+    #[test]
+    fn format_date_writes_the_offset_of_each_format() {
+        let time = 1700000000;
+        assert_eq!(
+            format_date(time, "+0545", DateFormat::Default),
+            "Wed Nov 15 03:58:20 2023 +0545"
+        );
+        assert_eq!(
+            format_date(time, "+0545", DateFormat::Iso),
+            "2023-11-15 03:58:20 +0545"
+        );
+        assert_eq!(
+            format_date(time, "+0545", DateFormat::IsoStrict),
+            "2023-11-15T03:58:20+05:45"
+        );
+        assert_eq!(
+            format_date(time, "-0130", DateFormat::Rfc2822),
+            "Tue, 14 Nov 2023 20:43:20 -0130"
+        );
+        assert_eq!(
+            format_date(time, "-0130", DateFormat::IsoStrict),
+            "2023-11-14T20:43:20-01:30"
+        );
+        assert_eq!(
+            format_date(time, "-0130", DateFormat::Raw),
+            "1700000000 -0130"
+        );
+        assert_eq!(format_offset(-1800), "-0030");
+        assert_eq!(format_offset(20700), "+0545");
+        assert_eq!(offset_seconds("-0030"), -1800);
+    }
+    #[test]
+    fn format_date_survives_malformed_offsets() {
+        let time = 1700000000;
+        assert_eq!(
+            format_date(time, "+1", DateFormat::IsoStrict),
+            "2023-11-14T22:14:20+1"
+        );
+        assert_eq!(
+            format_date(time, "+0\u{e9}0", DateFormat::IsoStrict),
+            "2023-11-14T22:13:20+0\u{e9}0"
+        );
+        assert_eq!(
+            format_date(time, "+99999999999", DateFormat::Iso),
+            "2023-11-14 22:13:20 +99999999999"
+        );
+    }
+    #[test]
+    fn dates_with_a_zone_keep_it() {
+        assert_eq!(
+            parse_date("2024-01-01T10:00:00+0200").unwrap(),
+            (1704096000, "+0200".to_string())
+        );
+        assert_eq!(
+            parse_date("Mon, 1 Jan 2024 10:00:00 -0130").unwrap(),
+            (1704108600, "-0130".to_string())
+        );
+        assert_eq!(
+            parse_date("1700000000 +0545").unwrap(),
+            (1700000000, "+0545".to_string())
+        );
+    }
+    #[test]
+    fn dates_without_a_time_keep_the_time_of_day() {
+        // 2026-10-19 03:25:13 UTC is 23:25:13 the day before in New York,
+        // where git puts 2024-01-01 at 23:25:13 -0400.
+        let days = days_from_civil(2024, 1, 1);
+        assert_eq!(at_time_of_day(days, 1792380313, -4 * 3600), 1704165913);
+        assert_eq!(at_time_of_day(days, 1792380313, 0), 1704079513);
+        assert_eq!(approxidate("2024-01-01 10:00 +0000").unwrap(), 1704103200);
+    }
+}
@@ -0,0 +1,2409 @@
+use crate::commands::is_under;
+use crate::config::Config;
+use crate::error::GitError;
+use crate::index::Index;
+use crate::objects::{file_mode, short_hash, GitObject, Kind};
+use crate::submodule;
+use std::cell::{Cell, OnceCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::ops::Range;
+pub type Snapshot = BTreeMap<String, (String, Vec<u8>)>;
+pub const DEFAULT_SIMILARITY: usize = 50;
+/// How many files on either side inexact rename detection takes on, as a
+/// square of sources times targets, when `diff.renameLimit` is unset.
+pub const DEFAULT_RENAME_LIMIT: usize = 1000;
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Myers,
+    /// Myers without the cost cutoffs, so the edit script is always the
+    /// shortest one.
+    Minimal,
+    Patience,
+    Histogram,
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Patch,
+    Stat,
+    Shortstat,
+    Numstat,
+    NameStatus,
+    NameOnly,
+    Raw,
+}
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    pub algorithm: Algorithm,
+    pub context: usize,
+    pub format: Format,
+    pub renames: Option<usize>,
+    pub copies: Option<usize>,
+    pub find_copies_harder: bool,
+    /// The `-l` limit on rename candidates; 0 for no limit.
+    pub rename_limit: usize,
+    /// The limit the last rename detection skipped would have needed.
+    pub needed_rename_limit: Cell<usize>,
+    /// Whether copies were only looked for among modified files to stay
+    /// under the limit.
+    pub degraded_copies: Cell<bool>,
+}
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::Myers,
+            context: 3,
+            format: Format::Patch,
+            renames: None,
+            copies: None,
+            find_copies_harder: false,
+            rename_limit: DEFAULT_RENAME_LIMIT,
+            needed_rename_limit: Cell::new(0),
+            degraded_copies: Cell::new(false),
+        }
+    }
+}
+impl DiffOptions {
+    /// Reads the rename limit from the first of `keys` that is set.
+    pub fn configure_rename_limit(&mut self, keys: &[&str]) -> Result<(), GitError> {
+        let config = Config::load()?;
+        if let Some((key, value)) = keys.iter().find_map(|&key| Some((key, config.get(key)?))) {
+            self.rename_limit = value
+                .parse::<i64>()
+                .map_err(|_| {
+                    GitError::any(format!(
+                        "bad numeric config value '{value}' for '{}': invalid unit",
+                        key.to_lowercase()
+                    ))
+                })?
+                .max(0) as usize;
+        }
+        Ok(())
+    }
+    /// Warns, like git does once a command is done, that rename detection
+    /// was cut short by the limit `variable` sets.
+    pub fn warn_rename_limit(&self, variable: &str) {
+        let needed = self.needed_rename_limit.get();
+        if needed == 0 {
+            return;
+        }
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+        if self.degraded_copies.get() {
+            eprintln!("warning: only found copies from modified paths due to too many files.");
+        } else {
+            eprintln!("warning: exhaustive rename detection was skipped due to too many files.");
+        }
+        eprintln!(
+            "warning: you may want to set your {variable} variable to at least {needed} and retry the command."
+        );
+    }
+    fn parse_threshold(value: &str) -> Result<usize, GitError> {
+        if value.is_empty() {
+            return Ok(DEFAULT_SIMILARITY);
+        }
+        let invalid = || GitError::any(format!("invalid similarity threshold: {value}"));
+        match value.strip_suffix('%') {
+            Some(percent) => percent.parse().map_err(|_| invalid()),
+            None if value.len() == 1 || !value.starts_with('0') => {
+                let digits: usize = value.parse().map_err(|_| invalid())?;
+                Ok(digits * 100 / 10usize.pow(value.len() as u32))
+            }
+            None => Err(invalid()),
+        }
+    }
+    pub fn parse_arg(&mut self, arg: &str) -> Result<bool, GitError> {
+        if let Some(value) = arg
+            .strip_prefix("--find-renames=")
+            .or_else(|| arg.strip_prefix("-M"))
+            .or((arg == "--find-renames").then_some(""))
+        {
+            self.renames = Some(Self::parse_threshold(value)?);
+            return Ok(true);
+        }
+        if let Some(value) = arg
+            .strip_prefix("--find-copies=")
+            .or_else(|| arg.strip_prefix("-C"))
+            .or((arg == "--find-copies").then_some(""))
+        {
+            let threshold = Self::parse_threshold(value)?;
+            self.find_copies_harder |= self.copies.is_some();
+            self.copies = Some(threshold);
+            self.renames.get_or_insert(threshold);
+            return Ok(true);
+        }
+        match arg {
+            "--no-renames" => {
+                self.renames = None;
+                self.copies = None;
+            }
+            "--find-copies-harder" => {
+                self.find_copies_harder = true;
+                self.copies.get_or_insert(DEFAULT_SIMILARITY);
+                self.renames.get_or_insert(DEFAULT_SIMILARITY);
+            }
+            "--stat" => self.format = Format::Stat,
+            "--shortstat" => self.format = Format::Shortstat,
+            "--numstat" => self.format = Format::Numstat,
+            "--name-status" => self.format = Format::NameStatus,
+            "--name-only" => self.format = Format::NameOnly,
+            "--raw" => self.format = Format::Raw,
+            "-p" | "--patch" => self.format = Format::Patch,
+            "--myers" => self.algorithm = Algorithm::Myers,
+            "--minimal" => self.algorithm = Algorithm::Minimal,
+            "--patience" => self.algorithm = Algorithm::Patience,
+            "--histogram" => self.algorithm = Algorithm::Histogram,
+            _ => {
+                if let Some(algorithm) = arg.strip_prefix("--diff-algorithm=") {
+                    self.algorithm = match algorithm {
+                        "myers" | "default" => Algorithm::Myers,
+                        "minimal" => Algorithm::Minimal,
+                        "patience" => Algorithm::Patience,
+                        "histogram" => Algorithm::Histogram,
+                        _ => {
+                            return Err(GitError::any(format!(
+                                "unknown diff algorithm: {algorithm}"
+                            )))
+                        }
+                    };
+                } else if let Some(context) = arg
+                    .strip_prefix("--unified=")
+                    .or_else(|| arg.strip_prefix("-U"))
+                {
+                    self.context = context
+                        .parse()
+                        .map_err(|_| GitError::any(format!("invalid context length: {arg}")))?;
+                } else if let Some(limit) =
+                    arg.strip_prefix("-l").and_then(|n| n.parse::<i64>().ok())
+                {
+                    self.rename_limit = limit.max(0) as usize;
+                } else {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|&b| b == 0)
+}
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&b| b == b'\n').collect()
+}
+/// The least edit cost searched before a split gives up on being minimal.
+const MIN_MAX_COST: isize = 256;
+/// The edit cost past which a split may settle for a long enough snake.
+const HEUR_MIN_COST: isize = 256;
+/// How long a run of matching lines counts as a long snake.
+const SNAKE_CNT: isize = 20;
+/// How many times the edit cost a snake's progress must be to be taken.
+const K_HEUR: isize = 4;
+/// The most matches on the other side a line may have before it is noise
+/// that Myers may discard.
+const MAX_EQ_LIMIT: usize = 1024;
+/// How far either way to look for the run a repeated line sits in.
+const SIMSCAN_WINDOW: usize = 100;
+/// The share of a run, out of this many lines, that must be repeated lines
+/// rather than unmatched ones for a repeated line in it to be kept.
+const KPDIS_RUN: usize = 4;
+/// Where a box is split, and whether each half still needs a minimal diff.
+struct Split {
+    x: isize,
+    y: isize,
+    min_lo: bool,
+    min_hi: bool,
+}
+/// Linear-space Myers: each box is split at its middle snake and both halves
+/// are diffed in turn, marking the lines that are not kept.
+struct Myers<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    /// Furthest reaching x on each diagonal `x - y`, shifted by `shift`.
+    forward: Vec<isize>,
+    backward: Vec<isize>,
+    shift: isize,
+    max_cost: isize,
+    changed_a: Vec<bool>,
+    changed_b: Vec<bool>,
+}
+impl<'a> Myers<'a> {
+    fn new(a: &'a [u32], b: &'a [u32]) -> Self {
+        let diagonals = a.len() + b.len() + 3;
+        let mut root = 1;
+        let mut rest = diagonals;
+        while rest > 0 {
+            root <<= 1;
+            rest >>= 2;
+        }
+        Self {
+            a,
+            b,
+            forward: vec![0; diagonals],
+            backward: vec![0; diagonals],
+            shift: b.len() as isize + 1,
+            max_cost: (root as isize).max(MIN_MAX_COST),
+            changed_a: vec![false; a.len()],
+            changed_b: vec![false; b.len()],
+        }
+    }
+    /// Finds where the forward and backward searches through the box
+    /// `a[off1..lim1]`, `b[off2..lim2]` meet. Past `max_cost`, unless a
+    /// minimal diff is needed, settles for the furthest either search got.
+    fn split(
+        &mut self,
+        (off1, lim1): (isize, isize),
+        (off2, lim2): (isize, isize),
+        need_min: bool,
+    ) -> Split {
+        let (a, b) = (self.a, self.b);
+        let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+        let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax, mut bmin, mut bmax) = (fmid, fmid, bmid, bmid);
+        let shift = self.shift;
+        let at = |k: isize| (k + shift) as usize;
+        self.forward[at(fmid)] = off1;
+        self.backward[at(bmid)] = lim1;
+        let meet = |x, y| Split {
+            x,
+            y,
+            min_lo: true,
+            min_hi: true,
+        };
+        for cost in 1.. {
+            let mut got_snake = false;
+            if fmin > dmin {
+                fmin -= 1;
+                self.forward[at(fmin - 1)] = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                self.forward[at(fmax + 1)] = -1;
+            } else {
+                fmax -= 1;
+            }
+            for k in (fmin..=fmax).rev().step_by(2) {
+                let forward = &mut self.forward;
+                let mut x = match forward[at(k - 1)] >= forward[at(k + 1)] {
+                    true => forward[at(k - 1)] + 1,
+                    false => forward[at(k + 1)],
+                };
+                let prev = x;
+                let mut y = x - k;
+                while x < lim1 && y < lim2 && a[x as usize] == b[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                got_snake |= x - prev > SNAKE_CNT;
+                forward[at(k)] = x;
+                if odd && bmin <= k && k <= bmax && self.backward[at(k)] <= x {
+                    return meet(x, y);
+                }
+            }
+            if bmin > dmin {
+                bmin -= 1;
+                self.backward[at(bmin - 1)] = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                self.backward[at(bmax + 1)] = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+            for k in (bmin..=bmax).rev().step_by(2) {
+                let backward = &mut self.backward;
+                let mut x = match backward[at(k - 1)] < backward[at(k + 1)] {
+                    true => backward[at(k - 1)],
+                    false => backward[at(k + 1)] - 1,
+                };
+                let prev = x;
+                let mut y = x - k;
+                while x > off1 && y > off2 && a[(x - 1) as usize] == b[(y - 1) as usize] {
+                    x -= 1;
+                    y -= 1;
+                }
+                got_snake |= prev - x > SNAKE_CNT;
+                backward[at(k)] = x;
+                if !odd && fmin <= k && k <= fmax && x <= self.forward[at(k)] {
+                    return meet(x, y);
+                }
+            }
+            if need_min {
+                continue;
+            }
+            if got_snake && cost > HEUR_MIN_COST {
+                let forward = (fmin, fmax, fmid);
+                let backward = (bmin, bmax, bmid);
+                let split = self.snake_split((off1, lim1), (off2, lim2), forward, backward, cost);
+                if let Some(split) = split {
+                    return split;
+                }
+            }
+            if cost < self.max_cost {
+                continue;
+            }
+            let (mut fbest, mut fbest_x) = (-1, -1);
+            for k in (fmin..=fmax).rev().step_by(2) {
+                let mut x = self.forward[at(k)].min(lim1);
+                let mut y = x - k;
+                if lim2 < y {
+                    (x, y) = (lim2 + k, lim2);
+                }
+                if fbest < x + y {
+                    (fbest, fbest_x) = (x + y, x);
+                }
+            }
+            let (mut bbest, mut bbest_x) = (isize::MAX, isize::MAX);
+            for k in (bmin..=bmax).rev().step_by(2) {
+                let mut x = self.backward[at(k)].max(off1);
+                let mut y = x - k;
+                if y < off2 {
+                    (x, y) = (off2 + k, off2);
+                }
+                if x + y < bbest {
+                    (bbest, bbest_x) = (x + y, x);
+                }
+            }
+            return match (lim1 + lim2) - bbest < fbest - (off1 + off2) {
+                true => Split {
+                    x: fbest_x,
+                    y: fbest - fbest_x,
+                    min_lo: true,
+                    min_hi: false,
+                },
+                false => Split {
+                    x: bbest_x,
+                    y: bbest - bbest_x,
+                    min_lo: false,
+                    min_hi: true,
+                },
+            };
+        }
+        unreachable!("the searches meet by cost n + m")
+    }
+    /// Past `HEUR_MIN_COST`, looks for a diagonal that has come far from the
+    /// corner it started in and ends a long snake, and splits there.
+    fn snake_split(
+        &self,
+        (off1, lim1): (isize, isize),
+        (off2, lim2): (isize, isize),
+        (fmin, fmax, fmid): (isize, isize, isize),
+        (bmin, bmax, bmid): (isize, isize, isize),
+        cost: isize,
+    ) -> Option<Split> {
+        let (a, b) = (self.a, self.b);
+        let at = |k: isize| (k + self.shift) as usize;
+        let (mut best, mut split) = (0, None);
+        for k in (fmin..=fmax).rev().step_by(2) {
+            let x = self.forward[at(k)];
+            let y = x - k;
+            let v = (x - off1) + (y - off2) - (k - fmid).abs();
+            if v > K_HEUR * cost
+                && v > best
+                && off1 + SNAKE_CNT <= x
+                && x < lim1
+                && off2 + SNAKE_CNT <= y
+                && y < lim2
+                && (1..=SNAKE_CNT).all(|n| a[(x - n) as usize] == b[(y - n) as usize])
+            {
+                best = v;
+                split = Some(Split {
+                    x,
+                    y,
+                    min_lo: true,
+                    min_hi: false,
+                });
+            }
+        }
+        if split.is_some() {
+            return split;
+        }
+        for k in (bmin..=bmax).rev().step_by(2) {
+            let x = self.backward[at(k)];
+            let y = x - k;
+            let v = (lim1 - x) + (lim2 - y) - (k - bmid).abs();
+            if v > K_HEUR * cost
+                && v > best
+                && off1 < x
+                && x <= lim1 - SNAKE_CNT
+                && off2 < y
+                && y <= lim2 - SNAKE_CNT
+                && (0..SNAKE_CNT).all(|n| a[(x + n) as usize] == b[(y + n) as usize])
+            {
+                best = v;
+                split = Some(Split {
+                    x,
+                    y,
+                    min_lo: false,
+                    min_hi: true,
+                });
+            }
+        }
+        split
+    }
+    /// Marks the lines of the box `a[off1..lim1]`, `b[off2..lim2]` that are
+    /// not kept, after trimming its common start and end.
+    fn compare(
+        &mut self,
+        (mut off1, mut lim1): (isize, isize),
+        (mut off2, mut lim2): (isize, isize),
+        need_min: bool,
+    ) {
+        let (a, b) = (self.a, self.b);
+        while off1 < lim1 && off2 < lim2 && a[off1 as usize] == b[off2 as usize] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && a[(lim1 - 1) as usize] == b[(lim2 - 1) as usize] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+        if off1 == lim1 {
+            self.changed_b[off2 as usize..lim2 as usize].fill(true);
+        } else if off2 == lim2 {
+            self.changed_a[off1 as usize..lim1 as usize].fill(true);
+        } else {
+            let split = self.split((off1, lim1), (off2, lim2), need_min);
+            self.compare((off1, split.x), (off2, split.y), split.min_lo);
+            self.compare((split.x, lim1), (split.y, lim2), split.min_hi);
+        }
+    }
+}
+/// Whether the repeated line `dis[i]` sits in a run made up mostly of lines
+/// with no match, looking at most `SIMSCAN_WINDOW` lines either way.
+fn clean_mmatch(dis: &[u8], i: usize) -> bool {
+    let start = i.saturating_sub(SIMSCAN_WINDOW);
+    let end = (i + SIMSCAN_WINDOW).min(dis.len() - 1);
+    let run = |lines: &mut dyn Iterator<Item = usize>| {
+        let (mut unmatched, mut repeated) = (0, 1);
+        for line in lines {
+            match dis[line] {
+                0 => unmatched += 1,
+                2 => repeated += 1,
+                _ => break,
+            }
+        }
+        (unmatched, repeated)
+    };
+    let (before, repeated_before) = run(&mut (start..i).rev());
+    if before == 0 {
+        return false;
+    }
+    let (after, repeated_after) = run(&mut (i + 1..=end));
+    if after == 0 {
+        return false;
+    }
+    let repeated = repeated_before + repeated_after;
+    repeated * KPDIS_RUN < repeated + before + after
+}
+/// The lines of `lines[range]` worth handing to Myers: those with a match on
+/// the other side, less repeated lines lost in runs of unmatched ones.
+/// `matches` counts the occurrences of a line on the other side.
+fn kept_lines(lines: &[u32], range: Range<usize>, matches: impl Fn(u32) -> usize) -> Vec<usize> {
+    let limit = bogosqrt(lines.len()).min(MAX_EQ_LIMIT);
+    let dis: Vec<u8> = lines[range.clone()]
+        .iter()
+        .map(|&line| match matches(line) {
+            0 => 0,
+            n if n >= limit => 2,
+            _ => 1,
+        })
+        .collect();
+    (0..dis.len())
+        .filter(|&i| dis[i] == 1 || (dis[i] == 2 && !clean_mmatch(&dis, i)))
+        .map(|i| range.start + i)
+        .collect()
+}
+/// xdiff's stand-in for a square root: the power of two `n` stops at when
+/// shifted two bits at a time.
+fn bogosqrt(mut n: usize) -> usize {
+    let mut root = 1;
+    while n > 0 {
+        root <<= 1;
+        n >>= 2;
+    }
+    root
+}
+/// The matching lines of an edit script from `a` to `b`: the shortest one,
+/// unless the inputs are so different that searching for it would take too
+/// long and `minimal` is not set. As in xdiff, the common start and end are matched up front, and
+/// lines without a match, or repeated ones among them, are left out of the
+/// search.
+fn myers(a: &[u32], b: &[u32], minimal: bool) -> Vec<(usize, usize)> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (mid_a, mid_b) = (prefix..a.len() - suffix, prefix..b.len() - suffix);
+    let mut counts: HashMap<u32, (usize, usize)> = HashMap::new();
+    for &line in a {
+        counts.entry(line).or_default().0 += 1;
+    }
+    for &line in b {
+        counts.entry(line).or_default().1 += 1;
+    }
+    let kept_a = kept_lines(a, mid_a.clone(), |line| counts[&line].1);
+    let kept_b = kept_lines(b, mid_b.clone(), |line| counts[&line].0);
+    let ha1: Vec<u32> = kept_a.iter().map(|&i| a[i]).collect();
+    let ha2: Vec<u32> = kept_b.iter().map(|&j| b[j]).collect();
+    let mut myers = Myers::new(&ha1, &ha2);
+    myers.compare((0, ha1.len() as isize), (0, ha2.len() as isize), minimal);
+    let mut changed_a = vec![false; a.len()];
+    changed_a[mid_a].fill(true);
+    for (n, &i) in kept_a.iter().enumerate() {
+        changed_a[i] = myers.changed_a[n];
+    }
+    let mut changed_b = vec![false; b.len()];
+    changed_b[mid_b].fill(true);
+    for (n, &j) in kept_b.iter().enumerate() {
+        changed_b[j] = myers.changed_b[n];
+    }
+    let mut matches = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if changed_a[i] {
+            i += 1;
+        } else if changed_b[j] {
+            j += 1;
+        } else {
+            matches.push((i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+    matches
+}
+/// The matching lines `patience` and `histogram` collect, keyed by the line
+/// ranges still left to diff so that neither recurses.
+type Ranges = Vec<(Range<usize>, Range<usize>)>;
+/// The matching lines of a patience diff, as xdiff computes them: lines that
+/// occur exactly once on each side anchor the diff, each anchor is grown into
+/// the lines around it, and the gaps between anchors are diffed in turn.
+fn patience(a: &[u32], b: &[u32]) -> Vec<(usize, usize)> {
+    let mut matches = Vec::new();
+    let mut ranges: Ranges = vec![(0..a.len(), 0..b.len())];
+    while let Some((r1, r2)) = ranges.pop() {
+        if r1.is_empty() || r2.is_empty() {
+            continue;
+        }
+        let mut first: HashMap<u32, usize> = HashMap::new();
+        let mut entries: Vec<(usize, Option<usize>, bool)> = Vec::new();
+        for i in r1.clone() {
+            match first.get(&a[i]) {
+                Some(&n) => entries[n].2 = false,
+                None => {
+                    first.insert(a[i], entries.len());
+                    entries.push((i, None, true));
+                }
+            }
+        }
+        let mut has_matches = false;
+        for j in r2.clone() {
+            if let Some(&n) = first.get(&b[j]) {
+                has_matches = true;
+                let entry = &mut entries[n];
+                entry.2 &= entry.1.is_none();
+                entry.1 = Some(j);
+            }
+        }
+        if !has_matches {
+            continue;
+        }
+        let unique: Vec<(usize, usize)> = entries
+            .into_iter()
+            .filter_map(|(i, j, unique)| j.filter(|_| unique).map(|j| (i, j)))
+            .collect();
+        let anchors = longest_increasing(&unique);
+        if anchors.is_empty() {
+            let (off1, off2) = (r1.start, r2.start);
+            let fallback = myers(&a[r1], &b[r2], false);
+            matches.extend(fallback.into_iter().map(|(x, y)| (off1 + x, off2 + y)));
+            continue;
+        }
+        let (mut line1, mut line2) = (r1.start, r2.start);
+        let mut anchors = anchors.into_iter().peekable();
+        loop {
+            let (mut next1, mut next2) = (r1.end, r2.end);
+            if let Some(&(i, j)) = anchors.peek() {
+                (next1, next2) = (i, j);
+                while next1 > line1 && next2 > line2 && a[next1 - 1] == b[next2 - 1] {
+                    next1 -= 1;
+                    next2 -= 1;
+                    matches.push((next1, next2));
+                }
+            }
+            while line1 < next1 && line2 < next2 && a[line1] == b[line2] {
+                matches.push((line1, line2));
+                line1 += 1;
+                line2 += 1;
+            }
+            if next1 > line1 || next2 > line2 {
+                ranges.push((line1..next1, line2..next2));
+            }
+            let Some(mut anchor) = anchors.next() else {
+                break;
+            };
+            matches.push(anchor);
+            while let Some(&next) = anchors.peek() {
+                if next != (anchor.0 + 1, anchor.1 + 1) {
+                    break;
+                }
+                anchor = next;
+                matches.push(anchor);
+                anchors.next();
+            }
+            (line1, line2) = (anchor.0 + 1, anchor.1 + 1);
+        }
+    }
+    matches.sort_unstable();
+    matches
+}
+/// The longest run of `pairs`, taken in order, whose second members
+/// increase.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![usize::MAX; pairs.len()];
+    for (n, &(_, j)) in pairs.iter().enumerate() {
+        let pos = tails.partition_point(|&t| pairs[t].1 < j);
+        if pos > 0 {
+            prev[n] = tails[pos - 1];
+        }
+        if pos == tails.len() {
+            tails.push(n);
+        } else {
+            tails[pos] = n;
+        }
+    }
+    let mut result = Vec::new();
+    let mut n = tails.last().copied().unwrap_or(usize::MAX);
+    while n != usize::MAX {
+        result.push(pairs[n]);
+        n = prev[n];
+    }
+    result.reverse();
+    result
+}
+/// The most times a line may occur in the old range and still be used to
+/// anchor a histogram match; past it the range falls back to Myers.
+const MAX_CHAIN: usize = 64;
+/// Marks the end of an occurrence chain.
+const NO_LINE: usize = usize::MAX;
+/// A line of the old range: its first occurrence and how often it occurs.
+#[derive(Clone, Copy)]
+struct Record {
+    first: usize,
+    count: usize,
+}
+/// What `Histogram::find_lcs` found in a pair of ranges.
+enum Lcs {
+    /// The lines `a[begin1..=end1]` match `b[begin2..=end2]`.
+    Found {
+        begin1: usize,
+        end1: usize,
+        begin2: usize,
+        end2: usize,
+    },
+    /// Every common line occurs too often; diff the ranges with Myers.
+    FallBack,
+    /// The ranges have no line in common.
+    Disjoint,
+}
+/// xdiff's histogram diff: the longest common region built around the
+/// rarest lines of the old range splits both ranges, and the ranges on
+/// either side are diffed the same way.
+struct Histogram<'a> {
+    a: &'a [u32],
+    b: &'a [u32],
+    /// The record of each line in the old range being searched, by line id.
+    records: Vec<Option<Record>>,
+    /// The next occurrence of each old line in the range, or `NO_LINE`.
+    next: Vec<usize>,
+    /// The occurrence count of the best region found so far.
+    count: usize,
+    has_common: bool,
+}
+impl<'a> Histogram<'a> {
+    fn new(a: &'a [u32], b: &'a [u32]) -> Self {
+        let ids = a.iter().chain(b).max().map_or(0, |&id| id as usize + 1);
+        Self {
+            a,
+            b,
+            records: vec![None; ids],
+            next: vec![NO_LINE; a.len()],
+            count: 0,
+            has_common: false,
+        }
+    }
+    fn count_of(&self, line: usize) -> usize {
+        self.records[self.a[line] as usize].map_or(0, |record| record.count)
+    }
+    /// Chains together the occurrences of each line of `a[range]`.
+    fn scan_a(&mut self, range: Range<usize>) {
+        for line in range.rev() {
+            let record = &mut self.records[self.a[line] as usize];
+            match record {
+                Some(record) => {
+                    self.next[line] = record.first;
+                    record.first = line;
+                    record.count += 1;
+                }
+                None => {
+                    self.next[line] = NO_LINE;
+                    *record = Some(Record {
+                        first: line,
+                        count: 1,
+                    });
+                }
+            }
+        }
+    }
+    /// Grows every occurrence of `b[b_line]` in the old range into a common
+    /// region, keeping it in `lcs` if it is longer or rarer than the best so
+    /// far. Returns the next line of `b` worth trying.
+    fn try_lcs(
+        &mut self,
+        lcs: &mut Lcs,
+        b_line: usize,
+        (r1, r2): (&Range<usize>, &Range<usize>),
+    ) -> usize {
+        let (a, b) = (self.a, self.b);
+        let mut b_next = b_line + 1;
+        let Some(record) = self.records[b[b_line] as usize] else {
+            return b_next;
+        };
+        self.has_common = true;
+        if record.count > self.count {
+            return b_next;
+        }
+        let mut as_ = record.first;
+        loop {
+            let mut np = self.next[as_];
+            let (mut bs, mut ae, mut be) = (b_line, as_, b_line);
+            let mut rc = record.count;
+            while r1.start < as_ && r2.start < bs && a[as_ - 1] == b[bs - 1] {
+                as_ -= 1;
+                bs -= 1;
+                if 1 < rc {
+                    rc = rc.min(self.count_of(as_));
+                }
+            }
+            while ae + 1 < r1.end && be + 1 < r2.end && a[ae + 1] == b[be + 1] {
+                ae += 1;
+                be += 1;
+                if 1 < rc {
+                    rc = rc.min(self.count_of(ae));
+                }
+            }
+            if b_next <= be {
+                b_next = be + 1;
+            }
+            let longer = match *lcs {
+                Lcs::Found { begin1, end1, .. } => end1 - begin1 < ae - as_,
+                _ => false,
+            };
+            if longer || rc < self.count {
+                *lcs = Lcs::Found {
+                    begin1: as_,
+                    end1: ae,
+                    begin2: bs,
+                    end2: be,
+                };
+                self.count = rc;
+            }
+            while np != NO_LINE && np <= ae {
+                np = self.next[np];
+            }
+            if np == NO_LINE {
+                break;
+            }
+            as_ = np;
+        }
+        b_next
+    }
+    fn find_lcs(&mut self, r1: &Range<usize>, r2: &Range<usize>) -> Lcs {
+        self.scan_a(r1.clone());
+        self.count = MAX_CHAIN + 1;
+        self.has_common = false;
+        let mut lcs = Lcs::Disjoint;
+        let mut b_line = r2.start;
+        while b_line < r2.end {
+            b_line = self.try_lcs(&mut lcs, b_line, (r1, r2));
+        }
+        for line in r1.clone() {
+            self.records[self.a[line] as usize] = None;
+        }
+        match lcs {
+            _ if self.has_common && MAX_CHAIN < self.count => Lcs::FallBack,
+            lcs => lcs,
+        }
+    }
+}
+/// The matching lines of a histogram diff.
+fn histogram(a: &[u32], b: &[u32]) -> Vec<(usize, usize)> {
+    let mut histogram = Histogram::new(a, b);
+    let mut matches = Vec::new();
+    let mut ranges: Ranges = vec![(0..a.len(), 0..b.len())];
+    while let Some((r1, r2)) = ranges.pop() {
+        if r1.is_empty() || r2.is_empty() {
+            continue;
+        }
+        match histogram.find_lcs(&r1, &r2) {
+            Lcs::Found {
+                begin1,
+                end1,
+                begin2,
+                end2,
+            } => {
+                matches.extend((begin1..=end1).zip(begin2..=end2));
+                ranges.push((r1.start..begin1, r2.start..begin2));
+                ranges.push((end1 + 1..r1.end, end2 + 1..r2.end));
+            }
+            Lcs::FallBack => {
+                let (off1, off2) = (r1.start, r2.start);
+                let fallback = myers(&a[r1], &b[r2], false);
+                matches.extend(fallback.into_iter().map(|(x, y)| (off1 + x, off2 + y)));
+            }
+            Lcs::Disjoint => {}
+        }
+    }
+    matches.sort_unstable();
+    matches
+}
+fn matching_lines<'a>(
+    old: &[&'a [u8]],
+    new: &[&'a [u8]],
+    algorithm: Algorithm,
+) -> Vec<(usize, usize)> {
+    let mut ids: HashMap<&'a [u8], u32> = HashMap::new();
+    let mut intern = |lines: &[&'a [u8]]| -> Vec<u32> {
+        lines
+            .iter()
+            .map(|line| {
+                let next = ids.len() as u32;
+                *ids.entry(line).or_insert(next)
+            })
+            .collect()
+    };
+    let (a, b) = (intern(old), intern(new));
+    match algorithm {
+        Algorithm::Myers => myers(&a, &b, false),
+        Algorithm::Minimal => myers(&a, &b, true),
+        Algorithm::Patience => patience(&a, &b),
+        Algorithm::Histogram => histogram(&a, &b),
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+/// A run of changed lines on one side of an edit script, `start..end`.
+#[derive(Clone, Copy)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+/// One side of an edit script: its lines and which of them changed.
+struct Side<'a, 'b> {
+    lines: &'b [&'a [u8]],
+    changed: Vec<bool>,
+}
+impl Side<'_, '_> {
+    fn is_changed(&self, line: usize) -> bool {
+        self.changed.get(line).copied().unwrap_or(false)
+    }
+    fn first_group(&self) -> Group {
+        let mut end = 0;
+        while self.is_changed(end) {
+            end += 1;
+        }
+        Group { start: 0, end }
+    }
+    fn next(&self, group: &mut Group) -> bool {
+        if group.end == self.lines.len() {
+            return false;
+        }
+        group.start = group.end + 1;
+        group.end = group.start;
+        while self.is_changed(group.end) {
+            group.end += 1;
+        }
+        true
+    }
+    fn previous(&self, group: &mut Group) -> bool {
+        if group.start == 0 {
+            return false;
+        }
+        group.end = group.start - 1;
+        group.start = group.end;
+        while group.start > 0 && self.is_changed(group.start - 1) {
+            group.start -= 1;
+        }
+        true
+    }
+    fn slide_down(&mut self, group: &mut Group) -> bool {
+        if group.end == self.lines.len() || self.lines[group.start] != self.lines[group.end] {
+            return false;
+        }
+        self.changed[group.start] = false;
+        self.changed[group.end] = true;
+        group.start += 1;
+        group.end += 1;
+        while self.is_changed(group.end) {
+            group.end += 1;
+        }
+        true
+    }
+    fn slide_up(&mut self, group: &mut Group) -> bool {
+        if group.start == 0 || self.lines[group.start - 1] != self.lines[group.end - 1] {
+            return false;
+        }
+        group.start -= 1;
+        group.end -= 1;
+        self.changed[group.start] = true;
+        self.changed[group.end] = false;
+        while group.start > 0 && self.is_changed(group.start - 1) {
+            group.start -= 1;
+        }
+        true
+    }
+    /// The indent of `line` with tabs to multiples of eight, capped at
+    /// `MAX_INDENT`, or `None` if it is blank.
+    fn indent(&self, line: usize) -> Option<isize> {
+        let mut indent = 0;
+        for &c in self.lines[line] {
+            match c {
+                b' ' => indent += 1,
+                b'\t' => indent += 8 - indent % 8,
+                b'\n' | b'\r' | b'\x0b' | b'\x0c' => {}
+                _ => return Some(indent),
+            }
+            if indent >= MAX_INDENT {
+                return Some(MAX_INDENT);
+            }
+        }
+        None
+    }
+    /// Describes the lines around a split just before line `split`.
+    fn measure_split(&self, split: usize) -> SplitMeasurement {
+        let len = self.lines.len();
+        let mut measurement = SplitMeasurement {
+            end_of_file: split >= len,
+            indent: (split < len).then(|| self.indent(split)).flatten(),
+            pre_blank: 0,
+            pre_indent: None,
+            post_blank: 0,
+            post_indent: None,
+        };
+        for line in (0..split.min(len)).rev() {
+            measurement.pre_indent = self.indent(line);
+            if measurement.pre_indent.is_some() {
+                break;
+            }
+            measurement.pre_blank += 1;
+            if measurement.pre_blank == MAX_BLANKS {
+                measurement.pre_indent = Some(0);
+                break;
+            }
+        }
+        for line in split + 1..len {
+            measurement.post_indent = self.indent(line);
+            if measurement.post_indent.is_some() {
+                break;
+            }
+            measurement.post_blank += 1;
+            if measurement.post_blank == MAX_BLANKS {
+                measurement.post_indent = Some(0);
+                break;
+            }
+        }
+        measurement
+    }
+    /// Slides each group of changes up and down over identical lines,
+    /// merging groups that touch, then settles it where it lines up with a
+    /// change on the other side or, failing that, where the indent
+    /// heuristic scores it best, as xdiff's `xdl_change_compact` does.
+    fn compact(&mut self, other: &Side) {
+        let mut group = self.first_group();
+        let mut other_group = other.first_group();
+        loop {
+            if group.end != group.start {
+                let (mut size, mut earliest_end, mut end_matching_other);
+                loop {
+                    size = group.end - group.start;
+                    end_matching_other = None;
+                    while self.slide_up(&mut group) {
+                        other.previous(&mut other_group);
+                    }
+                    earliest_end = group.end;
+                    if other_group.end > other_group.start {
+                        end_matching_other = Some(group.end);
+                    }
+                    while self.slide_down(&mut group) {
+                        other.next(&mut other_group);
+                        if other_group.end > other_group.start {
+                            end_matching_other = Some(group.end);
+                        }
+                    }
+                    if size == group.end - group.start {
+                        break;
+                    }
+                }
+                if group.end == earliest_end {
+                } else if end_matching_other.is_some() {
+                    while other_group.end == other_group.start {
+                        self.slide_up(&mut group);
+                        other.previous(&mut other_group);
+                    }
+                } else {
+                    let lowest = (group.end - size)
+                        .saturating_sub(1)
+                        .max(earliest_end)
+                        .max(group.end.saturating_sub(INDENT_HEURISTIC_MAX_SLIDING));
+                    let mut best: Option<(usize, SplitScore)> = None;
+                    for shift in lowest..=group.end {
+                        let mut score = SplitScore::default();
+                        score.add(&self.measure_split(shift));
+                        score.add(&self.measure_split(shift - size));
+                        if best.as_ref().map_or(true, |(_, best)| score.cmp(best) <= 0) {
+                            best = Some((shift, score));
+                        }
+                    }
+                    let best_shift = best.map_or(group.end, |(shift, _)| shift);
+                    while group.end > best_shift {
+                        self.slide_up(&mut group);
+                        other.previous(&mut other_group);
+                    }
+                }
+            }
+            if !self.next(&mut group) {
+                break;
+            }
+            other.next(&mut other_group);
+        }
+    }
+}
+const MAX_INDENT: isize = 200;
+const MAX_BLANKS: usize = 20;
+const INDENT_HEURISTIC_MAX_SLIDING: usize = 100;
+/// The surroundings of a place a group of changes could start or end.
+struct SplitMeasurement {
+    end_of_file: bool,
+    /// The indent of the line just after the split.
+    indent: Option<isize>,
+    /// The blank lines just before the split, and the indent of the first
+    /// line before them.
+    pre_blank: usize,
+    pre_indent: Option<isize>,
+    /// The blank lines after the line after the split, and the indent of
+    /// the first line after them.
+    post_blank: usize,
+    post_indent: Option<isize>,
+}
+/// How bad the splits around a group of changes look; lower is better.
+#[derive(Default)]
+struct SplitScore {
+    effective_indent: isize,
+    penalty: isize,
+}
+impl SplitScore {
+    fn add(&mut self, m: &SplitMeasurement) {
+        if m.pre_indent.is_none() && m.pre_blank == 0 {
+            self.penalty += 1;
+        }
+        if m.end_of_file {
+            self.penalty += 21;
+        }
+        let post_blank = match m.indent {
+            None => 1 + m.post_blank as isize,
+            Some(_) => 0,
+        };
+        let total_blank = m.pre_blank as isize + post_blank;
+        self.penalty += -30 * total_blank + 6 * post_blank;
+        let indent = m.indent.or(m.post_indent);
+        let any_blanks = total_blank != 0;
+        self.effective_indent += indent.unwrap_or(-1);
+        let (Some(indent), Some(pre_indent)) = (indent, m.pre_indent) else {
+            return;
+        };
+        self.penalty += if indent > pre_indent {
+            if any_blanks {
+                10
+            } else {
+                -4
+            }
+        } else if indent == pre_indent {
+            0
+        } else if m.post_indent.is_some_and(|post| post > indent) {
+            if any_blanks {
+                17
+            } else {
+                24
+            }
+        } else if any_blanks {
+            17
+        } else {
+            23
+        };
+    }
+    fn cmp(&self, other: &SplitScore) -> isize {
+        let indents = (self.effective_indent - other.effective_indent).signum();
+        60 * indents + (self.penalty - other.penalty)
+    }
+}
+fn edit_script(old: &[&[u8]], new: &[&[u8]], matches: &[(usize, usize)]) -> Vec<Op> {
+    let mut old_side = Side {
+        lines: old,
+        changed: vec![true; old.len()],
+    };
+    let mut new_side = Side {
+        lines: new,
+        changed: vec![true; new.len()],
+    };
+    for &(x, y) in matches {
+        old_side.changed[x] = false;
+        new_side.changed[y] = false;
+    }
+    old_side.compact(&new_side);
+    new_side.compact(&old_side);
+    let (deleted, inserted) = (old_side.changed, new_side.changed);
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && deleted[i] {
+            ops.push(Op::Delete(i));
+            i += 1;
+        } else if j < new.len() && inserted[j] {
+            ops.push(Op::Insert(j));
+            j += 1;
+        } else {
+            ops.push(Op::Equal(i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+    ops
+}
+pub struct LineDiff<'a> {
+    old: Vec<&'a [u8]>,
+    new: Vec<&'a [u8]>,
+    ops: Vec<Op>,
+}
+impl<'a> LineDiff<'a> {
+    pub fn new(old: &'a [u8], new: &'a [u8], algorithm: Algorithm) -> Self {
+        let (old, new) = (split_lines(old), split_lines(new));
+        let matches = matching_lines(&old, &new, algorithm);
+        let ops = edit_script(&old, &new, &matches);
+        Self { old, new, ops }
+    }
+    pub fn insertions(&self) -> usize {
+        self.ops
+            .iter()
+            .filter(|op| matches!(op, Op::Insert(_)))
+            .count()
+    }
+    pub fn deletions(&self) -> usize {
+        self.ops
+            .iter()
+            .filter(|op| matches!(op, Op::Delete(_)))
+            .count()
+    }
+    pub fn common_bytes(&self) -> usize {
+        self.ops
+            .iter()
+            .map(|op| match op {
+                Op::Equal(i, _) => self.old[*i].len(),
+                _ => 0,
+            })
+            .sum()
+    }
+    /// The changed regions as pairs of old and new line ranges, in order.
+    pub fn hunks(&self) -> Vec<(Range<usize>, Range<usize>)> {
+        let mut hunks: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+        let (mut old, mut new) = (0, 0);
+        let mut changed = false;
+        for op in &self.ops {
+            let (start_old, start_new) = (old, new);
+            match op {
+                Op::Equal(..) => {
+                    old += 1;
+                    new += 1;
+                    changed = false;
+                    continue;
+                }
+                Op::Delete(_) => old += 1,
+                Op::Insert(_) => new += 1,
+            }
+            match hunks.last_mut() {
+                Some((old_range, new_range)) if changed => {
+                    old_range.end = old;
+                    new_range.end = new;
+                }
+                _ => hunks.push((start_old..old, start_new..new)),
+            }
+            changed = true;
+        }
+        hunks
+    }
+    fn push_line(out: &mut Vec<u8>, prefix: u8, line: &[u8]) {
+        out.push(prefix);
+        out.extend_from_slice(line);
+        if !line.ends_with(b"\n") {
+            out.extend_from_slice(b"\n\\ No newline at end of file\n");
+        }
+    }
+    pub fn unified(&self, context: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        let changes: Vec<usize> = (0..self.ops.len())
+            .filter(|&n| !matches!(self.ops[n], Op::Equal(..)))
+            .collect();
+        let positions = self.positions();
+        let (mut scanned, mut function) = (0, None);
+        let mut n = 0;
+        while n < changes.len() {
+            let start = changes[n].saturating_sub(context);
+            let mut last = changes[n];
+            while n + 1 < changes.len() && changes[n + 1] - last <= 2 * context + 1 {
+                n += 1;
+                last = changes[n];
+            }
+            n += 1;
+            let end = (last + context + 1).min(self.ops.len());
+            let hunk = &self.ops[start..end];
+            let (mut old_start, mut new_start) = positions[start];
+            let old_len = hunk
+                .iter()
+                .filter(|op| !matches!(op, Op::Insert(_)))
+                .count();
+            let new_len = hunk
+                .iter()
+                .filter(|op| !matches!(op, Op::Delete(_)))
+                .count();
+            if old_len > 0 {
+                old_start += 1;
+            }
+            if new_len > 0 {
+                new_start += 1;
+            }
+            let range = |start: usize, len: usize| match len {
+                1 => format!("{start}"),
+                len => format!("{start},{len}"),
+            };
+            out.extend_from_slice(
+                format!(
+                    "@@ -{} +{} @@",
+                    range(old_start, old_len),
+                    range(new_start, new_len)
+                )
+                .as_bytes(),
+            );
+            let old_line = positions[start].0;
+            function = self.function_context(scanned..old_line).or(function);
+            scanned = old_line;
+            if let Some(line) = function {
+                out.push(b' ');
+                out.extend_from_slice(line);
+            }
+            out.push(b'\n');
+            for op in hunk {
+                match *op {
+                    Op::Equal(i, _) => Self::push_line(&mut out, b' ', self.old[i]),
+                    Op::Delete(i) => Self::push_line(&mut out, b'-', self.old[i]),
+                    Op::Insert(j) => Self::push_line(&mut out, b'+', self.new[j]),
+                }
+            }
+        }
+        out
+    }
+    /// The last line of `old[lines]` that looks like a function header. Each
+    /// hunk only scans the lines since the previous one.
+    fn function_context(&self, lines: Range<usize>) -> Option<&'a [u8]> {
+        self.old[lines]
+            .iter()
+            .rev()
+            .find(|line| {
+                line.first()
+                    .is_some_and(|&c| c.is_ascii_alphabetic() || c == b'_' || c == b'$')
+            })
+            .map(|line| {
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                &line[..line.len().min(80)]
+            })
+    }
+    /// The old and new line numbers each op starts at.
+    fn positions(&self) -> Vec<(usize, usize)> {
+        let mut position = (0, 0);
+        let mut positions = Vec::with_capacity(self.ops.len() + 1);
+        for op in &self.ops {
+            positions.push(position);
+            position = match op {
+                Op::Equal(..) => (position.0 + 1, position.1 + 1),
+                Op::Delete(_) => (position.0 + 1, position.1),
+                Op::Insert(_) => (position.0, position.1 + 1),
+            };
+        }
+        positions.push(position);
+        positions
+    }
+}
+#[derive(Clone, Debug, Default)]
+pub struct FileDiff {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    pub old_hash: Vec<u8>,
+    pub new_hash: Vec<u8>,
+    /// The contents of either side, read the first time they are needed.
+    pub old_data: OnceCell<Vec<u8>>,
+    pub new_data: OnceCell<Vec<u8>>,
+    /// Whether the new side is read from the working tree.
+    pub worktree: bool,
+    pub score: Option<usize>,
+    pub copied: bool,
+}
+impl FileDiff {
+    fn read_data(path: &str, mode: &str, hash: &[u8], worktree: bool) -> Result<Vec<u8>, GitError> {
+        if mode == "160000" {
+            return Ok(format!("Subproject commit {}\n", hex::encode(hash)).into_bytes());
+        }
+        if worktree {
+            if mode == "120000" {
+                return Ok(fs::read_link(path)?
+                    .to_string_lossy()
+                    .into_owned()
+                    .into_bytes());
+            }
+            return Ok(fs::read(path)?);
+        }
+        Ok(GitObject::from_hex_string(hex::encode(hash))?
+            .contents()
+            .clone())
+    }
+    pub fn load(
+        path: &str,
+        old: Option<&(String, Vec<u8>)>,
+        new: Option<&(String, Vec<u8>)>,
+        worktree: bool,
+    ) -> Self {
+        Self {
+            old_path: old.map(|_| path.to_string()),
+            new_path: new.map(|_| path.to_string()),
+            old_mode: old.map(|(mode, _)| mode.clone()),
+            new_mode: new.map(|(mode, _)| mode.clone()),
+            old_hash: old.map(|(_, hash)| hash.clone()).unwrap_or_default(),
+            new_hash: new.map(|(_, hash)| hash.clone()).unwrap_or_default(),
+            worktree,
+            ..Self::default()
+        }
+    }
+    fn data<'a>(
+        data: &'a OnceCell<Vec<u8>>,
+        path: &Option<String>,
+        mode: &Option<String>,
+        hash: &[u8],
+        worktree: bool,
+    ) -> Result<&'a [u8], GitError> {
+        if let Some(data) = data.get() {
+            return Ok(data);
+        }
+        let contents = match (path, mode) {
+            (Some(path), Some(mode)) => Self::read_data(path, mode, hash, worktree)?,
+            _ => Vec::new(),
+        };
+        Ok(data.get_or_init(|| contents))
+    }
+    pub fn old_data(&self) -> Result<&[u8], GitError> {
+        Self::data(
+            &self.old_data,
+            &self.old_path,
+            &self.old_mode,
+            &self.old_hash,
+            false,
+        )
+    }
+    pub fn new_data(&self) -> Result<&[u8], GitError> {
+        Self::data(
+            &self.new_data,
+            &self.new_path,
+            &self.new_mode,
+            &self.new_hash,
+            self.worktree,
+        )
+    }
+    /// The size of one side's contents, taken from the blob's header or
+    /// the file's metadata when they are not loaded yet.
+    fn size(
+        data: &OnceCell<Vec<u8>>,
+        path: &Option<String>,
+        mode: &Option<String>,
+        hash: &[u8],
+        worktree: bool,
+    ) -> Result<usize, GitError> {
+        match (path, mode, data.get()) {
+            (Some(path), Some(mode), None) if mode != "160000" => match worktree {
+                true => Ok(fs::symlink_metadata(path)?.len() as usize),
+                false => GitObject::size_from_hex_string(hex::encode(hash)),
+            },
+            _ => Ok(Self::data(data, path, mode, hash, worktree)?.len()),
+        }
+    }
+    fn old_size(&self) -> Result<usize, GitError> {
+        Self::size(
+            &self.old_data,
+            &self.old_path,
+            &self.old_mode,
+            &self.old_hash,
+            false,
+        )
+    }
+    fn new_size(&self) -> Result<usize, GitError> {
+        Self::size(
+            &self.new_data,
+            &self.new_path,
+            &self.new_mode,
+            &self.new_hash,
+            self.worktree,
+        )
+    }
+    fn is_type_change(&self) -> bool {
+        match (&self.old_mode, &self.new_mode) {
+            (Some(old), Some(new)) => old[..2] != new[..2],
+            _ => false,
+        }
+    }
+    pub fn status(&self) -> char {
+        match (&self.old_path, &self.new_path) {
+            _ if self.copied => 'C',
+            _ if self.score.is_some() => 'R',
+            (None, _) => 'A',
+            (_, None) => 'D',
+            _ if self.is_type_change() => 'T',
+            _ => 'M',
+        }
+    }
+    pub fn status_label(&self) -> String {
+        match self.score {
+            Some(score) => format!("{}{score:03}", self.status()),
+            None => self.status().to_string(),
+        }
+    }
+    pub fn tab_paths(&self) -> String {
+        match (&self.old_path, &self.new_path) {
+            (Some(old), Some(new)) if old != new => format!("{old}\t{new}"),
+            _ => self.display_path(),
+        }
+    }
+    pub fn display_path(&self) -> String {
+        match (&self.old_path, &self.new_path) {
+            (Some(old), Some(new)) if old != new => {
+                let prefix = old
+                    .char_indices()
+                    .zip(new.chars())
+                    .take_while(|((_, a), b)| a == b)
+                    .filter(|((_, a), _)| *a == '/')
+                    .last()
+                    .map_or(0, |((i, _), _)| i + 1);
+                let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+                let suffix = old_rest
+                    .char_indices()
+                    .rev()
+                    .zip(new_rest.chars().rev())
+                    .take_while(|((_, a), b)| a == b)
+                    .filter(|((_, a), _)| *a == '/')
+                    .last()
+                    .map_or(0, |((i, _), _)| old_rest.len() - i);
+                let suffix = suffix.min(old_rest.len()).min(new_rest.len());
+                if prefix == 0 && suffix == 0 {
+                    return format!("{old} => {new}");
+                }
+                format!(
+                    "{}{{{} => {}}}{}",
+                    &old[..prefix],
+                    &old_rest[..old_rest.len() - suffix],
+                    &new_rest[..new_rest.len() - suffix],
+                    &old_rest[old_rest.len() - suffix..]
+                )
+            }
+            (_, Some(path)) | (Some(path), None) => path.clone(),
+            (None, None) => String::new(),
+        }
+    }
+    fn is_binary(&self) -> Result<bool, GitError> {
+        Ok(is_binary(self.old_data()?) || is_binary(self.new_data()?))
+    }
+    fn counts(&self, algorithm: Algorithm) -> Result<Option<(usize, usize)>, GitError> {
+        if self.is_binary()? {
+            return Ok(None);
+        }
+        let diff = LineDiff::new(self.old_data()?, self.new_data()?, algorithm);
+        Ok(Some((diff.insertions(), diff.deletions())))
+    }
+    fn patch(&self, options: &DiffOptions) -> Result<Vec<u8>, GitError> {
+        let mut out = String::new();
+        let old_name = self
+            .old_path
+            .as_deref()
+            .or(self.new_path.as_deref())
+            .unwrap_or_default();
+        let new_name = self
+            .new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default();
+        out.push_str(&format!("diff --git a/{old_name} b/{new_name}\n"));
+        let short = |hash: &[u8]| {
+            if hash.is_empty() {
+                "0000000".to_string()
+            } else {
+                short_hash(&hex::encode(hash)).to_string()
+            }
+        };
+        let mut index_mode = String::new();
+        match (&self.old_mode, &self.new_mode) {
+            (None, Some(mode)) => out.push_str(&format!("new file mode {mode:0>6}\n")),
+            (Some(mode), None) => out.push_str(&format!("deleted file mode {mode:0>6}\n")),
+            (Some(old), Some(new)) if old != new => {
+                out.push_str(&format!("old mode {old:0>6}\nnew mode {new:0>6}\n"))
+            }
+            (Some(mode), Some(_)) => index_mode = format!(" {mode:0>6}"),
+            (None, None) => {}
+        }
+        if let Some(score) = self.score {
+            let verb = if self.copied { "copy" } else { "rename" };
+            out.push_str(&format!(
+                "similarity index {score}%\n{verb} from {old_name}\n{verb} to {new_name}\n"
+            ));
+        }
+        if self.old_hash == self.new_hash {
+            return Ok(out.into_bytes());
+        }
+        out.push_str(&format!(
+            "index {}..{}{index_mode}\n",
+            short(&self.old_hash),
+            short(&self.new_hash)
+        ));
+        let old_label = match &self.old_path {
+            Some(path) => format!("a/{path}"),
+            None => "/dev/null".into(),
+        };
+        let new_label = match &self.new_path {
+            Some(path) => format!("b/{path}"),
+            None => "/dev/null".into(),
+        };
+        if self.is_binary()? {
+            out.push_str(&format!(
+                "Binary files {old_label} and {new_label} differ\n"
+            ));
+            return Ok(out.into_bytes());
+        }
+        let hunks = LineDiff::new(self.old_data()?, self.new_data()?, options.algorithm)
+            .unified(options.context);
+        if !hunks.is_empty() {
+            out.push_str(&format!("--- {old_label}\n+++ {new_label}\n"));
+        }
+        let mut out = out.into_bytes();
+        out.extend(hunks);
+        Ok(out)
+    }
+}
+pub fn print_diffs(diffs: &[FileDiff], options: &DiffOptions) -> Result<(), GitError> {
+    write_diffs(&mut std::io::stdout().lock(), diffs, options)
+}
+pub fn write_diffs(
+    stdout: &mut impl std::io::Write,
+    diffs: &[FileDiff],
+    options: &DiffOptions,
+) -> Result<(), GitError> {
+    match options.format {
+        Format::Patch => {
+            for diff in diffs {
+                if diff.is_type_change() {
+                    let (mut deleted, mut added) = (diff.clone(), diff.clone());
+                    deleted.new_path = None;
+                    deleted.new_mode = None;
+                    deleted.new_hash = Vec::new();
+                    deleted.new_data = OnceCell::new();
+                    added.old_path = None;
+                    added.old_mode = None;
+                    added.old_hash = Vec::new();
+                    added.old_data = OnceCell::new();
+                    stdout.write_all(&deleted.patch(options)?)?;
+                    stdout.write_all(&added.patch(options)?)?;
+                } else {
+                    stdout.write_all(&diff.patch(options)?)?;
+                }
+            }
+        }
+        Format::Raw => {
+            for diff in diffs {
+                let mode =
+                    |mode: &Option<String>| format!("{:0>6}", mode.as_deref().unwrap_or("0"));
+                let hash = |hash: &[u8]| {
+                    if hash.is_empty() {
+                        "0".repeat(40)
+                    } else {
+                        hex::encode(hash)
+                    }
+                };
+                writeln!(
+                    stdout,
+                    ":{} {} {} {} {}\t{}",
+                    mode(&diff.old_mode),
+                    mode(&diff.new_mode),
+                    hash(&diff.old_hash),
+                    hash(&diff.new_hash),
+                    diff.status_label(),
+                    diff.tab_paths()
+                )?;
+            }
+        }
+        Format::NameOnly => {
+            for diff in diffs {
+                let path = diff.new_path.as_ref().or(diff.old_path.as_ref());
+                writeln!(stdout, "{}", path.cloned().unwrap_or_default())?;
+            }
+        }
+        Format::NameStatus => {
+            for diff in diffs {
+                writeln!(stdout, "{}\t{}", diff.status_label(), diff.tab_paths())?;
+            }
+        }
+        Format::Numstat => {
+            for diff in diffs {
+                match diff.counts(options.algorithm)? {
+                    Some((added, deleted)) => {
+                        writeln!(stdout, "{added}\t{deleted}\t{}", diff.display_path())?
+                    }
+                    None => writeln!(stdout, "-\t-\t{}", diff.display_path())?,
+                }
+            }
+        }
+        Format::Stat | Format::Shortstat => {
+            let counts = diffs
+                .iter()
+                .map(|d| d.counts(options.algorithm))
+                .collect::<Result<Vec<_>, _>>()?;
+            let max_len = diffs
+                .iter()
+                .map(|d| d.display_path().chars().count())
+                .max()
+                .unwrap_or(0);
+            let max_change = counts
+                .iter()
+                .flatten()
+                .map(|(a, d)| a + d)
+                .max()
+                .unwrap_or(0);
+            let digits = |n: usize| n.to_string().len();
+            // Binary files are shown as "Bin <old> -> <new> bytes", with
+            // the counts of the others aligned to "Bin".
+            let mut bin_width = 0;
+            for (diff, counts) in diffs.iter().zip(&counts) {
+                if counts.is_none() {
+                    let (old, new) = (diff.old_data()?.len(), diff.new_data()?.len());
+                    bin_width = bin_width.max(14 + digits(old) + digits(new));
+                }
+            }
+            let mut count_width = digits(max_change);
+            if bin_width > 0 {
+                count_width = count_width.max(3);
+            }
+            let width = 80.max(16 + 6 + count_width);
+            let mut bar_width = match max_change + 4 > bin_width {
+                true => max_change,
+                false => bin_width - 4,
+            };
+            let mut name_width = max_len;
+            if name_width + count_width + 6 + bar_width > width {
+                let share = (width * 3 / 8).saturating_sub(count_width + 6);
+                if bar_width > share {
+                    bar_width = share.max(6);
+                }
+                if name_width > width - count_width - 6 - bar_width {
+                    name_width = width - count_width - 6 - bar_width;
+                } else {
+                    bar_width = width - count_width - 6 - name_width;
+                }
+            }
+            let (mut insertions, mut deletions) = (0, 0);
+            for (diff, counts) in diffs.iter().zip(&counts) {
+                if let Some((added, deleted)) = counts {
+                    insertions += added;
+                    deletions += deleted;
+                }
+                if options.format == Format::Shortstat {
+                    continue;
+                }
+                // Names too long for their column keep their end, from a
+                // slash on where there is one.
+                let mut path = diff.display_path();
+                let length = path.chars().count();
+                if length > name_width {
+                    let keep = name_width.saturating_sub(3);
+                    let tail: String = path.chars().skip(length - keep).collect();
+                    let tail = match tail.find('/') {
+                        Some(slash) => tail[slash..].to_string(),
+                        None => tail,
+                    };
+                    path = format!("...{tail}");
+                }
+                match counts {
+                    Some((added, deleted)) => {
+                        let total = added + deleted;
+                        let (mut plus, mut minus) = (*added, *deleted);
+                        if bar_width <= max_change {
+                            let scale = |n: usize| match n {
+                                0 => 0,
+                                n => 1 + n * (bar_width - 1) / max_change,
+                            };
+                            let mut width = scale(total);
+                            if width < 2 && plus > 0 && minus > 0 {
+                                width = 2;
+                            }
+                            if plus < minus {
+                                plus = scale(plus);
+                                minus = width - plus;
+                            } else {
+                                minus = scale(minus);
+                                plus = width - minus;
+                            }
+                        }
+                        let bar = format!(" {}{}", "+".repeat(plus), "-".repeat(minus));
+                        writeln!(
+                            stdout,
+                            " {path:<name_width$} | {total:>count_width$}{}",
+                            bar.trim_end()
+                        )?;
+                    }
+                    None => writeln!(
+                        stdout,
+                        " {path:<name_width$} | {:>count_width$} {} -> {} bytes",
+                        "Bin",
+                        diff.old_data()?.len(),
+                        diff.new_data()?.len()
+                    )?,
+                }
+            }
+            if !diffs.is_empty() {
+                let plural = |n: usize, one: &str, many: &str| {
+                    format!("{n} {}", if n == 1 { one } else { many })
+                };
+                let mut summary =
+                    format!(" {}", plural(diffs.len(), "file changed", "files changed"));
+                if insertions > 0 || deletions == 0 {
+                    summary.push_str(&format!(
+                        ", {}",
+                        plural(insertions, "insertion(+)", "insertions(+)")
+                    ));
+                }
+                if deletions > 0 || insertions == 0 {
+                    summary.push_str(&format!(
+                        ", {}",
+                        plural(deletions, "deletion(-)", "deletions(-)")
+                    ));
+                }
+                writeln!(stdout, "{summary}")?;
+            }
+        }
+    }
+    Ok(())
+}
+/// Writes the `--summary` lines: created and deleted files, renames,
+/// copies and mode changes.
+pub fn write_summary(stdout: &mut impl std::io::Write, diffs: &[FileDiff]) -> Result<(), GitError> {
+    for diff in diffs {
+        let (old_mode, new_mode) = (diff.old_mode.as_deref(), diff.new_mode.as_deref());
+        match diff.status() {
+            'A' => writeln!(
+                stdout,
+                " create mode {} {}",
+                new_mode.unwrap_or_default(),
+                diff.display_path()
+            )?,
+            'D' => writeln!(
+                stdout,
+                " delete mode {} {}",
+                old_mode.unwrap_or_default(),
+                diff.display_path()
+            )?,
+            status @ ('R' | 'C') => writeln!(
+                stdout,
+                " {} {} ({}%)",
+                if status == 'R' { "rename" } else { "copy" },
+                diff.display_path(),
+                diff.score.unwrap_or_default()
+            )?,
+            _ => {}
+        }
+        if let (Some(old), Some(new)) = (old_mode, new_mode) {
+            if old != new && diff.status() != 'T' {
+                writeln!(
+                    stdout,
+                    " mode change {old} => {new} {}",
+                    diff.display_path()
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+/// Prints the `--stat --summary` of the changes from `old` to `new`,
+/// with renames detected, as after a merge. `short` prints only the
+/// totals line of the stat.
+pub fn print_stat(old: &Snapshot, new: &Snapshot, short: bool) -> Result<(), GitError> {
+    let options = DiffOptions {
+        renames: Some(DEFAULT_SIMILARITY),
+        format: if short {
+            Format::Shortstat
+        } else {
+            Format::Stat
+        },
+        ..DiffOptions::default()
+    };
+    let diffs = detect_renames(compare(old, new, false)?, old, &options)?;
+    let mut stdout = std::io::stdout().lock();
+    write_diffs(&mut stdout, &diffs, &options)?;
+    write_summary(&mut stdout, &diffs)
+}
+pub fn tree_snapshot(hex_string: &str, recursive: bool) -> Result<Snapshot, GitError> {
+    let mut snapshot = Snapshot::new();
+    if recursive {
+        for entry in Index::from_tree(hex_string)?.entries() {
+            snapshot.insert(entry.path().clone(), (entry.mode(), entry.hash().clone()));
+        }
+    } else {
+        let tree = GitObject::from_hex_string(hex_string)?;
+        for entry in tree.tree_entries()? {
+            snapshot.insert(
+                entry.filename().clone(),
+                (entry.mode().clone(), entry.hash().clone()),
+            );
+        }
+    }
+    Ok(snapshot)
+}
+pub fn limit_snapshot(snapshot: Snapshot, pathspecs: &[&String]) -> Snapshot {
+    if pathspecs.is_empty() {
+        return snapshot;
+    }
+    snapshot
+        .into_iter()
+        .filter(|(path, _)| {
+            pathspecs
+                .iter()
+                .any(|spec| is_under(path, spec.trim_end_matches('/')))
+        })
+        .collect()
+}
+pub fn index_snapshot(index: &Index) -> Snapshot {
+    index
+        .entries()
+        .iter()
+        .filter(|e| e.stage() == 0)
+        .map(|e| (e.path().clone(), (e.mode(), e.hash().clone())))
+        .collect()
+}
+pub fn worktree_snapshot<'a>(
+    paths: impl IntoIterator<Item = &'a String>,
+    index: &Index,
+) -> Result<Snapshot, GitError> {
+    let mut snapshot = Snapshot::new();
+    for path in paths {
+        let Ok(meta) = fs::symlink_metadata(path) else {
+            continue;
+        };
+        let Some(mode) = file_mode(&meta) else {
+            continue;
+        };
+        if meta.is_dir() {
+            let gitlink = index.get(path).filter(|e| e.mode() == "160000");
+            if let Some(head) = submodule::head(path)? {
+                snapshot.insert(path.clone(), ("160000".into(), hex::decode(head)?));
+            } else if let Some(entry) = gitlink {
+                snapshot.insert(path.clone(), (entry.mode(), entry.hash().clone()));
+            }
+            continue;
+        }
+        let hash = match index.get(path) {
+            Some(entry) if entry.matches_stat(&meta) => entry.hash().clone(),
+            _ => {
+                let data = FileDiff::read_data(path, &mode, &[], true)?;
+                GitObject::build(Kind::Blob, data)?.hash().clone()
+            }
+        };
+        snapshot.insert(path.clone(), (mode, hash));
+    }
+    Ok(snapshot)
+}
+pub fn compare(old: &Snapshot, new: &Snapshot, worktree: bool) -> Result<Vec<FileDiff>, GitError> {
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut diffs = Vec::new();
+    for path in paths {
+        let (old_entry, new_entry) = (old.get(path), new.get(path));
+        if old_entry == new_entry {
+            continue;
+        }
+        diffs.push(FileDiff::load(path, old_entry, new_entry, worktree));
+    }
+    Ok(diffs)
+}
+fn similarity(old: &FileDiff, new: &FileDiff) -> Result<usize, GitError> {
+    let (old_data, new_data) = (old.old_data()?, new.new_data()?);
+    let max = old_data.len().max(new_data.len());
+    if max == 0 {
+        return Ok(100);
+    }
+    if is_binary(old_data) || is_binary(new_data) {
+        return Ok(0);
+    }
+    let diff = LineDiff::new(old_data, new_data, Algorithm::Myers);
+    Ok(diff.common_bytes() * 100 / max)
+}
+/// Whether files of sizes `a` and `b` can be `threshold` percent similar,
+/// so that their contents are worth reading.
+fn sizes_allow(a: usize, b: usize, threshold: usize) -> bool {
+    a.min(b) * 100 >= a.max(b) * threshold
+}
+fn pair(source: &FileDiff, target: &FileDiff, score: usize, copied: bool) -> FileDiff {
+    FileDiff {
+        old_path: source.old_path.clone(),
+        old_mode: source.old_mode.clone(),
+        old_hash: source.old_hash.clone(),
+        old_data: source.old_data.clone(),
+        score: Some(score),
+        copied,
+        ..target.clone()
+    }
+}
+/// Pairs deleted and added files into renames, and with `copies` added
+/// files with the files they were copied from. Exact matches are always
+/// found; scoring contents is skipped when there are more candidates
+/// than the rename limit allows, which is recorded in `options` for
+/// [`DiffOptions::warn_rename_limit`].
+pub fn detect_renames(
+    diffs: Vec<FileDiff>,
+    old: &Snapshot,
+    options: &DiffOptions,
+) -> Result<Vec<FileDiff>, GitError> {
+    let Some(rename_threshold) = options.renames else {
+        return Ok(diffs);
+    };
+    let changed: BTreeSet<String> = diffs.iter().filter_map(|d| d.old_path.clone()).collect();
+    let (mut added, mut rest): (Vec<FileDiff>, Vec<FileDiff>) =
+        diffs.into_iter().partition(|d| d.status() == 'A');
+    let mut deleted: Vec<Option<FileDiff>> = Vec::new();
+    rest.retain(|d| {
+        if d.status() == 'D' {
+            deleted.push(Some(d.clone()));
+            false
+        } else {
+            true
+        }
+    });
+    let deleted_count = deleted.len();
+    let mut results = Vec::new();
+    let basename = |path: &Option<String>| {
+        path.as_deref()
+            .and_then(|p| p.rsplit('/').next())
+            .map(String::from)
+    };
+    added.retain(|target| {
+        let candidates: Vec<usize> = (0..deleted.len())
+            .filter(|&i| {
+                deleted[i]
+                    .as_ref()
+                    .is_some_and(|d| d.old_hash == target.new_hash)
+            })
+            .collect();
+        let best = candidates
+            .iter()
+            .find(|&&i| {
+                basename(&deleted[i].as_ref().unwrap().old_path) == basename(&target.new_path)
+            })
+            .or(candidates.first());
+        match best {
+            Some(&i) => {
+                let source = deleted[i].take().unwrap();
+                results.push(pair(&source, target, 100, false));
+                false
+            }
+            None => true,
+        }
+    });
+    let mut unmodified: Vec<FileDiff> = match options.copies {
+        Some(_) if options.find_copies_harder => old
+            .iter()
+            .filter(|(path, _)| !changed.contains(*path))
+            .map(|(path, entry)| FileDiff::load(path, Some(entry), None, false))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut copies = Vec::new();
+    if options.copies.is_some() {
+        added.retain(|target| {
+            let source = rest
+                .iter()
+                .chain(&results)
+                .chain(&unmodified)
+                .find(|source| source.old_hash == target.new_hash);
+            match source {
+                Some(source) => {
+                    copies.push(pair(source, target, 100, true));
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+    let limited_sources = deleted_count + rest.len();
+    let sources = match options.copies {
+        Some(_) => limited_sources + unmodified.len(),
+        None => deleted.iter().flatten().count(),
+    };
+    let targets = added.len();
+    let limit = options.rename_limit.saturating_mul(options.rename_limit);
+    let mut inexact = targets > 0 && sources > 0;
+    if inexact && limit > 0 && targets.saturating_mul(sources) > limit {
+        let needed = options.needed_rename_limit.get().max(sources.max(targets));
+        options.needed_rename_limit.set(needed);
+        if !unmodified.is_empty() && targets * limited_sources <= limit {
+            options.degraded_copies.set(true);
+            unmodified.clear();
+        } else {
+            inexact = false;
+        }
+    }
+    let mut scores = Vec::new();
+    if inexact {
+        let source_sizes = deleted
+            .iter()
+            .map(|source| source.as_ref().map(FileDiff::old_size).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        for (t, target) in added.iter().enumerate() {
+            let target_size = target.new_size()?;
+            for (s, source) in deleted.iter().enumerate() {
+                let (Some(source), Some(source_size)) = (source, source_sizes[s]) else {
+                    continue;
+                };
+                if !sizes_allow(source_size, target_size, rename_threshold) {
+                    continue;
+                }
+                let score = similarity(source, target)?;
+                if score >= rename_threshold {
+                    scores.push((score, t, s));
+                }
+            }
+        }
+    }
+    scores.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    let mut used_targets = vec![false; added.len()];
+    for (score, t, s) in scores {
+        if used_targets[t] || deleted[s].is_none() {
+            continue;
+        }
+        let source = deleted[s].take().unwrap();
+        results.push(pair(&source, &added[t], score, false));
+        used_targets[t] = true;
+    }
+    let mut remaining: Vec<FileDiff> = added
+        .into_iter()
+        .zip(used_targets)
+        .filter(|(_, used)| !used)
+        .map(|(d, _)| d)
+        .collect();
+    if let Some(copy_threshold) = options.copies.filter(|_| inexact) {
+        let sources: Vec<&FileDiff> = rest
+            .iter()
+            .chain(results.iter().filter(|d| !d.copied))
+            .chain(&unmodified)
+            .chain(
+                deleted
+                    .iter()
+                    .flatten()
+                    .filter(|_| options.find_copies_harder),
+            )
+            .collect();
+        let source_sizes = sources
+            .iter()
+            .map(|source| source.old_size())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut unmatched = Vec::new();
+        for target in remaining {
+            let target_size = target.new_size()?;
+            let mut best = None;
+            for (&source, &source_size) in sources.iter().zip(&source_sizes) {
+                if !sizes_allow(source_size, target_size, copy_threshold) {
+                    continue;
+                }
+                let score = similarity(source, &target)?;
+                if score >= copy_threshold && best.map_or(true, |(best, _)| score >= best) {
+                    best = Some((score, source));
+                }
+            }
+            match best {
+                Some((score, source)) => copies.push(pair(source, &target, score, true)),
+                None => unmatched.push(target),
+            }
+        }
+        remaining = unmatched;
+    }
+    results.extend(copies);
+    results.extend(rest);
+    results.extend(remaining);
+    results.extend(deleted.into_iter().flatten());
+    results.sort_by(|a, b| {
+        let key = |d: &FileDiff| d.new_path.clone().or(d.old_path.clone());
+        key(a).cmp(&key(b))
+    });
+    Ok(results)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    /// Old and new files that each algorithm diffs differently.
+    const OLD: &str = "x\nc\nd\nc\ny\ne\nx\n";
+    const NEW: &str = "x\nc\ny\nd\nc\nd\nc\nc\nx\n";
+    /// The lines the edit script keeps, as git's output shows them.
+    fn matches(old: &str, new: &str, algorithm: Algorithm) -> Vec<(usize, usize)> {
+        let diff = LineDiff::new(old.as_bytes(), new.as_bytes(), algorithm);
+        diff.ops
+            .iter()
+            .filter_map(|op| match *op {
+                Op::Equal(i, j) => Some((i, j)),
+                _ => None,
+            })
+            .collect()
+    }
+    fn unified(old: &str, new: &str, algorithm: Algorithm) -> String {
+        let diff = LineDiff::new(old.as_bytes(), new.as_bytes(), algorithm);
+        String::from_utf8(diff.unified(3)).unwrap()
+    }
+    fn lines(count: usize, line: impl FnMut(usize) -> String) -> String {
+        (0..count).map(line).collect()
+    }
+    /// Checks a large diff's counts and that its matches pair increasing
+    /// lines that are equal.
+    fn check_large(old: &str, new: &str, algorithm: Algorithm, changes: (usize, usize)) {
+        let diff = LineDiff::new(old.as_bytes(), new.as_bytes(), algorithm);
+        assert_eq!((diff.insertions(), diff.deletions()), changes);
+        let (old_lines, new_lines) = (split_lines(old.as_bytes()), split_lines(new.as_bytes()));
+        let matches = matching_lines(&old_lines, &new_lines, algorithm);
+        assert!(matches
+            .windows(2)
+            .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        assert!(matches.iter().all(|&(i, j)| old_lines[i] == new_lines[j]));
+    }
+    #[test]
+    fn myers_finds_a_shortest_edit_script() {
+        assert_eq!(
+            matches(OLD, NEW, Algorithm::Myers),
+            [(0, 0), (1, 1), (2, 3), (3, 4), (6, 8)]
+        );
+        assert_eq!(
+            unified(OLD, NEW, Algorithm::Myers),
+            "@@ -1,7 +1,9 @@\n x\n c\n+y\n d\n c\n-y\n-e\n+d\n+c\n+c\n x\n"
+        );
+        assert_eq!(
+            unified(
+                "a\nb\nc\na\nb\nb\na\n",
+                "c\nb\na\nb\na\nc\n",
+                Algorithm::Myers
+            ),
+            "@@ -1,7 +1,6 @@\n-a\n-b\n c\n-a\n b\n+a\n b\n a\n+c\n"
+        );
+    }
+    #[test]
+    fn minimal_searches_past_the_cost_cutoff() {
+        let random = |mut seed: u64| {
+            lines(1500, move |_| {
+                seed = (seed * 1103515245 + 12345) % (1 << 31);
+                format!("{}\n", (seed >> 16) % 4)
+            })
+        };
+        let (old, new) = (random(1), random(101));
+        check_large(&old, &new, Algorithm::Myers, (536, 536));
+        check_large(&old, &new, Algorithm::Minimal, (527, 527));
+    }
+    #[test]
+    fn patience_anchors_on_unique_lines() {
+        assert_eq!(
+            matches(OLD, NEW, Algorithm::Patience),
+            [(0, 0), (1, 1), (4, 2), (6, 8)]
+        );
+        assert_eq!(
+            unified(OLD, NEW, Algorithm::Patience),
+            "@@ -1,7 +1,9 @@\n x\n c\n-d\n-c\n y\n-e\n+d\n+c\n+d\n+c\n+c\n x\n"
+        );
+    }
+    #[test]
+    fn histogram_anchors_on_the_rarest_lines() {
+        assert_eq!(
+            matches(OLD, NEW, Algorithm::Histogram),
+            [(0, 0), (1, 1), (2, 5), (3, 6), (6, 8)]
+        );
+        assert_eq!(
+            unified(OLD, NEW, Algorithm::Histogram),
+            "@@ -1,7 +1,9 @@\n x\n c\n+y\n+d\n+c\n d\n c\n-y\n-e\n+c\n x\n"
+        );
+        assert_eq!(
+            unified(
+                "a\nb\nc\na\nb\nb\na\n",
+                "c\nb\na\nb\na\nc\n",
+                Algorithm::Histogram
+            ),
+            "@@ -1,7 +1,6 @@\n-a\n-b\n c\n-a\n-b\n b\n a\n+b\n+a\n+c\n"
+        );
+    }
+    #[test]
+    fn changes_slide_to_the_split_with_the_best_indentation() {
+        let old = "{\n    x\na\na\n";
+        let new = "{\n    x\n}\nb\n{\n    x\na\na\n";
+        for algorithm in [Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram] {
+            assert_eq!(
+                unified(old, new, algorithm),
+                "@@ -1,3 +1,7 @@\n+{\n+    x\n+}\n+b\n {\n     x\n a\n"
+            );
+        }
+    }
+    #[test]
+    fn hunks_carry_context_and_the_enclosing_function() {
+        let old = lines(20, |n| format!("    line {n}\n"));
+        let old = format!("fn main() {{\n{old}}}\n");
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 17\n", "line seventeen\n");
+        assert_eq!(
+            unified(&old, &new, Algorithm::Myers),
+            "@@ -1,7 +1,7 @@\n fn main() {\n     line 0\n     line 1\n-    line 2\n+    line two\n     line 3\n     line 4\n     line 5\n\
+             @@ -16,7 +16,7 @@ fn main() {\n     line 14\n     line 15\n     line 16\n-    line 17\n+    line seventeen\n     line 18\n     line 19\n }\n"
+        );
+        assert_eq!(
+            unified("a\nb", "a\nc", Algorithm::Myers),
+            "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n"
+        );
+    }
+    #[test]
+    fn stat_scales_the_bars_to_the_largest_change() {
+        let diff = |path: &str, old: String, new: String| FileDiff {
+            old_path: Some(path.to_string()),
+            new_path: Some(path.to_string()),
+            old_mode: Some("100644".to_string()),
+            new_mode: Some("100644".to_string()),
+            old_data: old.into_bytes().into(),
+            new_data: new.into_bytes().into(),
+            ..FileDiff::default()
+        };
+        let diffs = [
+            diff(
+                "dir/longer-name.txt",
+                lines(200, |n| format!("{}\n", n + 1)),
+                lines(100, |n| format!("{}\n", 2 * n + 1)),
+            ),
+            diff(
+                "short",
+                "a\nb\nc\na\nb\nb\na\n".to_string(),
+                "c\nb\na\nb\na\nc\n".to_string(),
+            ),
+        ];
+        let stat = |format, algorithm| {
+            let options = DiffOptions {
+                format,
+                algorithm,
+                ..DiffOptions::default()
+            };
+            let mut out = Vec::new();
+            write_diffs(&mut out, &diffs, &options).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let bar = "-".repeat(52);
+        assert_eq!(
+            stat(Format::Stat, Algorithm::Myers),
+            format!(
+                " dir/longer-name.txt | 100 {bar}\n short               |   5 ++-\n 2 files changed, 2 insertions(+), 103 deletions(-)\n"
+            )
+        );
+        assert_eq!(
+            stat(Format::Stat, Algorithm::Histogram),
+            format!(
+                " dir/longer-name.txt | 100 {bar}\n short               |   7 ++--\n 2 files changed, 3 insertions(+), 104 deletions(-)\n"
+            )
+        );
+        assert_eq!(
+            stat(Format::Shortstat, Algorithm::Myers),
+            " 2 files changed, 2 insertions(+), 103 deletions(-)\n"
+        );
+    }
+    #[test]
+    fn stat_aligns_binary_files_and_shortens_long_names() {
+        let diff = |path: &str, old: &[u8], new: &[u8]| FileDiff {
+            old_path: Some(path.to_string()),
+            new_path: Some(path.to_string()),
+            old_mode: Some("100644".to_string()),
+            new_mode: Some("100644".to_string()),
+            old_data: old.to_vec().into(),
+            new_data: new.to_vec().into(),
+            ..FileDiff::default()
+        };
+        let diffs = [
+            diff("bin", b"a\0b", b"a\0cd"),
+            diff(
+                "src/generated/bindings/very-long-module-name-for-the-platform-abstraction-layer",
+                b"x\n",
+                b"x\ny\nz\n",
+            ),
+        ];
+        let options = DiffOptions {
+            format: Format::Stat,
+            ..DiffOptions::default()
+        };
+        let mut out = Vec::new();
+        write_diffs(&mut out, &diffs, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            " bin                                                         | Bin 3 -> 4 bytes\n \
+             ...very-long-module-name-for-the-platform-abstraction-layer |   2 ++\n \
+             2 files changed, 2 insertions(+)\n"
+        );
+    }
+    #[test]
+    fn myers_gives_up_on_being_minimal_for_unrelated_files() {
+        let old = lines(3000, |n| format!("old {n}\n"));
+        let new = lines(3000, |n| format!("new {n}\n"));
+        check_large(&old, &new, Algorithm::Myers, (3000, 3000));
+        let old = lines(3000, |n| format!("{}\n", n % 7));
+        let new = lines(3000, |n| format!("{}\n", n % 11));
+        let diff = LineDiff::new(old.as_bytes(), new.as_bytes(), Algorithm::Myers);
+        assert_eq!(diff.insertions(), diff.deletions());
+    }
+    #[test]
+    fn patience_falls_back_to_myers_without_unique_lines() {
+        let old = "x\n".repeat(3000);
+        let new = lines(3000, |n| if n % 3 == 0 { "y\n" } else { "x\n" }.to_string());
+        check_large(&old, &new, Algorithm::Patience, (1000, 1000));
+    }
+    #[test]
+    fn histogram_handles_many_separate_changes() {
+        let old = lines(3000, |n| match n % 2 {
+            0 => format!("keep {n}\n"),
+            _ => format!("old {n}\n"),
+        });
+        let new = lines(3000, |n| match n % 2 {
+            0 => format!("keep {n}\n"),
+            _ => format!("new {n}\n"),
+        });
+        check_large(&old, &new, Algorithm::Histogram, (1500, 1500));
+        let old = "x\n".repeat(3000);
+        let new = lines(3000, |n| if n % 3 == 0 { "y\n" } else { "x\n" }.to_string());
+        check_large(&old, &new, Algorithm::Histogram, (1000, 1000));
+    }
+}
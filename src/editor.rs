@@ -0,0 +1,185 @@
+use crate::config::Config;
+use crate::error::GitError;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+const EDIT_MSG: &str = ".git/COMMIT_EDITMSG";
+const MESSAGE_HELP: &str = "
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be ignored, and an empty message aborts the commit.
+";
+const KEPT_HELP: &str = "
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be kept; you may remove them yourself if you want to.
+# An empty message aborts the commit.
+";
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+const SCISSORS_HELP: &str = "# Do not modify or remove the line above.
+# Everything below it will be ignored.
+";
+/// How a commit message is cleaned up before it is recorded, as chosen by
+/// `--cleanup` or `commit.cleanup`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cleanup {
+    Verbatim,
+    Whitespace,
+    Strip,
+    Scissors,
+    Default,
+}
+impl Cleanup {
+    pub fn parse(name: &str) -> Result<Self, GitError> {
+        match name {
+            "verbatim" => Ok(Self::Verbatim),
+            "whitespace" => Ok(Self::Whitespace),
+            "strip" => Ok(Self::Strip),
+            "scissors" => Ok(Self::Scissors),
+            "default" => Ok(Self::Default),
+            _ => Err(GitError::any(format!("Invalid cleanup mode {name}"))),
+        }
+    }
+    /// Cleans up `message`; `edited` says whether it came from the editor,
+    /// where the default mode also strips comments and scissors cut off
+    /// everything below their line.
+    pub fn apply(self, message: &str, edited: bool) -> String {
+        match self {
+            Self::Verbatim => message.to_string(),
+            Self::Strip => cleanup(message),
+            Self::Default if edited => cleanup(message),
+            Self::Scissors if edited => {
+                let cut = message
+                    .split_inclusive('\n')
+                    .take_while(|line| line.trim_end() != SCISSORS)
+                    .collect::<String>();
+                stripspace(&cut, false)
+            }
+            _ => stripspace(message, false),
+        }
+    }
+    fn help(self) -> String {
+        match self {
+            Self::Strip | Self::Default => MESSAGE_HELP.to_string(),
+            Self::Scissors => format!("\n{SCISSORS}\n{SCISSORS_HELP}"),
+            Self::Verbatim | Self::Whitespace => KEPT_HELP.to_string(),
+        }
+    }
+}
+/// The editor for commit messages: `GIT_EDITOR`, `core.editor`, `VISUAL`,
+/// `EDITOR`, and finally `vi`.
+pub fn editor() -> Result<String, GitError> {
+    let config = Config::load()?;
+    Ok(std::env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| config.get("core.editor").map(str::to_string))
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .unwrap_or_else(|| "vi".to_string()))
+}
+/// The editor for rebase todo lists: `GIT_SEQUENCE_EDITOR`,
+/// `sequence.editor`, or else the usual [`editor`].
+pub fn sequence_editor() -> Result<String, GitError> {
+    match std::env::var("GIT_SEQUENCE_EDITOR") {
+        Ok(editor) => Ok(editor),
+        Err(_) => match Config::load()?.get("sequence.editor") {
+            Some(editor) => Ok(editor.to_string()),
+            None => editor(),
+        },
+    }
+}
+/// Runs `editor` on `path` through the shell, so it may carry arguments.
+/// The editor `:` leaves the file as it is.
+pub fn edit(editor: &str, path: impl AsRef<Path>) -> Result<(), GitError> {
+    if editor == ":" {
+        return Ok(());
+    }
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(editor)
+        .arg(path.as_ref())
+        .status()?;
+    if !status.success() {
+        return Err(GitError::any(format!(
+            "there was a problem with the editor '{editor}'"
+        )));
+    }
+    Ok(())
+}
+/// Strips `#` comment lines, trailing whitespace, and leading, trailing and
+/// repeated blank lines from an edited message.
+pub fn cleanup(message: &str) -> String {
+    stripspace(message, true)
+}
+/// Strips trailing whitespace and leading, trailing and repeated blank
+/// lines, and `#` comment lines too with `comments`.
+fn stripspace(message: &str, comments: bool) -> String {
+    let mut cleaned = String::new();
+    let mut blank = false;
+    for line in message
+        .lines()
+        .filter(|line| !comments || !line.starts_with('#'))
+    {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = !cleaned.is_empty();
+            continue;
+        }
+        if blank {
+            cleaned.push('\n');
+            blank = false;
+        }
+        cleaned.push_str(line);
+        cleaned.push('\n');
+    }
+    cleaned
+}
+/// Lets the user edit `message` in `.git/COMMIT_EDITMSG` the way
+/// `git commit` does, returning the cleaned-up result.
+pub fn edit_message(message: &str) -> Result<String, GitError> {
+    let message = edit_message_with(message, Cleanup::Default)?;
+    if message.is_empty() {
+        return Err(GitError::any(
+            "Aborting commit due to empty commit message.",
+        ));
+    }
+    Ok(message)
+}
+/// Like [`edit_message`], cleaning up in `mode` and leaving it to the
+/// caller to reject an empty message.
+pub fn edit_message_with(message: &str, mode: Cleanup) -> Result<String, GitError> {
+    fs::write(EDIT_MSG, format!("{}\n{}", message.trim_end(), mode.help()))?;
+    edit(&editor()?, EDIT_MSG)?;
+    Ok(mode.apply(&fs::read_to_string(EDIT_MSG)?, true))
+}
+/// Records `message` as the last commit message, as `git commit` always
+/// does.
+pub fn save_message(message: &str) -> Result<(), GitError> {
+    Ok(fs::write(EDIT_MSG, message)?)
+}
+/// Whether the last paragraph of `message` is made of `Key: value`
+/// trailers.
+pub fn ends_with_trailers(message: &str) -> bool {
+    let last = message.trim_end().rsplit("\n\n").next().unwrap_or_default();
+    message.contains("\n\n")
+        && last.lines().all(|line| {
+            line.split_once(": ").is_some_and(|(key, _)| {
+                !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '-')
+            })
+        })
+}
+/// Appends `trailers` to `message`, joining its trailer block when it ends
+/// with one. A trailer identical to the last one is not repeated.
+pub fn add_trailers(message: &str, trailers: &[String]) -> String {
+    let mut message = message.trim_end().to_string();
+    for trailer in trailers {
+        if message.lines().last() == Some(trailer.as_str()) {
+            continue;
+        }
+        message.push_str(match ends_with_trailers(&message) {
+            true => "\n",
+            false => "\n\n",
+        });
+        message.push_str(trailer);
+    }
+    message + "\n"
+}
@@ -28,6 +28,11 @@ pub enum GitError {
 
     #[error("{message}")]
     Generic { message: String },
+
+    /// The command already reported why it stopped (conflicts, a failed
+    /// check, `--quiet` misses) and only needs to exit with `code`.
+    #[error("exit status {code}")]
+    Exit { code: i32 },
 }
 
 impl GitError {
@@ -37,6 +42,10 @@ impl GitError {
         }
     }
 
+    pub fn exit(code: i32) -> Self {
+        Self::Exit { code }
+    }
+
     pub fn invalid_object_format(message: impl Into<String>) -> Self {
         Self::InvalidObjectFormat {
             message: message.into(),
@@ -0,0 +1,144 @@
+use crate::commit::Signature;
+use crate::refs;
+use std::borrow::Cow;
+use std::iter::Peekable;
+use std::str::Split;
+fn is_hash(value: &str) -> bool {
+    value.len() == 40 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+/// The header lines of a commit or tag, read as text; the message after
+/// them may be in any encoding.
+fn headers(data: &[u8]) -> Result<Cow<'_, str>, String> {
+    let end = match data.windows(2).position(|pair| pair == b"\n\n") {
+        Some(end) => end,
+        None if data.ends_with(b"\n") => data.len() - 1,
+        None => return Err("unterminatedHeader: unterminated header".into()),
+    };
+    if let Some(nul) = data[..end].iter().position(|&b| b == 0) {
+        return Err(format!(
+            "nulInHeader: unterminated header: NUL at offset {nul}"
+        ));
+    }
+    Ok(String::from_utf8_lossy(&data[..end]))
+}
+/// The value of the next header line, which must be `key`.
+fn header<'a>(
+    lines: &mut Peekable<Split<'a, char>>,
+    key: &str,
+    entry: &str,
+) -> Result<&'a str, String> {
+    lines
+        .next()
+        .and_then(|line| line.strip_prefix(key))
+        .and_then(|line| line.strip_prefix(' '))
+        .ok_or(format!("{entry}: invalid format - expected '{key}' line"))
+}
+fn check_ident(ident: &str) -> Result<(), String> {
+    match Signature::parse(ident) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("badDate: invalid author/committer line - bad date".into()),
+    }
+}
+/// Checks a commit the way `git fsck` does: a `tree` line, any `parent`
+/// lines, then `author` and `committer`.
+pub fn check_commit(data: &[u8]) -> Result<(), String> {
+    let headers = headers(data)?;
+    let mut lines = headers.split('\n').peekable();
+    if !is_hash(header(&mut lines, "tree", "missingTree")?) {
+        return Err("badTreeSha1: invalid 'tree' line format - bad sha1".into());
+    }
+    while let Some(line) = lines.next_if(|line| line.starts_with("parent ")) {
+        if !is_hash(&line["parent ".len()..]) {
+            return Err("badParentSha1: invalid 'parent' line format - bad sha1".into());
+        }
+    }
+    check_ident(header(&mut lines, "author", "missingAuthor")?)?;
+    if lines.peek().is_some_and(|line| line.starts_with("author ")) {
+        return Err("multipleAuthors: invalid format - multiple 'author' lines".into());
+    }
+    check_ident(header(&mut lines, "committer", "missingCommitter")?)
+}
+/// Checks a tag the way `git fsck` does: the `object`, `type`, `tag` and
+/// `tagger` headers in that order. With `strict`, as `git mktag` asks,
+/// nothing may follow them. Returns the tagged object and its claimed type.
+pub fn check_tag(data: &[u8], strict: bool) -> Result<(String, String), String> {
+    let headers = headers(data)?;
+    let mut lines = headers.split('\n').peekable();
+    let object = header(&mut lines, "object", "missingObject")?;
+    if !is_hash(object) {
+        return Err("badObjectSha1: invalid 'object' line format - bad sha1".into());
+    }
+    let kind = header(&mut lines, "type", "missingTypeEntry")?;
+    if !matches!(kind, "blob" | "tree" | "commit" | "tag") {
+        return Err("badType: invalid 'type' value".into());
+    }
+    let name = header(&mut lines, "tag", "missingTagEntry")?;
+    if refs::check_name(&format!("refs/tags/{name}")).is_err() {
+        return Err(format!("badTagName: invalid 'tag' name: {name}"));
+    }
+    check_ident(header(&mut lines, "tagger", "missingTaggerEntry")?)?;
+    if strict && lines.next().is_some() {
+        return Err("extraHeaderEntry: invalid format - extra header(s) after 'tagger'".into());
+    }
+    Ok((object.to_string(), kind.to_string()))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+    const IDENT: &str = "A <a@example.com> 1700000000 +0000";
+    #[test]
+    fn commits_need_a_tree_author_and_committer() {
+        let commit =
+            format!("tree {TREE}\nparent {TREE}\nauthor {IDENT}\ncommitter {IDENT}\n\nm\n");
+        assert_eq!(check_commit(commit.as_bytes()), Ok(()));
+        for (data, error) in [
+            (
+                format!("tree {TREE}\ncommitter {IDENT}\n\n"),
+                "missingAuthor",
+            ),
+            (
+                format!("tree {TREE}\nauthor {IDENT}\n\n"),
+                "missingCommitter",
+            ),
+            (
+                format!("author {IDENT}\ncommitter {IDENT}\n\n"),
+                "missingTree",
+            ),
+            (
+                format!("tree {TREE}\nparent x\nauthor {IDENT}\n\n"),
+                "badParentSha1",
+            ),
+            (
+                format!("tree {TREE}\nauthor nobody\ncommitter {IDENT}\n\n"),
+                "badDate",
+            ),
+            (
+                format!("tree {TREE}\nauthor {IDENT}\ncommitter {IDENT}"),
+                "unterminatedHeader",
+            ),
+            (format!("tree {TREE}\nauthor {IDENT}\0\n\n"), "nulInHeader"),
+        ] {
+            let result = check_commit(data.as_bytes());
+            assert!(
+                result.as_ref().is_err_and(|e| e.starts_with(error)),
+                "{data:?}: {result:?}"
+            );
+        }
+    }
+    #[test]
+    fn tags_need_a_tagger_and_strict_tags_nothing_after_it() {
+        let tag = format!("object {TREE}\ntype tree\ntag v1\ntagger {IDENT}\n");
+        let latin1 = [format!("{tag}\n").as_bytes(), b"caf\xe9\n"].concat();
+        assert_eq!(
+            check_tag(&latin1, true),
+            Ok((TREE.to_string(), "tree".to_string()))
+        );
+        let extra = format!("{tag}extra header\n\n");
+        assert!(check_tag(extra.as_bytes(), false).is_ok());
+        assert!(check_tag(extra.as_bytes(), true).is_err_and(|e| e.starts_with("extraHeaderEntry")));
+        let untagged = format!("object {TREE}\ntype tree\ntag v1\n\n");
+        assert!(check_tag(untagged.as_bytes(), false)
+            .is_err_and(|e| e.starts_with("missingTaggerEntry")));
+    }
+}
@@ -0,0 +1,383 @@
+use crate::config::Config;
+use crate::error::GitError;
+use crate::submodule;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+#[derive(Debug)]
+struct Pattern {
+    pattern: String,
+    base: String,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+impl Pattern {
+    fn parse(line: &str, base: &str) -> Option<Self> {
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let mut line = line.to_string();
+        while line.ends_with(' ') && !line.ends_with("\\ ") {
+            line.pop();
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest.to_string()),
+            None => (
+                false,
+                line.strip_prefix('\\').map(String::from).unwrap_or(line),
+            ),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line.as_str()),
+        };
+        if line.is_empty() {
+            return None;
+        }
+        let anchored = line.contains('/');
+        Some(Self {
+            pattern: line.trim_start_matches('/').to_string(),
+            base: base.to_string(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative = if self.base.is_empty() {
+            path
+        } else {
+            match path
+                .strip_prefix(self.base.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(rest) => rest,
+                None => return false,
+            }
+        };
+        if self.anchored {
+            wildmatch(self.pattern.as_bytes(), relative.as_bytes())
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            wildmatch(self.pattern.as_bytes(), name.as_bytes())
+        }
+    }
+}
+pub fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    while p < pattern.len() {
+        match pattern[p] {
+            b'*' => {
+                let at_segment_start = p == 0 || pattern[p - 1] == b'/';
+                let mut q = p;
+                while q < pattern.len() && pattern[q] == b'*' {
+                    q += 1;
+                }
+                let rest = &pattern[q..];
+                if q - p >= 2 && at_segment_start {
+                    if rest.is_empty() {
+                        return true;
+                    }
+                    if rest[0] == b'/' {
+                        let rest = &rest[1..];
+                        if wildmatch(rest, &text[t..]) {
+                            return true;
+                        }
+                        return (t..text.len())
+                            .any(|i| text[i] == b'/' && wildmatch(rest, &text[i + 1..]));
+                    }
+                }
+                for i in t..=text.len() {
+                    if wildmatch(rest, &text[i..]) {
+                        return true;
+                    }
+                    if i < text.len() && text[i] == b'/' {
+                        break;
+                    }
+                }
+                return false;
+            }
+            b'?' => {
+                if t >= text.len() || text[t] == b'/' {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+            b'[' => {
+                if t >= text.len() || text[t] == b'/' {
+                    return false;
+                }
+                let c = text[t];
+                let mut i = p + 1;
+                let negated = matches!(pattern.get(i), Some(b'!' | b'^'));
+                if negated {
+                    i += 1;
+                }
+                let mut matched = false;
+                let mut first = true;
+                loop {
+                    let Some(&pc) = pattern.get(i) else {
+                        return false;
+                    };
+                    if pc == b']' && !first {
+                        break;
+                    }
+                    first = false;
+                    let lo = if pc == b'\\' {
+                        i += 1;
+                        match pattern.get(i) {
+                            Some(&lo) => lo,
+                            None => return false,
+                        }
+                    } else {
+                        pc
+                    };
+                    match (pattern.get(i + 1), pattern.get(i + 2)) {
+                        (Some(b'-'), Some(&hi)) if hi != b']' => {
+                            matched |= lo <= c && c <= hi;
+                            i += 3;
+                        }
+                        _ => {
+                            matched |= lo == c;
+                            i += 1;
+                        }
+                    }
+                }
+                if matched == negated {
+                    return false;
+                }
+                p = i + 1;
+                t += 1;
+            }
+            c => {
+                let c = if c == b'\\' && p + 1 < pattern.len() {
+                    p += 1;
+                    pattern[p]
+                } else {
+                    c
+                };
+                if t >= text.len() || text[t] != c {
+                    return false;
+                }
+                p += 1;
+                t += 1;
+            }
+        }
+    }
+    t == text.len()
+}
+pub struct Ignore {
+    base: Vec<Pattern>,
+    per_dir: HashMap<String, Vec<Pattern>>,
+}
+impl Ignore {
+    fn read_patterns(path: impl AsRef<Path>, base: &str) -> Result<Vec<Pattern>, GitError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let text = fs::read_to_string(path)?;
+        Ok(text
+            .lines()
+            .filter_map(|line| Pattern::parse(line, base))
+            .collect())
+    }
+    pub fn load() -> Result<Self, GitError> {
+        let config = Config::load()?;
+        let excludes_file = config
+            .get_path("core.excludesFile")
+            .or_else(|| Config::xdg_config_dir().map(|dir| dir.join("ignore")));
+        let mut base = Vec::new();
+        if let Some(path) = excludes_file {
+            base.extend(Self::read_patterns(path, "")?);
+        }
+        base.extend(Self::read_patterns(".git/info/exclude", "")?);
+        Ok(Self {
+            base,
+            per_dir: HashMap::new(),
+        })
+    }
+    fn dir_patterns(&mut self, dir: &str) -> Result<&Vec<Pattern>, GitError> {
+        if !self.per_dir.contains_key(dir) {
+            let path = if dir.is_empty() {
+                ".gitignore".to_string()
+            } else {
+                format!("{dir}/.gitignore")
+            };
+            let patterns = Self::read_patterns(path, dir)?;
+            self.per_dir.insert(dir.to_string(), patterns);
+        }
+        Ok(&self.per_dir[dir])
+    }
+    fn matches(&mut self, path: &str, is_dir: bool) -> Result<Option<bool>, GitError> {
+        let mut dirs = Vec::new();
+        let mut dir = path;
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            dirs.push(parent.to_string());
+            dir = parent;
+        }
+        dirs.push(String::new());
+        for dir in dirs {
+            let found = self
+                .dir_patterns(&dir)?
+                .iter()
+                .rev()
+                .find(|p| p.matches(path, is_dir));
+            if let Some(pattern) = found {
+                return Ok(Some(!pattern.negated));
+            }
+        }
+        Ok(self
+            .base
+            .iter()
+            .rev()
+            .find(|p| p.matches(path, is_dir))
+            .map(|p| !p.negated))
+    }
+    pub fn is_ignored(&mut self, path: &str, is_dir: bool) -> Result<bool, GitError> {
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        if path.is_empty() || path == "." {
+            return Ok(false);
+        }
+        let mut end = 0;
+        while let Some(offset) = path[end..].find('/') {
+            end += offset;
+            if self.matches(&path[..end], true)? == Some(true) {
+                return Ok(true);
+            }
+            end += 1;
+        }
+        Ok(self.matches(path, is_dir)? == Some(true))
+    }
+    pub fn walk(&mut self, dir: &str) -> Result<Vec<String>, GitError> {
+        let mut files = Vec::new();
+        self.walk_into(
+            dir.trim_start_matches("./").trim_end_matches('/'),
+            &mut files,
+        )?;
+        files.sort();
+        Ok(files)
+    }
+    fn walk_into(&mut self, dir: &str, files: &mut Vec<String>) -> Result<(), GitError> {
+        let dir = if dir == "." { "" } else { dir };
+        let read_path = if dir.is_empty() { "." } else { dir };
+        for entry in fs::read_dir(read_path)? {
+            let entry = entry?;
+            let Ok(filename) = entry.file_name().into_string() else {
+                continue;
+            };
+            if filename == ".git" {
+                continue;
+            }
+            let path = if dir.is_empty() {
+                filename
+            } else {
+                format!("{dir}/{filename}")
+            };
+            let file_type = entry.file_type()?;
+            if self.is_ignored(&path, file_type.is_dir())? {
+                continue;
+            }
+            if file_type.is_dir() && !submodule::is_repo(&path) {
+                self.walk_into(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn ignore(lines: &[&str]) -> Ignore {
+        let patterns = lines
+            .iter()
+            .filter_map(|line| Pattern::parse(line, ""))
+            .collect();
+        Ignore {
+            base: Vec::new(),
+            per_dir: HashMap::from([(String::new(), patterns)]),
+        }
+    }
+    #[test]
+    fn wildmatch_stars_stay_within_a_segment() {
+        assert!(wildmatch(b"*.rs", b"main.rs"));
+        assert!(wildmatch(b"*", b""));
+        assert!(!wildmatch(b"*.rs", b"src/main.rs"));
+        assert!(wildmatch(b"src/*.rs", b"src/main.rs"));
+        assert!(!wildmatch(b"src/*.rs", b"src/commands/add.rs"));
+        assert!(wildmatch(b"a?c", b"abc"));
+        assert!(!wildmatch(b"a?c", b"a/c"));
+    }
+    #[test]
+    fn wildmatch_double_stars_cross_segments() {
+        assert!(wildmatch(b"**/tmp", b"tmp"));
+        assert!(wildmatch(b"**/tmp", b"a/b/tmp"));
+        assert!(wildmatch(b"tmp/**", b"tmp/a/b"));
+        assert!(wildmatch(b"a/**/b", b"a/b"));
+        assert!(wildmatch(b"a/**/b", b"a/x/y/b"));
+        assert!(!wildmatch(b"a/**/b", b"xa/b"));
+        // Only a whole segment of stars is special.
+        assert!(!wildmatch(b"a**/b", b"ax/y/b"));
+    }
+    #[test]
+    fn wildmatch_brackets_and_escapes() {
+        assert!(wildmatch(b"[abc].txt", b"b.txt"));
+        assert!(!wildmatch(b"[abc].txt", b"d.txt"));
+        assert!(wildmatch(b"[a-c]x", b"bx"));
+        assert!(wildmatch(b"[!a-c]x", b"dx"));
+        assert!(!wildmatch(b"[^a-c]x", b"bx"));
+        assert!(wildmatch(b"[]]", b"]"));
+        assert!(!wildmatch(b"[a]", b"/"));
+        assert!(wildmatch(b"\\*", b"*"));
+        assert!(!wildmatch(b"\\*", b"x"));
+    }
+    #[test]
+    fn patterns_without_a_slash_match_at_any_depth() {
+        let mut ignore = ignore(&["*.log"]);
+        assert!(ignore.is_ignored("a.log", false).unwrap());
+        assert!(ignore.is_ignored("sub/a.log", false).unwrap());
+        assert!(!ignore.is_ignored("a.txt", false).unwrap());
+    }
+    #[test]
+    fn later_negations_reinclude() {
+        let mut ignore = ignore(&["*.log", "!keep.log"]);
+        assert!(!ignore.is_ignored("keep.log", false).unwrap());
+        assert!(!ignore.is_ignored("sub/keep.log", false).unwrap());
+        assert!(ignore.is_ignored("other.log", false).unwrap());
+    }
+    #[test]
+    fn patterns_with_a_slash_are_anchored() {
+        let mut ignore = ignore(&["/build", "doc/*.txt"]);
+        assert!(ignore.is_ignored("build", false).unwrap());
+        assert!(!ignore.is_ignored("sub/build", false).unwrap());
+        assert!(ignore.is_ignored("doc/a.txt", false).unwrap());
+        assert!(!ignore.is_ignored("doc/x/a.txt", false).unwrap());
+        assert!(!ignore.is_ignored("sub/doc/a.txt", false).unwrap());
+    }
+    #[test]
+    fn trailing_slashes_only_match_directories() {
+        let mut ignore = ignore(&["out/"]);
+        assert!(ignore.is_ignored("out", true).unwrap());
+        assert!(ignore.is_ignored("sub/out", true).unwrap());
+        assert!(!ignore.is_ignored("z/out", false).unwrap());
+        assert!(ignore.is_ignored("out/file", false).unwrap());
+    }
+    #[test]
+    fn comments_blank_lines_and_escapes() {
+        assert!(Pattern::parse("# comment", "").is_none());
+        assert!(Pattern::parse("", "").is_none());
+        let pattern = Pattern::parse("\\#file  ", "").unwrap();
+        assert_eq!(pattern.pattern, "#file");
+        assert!(!pattern.negated);
+        let pattern = Pattern::parse("\\!important", "").unwrap();
+        assert_eq!(pattern.pattern, "!important");
+        assert!(!pattern.negated);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::error::GitError;
+use crate::lockfile::LockFile;
+use crate::objects::{file_mode, GitObject, Kind};
+use crate::submodule;
+use sha1::Digest;
+use sha1::Sha1;
+use std::fs;
+use std::io::{Cursor, Read, Write as _};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+#[derive(Clone, Debug)]
+pub struct IndexEntry {
+    ctime: (u32, u32),
+    mtime: (u32, u32),
+    dev: u32,
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    size: u32,
+    hash: Vec<u8>,
+    stage: u8,
+    path: String,
+}
+impl IndexEntry {
+    pub fn new(
+        path: impl Into<String>,
+        mode: &str,
+        hash: Vec<u8>,
+        stage: u8,
+    ) -> Result<Self, GitError> {
+        Ok(Self {
+            ctime: (0, 0),
+            mtime: (0, 0),
+            dev: 0,
+            ino: 0,
+            mode: u32::from_str_radix(mode, 8)
+                .map_err(|_| GitError::any(format!("invalid file mode: {mode}")))?,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            hash,
+            stage,
+            path: path.into(),
+        })
+    }
+    pub fn from_file(path: impl Into<String>, hash: Vec<u8>) -> Result<Self, GitError> {
+        let path = path.into();
+        let meta = fs::symlink_metadata(&path)?;
+        let mode =
+            file_mode(&meta).ok_or(GitError::any(format!("unsupported file type: {path}")))?;
+        let mut entry = Self::new(path, &mode, hash, 0)?;
+        entry.refresh(&meta);
+        Ok(entry)
+    }
+    fn refresh(&mut self, meta: &fs::Metadata) {
+        self.ctime = (meta.ctime() as u32, meta.ctime_nsec() as u32);
+        self.mtime = (meta.mtime() as u32, meta.mtime_nsec() as u32);
+        self.dev = meta.dev() as u32;
+        self.ino = meta.ino() as u32;
+        self.uid = meta.uid();
+        self.gid = meta.gid();
+        self.size = meta.size() as u32;
+    }
+    /// Records the current stat data of the file at the entry's path, so a
+    /// freshly written file is not seen as modified.
+    pub fn refresh_stat(&mut self) -> Result<(), GitError> {
+        let meta = fs::symlink_metadata(&self.path)?;
+        self.refresh(&meta);
+        Ok(())
+    }
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+    pub fn hash(&self) -> &Vec<u8> {
+        &self.hash
+    }
+    pub fn hex_string(&self) -> String {
+        hex::encode(&self.hash)
+    }
+    pub fn mode(&self) -> String {
+        format!("{:o}", self.mode)
+    }
+    pub fn stage(&self) -> u8 {
+        self.stage
+    }
+    pub fn matches_stat(&self, meta: &fs::Metadata) -> bool {
+        self.mtime == (meta.mtime() as u32, meta.mtime_nsec() as u32)
+            && self.size == meta.size() as u32
+            && file_mode(meta).as_deref() == Some(self.mode().as_str())
+    }
+    pub fn is_modified(&self) -> Result<bool, GitError> {
+        let Ok(meta) = fs::symlink_metadata(&self.path) else {
+            return Ok(true);
+        };
+        if self.mode == 0o160000 {
+            return Ok(match submodule::head(&self.path)? {
+                Some(head) => head != self.hex_string(),
+                None => !meta.is_dir(),
+            });
+        }
+        if self.matches_stat(&meta) {
+            return Ok(false);
+        }
+        if file_mode(&meta).as_deref() != Some(self.mode().as_str()) {
+            return Ok(true);
+        }
+        let git_object = GitObject::from_path(&self.path, false)?;
+        Ok(git_object.hex_string() != self.hex_string())
+    }
+}
+#[derive(Default)]
+pub struct Index {
+    entries: Vec<IndexEntry>,
+}
+impl Index {
+    fn path() -> &'static Path {
+        Path::new(".git/index")
+    }
+    pub fn load() -> Result<Self, GitError> {
+        if !Self::path().exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read(Self::path())?;
+        if data.len() < 32 {
+            return Err(GitError::any("index file is truncated"));
+        }
+        let (body, checksum) = data.split_at(data.len() - 20);
+        if Sha1::digest(body).as_slice() != checksum {
+            return Err(GitError::any("index file checksum mismatch"));
+        }
+        let mut reader = Cursor::new(body);
+        let mut sig = [0u8; 4];
+        reader.read_exact(&mut sig)?;
+        if &sig != b"DIRC" {
+            return Err(GitError::any("index file has bad signature"));
+        }
+        let version = read_u32(&mut reader)?;
+        if !(2..=3).contains(&version) {
+            return Err(GitError::any(format!(
+                "unsupported index version: {version}"
+            )));
+        }
+        let count = read_u32(&mut reader)?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let start = reader.position();
+            let mut fields = [0u32; 10];
+            for field in fields.iter_mut() {
+                *field = read_u32(&mut reader)?;
+            }
+            let mut hash = vec![0u8; 20];
+            reader.read_exact(&mut hash)?;
+            let mut flags = [0u8; 2];
+            reader.read_exact(&mut flags)?;
+            let flags = u16::from_be_bytes(flags);
+            if version >= 3 && flags & 0x4000 != 0 {
+                reader.read_exact(&mut [0u8; 2])?;
+            }
+            let mut path = Vec::new();
+            let mut byte = [0u8; 1];
+            loop {
+                reader.read_exact(&mut byte)?;
+                if byte[0] == 0 {
+                    break;
+                }
+                path.push(byte[0]);
+            }
+            let len = (reader.position() - start) as usize;
+            let padding = (8 - len % 8) % 8;
+            reader.set_position(reader.position() + padding as u64);
+            entries.push(IndexEntry {
+                ctime: (fields[0], fields[1]),
+                mtime: (fields[2], fields[3]),
+                dev: fields[4],
+                ino: fields[5],
+                mode: fields[6],
+                uid: fields[7],
+                gid: fields[8],
+                size: fields[9],
+                hash,
+                stage: ((flags >> 12) & 0b11) as u8,
+                path: String::from_utf8(path)?,
+            });
+        }
+        Ok(Self { entries })
+    }
+    pub fn write(&self) -> Result<(), GitError> {
+        let mut data = Vec::new();
+        data.write_all(b"DIRC")?;
+        data.write_all(&2u32.to_be_bytes())?;
+        data.write_all(&(self.entries.len() as u32).to_be_bytes())?;
+        for e in &self.entries {
+            let start = data.len();
+            for field in [
+                e.ctime.0, e.ctime.1, e.mtime.0, e.mtime.1, e.dev, e.ino, e.mode, e.uid, e.gid,
+                e.size,
+            ] {
+                data.write_all(&field.to_be_bytes())?;
+            }
+            data.write_all(&e.hash)?;
+            let flags = ((e.stage as u16) << 12) | e.path.len().min(0xfff) as u16;
+            data.write_all(&flags.to_be_bytes())?;
+            data.write_all(e.path.as_bytes())?;
+            let len = data.len() - start;
+            data.resize(data.len() + 8 - len % 8, 0);
+        }
+        let checksum = Sha1::digest(&data);
+        data.write_all(&checksum)?;
+        let mut lock = LockFile::acquire(Self::path())?;
+        lock.write_all(&data)?;
+        lock.commit()?;
+        Ok(())
+    }
+    pub fn from_tree(hex_string: impl AsRef<str>) -> Result<Self, GitError> {
+        let mut index = Self::default();
+        index.read_tree(hex_string.as_ref(), "")?;
+        Ok(index)
+    }
+    fn read_tree(&mut self, hex_string: &str, prefix: &str) -> Result<(), GitError> {
+        let tree = GitObject::from_hex_string(hex_string)?;
+        for entry in tree.tree_entries()? {
+            let path = format!("{prefix}{}", entry.filename());
+            match entry.kind() {
+                Kind::Tree => self.read_tree(&entry.hex_string(), &format!("{path}/"))?,
+                _ => self.add(IndexEntry::new(
+                    path,
+                    entry.mode(),
+                    entry.hash().clone(),
+                    0,
+                )?),
+            }
+        }
+        Ok(())
+    }
+    /// Writes the index as tree objects and returns the root tree's hash.
+    /// Fails while any path is still unmerged.
+    pub fn write_tree(&self) -> Result<String, GitError> {
+        if let Some(entry) = self.entries.iter().find(|e| e.stage > 0) {
+            return Err(GitError::any(format!(
+                "{}: unmerged ({})\ngit-write-tree: error building trees",
+                entry.path,
+                entry.hex_string()
+            )));
+        }
+        let entries: Vec<&IndexEntry> = self.entries.iter().collect();
+        Ok(hex::encode(Self::write_subtree(&entries, "")?))
+    }
+    fn write_subtree(entries: &[&IndexEntry], prefix: &str) -> Result<Vec<u8>, GitError> {
+        let mut tree = Vec::new();
+        let mut i = 0;
+        while i < entries.len() {
+            let rest = &entries[i].path[prefix.len()..];
+            match rest.split_once('/') {
+                None => {
+                    tree.push((entries[i].mode(), rest.to_string(), entries[i].hash.clone()));
+                    i += 1;
+                }
+                Some((dir, _)) => {
+                    let subdir = format!("{prefix}{dir}/");
+                    let end = i + entries[i..]
+                        .iter()
+                        .take_while(|e| e.path.starts_with(&subdir))
+                        .count();
+                    let hash = Self::write_subtree(&entries[i..end], &subdir)?;
+                    tree.push(("40000".to_string(), dir.to_string(), hash));
+                    i = end;
+                }
+            }
+        }
+        let git_object = GitObject::build_tree(tree)?;
+        git_object.write()?;
+        Ok(git_object.hash().clone())
+    }
+    pub fn entries(&self) -> &Vec<IndexEntry> {
+        &self.entries
+    }
+    fn position(&self, path: &str, stage: u8) -> Result<usize, usize> {
+        self.entries
+            .binary_search_by(|e| (e.path.as_str(), e.stage).cmp(&(path, stage)))
+    }
+    pub fn get(&self, path: &str) -> Option<&IndexEntry> {
+        self.position(path, 0).ok().map(|i| &self.entries[i])
+    }
+    /// Whether `path` has an entry at any stage.
+    pub fn contains(&self, path: &str) -> bool {
+        let i = self.entries.partition_point(|e| e.path.as_str() < path);
+        self.entries.get(i).is_some_and(|e| e.path == path)
+    }
+    /// Whether any entry lies inside the directory `dir`.
+    pub fn tracks_dir(&self, dir: &str) -> bool {
+        let prefix = format!("{dir}/");
+        let i = self.entries.partition_point(|e| e.path < prefix);
+        self.entries
+            .get(i)
+            .is_some_and(|e| e.path.starts_with(&prefix))
+    }
+    pub fn add(&mut self, entry: IndexEntry) {
+        if entry.stage == 0 {
+            self.remove(&entry.path);
+        }
+        match self.position(&entry.path, entry.stage) {
+            Ok(i) => self.entries[i] = entry,
+            Err(i) => self.entries.insert(i, entry),
+        }
+    }
+    pub fn remove(&mut self, path: &str) {
+        self.entries.retain(|e| e.path != path);
+    }
+}
+fn read_u32(reader: &mut impl Read) -> Result<u32, GitError> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
@@ -0,0 +1,98 @@
+use crate::error::GitError;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+/// An exclusive `<path>.lock` file. Writes go to the lock file and only
+/// replace `path` on `commit`; dropping an uncommitted lock removes it.
+pub struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+}
+impl LockFile {
+    pub fn acquire(path: impl AsRef<Path>) -> Result<Self, GitError> {
+        let path = path.as_ref().to_path_buf();
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                return Err(GitError::any(format!(
+                    "unable to create '{}': File exists",
+                    lock_path.display()
+                )))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            lock_path,
+            file: Some(file),
+        })
+    }
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), GitError> {
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(data)?;
+        }
+        Ok(())
+    }
+    pub fn commit(mut self) -> Result<(), GitError> {
+        // A failed sync leaves the file in place for `drop` to clean up.
+        if let Some(file) = &self.file {
+            file.sync_all()?;
+        }
+        self.file = None;
+        fs::rename(&self.lock_path, &self.path).inspect_err(|_| {
+            let _ = fs::remove_file(&self.lock_path);
+        })?;
+        Ok(())
+    }
+}
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lockfile-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("ref")
+    }
+    #[test]
+    fn commit_replaces_the_file_and_releases_the_lock() {
+        let path = scratch("commit");
+        let mut lock = LockFile::acquire(&path).unwrap();
+        assert!(LockFile::acquire(&path).is_err());
+        lock.write_all(b"new\n").unwrap();
+        lock.commit().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new\n");
+        assert!(!path.with_extension("lock").exists());
+        LockFile::acquire(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    #[test]
+    fn dropping_an_uncommitted_lock_removes_it() {
+        let path = scratch("drop");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "old\n").unwrap();
+        let mut lock = LockFile::acquire(&path).unwrap();
+        lock.write_all(b"new\n").unwrap();
+        drop(lock);
+        assert_eq!(fs::read(&path).unwrap(), b"old\n");
+        assert!(!path.with_extension("lock").exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,7 +1,29 @@
+use error::GitError;
 use std::env;
+mod attributes;
 mod commands;
+mod commit;
+mod config;
+mod date;
+mod diff;
+mod editor;
 mod error;
+mod fsck;
+mod ignore;
+mod index;
+mod lockfile;
+mod merge;
 mod objects;
+mod reflog;
+mod refs;
+mod reftable;
+mod revision;
+mod revwalk;
+mod sequencer;
+mod state;
+mod submodule;
+mod tag;
+mod worktree;
 
 fn main() {
     let mut args = env::args().skip(1);
@@ -9,23 +31,55 @@ fn main() {
         let args: Vec<String> = args.collect();
         let command = match command.as_str() {
             "init" => commands::init,
+            "add" => commands::add,
+            "status" => commands::status,
+            "diff" => commands::diff,
+            "diff-tree" => commands::diff_tree,
+            "log" => commands::log,
+            "rev-parse" => commands::rev_parse,
+            "rev-list" => commands::rev_list,
+            "merge-base" => commands::merge_base,
+            "update-ref" => commands::update_ref,
+            "symbolic-ref" => commands::symbolic_ref,
+            "show-ref" => commands::show_ref,
+            "for-each-ref" => commands::for_each_ref,
+            "reflog" => commands::reflog,
+            "branch" => commands::branch,
+            "tag" => commands::tag,
+            "switch" => commands::switch,
+            "checkout" => commands::checkout,
+            "restore" => commands::restore,
+            "commit" => commands::commit,
+            "merge" => commands::merge,
+            "reset" => commands::reset,
+            "rebase" => commands::rebase,
+            "cherry-pick" => commands::cherry_pick,
+            "stash" => commands::stash,
+            "revert" => commands::revert,
+            "clean" => commands::clean,
             "clone" => commands::clone,
+            "submodule" => commands::submodule,
             "cat-file" => commands::cat_file,
             "hash-object" => commands::hash_object,
             "ls-tree" => commands::ls_tree,
             "write-tree" => commands::write_tree,
             "commit-tree" => commands::commit_tree,
+            "mktag" => commands::mktag,
             _ => {
                 eprintln!("Unknown `{}` command", command);
                 std::process::exit(1);
             }
         };
-        if let Err(e) = command(args) {
-            eprintln!("Command error: {}", e);
-            std::process::exit(1);
+        match command(args) {
+            Ok(()) => {}
+            Err(GitError::Exit { code }) => std::process::exit(code),
+            Err(e) => {
+                eprintln!("Command error: {}", e);
+                std::process::exit(1);
+            }
         };
     } else {
         eprintln!("No command provided");
         std::process::exit(1);
     };
-}
\ No newline at end of file
+}
@@ -0,0 +1,858 @@
+use crate::commit::Commit;
+use crate::config::Config;
+use crate::diff::{
+    compare, detect_renames, is_binary, split_lines, tree_snapshot, Algorithm, DiffOptions,
+    LineDiff, Snapshot, DEFAULT_SIMILARITY,
+};
+use crate::error::GitError;
+use crate::index::{Index, IndexEntry};
+use crate::objects::{short_hash, GitObject, Kind};
+use crate::revwalk::merge_bases;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+/// The rename limit of merges when neither `merge.renameLimit` nor
+/// `diff.renameLimit` is set.
+const MERGE_RENAME_LIMIT: usize = 7000;
+/// How conflicting hunks are written out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictStyle {
+    Merge,
+    Diff3,
+    Zdiff3,
+}
+impl ConflictStyle {
+    pub fn parse(value: &str) -> Result<Self, GitError> {
+        match value {
+            "merge" => Ok(Self::Merge),
+            "diff3" => Ok(Self::Diff3),
+            "zdiff3" => Ok(Self::Zdiff3),
+            _ => Err(GitError::any(format!(
+                "unknown style '{value}' given for 'merge.conflictstyle'"
+            ))),
+        }
+    }
+    /// The style set by `merge.conflictStyle`, `merge` when unset.
+    pub fn from_config() -> Result<Self, GitError> {
+        match Config::load()?.get("merge.conflictStyle") {
+            Some(value) => Self::parse(value),
+            None => Ok(Self::Merge),
+        }
+    }
+}
+/// The names written after the conflict markers of each side.
+#[derive(Clone, Debug)]
+pub struct Labels {
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+enum Chunk<'a> {
+    Unchanged(Vec<&'a [u8]>),
+    Resolved(Vec<&'a [u8]>),
+    Conflict {
+        base: Vec<&'a [u8]>,
+        ours: Vec<&'a [u8]>,
+        theirs: Vec<&'a [u8]>,
+    },
+}
+/// Splits a three-way merge into unchanged runs, hunks only one side (or
+/// both sides alike) changed, and conflicting hunks. Changes that overlap
+/// or touch on the base are taken together.
+fn chunks<'a>(base: &'a [u8], ours: &'a [u8], theirs: &'a [u8]) -> Vec<Chunk<'a>> {
+    let base_lines = split_lines(base);
+    let sides = [
+        (
+            split_lines(ours),
+            LineDiff::new(base, ours, Algorithm::Myers).hunks(),
+        ),
+        (
+            split_lines(theirs),
+            LineDiff::new(base, theirs, Algorithm::Myers).hunks(),
+        ),
+    ];
+    let mut chunks = Vec::new();
+    let (mut next, mut pos) = ([0, 0], 0);
+    loop {
+        let starts = [0, 1].map(|n| sides[n].1.get(next[n]).map(|(old, _)| old.start));
+        let Some(start) = starts.into_iter().flatten().min() else {
+            break;
+        };
+        let first = next;
+        let mut end = start;
+        while let Some(n) = (0..2).find(|&n| {
+            sides[n]
+                .1
+                .get(next[n])
+                .is_some_and(|(old, _)| old.start <= end)
+        }) {
+            end = end.max(sides[n].1[next[n]].0.end);
+            next[n] += 1;
+        }
+        if pos < start {
+            chunks.push(Chunk::Unchanged(base_lines[pos..start].to_vec()));
+        }
+        pos = end;
+        let side = |n: usize| -> Option<Vec<&'a [u8]>> {
+            let (lines, hunks) = &sides[n];
+            let range: Range<usize> = first[n]..next[n];
+            if range.is_empty() {
+                return None;
+            }
+            let (first, last) = (&hunks[range.start], &hunks[range.end - 1]);
+            let from = first.1.start - (first.0.start - start);
+            let to = last.1.end + (end - last.0.end);
+            Some(lines[from..to].to_vec())
+        };
+        chunks.push(match (side(0), side(1)) {
+            (Some(ours), None) => Chunk::Resolved(ours),
+            (None, Some(theirs)) => Chunk::Resolved(theirs),
+            (Some(ours), Some(theirs)) if ours == theirs => Chunk::Resolved(ours),
+            (ours, theirs) => Chunk::Conflict {
+                base: base_lines[start..end].to_vec(),
+                ours: ours.unwrap_or_default(),
+                theirs: theirs.unwrap_or_default(),
+            },
+        });
+    }
+    if pos < base_lines.len() {
+        chunks.push(Chunk::Unchanged(base_lines[pos..].to_vec()));
+    }
+    chunks
+}
+/// The number of lines `ours` and `theirs` share at their start and, in the
+/// rest, at their end.
+fn common_ends(ours: &[&[u8]], theirs: &[&[u8]]) -> (usize, usize) {
+    let prefix = ours.iter().zip(theirs).take_while(|(a, b)| a == b).count();
+    let suffix = ours[prefix..]
+        .iter()
+        .rev()
+        .zip(theirs[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
+}
+/// Narrows each conflict down to the lines where the two sides really
+/// differ, then joins conflicts only a few unchanged lines apart, like
+/// git's zealous merge level.
+fn refine(chunks: Vec<Chunk>) -> Vec<Chunk> {
+    let mut refined = Vec::new();
+    for chunk in chunks {
+        let Chunk::Conflict { ours, theirs, .. } = chunk else {
+            refined.push(chunk);
+            continue;
+        };
+        let (ours_text, theirs_text) = (ours.concat(), theirs.concat());
+        let mut pos = (0, 0);
+        let diff = LineDiff::new(&ours_text, &theirs_text, Algorithm::Myers);
+        for (old, new) in diff.hunks() {
+            if pos.0 < old.start {
+                refined.push(Chunk::Unchanged(ours[pos.0..old.start].to_vec()));
+            }
+            refined.push(Chunk::Conflict {
+                base: Vec::new(),
+                ours: ours[old.clone()].to_vec(),
+                theirs: theirs[new.clone()].to_vec(),
+            });
+            pos = (old.end, new.end);
+        }
+        if pos.0 < ours.len() {
+            refined.push(Chunk::Unchanged(ours[pos.0..].to_vec()));
+        }
+    }
+    let mut simplified: Vec<Chunk> = Vec::new();
+    let mut chunks = refined.into_iter().peekable();
+    while let Some(chunk) = chunks.next() {
+        if let (
+            Some(Chunk::Conflict { ours, theirs, .. }),
+            Chunk::Unchanged(lines),
+            Some(Chunk::Conflict { .. }),
+        ) = (simplified.last_mut(), &chunk, chunks.peek())
+        {
+            if lines.len() <= 3 {
+                let Some(Chunk::Conflict {
+                    ours: next_ours,
+                    theirs: next_theirs,
+                    ..
+                }) = chunks.next()
+                else {
+                    unreachable!("the next chunk is a conflict");
+                };
+                ours.extend(lines.iter().chain(&next_ours));
+                theirs.extend(lines.iter().chain(&next_theirs));
+                continue;
+            }
+        }
+        simplified.push(chunk);
+    }
+    simplified
+}
+fn push_lines(out: &mut Vec<u8>, lines: &[&[u8]]) {
+    for line in lines {
+        out.extend_from_slice(line);
+    }
+    if !lines.is_empty() && !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+}
+fn push_marker(out: &mut Vec<u8>, marker: &str, label: &str) {
+    out.extend_from_slice(marker.as_bytes());
+    if !label.is_empty() {
+        out.push(b' ');
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(b'\n');
+}
+/// Merges the changes `ours` and `theirs` each made to `base`, line by
+/// line. Overlapping changes that differ become conflicts marked up in
+/// `style`. Returns the merged contents and whether they are conflict-free.
+pub fn merge_content(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    labels: &Labels,
+    style: ConflictStyle,
+) -> (Vec<u8>, bool) {
+    let mut chunks = chunks(base, ours, theirs);
+    if style == ConflictStyle::Merge {
+        chunks = refine(chunks);
+    }
+    let mut out = Vec::new();
+    let mut clean = true;
+    for chunk in chunks {
+        let (base, mut ours, mut theirs) = match chunk {
+            Chunk::Unchanged(lines) | Chunk::Resolved(lines) => {
+                lines.iter().for_each(|line| out.extend_from_slice(line));
+                continue;
+            }
+            Chunk::Conflict { base, ours, theirs } => (base, ours, theirs),
+        };
+        clean = false;
+        let mut suffix = Vec::new();
+        if style == ConflictStyle::Zdiff3 {
+            let (prefix, common) = common_ends(&ours, &theirs);
+            ours.drain(..prefix)
+                .for_each(|line| out.extend_from_slice(line));
+            theirs.drain(..prefix);
+            suffix = ours.split_off(ours.len() - common);
+            theirs.truncate(theirs.len() - common);
+        }
+        if !out.is_empty() && !out.ends_with(b"\n") {
+            out.push(b'\n');
+        }
+        push_marker(&mut out, "<<<<<<<", &labels.ours);
+        push_lines(&mut out, &ours);
+        if style != ConflictStyle::Merge {
+            push_marker(&mut out, "|||||||", &labels.base);
+            push_lines(&mut out, &base);
+        }
+        push_marker(&mut out, "=======", "");
+        push_lines(&mut out, &theirs);
+        push_marker(&mut out, ">>>>>>>", &labels.theirs);
+        suffix.iter().for_each(|line| out.extend_from_slice(line));
+    }
+    (out, clean)
+}
+/// A mode and blob hash, as in a [`Snapshot`].
+pub type Entry = (String, Vec<u8>);
+/// A path the merge left for the user to resolve.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    /// The base, our and their versions, staged as stages 1, 2 and 3.
+    pub stages: [Option<Entry>; 3],
+}
+pub struct TreeMerge {
+    /// The merged tree. Conflicted paths hold their contents with conflict
+    /// markers, or whichever version was left in the working tree.
+    pub result: Snapshot,
+    pub conflicts: BTreeMap<String, Conflict>,
+    messages: BTreeMap<String, Vec<String>>,
+}
+impl TreeMerge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+    /// The merge's progress and conflict messages, in path order.
+    pub fn messages(&self) -> impl Iterator<Item = &String> {
+        self.messages.values().flatten()
+    }
+    /// Replaces the index entries of conflicted paths with their stages.
+    pub fn stage_conflicts(&self) -> Result<(), GitError> {
+        let mut index = Index::load()?;
+        for (path, conflict) in &self.conflicts {
+            index.remove(path);
+            for (stage, entry) in (1..).zip(&conflict.stages) {
+                if let Some((mode, hash)) = entry {
+                    index.add(IndexEntry::new(path.as_str(), mode, hash.clone(), stage)?);
+                }
+            }
+        }
+        index.write()
+    }
+}
+struct TreeMerger<'a> {
+    labels: &'a Labels,
+    style: ConflictStyle,
+    merge: TreeMerge,
+    /// The versions and sources each path was resolved from, to resolve it
+    /// again under another name.
+    resolved: BTreeMap<String, ([Option<Entry>; 3], [String; 3])>,
+}
+impl TreeMerger<'_> {
+    fn say(&mut self, path: &str, message: String) {
+        let messages = self.merge.messages.entry(path.to_string()).or_default();
+        messages.push(message);
+    }
+    fn conflict(&mut self, path: &str, stages: [Option<&Entry>; 3], kept: Option<&Entry>) {
+        let conflict = Conflict {
+            stages: stages.map(|stage| stage.cloned()),
+        };
+        self.merge.conflicts.insert(path.to_string(), conflict);
+        if let Some(kept) = kept {
+            self.merge.result.insert(path.to_string(), kept.clone());
+        }
+    }
+    /// Resolves one path from its base, our and their versions, which were
+    /// found at `sources` on each side when renames were involved.
+    fn merge_path(
+        &mut self,
+        path: &str,
+        versions: [Option<&Entry>; 3],
+        sources: [&str; 3],
+    ) -> Result<(), GitError> {
+        self.resolved.insert(
+            path.to_string(),
+            (versions.map(|v| v.cloned()), sources.map(String::from)),
+        );
+        let [base, ours, theirs] = versions;
+        let (ours_label, theirs_label) = (&self.labels.ours, &self.labels.theirs);
+        let kept = match (base, ours, theirs) {
+            _ if ours == theirs || base == theirs => ours,
+            _ if base == ours => theirs,
+            (_, Some(ours), Some(theirs)) => {
+                return self.merge_files(path, base, ours, theirs, sources)
+            }
+            (_, None, kept) => {
+                self.say(
+                    path,
+                    format!(
+                        "CONFLICT (modify/delete): {path} deleted in {ours_label} and modified in {theirs_label}.  Version {theirs_label} of {path} left in tree."
+                    ),
+                );
+                self.conflict(path, versions, kept);
+                return Ok(());
+            }
+            (_, kept, None) => {
+                self.say(
+                    path,
+                    format!(
+                        "CONFLICT (modify/delete): {path} deleted in {theirs_label} and modified in {ours_label}.  Version {ours_label} of {path} left in tree."
+                    ),
+                );
+                self.conflict(path, versions, kept);
+                return Ok(());
+            }
+        };
+        if let Some(kept) = kept {
+            self.merge.result.insert(path.to_string(), kept.clone());
+        }
+        Ok(())
+    }
+    /// Merges two differing versions of a file: their contents line by line
+    /// and their modes against the base's.
+    fn merge_files(
+        &mut self,
+        path: &str,
+        base: Option<&Entry>,
+        ours: &Entry,
+        theirs: &Entry,
+        sources: [&str; 3],
+    ) -> Result<(), GitError> {
+        let base_mode = base.map(|(mode, _)| mode);
+        let mode = if ours.0 == theirs.0 || base_mode == Some(&ours.0) {
+            Some(&theirs.0)
+        } else if base_mode == Some(&theirs.0) {
+            Some(&ours.0)
+        } else {
+            None
+        };
+        let kind = if base.is_some() { "content" } else { "add/add" };
+        let regular = |mode: &str| mode == "100644" || mode == "100755";
+        let Some(mode) = mode.filter(|_| regular(&ours.0) && regular(&theirs.0)) else {
+            self.say(path, format!("CONFLICT ({kind}): Merge conflict in {path}"));
+            self.conflict(path, [base, Some(ours), Some(theirs)], Some(ours));
+            return Ok(());
+        };
+        self.say(path, format!("Auto-merging {path}"));
+        let read = |(_, hash): &Entry| -> Result<Vec<u8>, GitError> {
+            Ok(GitObject::from_hex_string(hex::encode(hash))?
+                .contents()
+                .clone())
+        };
+        let base_data = base.map(read).transpose()?.unwrap_or_default();
+        let (ours_data, theirs_data) = (read(ours)?, read(theirs)?);
+        if [&base_data, &ours_data, &theirs_data]
+            .iter()
+            .any(|data| is_binary(data))
+        {
+            self.say(
+                path,
+                format!(
+                    "warning: Cannot merge binary files: {path} ({} vs. {})",
+                    self.labels.ours, self.labels.theirs
+                ),
+            );
+            self.say(path, format!("CONFLICT ({kind}): Merge conflict in {path}"));
+            self.conflict(path, [base, Some(ours), Some(theirs)], Some(ours));
+            return Ok(());
+        }
+        let renamed = sources.iter().any(|source| *source != path);
+        let label = |label: &str, source: &str| match renamed {
+            true => format!("{label}:{source}"),
+            false => label.to_string(),
+        };
+        let labels = Labels {
+            base: label(&self.labels.base, sources[0]),
+            ours: label(&self.labels.ours, sources[1]),
+            theirs: label(&self.labels.theirs, sources[2]),
+        };
+        let (merged, clean) =
+            merge_content(&base_data, &ours_data, &theirs_data, &labels, self.style);
+        let blob = GitObject::build(Kind::Blob, merged)?;
+        blob.write()?;
+        let entry = (mode.clone(), blob.hash().clone());
+        if clean {
+            self.merge.result.insert(path.to_string(), entry);
+        } else {
+            self.say(path, format!("CONFLICT ({kind}): Merge conflict in {path}"));
+            self.conflict(path, [base, Some(ours), Some(theirs)], Some(&entry));
+        }
+        Ok(())
+    }
+}
+impl TreeMerger<'_> {
+    /// Resolves `target` again after `source` was renamed to it on one side
+    /// while the other side added an unrelated file there: both versions
+    /// are staged and the working tree gets them merged as an add/add.
+    fn rename_add(
+        &mut self,
+        source: &str,
+        target: &str,
+        added: &Entry,
+        ours_renamed: bool,
+    ) -> Result<(), GitError> {
+        let (renamer, adder) = match ours_renamed {
+            true => (&self.labels.ours, &self.labels.theirs),
+            false => (&self.labels.theirs, &self.labels.ours),
+        };
+        let message = format!(
+            "CONFLICT (rename/add): Rename {source}->{target} in {renamer}.  Added {target} in {adder}"
+        );
+        self.say(target, message);
+        let Some(renamed) = self.merge.result.get(target).cloned() else {
+            let stages = match ours_renamed {
+                true => [None, None, Some(added)],
+                false => [None, Some(added), None],
+            };
+            self.conflict(target, stages, Some(added));
+            return Ok(());
+        };
+        let (ours, theirs) = match ours_renamed {
+            true => (&renamed, added),
+            false => (added, &renamed),
+        };
+        let regular = |mode: &str| mode == "100644" || mode == "100755";
+        let mut kept = ours.clone();
+        if regular(&ours.0) && regular(&theirs.0) {
+            let read = |(_, hash): &Entry| -> Result<Vec<u8>, GitError> {
+                Ok(GitObject::from_hex_string(hex::encode(hash))?
+                    .contents()
+                    .clone())
+            };
+            let (ours_data, theirs_data) = (read(ours)?, read(theirs)?);
+            if !is_binary(&ours_data) && !is_binary(&theirs_data) {
+                let (merged, _) =
+                    merge_content(&[], &ours_data, &theirs_data, self.labels, self.style);
+                let blob = GitObject::build(Kind::Blob, merged)?;
+                blob.write()?;
+                kept = (ours.0.clone(), blob.hash().clone());
+            }
+        }
+        self.conflict(target, [None, Some(ours), Some(theirs)], Some(&kept));
+        Ok(())
+    }
+    /// Moves each file the merge left where it also left a directory to
+    /// `path~<side>`, after the side it came from, and resolves it again
+    /// there as a file/directory conflict.
+    fn move_files_out_of_directories(&mut self, ours: &Snapshot) -> Result<(), GitError> {
+        let result = &self.merge.result;
+        let in_the_way: Vec<String> = result
+            .keys()
+            .filter(|path| {
+                let prefix = format!("{path}/");
+                result
+                    .range(prefix.clone()..)
+                    .next()
+                    .is_some_and(|(next, _)| next.starts_with(&prefix))
+            })
+            .cloned()
+            .collect();
+        for path in in_the_way {
+            let side = match ours.contains_key(&path) {
+                true => &self.labels.ours,
+                false => &self.labels.theirs,
+            };
+            let stem = format!("{path}~{}", side.replace('/', "_"));
+            let mut moved = stem.clone();
+            for n in 0.. {
+                if !self.merge.result.contains_key(&moved) {
+                    break;
+                }
+                moved = format!("{stem}_{n}");
+            }
+            self.say(
+                &moved,
+                format!(
+                    "CONFLICT (file/directory): directory in the way of {path} from {side}; moving it to {moved} instead."
+                ),
+            );
+            let entry = self.merge.result.remove(&path);
+            let conflict = self.merge.conflicts.remove(&path);
+            match self.resolved.remove(&path) {
+                Some((versions, sources)) => {
+                    self.merge.messages.remove(&path);
+                    let [base, ours, theirs] = &versions;
+                    let [base_source, ours_source, theirs_source] = &sources;
+                    let sources = [base_source, ours_source, theirs_source].map(|source| {
+                        match source == &path {
+                            true => moved.as_str(),
+                            false => source.as_str(),
+                        }
+                    });
+                    let versions = [base.as_ref(), ours.as_ref(), theirs.as_ref()];
+                    self.merge_path(&moved, versions, sources)?;
+                    if !self.merge.conflicts.contains_key(&moved) {
+                        let kept = self.merge.result.get(&moved).cloned();
+                        self.conflict(&moved, versions, kept.as_ref());
+                    }
+                }
+                None => {
+                    let stages = conflict.map(|conflict| conflict.stages).unwrap_or_default();
+                    let [base, ours, theirs] = &stages;
+                    let stages = [base.as_ref(), ours.as_ref(), theirs.as_ref()];
+                    self.conflict(&moved, stages, entry.as_ref());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+/// The renames `side` made relative to `base`, from old path to new.
+fn renames(
+    base: &Snapshot,
+    side: &Snapshot,
+    options: &DiffOptions,
+) -> Result<BTreeMap<String, String>, GitError> {
+    Ok(detect_renames(compare(base, side, false)?, base, options)?
+        .into_iter()
+        .filter(|diff| diff.status() == 'R')
+        .filter_map(|diff| Some((diff.old_path?, diff.new_path?)))
+        .collect())
+}
+/// The file `side` added at `target` on its own, which is neither in
+/// `base` nor the result of one of its renames.
+fn added_alongside<'a>(
+    base: &Snapshot,
+    side: &'a Snapshot,
+    side_renames: &BTreeMap<String, String>,
+    target: &str,
+) -> Option<&'a Entry> {
+    if base.contains_key(target) || side_renames.values().any(|t| t == target) {
+        return None;
+    }
+    side.get(target)
+}
+/// Merges the trees `ours` and `theirs` relative to `base`, path by path
+/// and following renames either side made, the way git's `ort` strategy
+/// does. Changed files are merged line by line with conflicts written in
+/// `style`.
+pub fn merge_trees(
+    base: &Snapshot,
+    ours: &Snapshot,
+    theirs: &Snapshot,
+    labels: &Labels,
+    style: ConflictStyle,
+) -> Result<TreeMerge, GitError> {
+    let mut merger = TreeMerger {
+        labels,
+        style,
+        merge: TreeMerge {
+            result: Snapshot::new(),
+            conflicts: BTreeMap::new(),
+            messages: BTreeMap::new(),
+        },
+        resolved: BTreeMap::new(),
+    };
+    let mut options = DiffOptions {
+        renames: Some(DEFAULT_SIMILARITY),
+        rename_limit: MERGE_RENAME_LIMIT,
+        ..DiffOptions::default()
+    };
+    options.configure_rename_limit(&["merge.renameLimit", "diff.renameLimit"])?;
+    let ours_renames = renames(base, ours, &options)?;
+    let theirs_renames = renames(base, theirs, &options)?;
+    options.warn_rename_limit("merge.renamelimit");
+    let mut handled = BTreeSet::new();
+    for (source, target) in &ours_renames {
+        handled.extend([source, target]);
+        let sources = [source.as_str(), target, source];
+        match theirs_renames.get(source) {
+            Some(theirs_target) if theirs_target == target => {
+                let versions = [base.get(source), ours.get(target), theirs.get(target)];
+                merger.merge_path(target, versions, [source, target, target])?;
+            }
+            Some(theirs_target) => {
+                handled.insert(theirs_target);
+                merger.say(
+                    source,
+                    format!(
+                        "CONFLICT (rename/rename): {source} renamed to {target} in {} and to {theirs_target} in {}.",
+                        labels.ours, labels.theirs
+                    ),
+                );
+                let (ours_entry, theirs_entry) = (ours.get(target), theirs.get(theirs_target));
+                merger.conflict(target, [base.get(source), ours_entry, None], ours_entry);
+                merger.conflict(
+                    theirs_target,
+                    [base.get(source), None, theirs_entry],
+                    theirs_entry,
+                );
+            }
+            None if theirs.contains_key(source) => {
+                let versions = [base.get(source), ours.get(target), theirs.get(source)];
+                merger.merge_path(target, versions, sources)?;
+            }
+            None => {
+                merger.say(
+                    target,
+                    format!(
+                        "CONFLICT (rename/delete): {source} renamed to {target} in {}, but deleted in {}.",
+                        labels.ours, labels.theirs
+                    ),
+                );
+                let kept = ours.get(target);
+                merger.conflict(target, [base.get(source), kept, None], kept);
+            }
+        }
+        if !theirs_renames.contains_key(source) {
+            if let Some(added) = added_alongside(base, theirs, &theirs_renames, target) {
+                merger.rename_add(source, target, added, true)?;
+            }
+        }
+    }
+    for (source, target) in &theirs_renames {
+        if ours_renames.contains_key(source) {
+            continue;
+        }
+        handled.extend([source, target]);
+        if ours.contains_key(source) {
+            let versions = [base.get(source), ours.get(source), theirs.get(target)];
+            merger.merge_path(target, versions, [source, source, target])?;
+        } else {
+            merger.say(
+                target,
+                format!(
+                    "CONFLICT (rename/delete): {source} renamed to {target} in {}, but deleted in {}.",
+                    labels.theirs, labels.ours
+                ),
+            );
+            let kept = theirs.get(target);
+            merger.conflict(target, [base.get(source), None, kept], kept);
+        }
+        if let Some(added) = added_alongside(base, ours, &ours_renames, target) {
+            merger.rename_add(source, target, added, false)?;
+        }
+    }
+    let paths: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    for path in paths.into_iter().filter(|path| !handled.contains(path)) {
+        let versions = [base.get(path), ours.get(path), theirs.get(path)];
+        merger.merge_path(path, versions, [path; 3])?;
+    }
+    merger.move_files_out_of_directories(ours)?;
+    Ok(merger.merge)
+}
+pub fn commit_snapshot(hash: &str) -> Result<Snapshot, GitError> {
+    tree_snapshot(Commit::from_hex_string(hash)?.tree(), true)
+}
+/// The tree to merge against given `bases`, and its label. Several bases
+/// are merged into a virtual one first, recursively.
+fn virtual_base(bases: &[String], style: ConflictStyle) -> Result<(Snapshot, String), GitError> {
+    let (first, rest) = match bases {
+        [] => return Ok((Snapshot::new(), "empty tree".to_string())),
+        [base] => return Ok((commit_snapshot(base)?, short_hash(base).to_string())),
+        [first, rest @ ..] => (first, rest),
+    };
+    let labels = Labels {
+        base: "merged common ancestors".to_string(),
+        ours: "Temporary merge branch 1".to_string(),
+        theirs: "Temporary merge branch 2".to_string(),
+    };
+    let mut merged = commit_snapshot(first)?;
+    for other in rest {
+        let (base, _) = virtual_base(&merge_bases(first, other)?, style)?;
+        merged = merge_trees(&base, &merged, &commit_snapshot(other)?, &labels, style)?.result;
+    }
+    Ok((merged, labels.base))
+}
+/// Merges commit `theirs` into commit `ours` over their merge bases. The
+/// base label in `labels` is filled in from the bases found.
+pub fn merge_commits(
+    ours: &str,
+    theirs: &str,
+    labels: &mut Labels,
+    style: ConflictStyle,
+) -> Result<TreeMerge, GitError> {
+    let (base, base_label) = virtual_base(&merge_bases(ours, theirs)?, style)?;
+    labels.base = base_label;
+    merge_trees(
+        &base,
+        &commit_snapshot(ours)?,
+        &commit_snapshot(theirs)?,
+        labels,
+        style,
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn merge(base: &str, ours: &str, theirs: &str, style: ConflictStyle) -> (String, bool) {
+        let labels = Labels {
+            base: "base".to_string(),
+            ours: "ours".to_string(),
+            theirs: "theirs".to_string(),
+        };
+        let (out, clean) = merge_content(
+            base.as_bytes(),
+            ours.as_bytes(),
+            theirs.as_bytes(),
+            &labels,
+            style,
+        );
+        (String::from_utf8(out).unwrap(), clean)
+    }
+    #[test]
+    fn separate_changes_merge_cleanly() {
+        let base = "a\nb\nc\nd\ne\nf\ng\n";
+        let merged = merge(
+            base,
+            "a\nB\nc\nd\ne\nf\ng\n",
+            "a\nb\nc\nd\ne\nF\ng\n",
+            ConflictStyle::Merge,
+        );
+        assert_eq!(merged, ("a\nB\nc\nd\ne\nF\ng\n".to_string(), true));
+        let merged = merge(
+            base,
+            "a\nc\nd\ne\nf\ng\n",
+            "a\nb\nc\nd\ne\nf\ng\nh\n",
+            ConflictStyle::Diff3,
+        );
+        assert_eq!(merged, ("a\nc\nd\ne\nf\ng\nh\n".to_string(), true));
+    }
+    #[test]
+    fn identical_and_one_sided_changes_merge_cleanly() {
+        let base = "a\nb\nc\n";
+        for style in [
+            ConflictStyle::Merge,
+            ConflictStyle::Diff3,
+            ConflictStyle::Zdiff3,
+        ] {
+            assert_eq!(
+                merge(base, "a\nX\nc\n", "a\nX\nc\n", style),
+                ("a\nX\nc\n".to_string(), true)
+            );
+            assert_eq!(
+                merge(base, base, "a\nc\n", style),
+                ("a\nc\n".to_string(), true)
+            );
+            assert_eq!(merge(base, "", base, style), (String::new(), true));
+        }
+    }
+    #[test]
+    fn conflicts_are_marked_with_labels() {
+        let (out, clean) = merge(
+            "a\nb\nx\ny\nz\nc\n",
+            "a\nb\nx\nours\nz\nc\n",
+            "a\nb\nx\ntheirs\nz\nc\n",
+            ConflictStyle::Merge,
+        );
+        assert!(!clean);
+        assert_eq!(
+            out,
+            "a\nb\nx\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nz\nc\n"
+        );
+    }
+    #[test]
+    fn merge_style_narrows_conflicts_to_differing_lines() {
+        let (out, clean) = merge(
+            "a\nb\nc\n",
+            "a\n1\n2\nX\n3\nc\n",
+            "a\n1\n2\nY\n3\nc\n",
+            ConflictStyle::Merge,
+        );
+        assert!(!clean);
+        assert_eq!(
+            out,
+            "a\n1\n2\n<<<<<<< ours\nX\n=======\nY\n>>>>>>> theirs\n3\nc\n"
+        );
+    }
+    #[test]
+    fn diff3_shows_the_whole_hunk_and_its_base() {
+        let (out, clean) = merge(
+            "a\nb\nc\n",
+            "a\n1\n2\nX\n3\nc\n",
+            "a\n1\n2\nY\n3\nc\n",
+            ConflictStyle::Diff3,
+        );
+        assert!(!clean);
+        assert_eq!(
+            out,
+            "a\n<<<<<<< ours\n1\n2\nX\n3\n||||||| base\nb\n=======\n1\n2\nY\n3\n>>>>>>> theirs\nc\n"
+        );
+        let (out, _) = merge("a\nb\nc\n", "a\nc\n", "a\nB\nc\n", ConflictStyle::Diff3);
+        assert_eq!(
+            out,
+            "a\n<<<<<<< ours\n||||||| base\nb\n=======\nB\n>>>>>>> theirs\nc\n"
+        );
+    }
+    #[test]
+    fn zdiff3_moves_common_lines_outside_the_markers() {
+        let (out, clean) = merge(
+            "a\nb\nc\n",
+            "a\n1\n2\nX\n3\nc\n",
+            "a\n1\n2\nY\n3\nc\n",
+            ConflictStyle::Zdiff3,
+        );
+        assert!(!clean);
+        assert_eq!(
+            out,
+            "a\n1\n2\n<<<<<<< ours\nX\n||||||| base\nb\n=======\nY\n>>>>>>> theirs\n3\nc\n"
+        );
+    }
+    #[test]
+    fn missing_final_newlines_do_not_run_into_markers() {
+        let (out, clean) = merge(
+            "a\nb\nc",
+            "a\nb\nc\nours",
+            "a\nb\nc\ntheirs",
+            ConflictStyle::Merge,
+        );
+        assert!(!clean);
+        assert_eq!(
+            out,
+            "a\nb\nc\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\n"
+        );
+    }
+}
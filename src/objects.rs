@@ -1,20 +1,58 @@
+use crate::commit::{CommitBuilder, Signature};
 use crate::error::GitError;
+use crate::ignore::Ignore;
+use crate::submodule;
+use crate::tag::Tag;
 use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use sha1::Digest;
 use sha1::Sha1;
-use std::fmt::Write as _;
 use std::fs;
 use std::io::Cursor;
 use std::io::{BufRead, Read, Write as _};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+/// The abbreviation git shows for an object name: its first seven digits.
+pub fn short_hash(hash: &str) -> &str {
+    hash.get(..7).unwrap_or(hash)
+}
+/// Hashes `contents` as an object of type `kind`, which need not be a type
+/// git knows, writing it as a loose object too when `write` is set.
+pub fn hash_literally(kind: &str, contents: &[u8], write: bool) -> Result<String, GitError> {
+    let header = format!("{kind} {}\0", contents.len()).into_bytes();
+    let hash = Sha1::new()
+        .chain_update(&header)
+        .chain_update(contents)
+        .finalize();
+    let hex_string = hex::encode(hash);
+    if write {
+        GitObject::write_loose(&hex_string, &header, contents)?;
+    }
+    Ok(hex_string)
+}
+pub fn file_mode(meta: &fs::Metadata) -> Option<String> {
+    if meta.is_dir() {
+        Some("40000".into())
+    } else if meta.is_symlink() {
+        Some("120000".into())
+    } else if meta.is_file() {
+        if (meta.permissions().mode() & 0o100) != 0 {
+            Some("100755".into())
+        } else {
+            Some("100644".into())
+        }
+    } else {
+        None
+    }
+}
 #[derive(Clone, Debug)]
 pub enum Kind {
     Blob,
     Tree,
     Commit,
+    Tag,
 }
 impl std::fmt::Display for Kind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -22,6 +60,18 @@ impl std::fmt::Display for Kind {
             Self::Blob => write!(f, "blob"),
             Self::Tree => write!(f, "tree"),
             Self::Commit => write!(f, "commit"),
+            Self::Tag => write!(f, "tag"),
+        }
+    }
+}
+impl Kind {
+    pub fn parse(name: &str) -> Result<Self, GitError> {
+        match name {
+            "blob" => Ok(Self::Blob),
+            "tree" => Ok(Self::Tree),
+            "commit" => Ok(Self::Commit),
+            "tag" => Ok(Self::Tag),
+            _ => Err(GitError::any(format!("invalid object type \"{name}\""))),
         }
     }
 }
@@ -39,6 +89,9 @@ impl TreeEntry {
     pub fn kind(&self) -> &Kind {
         &self.kind
     }
+    pub fn hash(&self) -> &Vec<u8> {
+        &self.hash
+    }
     pub fn hex_string(&self) -> String {
         hex::encode(&self.hash)
     }
@@ -56,9 +109,6 @@ impl GitObject {
     fn objects_dir_path() -> PathBuf {
         [".git", "objects"].iter().collect::<PathBuf>()
     }
-    fn committer() -> String {
-        "Code Crafters <000000000+codecrafters@users.noreply.github.com> 1750973235 +0000".into()
-    }
     pub fn build(kind: Kind, contents: Vec<u8>) -> Result<Self, GitError> {
         let header = {
             let mut buf = Vec::new();
@@ -66,6 +116,7 @@ impl GitObject {
                 Kind::Blob => buf.write_all(b"blob ")?,
                 Kind::Tree => buf.write_all(b"tree ")?,
                 Kind::Commit => buf.write_all(b"commit ")?,
+                Kind::Tag => buf.write_all(b"tag ")?,
             };
             buf.write_all(contents.len().to_string().as_bytes())?;
             buf.write_all(b"\0")?;
@@ -87,19 +138,34 @@ impl GitObject {
     pub fn build_commit(
         msg: impl AsRef<str>,
         tree_hash: impl AsRef<str>,
-        parent_hash: Option<impl AsRef<str>>,
+        parents: impl IntoIterator<Item = impl AsRef<str>>,
     ) -> Result<Self, GitError> {
-        let mut contents = String::new();
-        let committer = GitObject::committer();
-        writeln!(contents, "tree {}", tree_hash.as_ref())?;
-        if let Some(parent_hash) = parent_hash {
-            writeln!(contents, "parent {}", parent_hash.as_ref())?;
-        }
-        writeln!(contents, "author {committer}")?;
-        writeln!(contents, "committer {committer}")?;
-        writeln!(contents)?;
-        writeln!(contents, "{}", msg.as_ref())?;
-        Self::build(Kind::Commit, contents.into())
+        CommitBuilder::new(tree_hash.as_ref())
+            .parents(parents)
+            .message(format!("{}\n", msg.as_ref()))
+            .build()
+    }
+    /// Builds a commit keeping the author of a commit being rewritten, with
+    /// the current committer.
+    pub fn build_commit_by(
+        author: &Signature,
+        msg: impl AsRef<str>,
+        tree_hash: impl AsRef<str>,
+        parents: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self, GitError> {
+        CommitBuilder::new(tree_hash.as_ref())
+            .parents(parents)
+            .author(author.clone())
+            .message(format!("{}\n", msg.as_ref()))
+            .build()
+    }
+    /// Whether the object is stored in the repository.
+    pub fn exists(hex_string: impl AsRef<str>) -> bool {
+        let (prefix, filename) = hex_string.as_ref().split_at(2);
+        Self::objects_dir_path()
+            .join(prefix)
+            .join(filename)
+            .is_file()
     }
     pub fn from_hex_string(hex_string: impl AsRef<str>) -> Result<Self, GitError> {
         let hash = hex::decode(hex_string.as_ref())?;
@@ -123,6 +189,7 @@ impl GitObject {
             b"blob" => Kind::Blob,
             b"tree" => Kind::Tree,
             b"commit" => Kind::Commit,
+            b"tag" => Kind::Tag,
             _ => {
                 return Err(GitError::any(format!(
                     "unknown git object kind: {}",
@@ -152,8 +219,32 @@ impl GitObject {
             hash,
         })
     }
+    /// Reads the size of an object from its header, inflating no more of
+    /// it than that.
+    pub fn size_from_hex_string(hex_string: impl AsRef<str>) -> Result<usize, GitError> {
+        let (prefix, filename) = hex_string.as_ref().split_at(2);
+        let path = Self::objects_dir_path().join(prefix).join(filename);
+        let reader = ZlibDecoder::new(fs::File::open(path)?);
+        let mut header = Vec::new();
+        std::io::BufReader::new(reader.take(64)).read_until(b'\0', &mut header)?;
+        header
+            .strip_suffix(b"\0")
+            .and_then(|header| header.splitn(2, |&b| b == b' ').nth(1))
+            .and_then(|size| std::str::from_utf8(size).ok()?.parse().ok())
+            .ok_or(GitError::any(
+                "invalid git object format: cannot parse content size",
+            ))
+    }
     pub fn from_path(path: impl AsRef<Path>, write: bool) -> Result<Self, GitError> {
         let path = path.as_ref();
+        if path.is_symlink() {
+            let target = fs::read_link(path)?;
+            let git_object = Self::build(Kind::Blob, target.into_os_string().into_encoded_bytes())?;
+            if write {
+                git_object.write()?;
+            }
+            return Ok(git_object);
+        }
         if path.is_file() {
             let kind = Kind::Blob;
             let contents = fs::read(path)?;
@@ -164,58 +255,88 @@ impl GitObject {
             return Ok(git_object);
         }
         if path.is_dir() {
-            let ignored = [".git"];
-            let mut entries: Vec<_> = fs::read_dir(path)?
-                .filter_map(|entry| {
-                    let entry = entry.ok()?;
-                    let filename: String = entry.file_name().into_string().ok()?;
-                    if ignored.contains(&filename.as_str()) {
-                        return None;
-                    };
-                    let meta = entry.metadata().ok()?;
-                    let mode: String = if meta.is_dir() {
-                        "40000".into()
-                    } else if meta.is_symlink() {
-                        "120000".into()
-                    } else if meta.is_file() {
-                        if (meta.permissions().mode() & 0o100) != 0 {
-                            "100755".into()
-                        } else {
-                            "100644".into()
-                        }
-                    } else {
-                        return None;
-                    };
-                    Some((filename, mode))
-                })
-                .collect();
-            entries.sort_by(|e1, e2| e1.0.cmp(&e2.0));
-            let mut contents = Vec::new();
-            for (filename, mode) in entries {
-                contents.write_all(mode.as_bytes())?;
-                contents.write_all(b" ")?;
-                contents.write_all(filename.as_bytes())?;
-                contents.write_all(b"\0")?;
-                let git_object = Self::from_path(path.join(filename), write)?;
-                contents.write_all(&git_object.hash)?;
-            }
-            let git_object = Self::build(Kind::Tree, contents)?;
-            if write {
-                git_object.write()?;
-            }
-            return Ok(git_object);
+            let mut ignore = Ignore::load()?;
+            let relative = path.to_string_lossy().trim_start_matches("./").to_string();
+            return Self::tree_from_dir(path, &relative, write, &mut ignore);
         }
         Err(GitError::any(format!(
             "Unsupported filesystem object at path: {}",
             path.to_string_lossy()
         )))
     }
+    fn tree_from_dir(
+        path: &Path,
+        relative: &str,
+        write: bool,
+        ignore: &mut Ignore,
+    ) -> Result<Self, GitError> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let Ok(filename) = entry.file_name().into_string() else {
+                continue;
+            };
+            if filename == ".git" {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            let Some(mode) = file_mode(&meta) else {
+                continue;
+            };
+            let entry_relative = if relative.is_empty() || relative == "." {
+                filename.clone()
+            } else {
+                format!("{relative}/{filename}")
+            };
+            if ignore.is_ignored(&entry_relative, meta.is_dir())? {
+                continue;
+            }
+            if meta.is_dir() && submodule::is_repo(entry.path()) {
+                if let Some(head) = submodule::head(entry.path())? {
+                    entries.push(("160000".into(), filename, hex::decode(head)?));
+                }
+                continue;
+            }
+            let git_object = if meta.is_dir() {
+                Self::tree_from_dir(&entry.path(), &entry_relative, write, ignore)?
+            } else {
+                Self::from_path(entry.path(), write)?
+            };
+            entries.push((mode, filename, git_object.hash));
+        }
+        let git_object = Self::build_tree(entries)?;
+        if write {
+            git_object.write()?;
+        }
+        Ok(git_object)
+    }
+    pub fn build_tree(mut entries: Vec<(String, String, Vec<u8>)>) -> Result<Self, GitError> {
+        let sort_key = |(mode, filename, _): &(String, String, Vec<u8>)| {
+            let mut key = filename.clone().into_bytes();
+            if mode == "40000" {
+                key.push(b'/');
+            }
+            key
+        };
+        entries.sort_by_key(sort_key);
+        let mut contents = Vec::new();
+        for (mode, filename, hash) in entries {
+            contents.write_all(mode.as_bytes())?;
+            contents.write_all(b" ")?;
+            contents.write_all(filename.as_bytes())?;
+            contents.write_all(b"\0")?;
+            contents.write_all(&hash)?;
+        }
+        Self::build(Kind::Tree, contents)
+    }
     pub fn write(&self) -> Result<(), GitError> {
+        Self::write_loose(&hex::encode(&self.hash), &self.header, &self.contents)
+    }
+    fn write_loose(hex_string: &str, header: &[u8], contents: &[u8]) -> Result<(), GitError> {
         let mut zlib_encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        zlib_encoder.write_all(&self.header)?;
-        zlib_encoder.write_all(&self.contents)?;
+        zlib_encoder.write_all(header)?;
+        zlib_encoder.write_all(contents)?;
         let compressed_contents = zlib_encoder.finish()?;
-        let hex_string = hex::encode(&self.hash);
         let (prefix, filename) = &hex_string.split_at(2);
         let dir = Self::objects_dir_path().join(prefix);
         if !dir.exists() {
@@ -231,6 +352,9 @@ impl GitObject {
     pub fn contents(&self) -> &Vec<u8> {
         &self.contents
     }
+    pub fn hash(&self) -> &Vec<u8> {
+        &self.hash
+    }
     pub fn hex_string(&self) -> String {
         hex::encode(&self.hash)
     }
@@ -253,6 +377,7 @@ impl GitObject {
                     let kind = match mode.as_str() {
                         "100644" | "100755" | "120000" => Kind::Blob,
                         "40000" => Kind::Tree,
+                        "160000" => Kind::Commit,
                         _ => return Err(GitError::any("unrecognized tree entry")),
                     };
                     entries.push(TreeEntry {
@@ -270,8 +395,9 @@ impl GitObject {
             _ => Err(GitError::any("not a tree object")),
         }
     }
-    pub fn restore(&self, path: impl AsRef<Path>) -> Result<(), GitError> {
+    pub fn tree_hex_string(&self) -> Result<String, GitError> {
         match self.kind {
+            Kind::Tree => Ok(self.hex_string()),
             Kind::Commit => {
                 let Some(Ok(tree_line)) = self.contents.lines().next() else {
                     return Err(GitError::any("Cannot parse tree rev from commit object"));
@@ -279,7 +405,18 @@ impl GitObject {
                 let Some((_, tree_rev)) = tree_line.split_once(' ') else {
                     return Err(GitError::any("Cannot parse tree rev from commit object"));
                 };
-                let tree_object = GitObject::from_hex_string(tree_rev)?;
+                Ok(tree_rev.to_string())
+            }
+            _ => Err(GitError::any(format!(
+                "object {} is not a tree-ish",
+                self.hex_string()
+            ))),
+        }
+    }
+    pub fn restore(&self, path: impl AsRef<Path>) -> Result<(), GitError> {
+        match self.kind {
+            Kind::Commit => {
+                let tree_object = GitObject::from_hex_string(self.tree_hex_string()?)?;
                 tree_object.restore(path)?;
             }
             Kind::Tree => {
@@ -289,12 +426,50 @@ impl GitObject {
                         fs::create_dir(tree_path)?;
                     }
                     let entry_path = PathBuf::from(tree_path).join(entry.filename());
-                    let entry_object = GitObject::from_hex_string(entry.hex_string())?;
-                    entry_object.restore(&entry_path)?;
+                    match entry.mode().as_str() {
+                        "160000" => fs::create_dir_all(&entry_path)?,
+                        "40000" => {
+                            GitObject::from_hex_string(entry.hex_string())?.restore(&entry_path)?
+                        }
+                        mode => GitObject::from_hex_string(entry.hex_string())?
+                            .restore_blob(&entry_path, mode)?,
+                    }
                 }
             }
-            Kind::Blob => {
+            Kind::Blob => self.restore_blob(path, "100644")?,
+            Kind::Tag => {
+                let tag = Tag::parse(&self.contents)?;
+                GitObject::from_hex_string(tag.object())?.restore(path)?;
+            }
+        }
+        Ok(())
+    }
+    /// Writes a blob to `path` as a file of the given tree entry mode: a
+    /// symlink for `120000`, an executable file for `100755`, or a regular
+    /// file. Whatever file was at `path` is replaced.
+    pub fn restore_blob(&self, path: impl AsRef<Path>, mode: &str) -> Result<(), GitError> {
+        let path = path.as_ref();
+        if fs::symlink_metadata(path).is_ok_and(|meta| !meta.is_dir()) {
+            fs::remove_file(path)?;
+        }
+        match mode {
+            "120000" => {
+                let target = std::ffi::OsStr::from_bytes(&self.contents);
+                std::os::unix::fs::symlink(target, path)?;
+            }
+            "100755" => {
                 fs::write(path, &self.contents)?;
+                let mut permissions = fs::metadata(path)?.permissions();
+                let readable = permissions.mode() & 0o444;
+                permissions.set_mode(permissions.mode() | readable >> 2);
+                fs::set_permissions(path, permissions)?;
+            }
+            "100644" => fs::write(path, &self.contents)?,
+            mode => {
+                return Err(GitError::any(format!(
+                    "cannot restore {} with mode {mode}",
+                    path.display()
+                )))
             }
         }
         Ok(())
@@ -0,0 +1,240 @@
+use crate::commit::Signature;
+use crate::config::Config;
+use crate::error::GitError;
+use crate::lockfile::LockFile;
+use crate::reftable::{LogRecord, Stack};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[derive(Clone, Debug)]
+pub struct ReflogEntry {
+    old_hash: String,
+    new_hash: String,
+    identity: Signature,
+    message: String,
+    /// The reftable update index the entry was recorded at; 0 for files.
+    update_index: u64,
+}
+impl ReflogEntry {
+    pub fn new(
+        old_hash: String,
+        new_hash: String,
+        identity: Signature,
+        message: String,
+        update_index: u64,
+    ) -> Self {
+        Self {
+            old_hash,
+            new_hash,
+            identity,
+            message,
+            update_index,
+        }
+    }
+    fn parse(line: &str) -> Result<Self, GitError> {
+        let invalid = || GitError::any(format!("malformed reflog entry: {line}"));
+        let (head, message) = line.split_once('\t').unwrap_or((line, ""));
+        let (old, rest) = head.split_once(' ').ok_or_else(invalid)?;
+        let (new, identity) = rest.split_once(' ').ok_or_else(invalid)?;
+        Ok(Self {
+            old_hash: old.to_string(),
+            new_hash: new.to_string(),
+            identity: Signature::parse(identity)?,
+            message: message.to_string(),
+            update_index: 0,
+        })
+    }
+    pub fn old_hash(&self) -> &String {
+        &self.old_hash
+    }
+    pub fn new_hash(&self) -> &String {
+        &self.new_hash
+    }
+    pub fn identity(&self) -> &Signature {
+        &self.identity
+    }
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+    pub fn set_old_hash(&mut self, old: impl Into<String>) {
+        self.old_hash = old.into();
+    }
+}
+impl std::fmt::Display for ReflogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.old_hash, self.new_hash, self.identity)?;
+        if !self.message.is_empty() {
+            write!(f, "\t{}", self.message)?;
+        }
+        Ok(())
+    }
+}
+fn path(name: &str) -> PathBuf {
+    Path::new(".git/logs").join(name)
+}
+pub fn exists(name: &str) -> bool {
+    match Stack::open() {
+        Ok(Some(stack)) => stack.logs(Some(name)).is_ok_and(|logs| !logs.is_empty()),
+        _ => path(name).is_file(),
+    }
+}
+/// Whether an update to `name` should be logged, following
+/// `core.logAllRefUpdates` (which defaults to true). `refs/stash` is always
+/// logged, since its reflog holds the stash entries.
+pub fn should_log(name: &str) -> Result<bool, GitError> {
+    if exists(name) || name == "refs/stash" {
+        return Ok(true);
+    }
+    let config = Config::load()?;
+    Ok(match config.get("core.logAllRefUpdates") {
+        Some("always") => true,
+        Some("false") => false,
+        _ => {
+            name == "HEAD"
+                || ["refs/heads/", "refs/remotes/", "refs/notes/"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+        }
+    })
+}
+/// Returns the entries recorded for `name`, oldest first.
+pub fn read(name: &str) -> Result<Vec<ReflogEntry>, GitError> {
+    if let Some(stack) = Stack::open()? {
+        let logs = stack.logs(Some(name))?;
+        return Ok(logs.into_iter().rev().filter_map(|r| r.entry).collect());
+    }
+    let path = path(name);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ReflogEntry::parse)
+        .collect()
+}
+/// Builds the entry recording a move of `name` from `old` to `new` by the
+/// current committer. The message is collapsed onto a single line like git
+/// does.
+pub fn record(
+    name: &str,
+    old: &str,
+    new: &str,
+    message: &str,
+    update_index: u64,
+) -> Result<LogRecord, GitError> {
+    let entry = ReflogEntry::new(
+        old.to_string(),
+        new.to_string(),
+        Signature::committer()?,
+        message.split_whitespace().collect::<Vec<_>>().join(" "),
+        update_index,
+    );
+    Ok(LogRecord {
+        name: name.to_string(),
+        update_index,
+        entry: Some(entry),
+    })
+}
+/// Records a move of `name` from `old` to `new`.
+pub fn append(name: &str, old: &str, new: &str, message: &str) -> Result<(), GitError> {
+    if !should_log(name)? {
+        return Ok(());
+    }
+    if Stack::open()?.is_some() {
+        let stack = Stack::lock()?;
+        let log = record(name, old, new, message, stack.next_update_index())?;
+        return stack.add(Vec::new(), vec![log]);
+    }
+    let Some(entry) = record(name, old, new, message, 0)?.entry else {
+        return Ok(());
+    };
+    let path = path(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{entry}")?;
+    Ok(())
+}
+/// Replaces the log of `name` with `entries`, oldest first.
+pub fn write(name: &str, entries: &[ReflogEntry]) -> Result<(), GitError> {
+    if Stack::open()?.is_some() {
+        let stack = Stack::lock()?;
+        let mut logs: Vec<LogRecord> = stack
+            .logs(Some(name))?
+            .into_iter()
+            .filter(|r| !entries.iter().any(|e| e.update_index == r.update_index))
+            .map(|r| LogRecord { entry: None, ..r })
+            .collect();
+        logs.extend(entries.iter().map(|entry| LogRecord {
+            name: name.to_string(),
+            update_index: entry.update_index,
+            entry: Some(entry.clone()),
+        }));
+        return stack.add(Vec::new(), logs);
+    }
+    let mut lock = LockFile::acquire(path(name))?;
+    for entry in entries {
+        lock.write_all(format!("{entry}\n").as_bytes())?;
+    }
+    lock.commit()
+}
+/// Deletion records hiding every entry of `name`, for reftable stacks.
+pub fn tombstones(stack: &Stack, name: &str) -> Result<Vec<LogRecord>, GitError> {
+    Ok(stack
+        .logs(Some(name))?
+        .into_iter()
+        .map(|r| LogRecord { entry: None, ..r })
+        .collect())
+}
+pub fn remove(name: &str) -> Result<(), GitError> {
+    if Stack::open()?.is_some() {
+        let stack = Stack::lock()?;
+        let logs = tombstones(&stack, name)?;
+        if logs.is_empty() {
+            return Ok(());
+        }
+        return stack.add(Vec::new(), logs);
+    }
+    let path = path(name);
+    if path.is_file() {
+        fs::remove_file(&path)?;
+    }
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        if parent.ends_with("logs") || fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+/// Lists every ref that has a log, sorted by name.
+pub fn list() -> Result<Vec<String>, GitError> {
+    fn walk(dir: &Path, name: &str, names: &mut Vec<String>) -> Result<(), GitError> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = match name {
+                "" => file_name,
+                name => format!("{name}/{file_name}"),
+            };
+            if entry.file_type()?.is_dir() {
+                walk(&entry.path(), &name, names)?;
+            } else if !name.ends_with(".lock") {
+                names.push(name);
+            }
+        }
+        Ok(())
+    }
+    let mut names = Vec::new();
+    if let Some(stack) = Stack::open()? {
+        names = stack.logs(None)?.into_iter().map(|r| r.name).collect();
+    } else if Path::new(".git/logs").is_dir() {
+        walk(Path::new(".git/logs"), "", &mut names)?;
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
@@ -0,0 +1,536 @@
+use crate::config::Config;
+use crate::error::GitError;
+use crate::lockfile::LockFile;
+use crate::reflog;
+use crate::reftable::{RefRecord, RefValue, Stack};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+pub const ZERO_HASH: &str = "0000000000000000000000000000000000000000";
+const MAX_SYMREF_DEPTH: usize = 5;
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Direct(String),
+    Symbolic(String),
+}
+#[derive(Clone, Debug)]
+struct PackedRef {
+    name: String,
+    hash: String,
+    peeled: Option<String>,
+}
+/// Opens the reftable stack when it holds `name`. The pseudorefs written
+/// by fetch and merge always stay files in `.git`.
+fn reftable(name: &str) -> Result<Option<Stack>, GitError> {
+    if matches!(name, "FETCH_HEAD" | "MERGE_HEAD") {
+        return Ok(None);
+    }
+    Stack::open()
+}
+impl From<RefValue> for Option<Target> {
+    fn from(value: RefValue) -> Self {
+        match value {
+            RefValue::Deletion => None,
+            RefValue::Direct { hash, .. } => Some(Target::Direct(hash)),
+            RefValue::Symbolic(target) => Some(Target::Symbolic(target)),
+        }
+    }
+}
+fn ref_path(name: &str) -> PathBuf {
+    Path::new(".git").join(name)
+}
+/// Rejects ref names git would refuse, following `git check-ref-format`.
+pub fn check_name(name: &str) -> Result<(), GitError> {
+    let invalid = || GitError::any(format!("'{name}' is not a valid ref name"));
+    if name == "HEAD" || name.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+        return if name.is_empty() {
+            Err(invalid())
+        } else {
+            Ok(())
+        };
+    }
+    if !name.starts_with("refs/")
+        || name.ends_with('/')
+        || name.ends_with('.')
+        || name.contains("..")
+        || name.contains("//")
+        || name.contains("@{")
+        || name.chars().any(|c| {
+            c.is_ascii_control() || matches!(c, ' ' | '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+        })
+    {
+        return Err(invalid());
+    }
+    for component in name.split('/') {
+        if component.starts_with('.') || component.ends_with(".lock") {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+/// Strips the usual `refs/heads/`, `refs/tags/` or `refs/remotes/` prefix
+/// for display, like `--short` does.
+pub fn shorten(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+fn parse_target(contents: &str) -> Target {
+    let contents = contents.trim();
+    match contents.strip_prefix("ref: ") {
+        Some(target) => Target::Symbolic(target.trim().to_string()),
+        None => Target::Direct(contents.to_string()),
+    }
+}
+fn read_loose(name: &str) -> Result<Option<Target>, GitError> {
+    let path = ref_path(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(parse_target(&fs::read_to_string(path)?)))
+}
+fn packed() -> Result<Vec<PackedRef>, GitError> {
+    let path = Path::new(".git/packed-refs");
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let mut refs: Vec<PackedRef> = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        if let Some(peeled) = line.strip_prefix('^') {
+            let last = refs
+                .last_mut()
+                .ok_or(GitError::any("packed-refs: peeled line without a ref"))?;
+            last.peeled = Some(peeled.to_string());
+            continue;
+        }
+        let (hash, name) = line
+            .split_once(' ')
+            .ok_or_else(|| GitError::any(format!("packed-refs: unexpected line '{line}'")))?;
+        refs.push(PackedRef {
+            name: name.to_string(),
+            hash: hash.to_string(),
+            peeled: None,
+        });
+    }
+    Ok(refs)
+}
+fn write_packed(refs: &[PackedRef]) -> Result<(), GitError> {
+    let mut lock = LockFile::acquire(".git/packed-refs")?;
+    let mut contents = String::from("# pack-refs with: peeled fully-peeled sorted \n");
+    for r in refs {
+        contents.push_str(&format!("{} {}\n", r.hash, r.name));
+        if let Some(peeled) = &r.peeled {
+            contents.push_str(&format!("^{peeled}\n"));
+        }
+    }
+    lock.write_all(contents.as_bytes())?;
+    lock.commit()
+}
+/// Reads a ref without following symbolic refs, preferring the loose file
+/// over its `packed-refs` entry.
+pub fn read(name: &str) -> Result<Option<Target>, GitError> {
+    if let Some(stack) = reftable(name)? {
+        return Ok(stack.read_ref(name)?.and_then(Option::from));
+    }
+    if let Some(target) = read_loose(name)? {
+        return Ok(Some(target));
+    }
+    Ok(packed()?
+        .into_iter()
+        .find(|r| r.name == name)
+        .map(|r| Target::Direct(r.hash)))
+}
+/// Follows symbolic refs from `name`, returning the name of the ref that
+/// finally holds (or would hold) a hash, and that hash if it exists.
+pub fn resolve_name(name: &str) -> Result<(String, Option<String>), GitError> {
+    let mut name = name.to_string();
+    let mut seen = HashSet::new();
+    loop {
+        if !seen.insert(name.clone()) || seen.len() > MAX_SYMREF_DEPTH {
+            return Err(GitError::any(format!(
+                "symbolic ref cycle detected at '{name}'"
+            )));
+        }
+        match read(&name)? {
+            Some(Target::Symbolic(target)) => name = target,
+            Some(Target::Direct(hash)) => return Ok((name, Some(hash))),
+            None => return Ok((name, None)),
+        }
+    }
+}
+/// Resolves a full ref name to an object hash, following symbolic refs.
+pub fn resolve(name: &str) -> Result<Option<String>, GitError> {
+    Ok(resolve_name(name)?.1)
+}
+/// Returns the branch HEAD points at, or `None` when HEAD is detached.
+pub fn head_branch() -> Result<Option<String>, GitError> {
+    Ok(match read("HEAD")? {
+        Some(Target::Symbolic(name)) => Some(
+            name.strip_prefix("refs/heads/")
+                .unwrap_or(&name)
+                .to_string(),
+        ),
+        _ => None,
+    })
+}
+fn list_loose(dir: &Path, name: &str, refs: &mut BTreeMap<String, Target>) -> Result<(), GitError> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let name = format!("{name}/{file_name}");
+        if entry.file_type()?.is_dir() {
+            list_loose(&entry.path(), &name, refs)?;
+        } else if !file_name.ends_with(".lock") {
+            refs.insert(name, parse_target(&fs::read_to_string(entry.path())?));
+        }
+    }
+    Ok(())
+}
+/// Lists every ref under `refs/` whose name starts with `prefix`, sorted by
+/// name, with symbolic refs resolved. Dangling symbolic refs are skipped.
+pub fn list(prefix: &str) -> Result<Vec<(String, String)>, GitError> {
+    let mut refs = BTreeMap::new();
+    if let Some(stack) = Stack::open()? {
+        for (name, value) in stack.refs()? {
+            if let (true, Some(target)) = (name.starts_with("refs/"), value.into()) {
+                refs.insert(name, target);
+            }
+        }
+    } else {
+        for r in packed()? {
+            refs.insert(r.name, Target::Direct(r.hash));
+        }
+        list_loose(Path::new(".git/refs"), "refs", &mut refs)?;
+    }
+    let mut resolved = Vec::new();
+    for (name, target) in refs {
+        if !name.starts_with(prefix) {
+            continue;
+        }
+        let hash = match target {
+            Target::Direct(hash) => Some(hash),
+            Target::Symbolic(target) => resolve(&target)?,
+        };
+        if let Some(hash) = hash {
+            resolved.push((name, hash));
+        }
+    }
+    Ok(resolved)
+}
+/// Returns the peeled value recorded for `name` in `packed-refs` or the
+/// reftable stack, if any.
+pub fn peeled(name: &str) -> Result<Option<String>, GitError> {
+    if let Some(stack) = reftable(name)? {
+        return Ok(match stack.read_ref(name)? {
+            Some(RefValue::Direct { peeled, .. }) => peeled,
+            _ => None,
+        });
+    }
+    Ok(packed()?
+        .into_iter()
+        .find(|r| r.name == name)
+        .and_then(|r| r.peeled))
+}
+fn check_old(name: &str, current: Option<&String>, old: Option<&str>) -> Result<(), GitError> {
+    match (old, current) {
+        (None, _) => Ok(()),
+        (Some(ZERO_HASH), None) => Ok(()),
+        (Some(ZERO_HASH), Some(_)) => Err(GitError::any(format!(
+            "cannot lock ref '{name}': reference already exists"
+        ))),
+        (Some(old), Some(current)) if old == current => Ok(()),
+        (Some(old), Some(current)) => Err(GitError::any(format!(
+            "cannot lock ref '{name}': is at {current} but expected {old}"
+        ))),
+        (Some(_), None) => Err(GitError::any(format!(
+            "cannot lock ref '{name}': unable to resolve reference '{name}'"
+        ))),
+    }
+}
+fn check_conflicts(name: &str) -> Result<(), GitError> {
+    check_available(name, None).map_err(|e| GitError::any(format!("cannot lock ref '{name}': {e}")))
+}
+fn write_loose(name: &str, contents: &str, lock: LockFile) -> Result<(), GitError> {
+    let mut lock = lock;
+    let path = ref_path(name);
+    if path.is_dir() {
+        fs::remove_dir(&path)?;
+    }
+    lock.write_all(contents.as_bytes())?;
+    lock.commit()
+}
+/// Atomically points `name` at `new`, recording the move with `message`.
+/// See `store`.
+pub fn update(
+    name: &str,
+    new: &str,
+    old: Option<&str>,
+    deref: bool,
+    message: &str,
+) -> Result<(), GitError> {
+    store(name, new, old, deref, Some(message))
+}
+/// Atomically points `name` at `new`. With `deref`, symbolic refs are
+/// followed and the ref they point to is updated instead. `old` is checked
+/// against the current value under the lock; the all-zero hash means the
+/// ref must not exist yet. With a message, the move is recorded in the
+/// ref's reflog, and in HEAD's when HEAD points at the updated ref.
+fn store(
+    name: &str,
+    new: &str,
+    old: Option<&str>,
+    deref: bool,
+    message: Option<&str>,
+) -> Result<(), GitError> {
+    let name = match deref {
+        true => resolve_name(name)?.0,
+        false => name.to_string(),
+    };
+    check_name(&name)?;
+    check_conflicts(&name)?;
+    let current = |name: &str| -> Result<Option<String>, GitError> {
+        Ok(match read(name)? {
+            Some(Target::Direct(hash)) => Some(hash),
+            Some(Target::Symbolic(target)) => resolve(&target)?,
+            None => None,
+        })
+    };
+    let logs_head =
+        name != "HEAD" && matches!(read("HEAD")?, Some(Target::Symbolic(head)) if head == name);
+    if reftable(&name)?.is_some() {
+        let stack = Stack::lock()?;
+        let current = current(&name)?;
+        check_old(&name, current.as_ref(), old)?;
+        let update_index = stack.next_update_index();
+        let current = current.as_deref().unwrap_or(ZERO_HASH);
+        let mut logs = Vec::new();
+        for log in [name.as_str(), "HEAD"]
+            .into_iter()
+            .filter(|_| message.is_some())
+        {
+            if (log == name || logs_head) && reflog::should_log(log)? {
+                let message = message.unwrap_or_default();
+                logs.push(reflog::record(log, current, new, message, update_index)?);
+            }
+        }
+        let record = RefRecord {
+            name,
+            update_index,
+            value: RefValue::Direct {
+                hash: new.to_string(),
+                peeled: None,
+            },
+        };
+        return stack.add(vec![record], logs);
+    }
+    let lock = LockFile::acquire(ref_path(&name))?;
+    let current = current(&name)?;
+    check_old(&name, current.as_ref(), old)?;
+    write_loose(&name, &format!("{new}\n"), lock)?;
+    let Some(message) = message else {
+        return Ok(());
+    };
+    let current = current.as_deref().unwrap_or(ZERO_HASH);
+    reflog::append(&name, current, new, message)?;
+    if logs_head {
+        reflog::append("HEAD", current, new, message)?;
+    }
+    Ok(())
+}
+/// Points `name` at another ref, creating or replacing a symbolic ref. With
+/// a message, the change of the resolved value is recorded in the reflog.
+pub fn set_symbolic(name: &str, target: &str, message: Option<&str>) -> Result<(), GitError> {
+    check_name(name)?;
+    check_name(target)?;
+    check_conflicts(name)?;
+    if reftable(name)?.is_some() {
+        let stack = Stack::lock()?;
+        let update_index = stack.next_update_index();
+        let mut logs = Vec::new();
+        if let (Some(message), true) = (message, reflog::should_log(name)?) {
+            let (old, new) = (resolve(name)?, resolve(target)?);
+            logs.push(reflog::record(
+                name,
+                old.as_deref().unwrap_or(ZERO_HASH),
+                new.as_deref().unwrap_or(ZERO_HASH),
+                message,
+                update_index,
+            )?);
+        }
+        let record = RefRecord {
+            name: name.to_string(),
+            update_index,
+            value: RefValue::Symbolic(target.to_string()),
+        };
+        return stack.add(vec![record], logs);
+    }
+    let lock = LockFile::acquire(ref_path(name))?;
+    let old = resolve(name)?;
+    write_loose(name, &format!("ref: {target}\n"), lock)?;
+    if let Some(message) = message {
+        let new = resolve(target)?;
+        reflog::append(
+            name,
+            old.as_deref().unwrap_or(ZERO_HASH),
+            new.as_deref().unwrap_or(ZERO_HASH),
+            message,
+        )?;
+    }
+    Ok(())
+}
+/// Deletes `name` from both the loose refs and `packed-refs`, or records
+/// its deletion in the reftable stack, checking `old` like `update` does.
+pub fn delete(name: &str, old: Option<&str>, deref: bool) -> Result<(), GitError> {
+    let name = match deref {
+        true => resolve_name(name)?.0,
+        false => name.to_string(),
+    };
+    let current = || -> Result<Option<String>, GitError> {
+        match read(&name)? {
+            Some(Target::Direct(hash)) => Ok(Some(hash)),
+            Some(Target::Symbolic(target)) => Ok(resolve(&target)?.or(Some(target))),
+            None => Err(GitError::any(format!("ref '{name}' does not exist"))),
+        }
+    };
+    if reftable(&name)?.is_some() {
+        let stack = Stack::lock()?;
+        check_old(&name, current()?.as_ref(), old)?;
+        let logs = reflog::tombstones(&stack, &name)?;
+        let record = RefRecord {
+            update_index: stack.next_update_index(),
+            name,
+            value: RefValue::Deletion,
+        };
+        return stack.add(vec![record], logs);
+    }
+    let lock = LockFile::acquire(ref_path(&name))?;
+    check_old(&name, current()?.as_ref(), old)?;
+    let mut packed = packed()?;
+    if packed.iter().any(|r| r.name == name) {
+        packed.retain(|r| r.name != name);
+        write_packed(&packed)?;
+    }
+    let path = ref_path(&name);
+    if path.is_file() {
+        fs::remove_file(&path)?;
+    }
+    drop(lock);
+    reflog::remove(&name)?;
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        let top_level = parent.parent().is_some_and(|p| p.ends_with("refs"));
+        if top_level || parent.ends_with("refs") || fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+/// Checks that `name` can be created next to the existing refs: no ref may
+/// be named by a leading part of it, and none may nest under it. `skip` is
+/// a ref that is about to go away and does not count.
+pub fn check_available(name: &str, skip: Option<&str>) -> Result<(), GitError> {
+    let taken =
+        |existing: &str| GitError::any(format!("'{existing}' exists; cannot create '{name}'"));
+    let mut prefix = String::new();
+    for component in name.split('/') {
+        if !prefix.is_empty()
+            && prefix != "refs"
+            && Some(prefix.as_str()) != skip
+            && read(&prefix)?.is_some()
+        {
+            return Err(taken(&prefix));
+        }
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        prefix.push_str(component);
+    }
+    let nested = list(&format!("{name}/"))?;
+    match nested.iter().find(|(r, _)| Some(r.as_str()) != skip) {
+        Some((existing, _)) => Err(taken(existing)),
+        None => Ok(()),
+    }
+}
+/// Creates `name` at `hash` with `entries` as its reflog, logging `message`
+/// on top. The ref's lock is held while its log is written.
+fn create(
+    name: &str,
+    hash: &str,
+    entries: &[reflog::ReflogEntry],
+    message: Option<&str>,
+) -> Result<(), GitError> {
+    let write_log = || -> Result<(), GitError> {
+        if !entries.is_empty() {
+            reflog::write(name, entries)?;
+        }
+        match message {
+            Some(message) => reflog::append(name, hash, hash, message),
+            None => Ok(()),
+        }
+    };
+    if reftable(name)?.is_some() {
+        write_log()?;
+        return store(name, hash, Some(ZERO_HASH), false, None);
+    }
+    check_conflicts(name)?;
+    let lock = LockFile::acquire(ref_path(name))?;
+    check_old(name, resolve(name)?.as_ref(), Some(ZERO_HASH))?;
+    write_log()?;
+    write_loose(name, &format!("{hash}\n"), lock)
+}
+/// Moves ref `old` to `new` together with its reflog, or copies both when
+/// `copy` is set, logging `message` on the new ref. HEAD follows a renamed
+/// branch it points at. An existing `new` is replaced, but `new` must not
+/// clash with other refs; see `check_available`.
+pub fn rename(old: &str, new: &str, message: &str, copy: bool) -> Result<(), GitError> {
+    check_name(new)?;
+    let hash = resolve(old)?.ok_or_else(|| GitError::any(format!("ref '{old}' does not exist")))?;
+    if old != new {
+        check_available(new, Some(old).filter(|_| !copy))?;
+    }
+    let entries = reflog::read(old)?;
+    let head_follows =
+        !copy && matches!(read("HEAD")?, Some(Target::Symbolic(head)) if head == old);
+    if old != new && read(new)?.is_some() {
+        delete(new, None, false)?;
+    }
+    // When one name nests under the other, `new` can only be written once
+    // `old` is gone; `old` is put back if that fails.
+    let nested = [(old, new), (new, old)]
+        .iter()
+        .any(|(outer, inner)| inner.starts_with(&format!("{outer}/")));
+    if !copy && (old == new || nested) {
+        delete(old, Some(&hash), false)?;
+        if let Err(err) = create(new, &hash, &entries, Some(message)) {
+            create(old, &hash, &entries, None)?;
+            return Err(err);
+        }
+    } else {
+        create(new, &hash, &entries, Some(message))?;
+        if !copy {
+            delete(old, Some(&hash), false)?;
+        }
+    }
+    if head_follows {
+        set_symbolic("HEAD", new, None)?;
+        reflog::append("HEAD", &hash, ZERO_HASH, message)?;
+        reflog::append("HEAD", ZERO_HASH, &hash, message)?;
+    }
+    Ok(())
+}
+/// The branch a new repository starts on: `init.defaultBranch`, or `main`.
+pub fn default_branch() -> Result<String, GitError> {
+    let config = Config::load()?;
+    Ok(config
+        .get("init.defaultBranch")
+        .unwrap_or("main")
+        .to_string())
+}
@@ -0,0 +1,1154 @@
+use crate::commit::Signature;
+use crate::config::Config;
+use crate::date::offset_seconds;
+use crate::error::GitError;
+use crate::lockfile::LockFile;
+use crate::reflog::ReflogEntry;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc, Decompress, FlushDecompress, Status};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+const REFTABLE_DIR: &str = ".git/reftable";
+const MAGIC: &[u8; 4] = b"REFT";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 24;
+const FOOTER_SIZE: usize = 68;
+const BLOCK_SIZE: usize = 4096;
+const RESTART_INTERVAL: usize = 16;
+/// Levels of index blocks are added until the top one fits in this many.
+const MAX_INDEX_BLOCKS: usize = 3;
+const HASH_SIZE: usize = 20;
+const BLOCK_REF: u8 = b'r';
+const BLOCK_LOG: u8 = b'g';
+const BLOCK_INDEX: u8 = b'i';
+#[derive(Clone, Debug, PartialEq)]
+pub enum RefValue {
+    Deletion,
+    Direct {
+        hash: String,
+        peeled: Option<String>,
+    },
+    Symbolic(String),
+}
+#[derive(Clone, Debug)]
+pub struct RefRecord {
+    pub name: String,
+    pub update_index: u64,
+    pub value: RefValue,
+}
+/// A reflog entry keyed by ref name and update index; `None` is a deletion
+/// that hides the same key in older tables.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub name: String,
+    pub update_index: u64,
+    pub entry: Option<ReflogEntry>,
+}
+fn corrupt() -> GitError {
+    GitError::any("reftable: corrupt table")
+}
+/// Whether the repository keeps its refs in reftables rather than files.
+pub fn enabled() -> Result<bool, GitError> {
+    Ok(Config::load()?.get("extensions.refStorage") == Some("reftable"))
+}
+/// Git's offset varint: each continuation byte also adds one, so every
+/// value has exactly one encoding.
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    let mut buf = [0u8; 10];
+    let mut pos = buf.len() - 1;
+    buf[pos] = (value & 0x7f) as u8;
+    value >>= 7;
+    while value != 0 {
+        value -= 1;
+        pos -= 1;
+        buf[pos] = 0x80 | (value & 0x7f) as u8;
+        value >>= 7;
+    }
+    out.extend_from_slice(&buf[pos..]);
+}
+fn get_varint(data: &[u8], pos: &mut usize) -> Result<u64, GitError> {
+    let mut byte = *data.get(*pos).ok_or_else(corrupt)?;
+    *pos += 1;
+    let mut value = u64::from(byte & 0x7f);
+    while byte & 0x80 != 0 {
+        byte = *data.get(*pos).ok_or_else(corrupt)?;
+        *pos += 1;
+        value = value
+            .checked_add(1)
+            .and_then(|v| v.checked_mul(128))
+            .ok_or_else(corrupt)?
+            | u64::from(byte & 0x7f);
+    }
+    Ok(value)
+}
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], GitError> {
+    let bytes = data.get(*pos..*pos + len).ok_or_else(corrupt)?;
+    *pos += len;
+    Ok(bytes)
+}
+fn be24(bytes: &[u8]) -> usize {
+    (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize
+}
+fn put_be24(out: &mut [u8], value: usize) {
+    out[..3].copy_from_slice(&(value as u32).to_be_bytes()[1..]);
+}
+fn log_key(name: &str, update_index: u64) -> Vec<u8> {
+    let mut key = name.as_bytes().to_vec();
+    key.push(0);
+    key.extend_from_slice(&(u64::MAX - update_index).to_be_bytes());
+    key
+}
+fn parse_log_key(key: &[u8]) -> Result<(String, u64), GitError> {
+    let split = key.len().checked_sub(9).ok_or_else(corrupt)?;
+    if key[split] != 0 {
+        return Err(corrupt());
+    }
+    let reversed = u64::from_be_bytes(key[split + 1..].try_into().map_err(|_| corrupt())?);
+    Ok((
+        String::from_utf8(key[..split].to_vec())?,
+        u64::MAX - reversed,
+    ))
+}
+fn format_tz(minutes: i16) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.unsigned_abs();
+    format!("{sign}{:02}{:02}", minutes / 60, minutes % 60)
+}
+struct BlockWriter {
+    buf: Vec<u8>,
+    header_off: usize,
+    restarts: Vec<usize>,
+    last_key: Vec<u8>,
+    entries: usize,
+}
+impl BlockWriter {
+    /// Starts a block; the first block of a file also carries the file
+    /// header, which counts towards its length and restart offsets.
+    fn new(kind: u8, file_header: &[u8]) -> Self {
+        let mut buf = file_header.to_vec();
+        buf.extend_from_slice(&[kind, 0, 0, 0]);
+        Self {
+            buf,
+            header_off: file_header.len(),
+            restarts: Vec::new(),
+            last_key: Vec::new(),
+            entries: 0,
+        }
+    }
+    /// Appends a record, returning false when it would overflow the block.
+    /// An empty block always takes the record.
+    fn add(&mut self, key: &[u8], value_type: u8, value: &[u8]) -> bool {
+        let restart = self.entries % RESTART_INTERVAL == 0;
+        let prefix = match restart {
+            true => 0,
+            false => self
+                .last_key
+                .iter()
+                .zip(key)
+                .take_while(|(a, b)| a == b)
+                .count(),
+        };
+        let mut record = Vec::new();
+        put_varint(&mut record, prefix as u64);
+        put_varint(
+            &mut record,
+            ((key.len() - prefix) as u64) << 3 | u64::from(value_type),
+        );
+        record.extend_from_slice(&key[prefix..]);
+        record.extend_from_slice(value);
+        let restarts = self.restarts.len() + usize::from(restart);
+        if self.entries > 0 && self.buf.len() + record.len() + 3 * restarts + 2 > BLOCK_SIZE {
+            return false;
+        }
+        if restart {
+            self.restarts.push(self.buf.len());
+        }
+        self.buf.extend_from_slice(&record);
+        self.last_key = key.to_vec();
+        self.entries += 1;
+        true
+    }
+    fn finish(mut self) -> Vec<u8> {
+        for &offset in &self.restarts {
+            let mut bytes = [0u8; 3];
+            put_be24(&mut bytes, offset);
+            self.buf.extend_from_slice(&bytes);
+        }
+        self.buf
+            .extend_from_slice(&(self.restarts.len() as u16).to_be_bytes());
+        let len = self.buf.len();
+        put_be24(&mut self.buf[self.header_off + 1..], len);
+        self.buf
+    }
+}
+struct TableWriter {
+    header: Vec<u8>,
+    out: Vec<u8>,
+}
+type RawRecord = (Vec<u8>, u8, Vec<u8>);
+impl TableWriter {
+    fn file_header(&self) -> &[u8] {
+        match self.out.is_empty() {
+            true => &self.header,
+            false => &[],
+        }
+    }
+    /// Appends a finished block. Log blocks are deflated after their
+    /// header; all other blocks are padded to the block size.
+    fn flush(&mut self, kind: u8, writer: BlockWriter) -> Result<(Vec<u8>, usize), GitError> {
+        let pos = self.out.len();
+        let header_end = writer.header_off + 4;
+        let last_key = writer.last_key.clone();
+        let block = writer.finish();
+        if kind == BLOCK_LOG {
+            self.out.extend_from_slice(&block[..header_end]);
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&block[header_end..])?;
+            self.out.extend_from_slice(&encoder.finish()?);
+        } else {
+            self.out.extend_from_slice(&block);
+            if self.out.len() < pos + BLOCK_SIZE {
+                self.out.resize(pos + BLOCK_SIZE, 0);
+            }
+        }
+        Ok((last_key, pos))
+    }
+    /// Writes sorted records as blocks of `kind`, returning the last key and
+    /// position of each block.
+    fn write_blocks(
+        &mut self,
+        kind: u8,
+        records: &[RawRecord],
+    ) -> Result<Vec<(Vec<u8>, usize)>, GitError> {
+        let mut blocks = Vec::new();
+        let mut writer = BlockWriter::new(kind, self.file_header());
+        for (key, value_type, value) in records {
+            if writer.add(key, *value_type, value) {
+                continue;
+            }
+            blocks.push(self.flush(kind, writer)?);
+            writer = BlockWriter::new(kind, self.file_header());
+            writer.add(key, *value_type, value);
+        }
+        if writer.entries > 0 {
+            blocks.push(self.flush(kind, writer)?);
+        }
+        Ok(blocks)
+    }
+    /// Writes index levels over `blocks` until the top level is small,
+    /// returning its position, or 0 when no index is needed.
+    fn write_index(&mut self, mut blocks: Vec<(Vec<u8>, usize)>) -> Result<u64, GitError> {
+        let mut index_pos = 0;
+        while blocks.len() > MAX_INDEX_BLOCKS {
+            let records: Vec<RawRecord> = blocks
+                .into_iter()
+                .map(|(key, pos)| {
+                    let mut value = Vec::new();
+                    put_varint(&mut value, pos as u64);
+                    (key, 0, value)
+                })
+                .collect();
+            index_pos = self.out.len() as u64;
+            blocks = self.write_blocks(BLOCK_INDEX, &records)?;
+        }
+        Ok(index_pos)
+    }
+}
+fn encode_ref(record: &RefRecord, min_update_index: u64) -> Result<RawRecord, GitError> {
+    let mut value = Vec::new();
+    put_varint(&mut value, record.update_index - min_update_index);
+    let value_type = match &record.value {
+        RefValue::Deletion => 0,
+        RefValue::Direct { hash, peeled } => {
+            value.extend_from_slice(&hex::decode(hash)?);
+            match peeled {
+                Some(peeled) => {
+                    value.extend_from_slice(&hex::decode(peeled)?);
+                    2
+                }
+                None => 1,
+            }
+        }
+        RefValue::Symbolic(target) => {
+            put_varint(&mut value, target.len() as u64);
+            value.extend_from_slice(target.as_bytes());
+            3
+        }
+    };
+    Ok((record.name.as_bytes().to_vec(), value_type, value))
+}
+fn encode_log(record: &LogRecord) -> Result<RawRecord, GitError> {
+    let key = log_key(&record.name, record.update_index);
+    let Some(entry) = &record.entry else {
+        return Ok((key, 0, Vec::new()));
+    };
+    let mut value = Vec::new();
+    value.extend_from_slice(&hex::decode(entry.old_hash())?);
+    value.extend_from_slice(&hex::decode(entry.new_hash())?);
+    let identity = entry.identity();
+    for field in [identity.name(), identity.email()] {
+        put_varint(&mut value, field.len() as u64);
+        value.extend_from_slice(field.as_bytes());
+    }
+    put_varint(&mut value, identity.time().max(0) as u64);
+    let minutes = (offset_seconds(identity.offset()) / 60) as i16;
+    value.extend_from_slice(&minutes.to_be_bytes());
+    put_varint(&mut value, entry.message().len() as u64);
+    value.extend_from_slice(entry.message().as_bytes());
+    Ok((key, 1, value))
+}
+/// Serializes one table: ref blocks, their index, log blocks, their index
+/// and the footer. Records need not be sorted.
+fn write_table(
+    min_update_index: u64,
+    max_update_index: u64,
+    refs: &[RefRecord],
+    logs: &[LogRecord],
+) -> Result<Vec<u8>, GitError> {
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    let mut block_size = [0u8; 3];
+    put_be24(&mut block_size, BLOCK_SIZE);
+    header.extend_from_slice(&block_size);
+    header.extend_from_slice(&min_update_index.to_be_bytes());
+    header.extend_from_slice(&max_update_index.to_be_bytes());
+    let mut writer = TableWriter {
+        header,
+        out: Vec::new(),
+    };
+    let mut ref_records = refs
+        .iter()
+        .map(|r| encode_ref(r, min_update_index))
+        .collect::<Result<Vec<_>, _>>()?;
+    ref_records.sort_by(|a, b| a.0.cmp(&b.0));
+    let blocks = writer.write_blocks(BLOCK_REF, &ref_records)?;
+    let ref_index_pos = writer.write_index(blocks)?;
+    let log_pos = writer.out.len() as u64;
+    let mut log_records = logs.iter().map(encode_log).collect::<Result<Vec<_>, _>>()?;
+    log_records.sort_by(|a, b| a.0.cmp(&b.0));
+    let blocks = writer.write_blocks(BLOCK_LOG, &log_records)?;
+    let log_index_pos = writer.write_index(blocks)?;
+    let TableWriter { header, mut out } = writer;
+    if out.is_empty() {
+        out.extend_from_slice(&header);
+    }
+    let mut footer = header;
+    footer.extend_from_slice(&ref_index_pos.to_be_bytes());
+    footer.extend_from_slice(&0u64.to_be_bytes());
+    footer.extend_from_slice(&0u64.to_be_bytes());
+    footer.extend_from_slice(&match logs.is_empty() {
+        true => 0u64.to_be_bytes(),
+        false => log_pos.to_be_bytes(),
+    });
+    footer.extend_from_slice(&log_index_pos.to_be_bytes());
+    let mut crc = Crc::new();
+    crc.update(&footer);
+    footer.extend_from_slice(&crc.sum().to_be_bytes());
+    out.extend_from_slice(&footer);
+    Ok(out)
+}
+struct Block {
+    kind: u8,
+    data: Vec<u8>,
+    header_off: usize,
+    restarts: Vec<usize>,
+    /// Where the records end and the restart table begins.
+    end: usize,
+    /// File position of the following block.
+    next: usize,
+}
+enum Value {
+    Ref(RefValue, u64),
+    Log(Option<ReflogEntry>),
+    Index(usize),
+}
+struct Record {
+    key: Vec<u8>,
+    value: Value,
+}
+impl Block {
+    fn first_record(&self) -> usize {
+        self.header_off + 4
+    }
+    fn key_at(&self, mut pos: usize) -> Result<&[u8], GitError> {
+        get_varint(&self.data, &mut pos)?;
+        let suffix = get_varint(&self.data, &mut pos)? >> 3;
+        take(&self.data, &mut pos, suffix as usize)
+    }
+    /// Finds the last restart point whose key is not past `key`, where a
+    /// linear scan for `key` can begin.
+    fn restart_for(&self, key: &[u8]) -> Result<usize, GitError> {
+        let (mut low, mut high) = (0, self.restarts.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if self.key_at(self.restarts[mid])? <= key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(match low {
+            0 => self.first_record(),
+            low => self.restarts[low - 1],
+        })
+    }
+    fn decode(
+        &self,
+        pos: &mut usize,
+        last_key: &[u8],
+        min_update_index: u64,
+    ) -> Result<Record, GitError> {
+        let data = &self.data;
+        let prefix = get_varint(data, pos)? as usize;
+        let suffix_and_type = get_varint(data, pos)?;
+        let value_type = (suffix_and_type & 7) as u8;
+        let mut key = last_key.get(..prefix).ok_or_else(corrupt)?.to_vec();
+        key.extend_from_slice(take(data, pos, (suffix_and_type >> 3) as usize)?);
+        let hash = |pos: &mut usize| take(data, pos, HASH_SIZE).map(hex::encode);
+        let value = match self.kind {
+            BLOCK_REF => {
+                let update_index = min_update_index + get_varint(data, pos)?;
+                let value = match value_type {
+                    0 => RefValue::Deletion,
+                    1 => RefValue::Direct {
+                        hash: hash(pos)?,
+                        peeled: None,
+                    },
+                    2 => RefValue::Direct {
+                        hash: hash(pos)?,
+                        peeled: Some(hash(pos)?),
+                    },
+                    3 => {
+                        let len = get_varint(data, pos)? as usize;
+                        RefValue::Symbolic(String::from_utf8(take(data, pos, len)?.to_vec())?)
+                    }
+                    _ => return Err(corrupt()),
+                };
+                Value::Ref(value, update_index)
+            }
+            BLOCK_LOG if value_type == 0 => Value::Log(None),
+            BLOCK_LOG => {
+                let (old, new) = (hash(pos)?, hash(pos)?);
+                let string = |pos: &mut usize| -> Result<String, GitError> {
+                    let len = get_varint(data, pos)? as usize;
+                    Ok(String::from_utf8(take(data, pos, len)?.to_vec())?)
+                };
+                let (name, email) = (string(pos)?, string(pos)?);
+                let time = get_varint(data, pos)? as i64;
+                let tz = i16::from_be_bytes(take(data, pos, 2)?.try_into().map_err(|_| corrupt())?);
+                let message = string(pos)?;
+                let (_, update_index) = parse_log_key(&key)?;
+                let identity = Signature::new(name, email, time, &format_tz(tz));
+                Value::Log(Some(ReflogEntry::new(
+                    old,
+                    new,
+                    identity,
+                    message,
+                    update_index,
+                )))
+            }
+            BLOCK_INDEX => Value::Index(get_varint(data, pos)? as usize),
+            _ => return Err(corrupt()),
+        };
+        Ok(Record { key, value })
+    }
+}
+/// A single immutable reftable file, held in memory.
+pub struct Table {
+    data: Vec<u8>,
+    min_update_index: u64,
+    max_update_index: u64,
+    ref_start: Option<usize>,
+    ref_index: usize,
+    log_start: Option<usize>,
+    log_index: usize,
+    /// Where the footer begins.
+    end: usize,
+}
+/// Iterates records of one kind from a position in a block onwards,
+/// continuing into the blocks that follow.
+struct Records<'a> {
+    table: &'a Table,
+    block: Option<Block>,
+    pos: usize,
+    last_key: Vec<u8>,
+}
+impl Iterator for Records<'_> {
+    type Item = Result<Record, GitError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let block = self.block.as_ref()?;
+            if self.pos < block.end {
+                let record =
+                    block.decode(&mut self.pos, &self.last_key, self.table.min_update_index);
+                if let Ok(record) = &record {
+                    self.last_key.clone_from(&record.key);
+                } else {
+                    self.block = None;
+                }
+                return Some(record);
+            }
+            let (kind, next) = (block.kind, block.next);
+            self.block = None;
+            if next >= self.table.end {
+                return None;
+            }
+            match self.table.block(next) {
+                Ok(block) if block.kind == kind => {
+                    self.pos = block.first_record();
+                    self.last_key.clear();
+                    self.block = Some(block);
+                }
+                Ok(_) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+impl Table {
+    pub fn open(path: &Path) -> Result<Self, GitError> {
+        let data = fs::read(path)?;
+        if data.len() < HEADER_SIZE + FOOTER_SIZE || &data[..4] != MAGIC || data[4] != VERSION {
+            return Err(GitError::any(format!(
+                "reftable: unsupported table '{}'",
+                path.display()
+            )));
+        }
+        let end = data.len() - FOOTER_SIZE;
+        let footer = &data[end..];
+        let mut crc = Crc::new();
+        crc.update(&footer[..FOOTER_SIZE - 4]);
+        if footer[..HEADER_SIZE] != data[..HEADER_SIZE]
+            || crc.sum().to_be_bytes() != footer[FOOTER_SIZE - 4..]
+        {
+            return Err(corrupt());
+        }
+        let field = |at: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&footer[at..at + 8]);
+            u64::from_be_bytes(bytes)
+        };
+        let first_kind = (end > HEADER_SIZE).then(|| data[HEADER_SIZE]);
+        let log_pos = field(48) as usize;
+        Ok(Self {
+            min_update_index: field(8),
+            max_update_index: field(16),
+            ref_start: (first_kind == Some(BLOCK_REF)).then_some(0),
+            ref_index: field(24) as usize,
+            log_start: match log_pos {
+                0 => (first_kind == Some(BLOCK_LOG)).then_some(0),
+                pos => Some(pos),
+            },
+            log_index: field(56) as usize,
+            end,
+            data,
+        })
+    }
+    fn block(&self, pos: usize) -> Result<Block, GitError> {
+        let header_off = if pos == 0 { HEADER_SIZE } else { 0 };
+        let body = pos + header_off + 4;
+        let head = self.data.get(pos + header_off..body).ok_or_else(corrupt)?;
+        let (kind, len) = (head[0], be24(&head[1..]));
+        if len < header_off + 6 || body > self.end {
+            return Err(corrupt());
+        }
+        let (data, next) = if kind == BLOCK_LOG {
+            let mut data = self.data[pos..body].to_vec();
+            data.reserve_exact(len - data.len());
+            let mut inflate = Decompress::new(true);
+            let status = inflate
+                .decompress_vec(
+                    &self.data[body..self.end],
+                    &mut data,
+                    FlushDecompress::Finish,
+                )
+                .map_err(|_| corrupt())?;
+            if status != Status::StreamEnd || data.len() != len {
+                return Err(corrupt());
+            }
+            (data, body + inflate.total_in() as usize)
+        } else {
+            let data = self.data.get(pos..pos + len).ok_or_else(corrupt)?.to_vec();
+            let mut next = pos + len;
+            while next < self.end && self.data[next] == 0 {
+                next += 1;
+            }
+            (data, next)
+        };
+        let count = u16::from_be_bytes([data[len - 2], data[len - 1]]) as usize;
+        let end = (len - 2)
+            .checked_sub(3 * count)
+            .filter(|&end| end >= header_off + 4)
+            .ok_or_else(corrupt)?;
+        let restarts = (0..count).map(|i| be24(&data[end + 3 * i..])).collect();
+        Ok(Block {
+            kind,
+            data,
+            header_off,
+            restarts,
+            end,
+            next,
+        })
+    }
+    /// Positions an iterator over `kind` records at the restart point
+    /// preceding `key`, descending through the section's index if present.
+    fn seek(&self, kind: u8, key: &[u8]) -> Result<Records<'_>, GitError> {
+        let (start, index) = match kind {
+            BLOCK_REF => (self.ref_start, self.ref_index),
+            _ => (self.log_start, self.log_index),
+        };
+        let mut pos = match (index, start) {
+            (0, Some(start)) => start,
+            (0, None) => {
+                return Ok(Records {
+                    table: self,
+                    block: None,
+                    pos: 0,
+                    last_key: Vec::new(),
+                })
+            }
+            (index, _) => index,
+        };
+        loop {
+            let block = self.block(pos)?;
+            let offset = block.restart_for(key)?;
+            let mut records = Records {
+                table: self,
+                block: Some(block),
+                pos: offset,
+                last_key: Vec::new(),
+            };
+            let kind_found = records.block.as_ref().map(|b| b.kind);
+            if kind_found == Some(kind) {
+                return Ok(records);
+            }
+            if kind_found != Some(BLOCK_INDEX) {
+                return Err(corrupt());
+            }
+            let mut child = None;
+            for record in records.by_ref() {
+                let record = record?;
+                if record.key.as_slice() >= key {
+                    child = Some(record.value);
+                    break;
+                }
+            }
+            match child {
+                Some(Value::Index(child)) => pos = child,
+                Some(_) => return Err(corrupt()),
+                None => {
+                    return Ok(Records {
+                        table: self,
+                        block: None,
+                        pos: 0,
+                        last_key: Vec::new(),
+                    })
+                }
+            }
+        }
+    }
+    /// Looks up the record for `name`, including deletions.
+    pub fn find_ref(&self, name: &str) -> Result<Option<RefRecord>, GitError> {
+        for record in self.seek(BLOCK_REF, name.as_bytes())? {
+            let record = record?;
+            match record.key.as_slice().cmp(name.as_bytes()) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Greater => break,
+                std::cmp::Ordering::Equal => {}
+            }
+            let Value::Ref(value, update_index) = record.value else {
+                return Err(corrupt());
+            };
+            return Ok(Some(RefRecord {
+                name: name.to_string(),
+                update_index,
+                value,
+            }));
+        }
+        Ok(None)
+    }
+    /// Every ref record in the table, including deletions, sorted by name.
+    pub fn refs(&self) -> Result<Vec<RefRecord>, GitError> {
+        let mut refs = Vec::new();
+        for record in self.seek(BLOCK_REF, b"")? {
+            let record = record?;
+            let Value::Ref(value, update_index) = record.value else {
+                return Err(corrupt());
+            };
+            refs.push(RefRecord {
+                name: String::from_utf8(record.key)?,
+                update_index,
+                value,
+            });
+        }
+        Ok(refs)
+    }
+    /// The log records for `name`, or for every ref, including deletions.
+    /// Each ref's records come newest first.
+    pub fn logs(&self, name: Option<&str>) -> Result<Vec<LogRecord>, GitError> {
+        let prefix = match name {
+            Some(name) => [name.as_bytes(), &[0]].concat(),
+            None => Vec::new(),
+        };
+        let mut logs = Vec::new();
+        for record in self.seek(BLOCK_LOG, &prefix)? {
+            let record = record?;
+            if record.key < prefix {
+                continue;
+            }
+            if !record.key.starts_with(&prefix) {
+                break;
+            }
+            let Value::Log(entry) = record.value else {
+                return Err(corrupt());
+            };
+            let (name, update_index) = parse_log_key(&record.key)?;
+            logs.push(LogRecord {
+                name,
+                update_index,
+                entry,
+            });
+        }
+        Ok(logs)
+    }
+}
+fn list_path(dir: &Path) -> PathBuf {
+    dir.join("tables.list")
+}
+/// Creates an empty stack for a new repository.
+pub fn init() -> Result<(), GitError> {
+    init_in(Path::new(REFTABLE_DIR))
+}
+fn init_in(dir: &Path) -> Result<(), GitError> {
+    fs::create_dir_all(dir)?;
+    fs::write(list_path(dir), "")?;
+    Ok(())
+}
+/// The stack of tables named in `tables.list`, oldest first. Newer tables
+/// take precedence, so a deletion record hides older values of its key.
+pub struct Stack {
+    dir: PathBuf,
+    names: Vec<String>,
+    tables: Vec<Table>,
+    lock: Option<LockFile>,
+}
+impl Stack {
+    /// Opens the stack for reading, or returns `None` when the repository
+    /// uses loose and packed refs.
+    pub fn open() -> Result<Option<Self>, GitError> {
+        if !enabled()? {
+            return Ok(None);
+        }
+        Self::load(Path::new(REFTABLE_DIR), None).map(Some)
+    }
+    /// Opens the stack holding `tables.list.lock`, so it can be added to.
+    pub fn lock() -> Result<Self, GitError> {
+        Self::lock_in(Path::new(REFTABLE_DIR))
+    }
+    fn lock_in(dir: &Path) -> Result<Self, GitError> {
+        let lock = LockFile::acquire(list_path(dir))?;
+        Self::load(dir, Some(lock))
+    }
+    fn load(dir: &Path, lock: Option<LockFile>) -> Result<Self, GitError> {
+        let list = match fs::read_to_string(list_path(dir)) {
+            Ok(list) => list,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let names: Vec<String> = list
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        let tables = names
+            .iter()
+            .map(|name| Table::open(&dir.join(name)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            names,
+            tables,
+            lock,
+        })
+    }
+    pub fn next_update_index(&self) -> u64 {
+        self.tables
+            .iter()
+            .map(|t| t.max_update_index)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+    /// Reads `name` from the newest table that has a record for it.
+    pub fn read_ref(&self, name: &str) -> Result<Option<RefValue>, GitError> {
+        for table in self.tables.iter().rev() {
+            if let Some(record) = table.find_ref(name)? {
+                return Ok(match record.value {
+                    RefValue::Deletion => None,
+                    value => Some(value),
+                });
+            }
+        }
+        Ok(None)
+    }
+    fn merged_refs(tables: &[Table]) -> Result<BTreeMap<String, RefRecord>, GitError> {
+        let mut refs = BTreeMap::new();
+        for table in tables {
+            for record in table.refs()? {
+                refs.insert(record.name.clone(), record);
+            }
+        }
+        Ok(refs)
+    }
+    fn merged_logs(
+        tables: &[Table],
+        name: Option<&str>,
+    ) -> Result<BTreeMap<(String, Reverse<u64>), LogRecord>, GitError> {
+        let mut logs = BTreeMap::new();
+        for table in tables {
+            for record in table.logs(name)? {
+                logs.insert((record.name.clone(), Reverse(record.update_index)), record);
+            }
+        }
+        Ok(logs)
+    }
+    /// Every live ref, sorted by name.
+    pub fn refs(&self) -> Result<BTreeMap<String, RefValue>, GitError> {
+        Ok(Self::merged_refs(&self.tables)?
+            .into_iter()
+            .filter(|(_, r)| r.value != RefValue::Deletion)
+            .map(|(name, r)| (name, r.value))
+            .collect())
+    }
+    /// The live log records of `name`, or of every ref, sorted by name and
+    /// then newest first.
+    pub fn logs(&self, name: Option<&str>) -> Result<Vec<LogRecord>, GitError> {
+        Ok(Self::merged_logs(&self.tables, name)?
+            .into_values()
+            .filter(|r| r.entry.is_some())
+            .collect())
+    }
+    fn write_table(
+        dir: &Path,
+        min_update_index: u64,
+        max_update_index: u64,
+        refs: &[RefRecord],
+        logs: &[LogRecord],
+    ) -> Result<String, GitError> {
+        let data = write_table(min_update_index, max_update_index, refs, logs)?;
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let suffix = seed ^ std::process::id().rotate_left(16);
+        let name = format!("0x{min_update_index:012x}-0x{max_update_index:012x}-{suffix:08x}.ref");
+        let mut lock = LockFile::acquire(dir.join(&name))?;
+        lock.write_all(&data)?;
+        lock.commit()?;
+        Ok(name)
+    }
+    fn commit_list(lock: LockFile, names: &[String]) -> Result<(), GitError> {
+        let mut lock = lock;
+        let list: String = names.iter().map(|name| format!("{name}\n")).collect();
+        lock.write_all(list.as_bytes())?;
+        lock.commit()
+    }
+    /// Writes the records as a new table at the next update index and
+    /// appends it to the stack, then compacts the stack if it has grown.
+    /// The stack must have been opened with `lock`.
+    pub fn add(self, refs: Vec<RefRecord>, logs: Vec<LogRecord>) -> Result<(), GitError> {
+        let update_index = self.next_update_index();
+        let Self {
+            dir,
+            mut names,
+            lock,
+            ..
+        } = self;
+        let lock = lock.ok_or(GitError::any("reftable: stack is not locked"))?;
+        names.push(Self::write_table(
+            &dir,
+            update_index,
+            update_index,
+            &refs,
+            &logs,
+        )?);
+        Self::commit_list(lock, &names)?;
+        Self::auto_compact(&dir)
+    }
+    /// Keeps table sizes geometric: the newest tables are merged while the
+    /// table below them is no more than twice their combined size. Skipped
+    /// when another process holds the lock.
+    fn auto_compact(dir: &Path) -> Result<(), GitError> {
+        let Ok(stack) = Self::lock_in(dir) else {
+            return Ok(());
+        };
+        let sizes: Vec<usize> = stack.tables.iter().map(|t| t.data.len()).collect();
+        let mut start = sizes.len();
+        let mut total = 0;
+        while start > 0 {
+            if start < sizes.len() && sizes[start - 1] > 2 * total {
+                break;
+            }
+            start -= 1;
+            total += sizes[start];
+        }
+        if sizes.len() - start < 2 {
+            return Ok(());
+        }
+        stack.compact(start)
+    }
+    /// Merges the tables from `start` up into one. Deletions are only
+    /// dropped when nothing older remains for them to hide.
+    fn compact(self, start: usize) -> Result<(), GitError> {
+        let Self {
+            dir,
+            names,
+            tables,
+            lock,
+        } = self;
+        let lock = lock.ok_or(GitError::any("reftable: stack is not locked"))?;
+        let merging = &tables[start..];
+        let mut refs: Vec<RefRecord> = Self::merged_refs(merging)?.into_values().collect();
+        let mut logs: Vec<LogRecord> = Self::merged_logs(merging, None)?.into_values().collect();
+        if start == 0 {
+            refs.retain(|r| r.value != RefValue::Deletion);
+            logs.retain(|r| r.entry.is_some());
+        }
+        let min = merging
+            .iter()
+            .map(|t| t.min_update_index)
+            .min()
+            .unwrap_or(0);
+        let max = merging
+            .iter()
+            .map(|t| t.max_update_index)
+            .max()
+            .unwrap_or(0);
+        let mut kept = names[..start].to_vec();
+        kept.push(Self::write_table(&dir, min, max, &refs, &logs)?);
+        Self::commit_list(lock, &kept)?;
+        for name in &names[start..] {
+            let _ = fs::remove_file(dir.join(name));
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn hash(n: usize) -> String {
+        format!("{n:040x}")
+    }
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("reftable-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    fn direct(name: &str, update_index: u64, n: usize) -> RefRecord {
+        RefRecord {
+            name: name.to_string(),
+            update_index,
+            value: RefValue::Direct {
+                hash: hash(n),
+                peeled: None,
+            },
+        }
+    }
+    fn log(name: &str, update_index: u64, n: usize) -> LogRecord {
+        let identity = Signature::new("A U Thor", "author@example.com", 1700000000, "+0000");
+        let message = format!("commit: change {n}\n");
+        LogRecord {
+            name: name.to_string(),
+            update_index,
+            entry: Some(ReflogEntry::new(
+                hash(n),
+                hash(n + 1),
+                identity,
+                message,
+                update_index,
+            )),
+        }
+    }
+    fn read_back(name: &str, data: &[u8]) -> Table {
+        let path = scratch(name).join("table.ref");
+        fs::write(&path, data).unwrap();
+        Table::open(&path).unwrap()
+    }
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 255, 16511, 16512, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            put_varint(&mut out, value);
+            let mut pos = 0;
+            assert_eq!(get_varint(&out, &mut pos).unwrap(), value);
+            assert_eq!(pos, out.len());
+        }
+    }
+    #[test]
+    fn refs_round_trip_across_restarts_and_blocks() {
+        let mut refs: Vec<RefRecord> = (0..500)
+            .map(|i| direct(&format!("refs/heads/branch-{i:04}"), 3, i))
+            .collect();
+        refs.push(RefRecord {
+            name: "HEAD".to_string(),
+            update_index: 3,
+            value: RefValue::Symbolic("refs/heads/branch-0000".to_string()),
+        });
+        refs.push(RefRecord {
+            name: "refs/tags/v1".to_string(),
+            update_index: 3,
+            value: RefValue::Direct {
+                hash: hash(1000),
+                peeled: Some(hash(1001)),
+            },
+        });
+        refs.push(RefRecord {
+            name: "refs/heads/gone".to_string(),
+            update_index: 3,
+            value: RefValue::Deletion,
+        });
+        // Unsorted input is sorted by the writer.
+        refs.reverse();
+        let table = read_back("refs", &write_table(3, 3, &refs, &[]).unwrap());
+        assert_eq!((table.min_update_index, table.max_update_index), (3, 3));
+        let first = table.block(0).unwrap();
+        assert_eq!(first.kind, BLOCK_REF);
+        assert!(first.restarts.len() > 1);
+        assert!(first.next < table.end);
+        assert_ne!(table.ref_index, 0);
+        let read = table.refs().unwrap();
+        assert_eq!(read.len(), refs.len());
+        assert!(read.windows(2).all(|w| w[0].name < w[1].name));
+        for record in &refs {
+            let found = table.find_ref(&record.name).unwrap().unwrap();
+            assert_eq!(found.value, record.value);
+            assert_eq!(found.update_index, 3);
+        }
+        assert!(table.find_ref("refs/heads/branch-0250x").unwrap().is_none());
+        assert!(table.find_ref("refs/heads/zzz").unwrap().is_none());
+        assert!(table.find_ref("A").unwrap().is_none());
+        assert!(table.logs(None).unwrap().is_empty());
+    }
+    #[test]
+    fn logs_round_trip_newest_first() {
+        let refs = [direct("refs/heads/main", 5, 1)];
+        let mut logs: Vec<LogRecord> = (1..=200)
+            .map(|i| log("refs/heads/main", i, i as usize))
+            .collect();
+        logs.push(log("HEAD", 7, 7));
+        logs.push(LogRecord {
+            name: "refs/heads/old".to_string(),
+            update_index: 4,
+            entry: None,
+        });
+        let table = read_back("logs", &write_table(1, 200, &refs, &logs).unwrap());
+        assert!(table.log_start.is_some());
+        let main = table.logs(Some("refs/heads/main")).unwrap();
+        assert_eq!(main.len(), 200);
+        assert!(main
+            .windows(2)
+            .all(|w| w[0].update_index > w[1].update_index));
+        let newest = main[0].entry.as_ref().unwrap();
+        assert_eq!(newest.old_hash(), &hash(200));
+        assert_eq!(newest.new_hash(), &hash(201));
+        assert_eq!(newest.message(), "commit: change 200\n");
+        assert_eq!(
+            newest.identity(),
+            logs[199].entry.as_ref().unwrap().identity()
+        );
+        assert_eq!(table.logs(Some("HEAD")).unwrap().len(), 1);
+        assert!(table.logs(Some("refs/heads/old")).unwrap()[0]
+            .entry
+            .is_none());
+        assert!(table.logs(Some("refs/heads/mai")).unwrap().is_empty());
+        assert_eq!(table.logs(None).unwrap().len(), logs.len());
+        assert_eq!(table.refs().unwrap().len(), 1);
+    }
+    #[test]
+    fn empty_table_round_trips() {
+        let table = read_back("empty", &write_table(1, 1, &[], &[]).unwrap());
+        assert!(table.refs().unwrap().is_empty());
+        assert!(table.logs(None).unwrap().is_empty());
+        assert!(table.find_ref("HEAD").unwrap().is_none());
+    }
+    #[test]
+    fn corrupt_footer_is_rejected() {
+        let mut data = write_table(1, 1, &[direct("HEAD", 1, 1)], &[]).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        let path = scratch("corrupt").join("table.ref");
+        fs::write(&path, data).unwrap();
+        assert!(Table::open(&path).is_err());
+    }
+    #[test]
+    fn stack_compacts_tables_list() {
+        let dir = scratch("stack").join("reftable");
+        init_in(&dir).unwrap();
+        for i in 1..=8 {
+            let stack = Stack::lock_in(&dir).unwrap();
+            let update_index = stack.next_update_index();
+            assert_eq!(update_index, i);
+            let name = format!("refs/heads/b{}", i % 3);
+            stack
+                .add(
+                    vec![direct(&name, update_index, i as usize)],
+                    vec![log(&name, update_index, i as usize)],
+                )
+                .unwrap();
+        }
+        let stack = Stack::lock_in(&dir).unwrap();
+        stack
+            .add(
+                vec![RefRecord {
+                    name: "refs/heads/b0".to_string(),
+                    update_index: 9,
+                    value: RefValue::Deletion,
+                }],
+                vec![],
+            )
+            .unwrap();
+        let list = fs::read_to_string(list_path(&dir)).unwrap();
+        let names: Vec<&str> = list.lines().collect();
+        assert!(names.len() < 4, "stack was not compacted: {list}");
+        let mut on_disk: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".ref"))
+            .collect();
+        on_disk.sort();
+        let mut listed: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        listed.sort();
+        assert_eq!(on_disk, listed);
+        let stack = Stack::load(&dir, None).unwrap();
+        assert_eq!(stack.next_update_index(), 10);
+        let refs = stack.refs().unwrap();
+        assert_eq!(
+            refs.keys().collect::<Vec<_>>(),
+            ["refs/heads/b1", "refs/heads/b2"]
+        );
+        assert_eq!(
+            refs["refs/heads/b1"],
+            RefValue::Direct {
+                hash: hash(7),
+                peeled: None
+            }
+        );
+        assert!(stack.read_ref("refs/heads/b0").unwrap().is_none());
+        assert_eq!(stack.logs(Some("refs/heads/b1")).unwrap().len(), 3);
+        assert_eq!(stack.logs(None).unwrap().len(), 8);
+        // Compacting from the bottom drops deletions nothing older needs.
+        Stack::lock_in(&dir).unwrap().compact(0).unwrap();
+        let stack = Stack::load(&dir, None).unwrap();
+        assert_eq!(stack.names.len(), 1);
+        assert_eq!(stack.tables[0].min_update_index, 1);
+        assert_eq!(stack.tables[0].max_update_index, 9);
+        assert!(stack.tables[0].find_ref("refs/heads/b0").unwrap().is_none());
+        assert_eq!(stack.refs().unwrap(), refs);
+        assert_eq!(stack.logs(None).unwrap().len(), 8);
+    }
+}
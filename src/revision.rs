@@ -0,0 +1,404 @@
+use crate::commit::Commit;
+use crate::config::Config;
+use crate::date::{approxidate, DateFormat};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::objects::{GitObject, Kind};
+use crate::reflog;
+use crate::refs;
+use crate::revwalk::{merge_bases, RevWalk};
+use crate::tag::Tag;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+const SEARCH_ORDER: [&str; 6] = [
+    "{}",
+    "refs/{}",
+    "refs/tags/{}",
+    "refs/heads/{}",
+    "refs/remotes/{}",
+    "refs/remotes/{}/HEAD",
+];
+fn unknown(spec: &str) -> GitError {
+    GitError::any(format!("unknown revision: {spec}"))
+}
+/// Expands a short ref name using git's search order, returning the full
+/// ref name together with the hash it points to.
+pub fn dwim_ref(name: &str) -> Result<Option<(String, String)>, GitError> {
+    for rule in SEARCH_ORDER {
+        let refname = rule.replace("{}", name);
+        let pseudo = refname.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+        if rule == "{}" && !(pseudo || refname.starts_with("refs/")) {
+            continue;
+        }
+        if let Some(hash) = refs::resolve(&refname)? {
+            return Ok(Some((refname, hash)));
+        }
+    }
+    Ok(None)
+}
+pub fn upstream(branch: Option<&str>) -> Result<String, GitError> {
+    let branch = match branch {
+        Some(branch) => branch.to_string(),
+        None => refs::head_branch()?.ok_or(GitError::any("HEAD does not point to a branch"))?,
+    };
+    let config = Config::load()?;
+    let no_upstream = || GitError::any(format!("no upstream configured for branch '{branch}'"));
+    let remote = config
+        .get(&format!("branch.{branch}.remote"))
+        .ok_or_else(no_upstream)?;
+    let merge = config
+        .get(&format!("branch.{branch}.merge"))
+        .ok_or_else(no_upstream)?;
+    if remote == "." {
+        return Ok(merge.to_string());
+    }
+    let name = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+    Ok(format!("refs/remotes/{remote}/{name}"))
+}
+/// The objects named in the pack indexes whose hashes start with `first`.
+fn packed_objects(first: u8) -> Result<Vec<String>, GitError> {
+    let dir = Path::new(".git/objects/pack");
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("idx") {
+            continue;
+        }
+        let idx = fs::read(&path)?;
+        // Version 2 indexes start with a magic number and list bare names
+        // after the fan-out table; version 1 ones pair each with an offset.
+        let (fanout, width, skip) = match idx.starts_with(b"\xfftOc") {
+            true => (8, 20, 0),
+            false => (0, 24, 4),
+        };
+        let count = |byte: usize| -> usize {
+            idx.get(fanout + 4 * byte..fanout + 4 * byte + 4)
+                .map_or(0, |n| u32::from_be_bytes(n.try_into().unwrap()) as usize)
+        };
+        let start = match first {
+            0 => 0,
+            first => count(first as usize - 1),
+        };
+        for n in start..count(first as usize) {
+            let at = fanout + 1024 + n * width + skip;
+            match idx.get(at..at + 20) {
+                Some(name) => names.push(hex::encode(name)),
+                None => return Err(GitError::any(format!("{} is corrupt", path.display()))),
+            }
+        }
+    }
+    Ok(names)
+}
+fn abbreviated(prefix: &str) -> Result<Option<String>, GitError> {
+    if prefix.len() < 4 || prefix.len() > 40 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(None);
+    }
+    let prefix = prefix.to_lowercase();
+    let dir = Path::new(".git/objects").join(&prefix[..2]);
+    let mut matches = BTreeSet::new();
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if name.starts_with(&prefix[2..]) {
+                matches.insert(format!("{}{name}", &prefix[..2]));
+            }
+        }
+    }
+    let first = u8::from_str_radix(&prefix[..2], 16).unwrap_or_default();
+    for name in packed_objects(first)? {
+        if name.starts_with(&prefix) {
+            matches.insert(name);
+        }
+    }
+    match matches.len() {
+        0 => Ok(None),
+        1 => Ok(matches.pop_first()),
+        _ => Err(GitError::any(format!(
+            "short object ID {prefix} is ambiguous"
+        ))),
+    }
+}
+fn split_outside_braces(spec: &str, separators: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            c if depth == 0 && separators.contains(&c) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+fn resolve_base(name: &str) -> Result<String, GitError> {
+    if name.is_empty() || name == "@" {
+        return refs::resolve("HEAD")?.ok_or_else(|| unknown("HEAD"));
+    }
+    if let Some((branch, suffix)) = name.split_once("@{") {
+        let suffix = suffix.strip_suffix('}').ok_or_else(|| unknown(name))?;
+        if matches!(suffix.to_lowercase().as_str(), "upstream" | "u") {
+            let branch = (!branch.is_empty()).then_some(branch);
+            let refname = upstream(branch)?;
+            return refs::resolve(&refname)?
+                .ok_or_else(|| GitError::any(format!("upstream {refname} does not exist")));
+        }
+        if let Some(n) = suffix.strip_prefix('-').filter(|_| branch.is_empty()) {
+            let n = n.parse().map_err(|_| unknown(name))?;
+            let previous = previous_branch(n)?.ok_or_else(|| unknown(name))?;
+            return resolve_base(&previous);
+        }
+        return at_reflog(branch, suffix);
+    }
+    if name.len() == 40 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(name.to_lowercase());
+    }
+    if let Some((_, hash)) = dwim_ref(name)? {
+        return Ok(hash);
+    }
+    abbreviated(name)?.ok_or_else(|| unknown(name))
+}
+/// Returns the branch checked out before the current one, `n` switches
+/// back, as recorded by checkout messages in HEAD's reflog.
+pub fn previous_branch(n: usize) -> Result<Option<String>, GitError> {
+    let Some(skip) = n.checked_sub(1) else {
+        return Ok(None);
+    };
+    Ok(reflog::read("HEAD")?
+        .iter()
+        .rev()
+        .filter_map(|entry| {
+            let (from, _) = entry
+                .message()
+                .strip_prefix("checkout: moving from ")?
+                .split_once(" to ")?;
+            Some(from.to_string())
+        })
+        .nth(skip))
+}
+/// Resolves `<ref>@{<n>}` and `<ref>@{<date>}` from the ref's reflog. An
+/// empty ref means the current branch.
+fn at_reflog(base: &str, selector: &str) -> Result<String, GitError> {
+    let refname = match base {
+        "" => match refs::head_branch()? {
+            Some(branch) => format!("refs/heads/{branch}"),
+            None => "HEAD".to_string(),
+        },
+        "HEAD" => "HEAD".to_string(),
+        base => dwim_ref(base)?.ok_or_else(|| unknown(base))?.0,
+    };
+    let entries = reflog::read(&refname)?;
+    let Some(first) = entries.first() else {
+        return Err(GitError::any(format!("log for '{refname}' is empty")));
+    };
+    if let Ok(n) = selector.parse::<usize>() {
+        return match entries.len().checked_sub(n + 1) {
+            Some(index) => Ok(entries[index].new_hash().clone()),
+            None if n == entries.len() && first.old_hash() != refs::ZERO_HASH => {
+                Ok(first.old_hash().clone())
+            }
+            None => Err(GitError::any(format!(
+                "log for '{}' only has {} entries",
+                refs::shorten(&refname),
+                entries.len()
+            ))),
+        };
+    }
+    let time = approxidate(selector)?;
+    Ok(
+        match entries.iter().rev().find(|e| e.identity().time() <= time) {
+            Some(entry) => entry.new_hash().clone(),
+            None => {
+                eprintln!(
+                    "warning: log for '{}' only goes back to {}",
+                    refs::shorten(&refname),
+                    first.identity().date(DateFormat::Rfc2822)
+                );
+                match first.old_hash() == refs::ZERO_HASH {
+                    true => first.new_hash().clone(),
+                    false => first.old_hash().clone(),
+                }
+            }
+        },
+    )
+}
+/// Peels an object, following tags, until it has the requested kind; `None`
+/// peels tags only.
+pub fn peel(hash: &str, kind: Option<&str>) -> Result<String, GitError> {
+    let object = GitObject::from_hex_string(hash)?;
+    match (kind, object.kind()) {
+        (Some("object"), _) | (Some("tag"), Kind::Tag) => Ok(hash.to_string()),
+        (_, Kind::Tag) => peel(Tag::parse(object.contents())?.object(), kind),
+        (None, _) => Ok(hash.to_string()),
+        (Some("commit"), Kind::Commit)
+        | (Some("tree"), Kind::Tree)
+        | (Some("blob"), Kind::Blob) => Ok(hash.to_string()),
+        (Some("tree"), Kind::Commit) => object.tree_hex_string(),
+        (Some(kind), actual) => Err(GitError::any(format!("{hash} is a {actual}, not a {kind}"))),
+    }
+}
+fn tree_path(tree: &str, path: &str, rev: &str) -> Result<String, GitError> {
+    let mut hash = tree.to_string();
+    for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let entry = GitObject::from_hex_string(&hash)?
+            .tree_entries()?
+            .into_iter()
+            .find(|e| e.filename() == component)
+            .ok_or_else(|| GitError::any(format!("path '{path}' does not exist in '{rev}'")))?;
+        hash = entry.hex_string();
+    }
+    Ok(hash)
+}
+/// Finds the youngest commit reachable from any ref whose message matches the
+/// `:/` pattern; `!-` negates the match, `!!` escapes a leading `!`, and any
+/// other `!` prefix is reserved.
+fn search_message(pattern: &str) -> Result<String, GitError> {
+    let (negate, text) = match pattern.strip_prefix('!') {
+        None => (false, pattern),
+        Some(rest) if rest.starts_with('!') => (false, rest),
+        Some(rest) => match rest.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => {
+                return Err(GitError::any(format!(
+                    "unknown :/! modifier in ':/{pattern}'"
+                )))
+            }
+        },
+    };
+    let regex =
+        Regex::new(text).map_err(|_| GitError::any(format!("invalid regex in ':/{pattern}'")))?;
+    let mut walk = RevWalk::new();
+    for (_, hash) in refs::list("refs/")? {
+        if let Ok(commit) = peel(&hash, Some("commit")) {
+            walk.push(&commit)?;
+        }
+    }
+    if let Some(head) = refs::resolve("HEAD")? {
+        walk.push(&head)?;
+    }
+    while let Some(commit) = walk.pop()? {
+        if regex.is_match(commit.message()) != negate {
+            return Ok(commit.hash().clone());
+        }
+        for parent in commit.parents() {
+            walk.push(parent)?;
+        }
+    }
+    Err(GitError::any(format!(
+        "no commit message matches ':/{pattern}'"
+    )))
+}
+/// Resolves a single revision expression to the hash of the object it names.
+pub fn resolve(spec: &str) -> Result<String, GitError> {
+    if let Some(text) = spec.strip_prefix(":/") {
+        return search_message(text);
+    }
+    if let Some(path) = spec.strip_prefix(':') {
+        let (stage, path) = match path.split_once(':') {
+            Some((stage @ ("0" | "1" | "2" | "3"), path)) => {
+                (stage.parse::<u8>().unwrap_or(0), path)
+            }
+            _ => (0, path),
+        };
+        let index = Index::load()?;
+        return index
+            .entries()
+            .iter()
+            .find(|e| e.path() == path && e.stage() == stage)
+            .map(|e| e.hex_string())
+            .ok_or_else(|| GitError::any(format!("path '{path}' is not in the index")));
+    }
+    if let Some(colon) = split_outside_braces(spec, &[':']) {
+        let rev = &spec[..colon];
+        return tree_path(&resolve_tree(rev)?, &spec[colon + 1..], rev);
+    }
+    let base_end = split_outside_braces(spec, &['~', '^']).unwrap_or(spec.len());
+    let mut hash = resolve_base(&spec[..base_end])?;
+    let mut rest = &spec[base_end..];
+    while let Some(op) = rest.chars().next() {
+        rest = &rest[1..];
+        if op == '^' && rest.starts_with('{') {
+            let end = rest.find('}').ok_or_else(|| unknown(spec))?;
+            let kind = &rest[1..end];
+            hash = peel(&hash, (!kind.is_empty()).then_some(kind))?;
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let count: usize = if digits == 0 {
+            1
+        } else {
+            rest[..digits].parse().map_err(|_| unknown(spec))?
+        };
+        rest = &rest[digits..];
+        let commit = Commit::from_hex_string(peel(&hash, Some("commit"))?)?;
+        hash = match op {
+            '~' => {
+                let mut commit = commit;
+                for _ in 0..count {
+                    let parent = commit.parents().first().ok_or_else(|| unknown(spec))?;
+                    commit = Commit::from_hex_string(parent)?;
+                }
+                commit.hash().clone()
+            }
+            '^' if count == 0 => commit.hash().clone(),
+            '^' => commit
+                .parents()
+                .get(count - 1)
+                .ok_or_else(|| unknown(spec))?
+                .clone(),
+            _ => return Err(unknown(spec)),
+        };
+    }
+    Ok(hash)
+}
+pub fn resolve_commit(spec: &str) -> Result<String, GitError> {
+    peel(&resolve(spec)?, Some("commit"))
+}
+pub fn resolve_tree(spec: &str) -> Result<String, GitError> {
+    peel(&resolve(spec)?, Some("tree"))
+}
+/// One side of a revision range: a commit to include, or one whose
+/// history should be excluded (`^rev`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum RevSpec {
+    Include(String),
+    Exclude(String),
+}
+/// Expands `a..b`, `a...b`, `^a` and plain revisions into the commits they
+/// include and exclude.
+pub fn resolve_range(spec: &str) -> Result<Vec<RevSpec>, GitError> {
+    let side = |rev: &str| resolve_commit(if rev.is_empty() { "HEAD" } else { rev });
+    if let Some((left, right)) = spec.split_once("...") {
+        let (left, right) = (side(left)?, side(right)?);
+        let mut specs = vec![
+            RevSpec::Include(right.clone()),
+            RevSpec::Include(left.clone()),
+        ];
+        specs.extend(
+            merge_bases(&left, &right)?
+                .into_iter()
+                .map(RevSpec::Exclude),
+        );
+        return Ok(specs);
+    }
+    if let Some((left, right)) = spec.split_once("..") {
+        return Ok(vec![
+            RevSpec::Include(side(right)?),
+            RevSpec::Exclude(side(left)?),
+        ]);
+    }
+    match spec.strip_prefix('^') {
+        Some(rev) => Ok(vec![RevSpec::Exclude(resolve_commit(rev)?)]),
+        None => Ok(vec![RevSpec::Include(resolve(spec)?)]),
+    }
+}
+/// Returns whether an argument looks like a revision range rather than a
+/// single revision.
+pub fn is_range(spec: &str) -> bool {
+    spec.contains("..") || (spec.starts_with('^') && spec.len() > 1)
+}
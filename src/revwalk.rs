@@ -0,0 +1,295 @@
+use crate::commit::Commit;
+use crate::error::GitError;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+struct Queued {
+    time: i64,
+    sequence: Reverse<usize>,
+    commit: Commit,
+}
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Queued {}
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.sequence).cmp(&(other.time, other.sequence))
+    }
+}
+#[derive(Default)]
+pub struct RevWalk {
+    queue: BinaryHeap<Queued>,
+    /// Every commit queued so far, and whether it is hidden.
+    hidden: HashMap<String, bool>,
+    sequence: usize,
+}
+impl RevWalk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn enqueue(&mut self, hash: &str) -> Result<(), GitError> {
+        let commit = Commit::from_hex_string(hash)?;
+        self.sequence += 1;
+        self.queue.push(Queued {
+            time: commit.committer().time(),
+            sequence: Reverse(self.sequence),
+            commit,
+        });
+        Ok(())
+    }
+    pub fn push(&mut self, hash: &str) -> Result<(), GitError> {
+        if self.hidden.contains_key(hash) {
+            return Ok(());
+        }
+        self.hidden.insert(hash.to_string(), false);
+        self.enqueue(hash)
+    }
+    /// Marks `hash` and all of its ancestors as uninteresting, so they are
+    /// never returned by the walk. The mark spreads to the ancestors as the
+    /// walk reaches them, and the walk ends once only hidden commits are
+    /// left to visit.
+    pub fn hide(&mut self, hash: &str) -> Result<(), GitError> {
+        match self.hidden.insert(hash.to_string(), true) {
+            Some(true) => Ok(()),
+            _ => self.enqueue(hash),
+        }
+    }
+    pub fn pop(&mut self) -> Result<Option<Commit>, GitError> {
+        while self.queue.iter().any(|q| !self.hidden[q.commit.hash()]) {
+            let Some(Queued { commit, .. }) = self.queue.pop() else {
+                break;
+            };
+            if !self.hidden[commit.hash()] {
+                return Ok(Some(commit));
+            }
+            for parent in commit.parents() {
+                self.hide(parent)?;
+            }
+        }
+        self.queue.clear();
+        Ok(None)
+    }
+}
+pub fn topo_order<T>(items: Vec<T>, links: impl Fn(&T) -> (&String, &Vec<String>)) -> Vec<T> {
+    let positions: HashMap<String, usize> = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| (links(item).0.clone(), i))
+        .collect();
+    let mut indegree = vec![0usize; items.len()];
+    for item in &items {
+        for parent in links(item).1 {
+            if let Some(&j) = positions.get(parent) {
+                indegree[j] += 1;
+            }
+        }
+    }
+    let mut stack: Vec<usize> = (0..items.len())
+        .filter(|&i| indegree[i] == 0)
+        .rev()
+        .collect();
+    let mut order = Vec::with_capacity(items.len());
+    while let Some(i) = stack.pop() {
+        order.push(i);
+        for parent in links(&items[i]).1 {
+            if let Some(&j) = positions.get(parent) {
+                indegree[j] -= 1;
+                if indegree[j] == 0 {
+                    stack.push(j);
+                }
+            }
+        }
+    }
+    let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+/// Returns the hashes of every commit reachable from `hash`, itself included.
+pub fn ancestors(hash: &str) -> Result<HashSet<String>, GitError> {
+    let mut seen = HashSet::new();
+    let mut stack = vec![hash.to_string()];
+    while let Some(hash) = stack.pop() {
+        if seen.insert(hash.clone()) {
+            stack.extend(Commit::from_hex_string(&hash)?.parents().iter().cloned());
+        }
+    }
+    Ok(seen)
+}
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 2;
+const STALE: u8 = 4;
+const RESULT: u8 = 8;
+/// Walks down from `one` and `twos` newest first, painting each commit
+/// with the sides it is reachable from, until every commit left to visit
+/// lies below a common ancestor already found. Returns the common
+/// ancestors no other one reaches, along with the paint.
+fn paint_down_to_common(
+    one: &str,
+    twos: &[String],
+) -> Result<(Vec<String>, HashMap<String, u8>), GitError> {
+    let mut walk = RevWalk::new();
+    let mut flags = HashMap::from([(one.to_string(), PARENT1)]);
+    walk.enqueue(one)?;
+    for two in twos {
+        *flags.entry(two.clone()).or_default() |= PARENT2;
+        walk.enqueue(two)?;
+    }
+    let mut results = Vec::new();
+    while walk
+        .queue
+        .iter()
+        .any(|q| flags[q.commit.hash()] & STALE == 0)
+    {
+        let Some(Queued { commit, .. }) = walk.queue.pop() else {
+            break;
+        };
+        let mut paint = flags[commit.hash()] & (PARENT1 | PARENT2 | STALE);
+        if paint == PARENT1 | PARENT2 {
+            let commit_flags = flags.get_mut(commit.hash()).unwrap();
+            if *commit_flags & RESULT == 0 {
+                *commit_flags |= RESULT;
+                results.push(commit.hash().clone());
+            }
+            paint |= STALE;
+        }
+        for parent in commit.parents() {
+            let parent_flags = flags.entry(parent.clone()).or_default();
+            if *parent_flags & paint != paint {
+                *parent_flags |= paint;
+                walk.enqueue(parent)?;
+            }
+        }
+    }
+    results.retain(|hash| flags[hash] & STALE == 0);
+    Ok((results, flags))
+}
+/// Whether commit `ancestor` is reachable from commit `descendant`.
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> Result<bool, GitError> {
+    if ancestor == descendant {
+        return Ok(true);
+    }
+    let (_, flags) = paint_down_to_common(ancestor, &[descendant.to_string()])?;
+    Ok(flags[ancestor] & PARENT2 != 0)
+}
+/// Drops the commits that are ancestors of another one in `commits`.
+pub fn independent(commits: &[String]) -> Result<Vec<String>, GitError> {
+    let mut unique: Vec<&String> = Vec::new();
+    for commit in commits {
+        if !unique.contains(&commit) {
+            unique.push(commit);
+        }
+    }
+    let mut redundant = vec![false; unique.len()];
+    for i in 0..unique.len() {
+        if redundant[i] {
+            continue;
+        }
+        let others: Vec<usize> = (0..unique.len())
+            .filter(|&j| j != i && !redundant[j])
+            .collect();
+        let twos: Vec<String> = others.iter().map(|&j| unique[j].clone()).collect();
+        let (_, flags) = paint_down_to_common(unique[i], &twos)?;
+        if flags[unique[i]] & PARENT2 != 0 {
+            redundant[i] = true;
+        }
+        for j in others {
+            if flags[unique[j]] & PARENT1 != 0 {
+                redundant[j] = true;
+            }
+        }
+    }
+    Ok(unique
+        .into_iter()
+        .zip(redundant)
+        .filter(|(_, redundant)| !redundant)
+        .map(|(commit, _)| commit.clone())
+        .collect())
+}
+/// Returns the best common ancestors of two commits: those shared by both
+/// histories that are not themselves ancestors of another shared commit.
+pub fn merge_bases(a: &str, b: &str) -> Result<Vec<String>, GitError> {
+    if a == b {
+        return Ok(vec![a.to_string()]);
+    }
+    let (candidates, _) = paint_down_to_common(a, &[b.to_string()])?;
+    match candidates.len() {
+        0 | 1 => Ok(candidates),
+        _ => independent(&candidates),
+    }
+}
+/// Returns the merge bases of `one` and a hypothetical merge of `others`.
+pub fn merge_bases_many(one: &str, others: &[String]) -> Result<Vec<String>, GitError> {
+    let mut candidates: Vec<String> = Vec::new();
+    for other in others {
+        for base in merge_bases(one, other)? {
+            if !candidates.contains(&base) {
+                candidates.push(base);
+            }
+        }
+    }
+    independent(&candidates)
+}
+/// How [`list`] walks history and orders what it finds.
+#[derive(Clone, Debug, Default)]
+pub struct WalkOptions {
+    /// Never show a commit before all of its children.
+    pub topo_order: bool,
+    /// Follow only the first parent of merge commits.
+    pub first_parent: bool,
+    /// Keep only commits descending from one of the excluded commits.
+    pub ancestry_path: bool,
+}
+/// Lists the commits reachable from `include` but from none of `exclude`,
+/// newest first unless `options` ask for topological order.
+pub fn list(
+    include: &[String],
+    exclude: &[String],
+    options: &WalkOptions,
+) -> Result<Vec<Commit>, GitError> {
+    let mut walk = RevWalk::new();
+    for hash in exclude {
+        walk.hide(hash)?;
+    }
+    for hash in include {
+        walk.push(hash)?;
+    }
+    let mut commits = Vec::new();
+    while let Some(commit) = walk.pop()? {
+        let parents = commit.parents();
+        let followed = match options.first_parent {
+            true => &parents[..parents.len().min(1)],
+            false => &parents[..],
+        };
+        for parent in followed {
+            walk.push(parent)?;
+        }
+        commits.push(commit);
+    }
+    if options.ancestry_path && !exclude.is_empty() {
+        let sorted = topo_order(commits.iter().collect(), |commit| {
+            (commit.hash(), commit.parents())
+        });
+        let mut on_path: HashSet<&String> = exclude.iter().collect();
+        for commit in sorted.into_iter().rev() {
+            if commit
+                .parents()
+                .iter()
+                .any(|parent| on_path.contains(parent))
+            {
+                on_path.insert(commit.hash());
+            }
+        }
+        let on_path: HashSet<String> = on_path.into_iter().cloned().collect();
+        commits.retain(|commit| on_path.contains(commit.hash()));
+    }
+    if options.topo_order {
+        commits = topo_order(commits, |commit| (commit.hash(), commit.parents()));
+    }
+    Ok(commits)
+}
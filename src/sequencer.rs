@@ -0,0 +1,561 @@
+use crate::commit::{Commit, Signature};
+use crate::config::Config;
+use crate::date::DateFormat;
+use crate::diff::{index_snapshot, print_stat, tree_snapshot, Snapshot};
+use crate::editor::{edit_message, ends_with_trailers};
+use crate::error::GitError;
+use crate::index::Index;
+use crate::merge::{commit_snapshot, merge_trees, ConflictStyle, Labels, TreeMerge};
+use crate::objects::{short_hash, GitObject};
+use crate::refs;
+use crate::revision::{is_range, resolve_commit, resolve_range, RevSpec};
+use crate::revwalk::{list, WalkOptions};
+use crate::state::{self, CHERRY_PICK_HEAD, MERGE_MSG, REVERT_HEAD, SEQUENCER_DIR};
+use crate::worktree::update_tree;
+use std::collections::BTreeSet;
+use std::fs;
+/// The commands of a rebase or cherry-pick todo list.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    /// `fixup -C`: a fixup whose message replaces the ones before it.
+    FixupMessage,
+    Exec,
+    Break,
+    Drop,
+    Revert,
+}
+impl Action {
+    pub fn parse(word: &str) -> Option<Self> {
+        match word {
+            "p" | "pick" => Some(Self::Pick),
+            "r" | "reword" => Some(Self::Reword),
+            "e" | "edit" => Some(Self::Edit),
+            "s" | "squash" => Some(Self::Squash),
+            "f" | "fixup" => Some(Self::Fixup),
+            "x" | "exec" => Some(Self::Exec),
+            "b" | "break" => Some(Self::Break),
+            "d" | "drop" => Some(Self::Drop),
+            "revert" => Some(Self::Revert),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pick => "pick",
+            Self::Reword => "reword",
+            Self::Edit => "edit",
+            Self::Squash => "squash",
+            Self::Fixup | Self::FixupMessage => "fixup",
+            Self::Exec => "exec",
+            Self::Break => "break",
+            Self::Drop => "drop",
+            Self::Revert => "revert",
+        }
+    }
+}
+/// One line of a todo list: a command and the commit it applies to, or the
+/// shell command `exec` runs.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub action: Action,
+    pub arg: String,
+    pub subject: String,
+}
+impl Step {
+    pub fn new(action: Action, commit: &Commit) -> Self {
+        Self {
+            action,
+            arg: commit.hash().clone(),
+            subject: commit.subject(),
+        }
+    }
+    /// Parses a todo line, expanding abbreviated commits. Blank lines and
+    /// comments give `None`.
+    pub fn parse(line: &str) -> Result<Option<Self>, GitError> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let invalid = || GitError::any(format!("invalid line: {line}"));
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mut action = Action::parse(word).ok_or_else(invalid)?;
+        let mut rest = rest.trim();
+        if action == Action::Fixup {
+            if let Some(after) = rest
+                .strip_prefix("-C")
+                .filter(|r| r.starts_with(char::is_whitespace))
+            {
+                action = Action::FixupMessage;
+                rest = after.trim_start();
+            }
+        }
+        let (arg, subject) = match action {
+            Action::Exec if rest.is_empty() => return Err(invalid()),
+            Action::Exec => (rest.to_string(), String::new()),
+            Action::Break => (String::new(), String::new()),
+            _ => {
+                let (commit, subject) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let commit = resolve_commit(commit).map_err(|_| invalid())?;
+                (commit, subject.trim().to_string())
+            }
+        };
+        Ok(Some(Self {
+            action,
+            arg,
+            subject,
+        }))
+    }
+    /// The todo line for this step, with the commit abbreviated if asked.
+    pub fn format(&self, abbreviate: bool) -> String {
+        match self.action {
+            Action::Exec => format!("exec {}", self.arg),
+            Action::Break => "break".to_string(),
+            action => {
+                let commit = match abbreviate {
+                    true => short_hash(&self.arg),
+                    false => &self.arg,
+                };
+                let name = match action {
+                    Action::FixupMessage => "fixup -C",
+                    action => action.name(),
+                };
+                format!("{name} {commit} {}", self.subject)
+            }
+        }
+    }
+}
+/// How a picked commit is named in conflict markers and messages.
+pub fn describe(commit: &Commit) -> String {
+    format!("{} ({})", short_hash(commit.hash()), commit.subject())
+}
+/// Applies the change from `base` to `theirs` on top of `ours`, the tree
+/// the index holds, updating the index and working tree. Conflicts are
+/// left staged for the user to resolve.
+pub fn apply(
+    base: &Snapshot,
+    ours: &Snapshot,
+    theirs: &Snapshot,
+    labels: &Labels,
+) -> Result<TreeMerge, GitError> {
+    let merge = merge_trees(base, ours, theirs, labels, ConflictStyle::from_config()?)?;
+    update_tree(ours, &merge.result, false, "merge")?;
+    merge.stage_conflicts()?;
+    Ok(merge)
+}
+/// Prints the one-line summary of a commit just made on HEAD, followed by
+/// its shortstat, like `git commit` does, which merge commits go without.
+/// The author is shown when it is not the committer, and the author date
+/// when asked for.
+pub fn print_summary(hash: &str, show_date: bool) -> Result<(), GitError> {
+    let commit = Commit::from_hex_string(hash)?;
+    let branch = refs::head_branch()?.unwrap_or_else(|| "detached HEAD".to_string());
+    let root = match commit.parents().is_empty() {
+        true => " (root-commit)",
+        false => "",
+    };
+    println!("[{branch}{root} {}] {}", short_hash(hash), commit.subject());
+    let (author, committer) = (commit.author(), commit.committer());
+    if (author.name(), author.email()) != (committer.name(), committer.email()) {
+        println!(" Author: {} <{}>", author.name(), author.email());
+    }
+    if show_date {
+        println!(" Date: {}", author.date(DateFormat::Default));
+    }
+    let old = match commit.parents().as_slice() {
+        [] => Snapshot::new(),
+        [parent] => commit_snapshot(parent)?,
+        _ => return Ok(()),
+    };
+    print_stat(&old, &tree_snapshot(commit.tree(), true)?, true)
+}
+/// How cherry-pick and revert apply each commit, kept in
+/// `.git/sequencer/opts` while a sequence is stopped.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayOptions {
+    /// The parent of merge commits to take the change against, from 1.
+    pub mainline: Option<usize>,
+    /// Append a `(cherry picked from commit ...)` line to the message.
+    pub record_origin: bool,
+    /// Only update the index and working tree, without committing.
+    pub no_commit: bool,
+}
+impl ReplayOptions {
+    fn load() -> Result<Self, GitError> {
+        let config = Config::from_file(format!("{SEQUENCER_DIR}/opts"))?;
+        Ok(Self {
+            mainline: config.get("options.mainline").and_then(|n| n.parse().ok()),
+            record_origin: config.get("options.record-origin") == Some("true"),
+            no_commit: config.get("options.no-commit") == Some("true"),
+        })
+    }
+    fn save(&self) -> Result<(), GitError> {
+        let mut opts = String::from("[options]\n");
+        if let Some(mainline) = self.mainline {
+            opts.push_str(&format!("\tmainline = {mainline}\n"));
+        }
+        if self.record_origin {
+            opts.push_str("\trecord-origin = true\n");
+        }
+        if self.no_commit {
+            opts.push_str("\tno-commit = true\n");
+        }
+        Ok(fs::write(format!("{SEQUENCER_DIR}/opts"), opts)?)
+    }
+}
+fn command(action: Action) -> &'static str {
+    match action {
+        Action::Revert => "revert",
+        _ => "cherry-pick",
+    }
+}
+/// The file naming the commit a stopped cherry-pick or revert was at.
+fn pending_path(action: Action) -> &'static str {
+    match action {
+        Action::Revert => REVERT_HEAD,
+        _ => CHERRY_PICK_HEAD,
+    }
+}
+fn save_todo(todo: &[Step]) -> Result<(), GitError> {
+    let lines: String = todo.iter().map(|step| step.format(true) + "\n").collect();
+    Ok(fs::write(format!("{SEQUENCER_DIR}/todo"), lines)?)
+}
+fn load_todo() -> Result<Option<Vec<Step>>, GitError> {
+    let Some(text) = state::read(&format!("{SEQUENCER_DIR}/todo"))? else {
+        return Ok(None);
+    };
+    let mut todo = Vec::new();
+    for line in text.lines() {
+        todo.extend(Step::parse(line)?);
+    }
+    Ok(Some(todo))
+}
+/// The parent `commit` is replayed against, checking `-m` against the
+/// number of parents it has. Like git, `-m 1` is allowed for any commit.
+fn mainline_parent(commit: &Commit, mainline: Option<usize>) -> Result<Option<String>, GitError> {
+    let hash = commit.hash();
+    match (commit.parents().len(), mainline) {
+        (_, None | Some(1)) if commit.parents().len() < 2 => Ok(commit.parents().first().cloned()),
+        (_, None) => Err(GitError::any(format!(
+            "commit {hash} is a merge but no -m option was given."
+        ))),
+        (count, Some(n)) if n == 0 || n > count => Err(GitError::any(format!(
+            "commit {hash} does not have parent {n}"
+        ))),
+        (_, Some(n)) => Ok(Some(commit.parents()[n - 1].clone())),
+    }
+}
+/// The message of the commit replaying `commit`.
+fn replay_message(
+    action: Action,
+    commit: &Commit,
+    parent: Option<&str>,
+    options: &ReplayOptions,
+) -> String {
+    if action == Action::Revert {
+        let mut message = format!(
+            "Revert \"{}\"\n\nThis reverts commit {}",
+            commit.subject(),
+            commit.hash()
+        );
+        match parent {
+            Some(parent) if commit.parents().len() > 1 => {
+                message.push_str(&format!(", reversing\nchanges made to {parent}"))
+            }
+            _ => {}
+        }
+        return message + ".\n";
+    }
+    let mut message = commit.message().trim_end().to_string();
+    if options.record_origin {
+        message.push_str(match ends_with_trailers(&message) {
+            true => "\n",
+            false => "\n\n",
+        });
+        message.push_str(&format!("(cherry picked from commit {})", commit.hash()));
+    }
+    message + "\n"
+}
+/// Commits the index on HEAD as the replay of `commit`, keeping its author
+/// when picking it. A revert concluded by `--continue` is written by the
+/// committer just now, so its summary leaves out the date.
+fn commit_replayed(
+    action: Action,
+    commit: &Commit,
+    message: &str,
+    continued: bool,
+) -> Result<(), GitError> {
+    let head = refs::resolve("HEAD")?.ok_or(GitError::any("HEAD does not point to a commit"))?;
+    let tree = Index::load()?.write_tree()?;
+    if &tree == Commit::from_hex_string(&head)?.tree() {
+        fs::write(pending_path(action), format!("{}\n", commit.hash()))?;
+        let what = command(action);
+        eprintln!(
+            "The previous {what} is now empty, possibly due to conflict resolution.
+If you wish to commit it anyway, use:
+
+    git commit --allow-empty
+
+Otherwise, please use 'git {what} --skip'"
+        );
+        return Err(GitError::exit(1));
+    }
+    let author = match action {
+        Action::Revert => Signature::author()?,
+        _ => commit.author().clone(),
+    };
+    let new = GitObject::build_commit_by(&author, message.trim_end(), &tree, [&head])?;
+    new.write()?;
+    let subject = message.lines().next().unwrap_or_default();
+    let reflog = format!("{}: {subject}", command(action));
+    refs::update("HEAD", &new.hex_string(), Some(&head), true, &reflog)?;
+    state::remove(CHERRY_PICK_HEAD)?;
+    state::remove(REVERT_HEAD)?;
+    print_summary(&new.hex_string(), !(continued && action == Action::Revert))
+}
+/// Applies the change `commit` made, or its reverse, to the index and
+/// working tree, and commits it unless asked not to. Stops the process on
+/// conflicts, leaving the pending commit and message for `--continue`.
+fn replay_one(step: &Step, options: &ReplayOptions, todo: &[Step]) -> Result<(), GitError> {
+    let commit = Commit::from_hex_string(&step.arg)?;
+    let parent = mainline_parent(&commit, options.mainline)?;
+    let parent_snapshot = match &parent {
+        Some(parent) => commit_snapshot(parent)?,
+        None => Snapshot::new(),
+    };
+    let commit_tree = commit_snapshot(commit.hash())?;
+    let ours = match options.no_commit {
+        true => index_snapshot(&Index::load()?),
+        false => match refs::resolve("HEAD")? {
+            Some(head) => commit_snapshot(&head)?,
+            None => Snapshot::new(),
+        },
+    };
+    let (this, parent_label) = (
+        describe(&commit),
+        format!("parent of {}", describe(&commit)),
+    );
+    let (base, theirs, labels) = match step.action {
+        Action::Revert => (&commit_tree, &parent_snapshot, [this, parent_label]),
+        _ => (&parent_snapshot, &commit_tree, [parent_label, this]),
+    };
+    let [base_label, theirs_label] = labels;
+    let labels = Labels {
+        base: base_label,
+        ours: "HEAD".to_string(),
+        theirs: theirs_label,
+    };
+    let merge = apply(base, &ours, theirs, &labels)?;
+    for message in merge.messages() {
+        println!("{message}");
+    }
+    let message = replay_message(step.action, &commit, parent.as_deref(), options);
+    if merge.is_clean() && options.no_commit {
+        return Ok(());
+    }
+    if merge.is_clean() {
+        return commit_replayed(step.action, &commit, &message, false);
+    }
+    fs::write(pending_path(step.action), format!("{}\n", commit.hash()))?;
+    let mut conflicts = String::from("\n# Conflicts:\n");
+    for path in merge.conflicts.keys() {
+        conflicts.push_str(&format!("#\t{path}\n"));
+    }
+    fs::write(MERGE_MSG, message + &conflicts)?;
+    if fs::metadata(SEQUENCER_DIR).is_ok() {
+        save_todo(todo)?;
+    }
+    let what = command(step.action);
+    let verb = match step.action {
+        Action::Revert => "revert",
+        _ => "apply",
+    };
+    eprintln!(
+        "error: could not {verb} {}... {}
+hint: After resolving the conflicts, mark them with
+hint: \"git add/rm <pathspec>\", then run
+hint: \"git {what} --continue\".
+hint: You can instead skip this commit with \"git {what} --skip\".
+hint: To abort and get back to the state before \"git {what}\",
+hint: run \"git {what} --abort\".",
+        short_hash(commit.hash()),
+        commit.subject()
+    );
+    Err(GitError::exit(1))
+}
+/// Replays the steps of `todo` in order, keeping the rest of the sequence
+/// in `.git/sequencer` in case one of them stops.
+fn run_replay(mut todo: Vec<Step>, options: &ReplayOptions) -> Result<(), GitError> {
+    while !todo.is_empty() {
+        replay_one(&todo[0], options, &todo)?;
+        todo.remove(0);
+        if fs::metadata(SEQUENCER_DIR).is_ok() {
+            save_todo(&todo)?;
+        }
+    }
+    state::remove_dir(SEQUENCER_DIR)
+}
+/// The commits named by `revs`: each one given, or, when ranges are among
+/// them, the commits they select, oldest first if `reverse`.
+pub fn replay_commits(revs: &[String], reverse: bool) -> Result<Vec<Commit>, GitError> {
+    if !revs.iter().any(|rev| is_range(rev)) {
+        return revs
+            .iter()
+            .map(|rev| Commit::from_hex_string(resolve_commit(rev)?))
+            .collect();
+    }
+    let (mut include, mut exclude) = (Vec::new(), Vec::new());
+    for rev in revs {
+        for spec in resolve_range(rev)? {
+            match spec {
+                RevSpec::Include(hash) => include.push(resolve_commit(&hash)?),
+                RevSpec::Exclude(hash) => exclude.push(resolve_commit(&hash)?),
+            }
+        }
+    }
+    let mut commits = list(&include, &exclude, &WalkOptions::default())?;
+    if reverse {
+        commits.reverse();
+    }
+    Ok(commits)
+}
+/// Cherry-picks or reverts `commits` on HEAD, one commit each, the way
+/// `action` says.
+pub fn replay(action: Action, commits: &[Commit], options: ReplayOptions) -> Result<(), GitError> {
+    let what = command(action);
+    if fs::metadata(SEQUENCER_DIR).is_ok() {
+        return Err(GitError::any(format!(
+            "{what} is already in progress\nhint: try \"git {what} (--continue | --quit | --abort)\"\nfatal: {what} failed"
+        )));
+    }
+    let head = refs::resolve("HEAD")?;
+    if !options.no_commit {
+        let head_snapshot = match &head {
+            Some(head) => commit_snapshot(head)?,
+            None => Snapshot::new(),
+        };
+        if index_snapshot(&Index::load()?) != head_snapshot {
+            return Err(GitError::any(format!(
+                "your local changes would be overwritten by {what}.\nhint: commit your changes or stash them to proceed.\nfatal: {what} failed"
+            )));
+        }
+    }
+    let todo: Vec<Step> = commits
+        .iter()
+        .map(|commit| Step::new(action, commit))
+        .collect();
+    if todo.len() > 1 {
+        fs::create_dir_all(SEQUENCER_DIR)?;
+        if let Some(head) = head {
+            fs::write(format!("{SEQUENCER_DIR}/head"), format!("{head}\n"))?;
+        }
+        options.save()?;
+        save_todo(&todo)?;
+    }
+    run_replay(todo, &options)
+}
+fn pending() -> Result<Option<(Action, String)>, GitError> {
+    for (action, path) in [
+        (Action::Pick, CHERRY_PICK_HEAD),
+        (Action::Revert, REVERT_HEAD),
+    ] {
+        if let Some(hash) = state::read(path)? {
+            return Ok(Some((action, hash.trim().to_string())));
+        }
+    }
+    Ok(None)
+}
+/// The remaining steps after the one that stopped, or an error when no
+/// cherry-pick or revert is in progress.
+fn remaining(has_pending: bool, what: &str) -> Result<Vec<Step>, GitError> {
+    match load_todo()? {
+        Some(mut todo) => {
+            if !todo.is_empty() {
+                todo.remove(0);
+            }
+            Ok(todo)
+        }
+        None if has_pending => Ok(Vec::new()),
+        None => Err(GitError::any(format!(
+            "no {what} in progress\nfatal: {what} failed"
+        ))),
+    }
+}
+/// Fails when `index` still has unmerged paths, which cannot be committed.
+pub fn check_merged(index: &Index) -> Result<(), GitError> {
+    let unmerged: BTreeSet<&String> = index
+        .entries()
+        .iter()
+        .filter(|entry| entry.stage() > 0)
+        .map(|entry| entry.path())
+        .collect();
+    if unmerged.is_empty() {
+        return Ok(());
+    }
+    let paths: String = unmerged.iter().map(|path| format!("\nU\t{path}")).collect();
+    Err(GitError::any(format!(
+        "Committing is not possible because you have unmerged files.\nhint: Fix them up in the work tree, and then use 'git add/rm <file>'\nhint: as appropriate to mark resolution and make a commit.\nfatal: Exiting because of an unresolved conflict.{paths}"
+    )))
+}
+/// Commits the resolved conflict of a stopped cherry-pick or revert and
+/// replays the rest of the sequence.
+pub fn replay_continue(what: &str) -> Result<(), GitError> {
+    check_merged(&Index::load()?)?;
+    let pending = pending()?;
+    let todo = remaining(pending.is_some(), what)?;
+    if let Some((action, hash)) = pending {
+        let commit = Commit::from_hex_string(&hash)?;
+        let message = edit_message(&fs::read_to_string(MERGE_MSG).unwrap_or_default())?;
+        commit_replayed(action, &commit, &message, true)?;
+    }
+    run_replay(todo, &ReplayOptions::load()?)
+}
+/// Drops the commit a cherry-pick or revert stopped at and goes on.
+pub fn replay_skip(what: &str) -> Result<(), GitError> {
+    let pending = pending()?;
+    let todo = remaining(pending.is_some(), what)?;
+    if let Some(head) = refs::resolve("HEAD")? {
+        let head = commit_snapshot(&head)?;
+        update_tree(&head, &head, true, "reset")?;
+    }
+    state::remove(CHERRY_PICK_HEAD)?;
+    state::remove(REVERT_HEAD)?;
+    run_replay(todo, &ReplayOptions::load()?)
+}
+/// Stops a cherry-pick or revert, forgetting the rest of the sequence;
+/// with `abort`, HEAD, the index and working tree go back to where they
+/// were before it started.
+pub fn replay_abort(what: &str, abort: bool) -> Result<(), GitError> {
+    let pending = pending()?;
+    if pending.is_none() && fs::metadata(SEQUENCER_DIR).is_err() {
+        return Err(GitError::any(format!(
+            "no cherry-pick or revert in progress\nfatal: {what} failed"
+        )));
+    }
+    if abort {
+        let head =
+            refs::resolve("HEAD")?.ok_or(GitError::any("HEAD does not point to a commit"))?;
+        let orig = match fs::read_to_string(format!("{SEQUENCER_DIR}/head")) {
+            Ok(orig) => orig.trim().to_string(),
+            Err(_) => head.clone(),
+        };
+        update_tree(
+            &commit_snapshot(&head)?,
+            &commit_snapshot(&orig)?,
+            true,
+            "reset",
+        )?;
+        if orig != head {
+            let message = format!("{what}: aborting");
+            refs::update("HEAD", &orig, Some(&head), true, &message)?;
+        }
+        state::remove(MERGE_MSG)?;
+    }
+    state::remove(CHERRY_PICK_HEAD)?;
+    state::remove(REVERT_HEAD)?;
+    state::remove_dir(SEQUENCER_DIR)
+}
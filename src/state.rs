@@ -0,0 +1,95 @@
+use crate::error::GitError;
+use std::fs;
+use std::io::ErrorKind;
+pub const ORIG_HEAD: &str = ".git/ORIG_HEAD";
+pub const MERGE_HEAD: &str = ".git/MERGE_HEAD";
+pub const MERGE_MSG: &str = ".git/MERGE_MSG";
+pub const MERGE_MODE: &str = ".git/MERGE_MODE";
+pub const AUTO_MERGE: &str = ".git/AUTO_MERGE";
+pub const SQUASH_MSG: &str = ".git/SQUASH_MSG";
+pub const CHERRY_PICK_HEAD: &str = ".git/CHERRY_PICK_HEAD";
+pub const REVERT_HEAD: &str = ".git/REVERT_HEAD";
+pub const SEQUENCER_DIR: &str = ".git/sequencer";
+pub const REBASE_DIR: &str = ".git/rebase-merge";
+/// State a merge, cherry-pick or revert leaves behind, dropped once the
+/// result is committed or HEAD is reset.
+pub const BRANCH_STATE: [&str; 7] = [
+    MERGE_HEAD,
+    MERGE_MSG,
+    MERGE_MODE,
+    AUTO_MERGE,
+    SQUASH_MSG,
+    CHERRY_PICK_HEAD,
+    REVERT_HEAD,
+];
+/// Reads a state file, or `None` when there is none.
+pub fn read(path: &str) -> Result<Option<String>, GitError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+/// Removes a state file if there is one.
+pub fn remove(path: &str) -> Result<(), GitError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+/// Removes each of the state files `paths` that exist.
+pub fn remove_all(paths: &[&str]) -> Result<(), GitError> {
+    paths.iter().try_for_each(|path| remove(path))
+}
+/// Removes a state directory and everything in it if there is one.
+pub fn remove_dir(path: &str) -> Result<(), GitError> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("state-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    #[test]
+    fn missing_files_read_as_none_and_remove_quietly() {
+        let dir = scratch("missing");
+        let path = dir.join("MERGE_HEAD");
+        let path = path.to_str().unwrap();
+        assert_eq!(read(path).unwrap(), None);
+        remove(path).unwrap();
+        fs::write(path, "abc\n").unwrap();
+        assert_eq!(read(path).unwrap().as_deref(), Some("abc\n"));
+        remove(path).unwrap();
+        assert!(!dir.join("MERGE_HEAD").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn remove_all_and_remove_dir_clear_what_exists() {
+        let dir = scratch("remove-all");
+        let paths: Vec<String> = ["MERGE_HEAD", "MERGE_MSG", "SQUASH_MSG"]
+            .map(|name| dir.join(name).to_string_lossy().into_owned())
+            .to_vec();
+        fs::write(&paths[0], "").unwrap();
+        fs::write(&paths[2], "").unwrap();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        remove_all(&paths).unwrap();
+        assert!(paths.iter().all(|path| fs::metadata(path).is_err()));
+
+        let sequencer = dir.join("sequencer");
+        fs::create_dir(&sequencer).unwrap();
+        fs::write(sequencer.join("todo"), "").unwrap();
+        remove_dir(sequencer.to_str().unwrap()).unwrap();
+        assert!(!sequencer.exists());
+        remove_dir(sequencer.to_str().unwrap()).unwrap();
+        assert!(remove(dir.to_str().unwrap()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+use crate::config::Config;
+use crate::error::GitError;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+/// A submodule as described in `.gitmodules`.
+pub struct Submodule {
+    pub name: String,
+    pub path: String,
+    pub url: Option<String>,
+}
+/// Lists the submodules declared in `.gitmodules`, in file order.
+pub fn list() -> Result<Vec<Submodule>, GitError> {
+    let config = Config::from_file(".gitmodules")?;
+    Ok(config
+        .subsections("submodule")
+        .into_iter()
+        .filter_map(|name| {
+            let path = config.get(&format!("submodule.{name}.path"))?;
+            Some(Submodule {
+                name: name.to_string(),
+                path: path.trim_end_matches('/').to_string(),
+                url: config
+                    .get(&format!("submodule.{name}.url"))
+                    .map(str::to_string),
+            })
+        })
+        .collect())
+}
+/// The git directory of the repository checked out at `path`: its `.git`
+/// directory, or the directory a `.git` file points to.
+pub fn git_dir(path: impl AsRef<Path>) -> Option<PathBuf> {
+    let dot_git = path.as_ref().join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let contents = fs::read_to_string(&dot_git).ok()?;
+    let dir = contents.trim().strip_prefix("gitdir: ")?;
+    Some(path.as_ref().join(dir)).filter(|dir| dir.is_dir())
+}
+pub fn is_repo(path: impl AsRef<Path>) -> bool {
+    git_dir(path).is_some()
+}
+/// Resolves HEAD of the repository checked out at `path` without entering
+/// it, following symbolic refs through loose and packed refs.
+pub fn head(path: impl AsRef<Path>) -> Result<Option<String>, GitError> {
+    let Some(dir) = git_dir(path) else {
+        return Ok(None);
+    };
+    let mut name = "HEAD".to_string();
+    for _ in 0..5 {
+        let Ok(contents) = fs::read_to_string(dir.join(&name)) else {
+            let packed = fs::read_to_string(dir.join("packed-refs")).unwrap_or_default();
+            return Ok(packed.lines().find_map(|line| {
+                let (hash, refname) = line.split_once(' ')?;
+                (refname == name).then(|| hash.to_string())
+            }));
+        };
+        match contents.trim().strip_prefix("ref: ") {
+            Some(target) => name = target.to_string(),
+            None => return Ok(Some(contents.trim().to_string())),
+        }
+    }
+    Ok(None)
+}
+/// Runs `f` with `path` as the current directory, so the repository there
+/// is the one the ref, config and index code works on.
+pub fn within<T>(
+    path: impl AsRef<Path>,
+    f: impl FnOnce() -> Result<T, GitError>,
+) -> Result<T, GitError> {
+    let cwd = env::current_dir()?;
+    env::set_current_dir(path)?;
+    let result = f();
+    env::set_current_dir(cwd)?;
+    result
+}
+/// Resolves a `./` or `../` submodule URL against the superproject's
+/// `origin` remote, or its working tree when it has none.
+pub fn resolve_url(url: &str) -> Result<String, GitError> {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return Ok(url.to_string());
+    }
+    let mut base = match Config::load()?.get("remote.origin.url") {
+        Some(remote) => remote.trim_end_matches('/').to_string(),
+        None => env::current_dir()?.to_string_lossy().into_owned(),
+    };
+    let mut rest = url;
+    loop {
+        if let Some(stripped) = rest.strip_prefix("./") {
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("../") {
+            rest = stripped;
+            base = match base.rsplit_once('/') {
+                Some((parent, _)) => parent.to_string(),
+                None => ".".to_string(),
+            };
+        } else {
+            break;
+        }
+    }
+    Ok(format!("{base}/{rest}"))
+}
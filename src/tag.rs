@@ -0,0 +1,126 @@
+use crate::commit::Signature;
+use crate::error::GitError;
+use crate::objects::{GitObject, Kind};
+/// Lines that open the signature appended to a signed tag's message.
+const SIGNATURE_STARTS: [&str; 3] = [
+    "-----BEGIN PGP SIGNATURE-----",
+    "-----BEGIN SSH SIGNATURE-----",
+    "-----BEGIN SIGNED MESSAGE-----",
+];
+/// An annotated tag: a named, signed-off pointer to another object.
+#[derive(Clone, Debug)]
+pub struct Tag {
+    object: String,
+    kind: String,
+    name: String,
+    tagger: Option<Signature>,
+    message: String,
+    signature: Option<String>,
+}
+impl Tag {
+    pub fn parse(data: &[u8]) -> Result<Self, GitError> {
+        let text = String::from_utf8_lossy(data);
+        let (headers, body) = match text.split_once("\n\n") {
+            Some((headers, body)) => (headers, body),
+            None => (text.trim_end_matches('\n'), ""),
+        };
+        let mut object = None;
+        let mut kind = None;
+        let mut name = None;
+        let mut tagger = None;
+        for line in headers.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "object" if object.is_none() => object = Some(value.to_string()),
+                "type" if kind.is_none() => kind = Some(value.to_string()),
+                "tag" if name.is_none() => name = Some(value.to_string()),
+                "tagger" if tagger.is_none() => tagger = Some(Signature::parse(value)?),
+                _ => {}
+            }
+        }
+        let signature_start = std::iter::once(0)
+            .chain(body.match_indices('\n').map(|(i, _)| i + 1))
+            .rfind(|&i| SIGNATURE_STARTS.iter().any(|s| body[i..].starts_with(s)));
+        let (message, signature) = match signature_start {
+            Some(i) => (&body[..i], Some(body[i..].to_string())),
+            None => (body, None),
+        };
+        Ok(Self {
+            object: object.ok_or(GitError::invalid_object_format("tag is missing object"))?,
+            kind: kind.ok_or(GitError::invalid_object_format("tag is missing type"))?,
+            name: name.ok_or(GitError::invalid_object_format("tag is missing name"))?,
+            tagger,
+            message: message.to_string(),
+            signature,
+        })
+    }
+    pub fn from_hex_string(hex_string: impl AsRef<str>) -> Result<Self, GitError> {
+        let git_object = GitObject::from_hex_string(hex_string.as_ref())?;
+        match git_object.kind() {
+            Kind::Tag => Self::parse(git_object.contents()),
+            kind => Err(GitError::any(format!(
+                "object {} is a {kind}, not a tag",
+                hex_string.as_ref()
+            ))),
+        }
+    }
+    /// Builds a tag object named `name` for `object` of type `kind`, with
+    /// `message` (and any signature) recorded as given.
+    pub fn build(
+        object: &str,
+        kind: &Kind,
+        name: &str,
+        tagger: &Signature,
+        message: &str,
+    ) -> Result<GitObject, GitError> {
+        let contents =
+            format!("object {object}\ntype {kind}\ntag {name}\ntagger {tagger}\n\n{message}");
+        GitObject::build(Kind::Tag, contents.into())
+    }
+    pub fn object(&self) -> &String {
+        &self.object
+    }
+    pub fn kind(&self) -> &String {
+        &self.kind
+    }
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+    /// The tagger; very old tags were written without one.
+    pub fn tagger(&self) -> Option<&Signature> {
+        self.tagger.as_ref()
+    }
+    /// The message, without the signature that may follow it.
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+    pub fn signature(&self) -> Option<&String> {
+        self.signature.as_ref()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_splits_the_signature_at_the_last_begin_line() {
+        let data = "object 0123456789012345678901234567890123456789\ntype commit\ntag v1\n\
+                    tagger A <a@example.com> 1700000000 +0000\n\n\
+                    quoting\n-----BEGIN PGP SIGNATURE-----\nin the message\n\
+                    -----BEGIN PGP SIGNATURE-----\n\nsig\n-----END PGP SIGNATURE-----\n";
+        let tag = Tag::parse(data.as_bytes()).unwrap();
+        assert_eq!(tag.name(), "v1");
+        assert_eq!(
+            tag.message(),
+            "quoting\n-----BEGIN PGP SIGNATURE-----\nin the message\n"
+        );
+        assert_eq!(
+            tag.signature().map(String::as_str),
+            Some("-----BEGIN PGP SIGNATURE-----\n\nsig\n-----END PGP SIGNATURE-----\n")
+        );
+        let unsigned = Tag::parse(b"object 0\ntype blob\ntag v2\n\nplain\n").unwrap();
+        assert!(unsigned.tagger().is_none());
+        assert_eq!(unsigned.message(), "plain\n");
+        assert!(unsigned.signature().is_none());
+        assert!(Tag::parse(b"object 0\ntype blob\n\nno name\n").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use crate::diff::{index_snapshot, tree_snapshot, Snapshot};
+use crate::error::GitError;
+use crate::ignore::Ignore;
+use crate::index::{Index, IndexEntry};
+use crate::objects::GitObject;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+/// Writes the blob `hash` to `path` as a file of the given mode, creating
+/// leading directories and replacing whatever file is already there. A
+/// gitlink only gets an empty directory for the submodule to live in.
+pub fn write_file(path: &str, mode: &str, hash: &[u8]) -> Result<(), GitError> {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if mode == "160000" {
+        if fs::symlink_metadata(path).is_ok_and(|meta| !meta.is_dir()) {
+            fs::remove_file(path)?;
+        }
+        fs::create_dir_all(path)?;
+        return Ok(());
+    }
+    GitObject::from_hex_string(hex::encode(hash))?.restore_blob(path, mode)
+}
+/// Removes the file at `path` along with any directories it leaves empty.
+/// A submodule directory is only removed when it is empty.
+pub fn remove_file(path: &str) -> Result<(), GitError> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => {
+            if fs::remove_dir(path).is_err() {
+                return Ok(());
+            }
+        }
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        Err(_) => {}
+    }
+    let mut dir = Path::new(path).parent();
+    while let Some(parent) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+        if fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+/// Writes an index entry's blob to the working tree and stages it with the
+/// new file's stat data.
+pub fn checkout_entry(
+    index: &mut Index,
+    path: &str,
+    mode: &str,
+    hash: &[u8],
+) -> Result<(), GitError> {
+    write_file(path, mode, hash)?;
+    let mut entry = IndexEntry::new(path, mode, hash.to_vec(), 0)?;
+    entry.refresh_stat()?;
+    index.add(entry);
+    Ok(())
+}
+/// Whether the working tree file at `path` matches what the index records
+/// for it, or is absent when the index does not track it.
+pub fn is_clean(index: &Index, path: &str) -> Result<bool, GitError> {
+    match index.get(path) {
+        Some(entry) => Ok(!entry.is_modified()?),
+        None => Ok(fs::symlink_metadata(path).is_err()),
+    }
+}
+/// Whether writing `hash` to the untracked `path` would lose data: an
+/// unignored file there holds something else, or a directory there holds
+/// untracked files.
+fn in_the_way(
+    index: &Index,
+    ignore: &mut Ignore,
+    path: &str,
+    hash: &[u8],
+) -> Result<bool, GitError> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => {
+            Ok(ignore.walk(path)?.iter().any(|file| !index.contains(file)))
+        }
+        Ok(_) if ignore.is_ignored(path, false)? => Ok(false),
+        Ok(_) => Ok(GitObject::from_path(path, false)?.hash().as_slice() != hash),
+        Err(_) => Ok(false),
+    }
+}
+fn aborting(header: &str, paths: &[&String], advice: &str) -> GitError {
+    let paths: String = paths.iter().map(|path| format!("\t{path}\n")).collect();
+    GitError::any(format!("{header}\n{paths}{advice}\nAborting"))
+}
+/// Moves the index and working tree from tree `old` to tree `new`. Paths
+/// that are the same in both trees keep their local changes; the others are
+/// only touched when the index and working tree still match `old`, so local
+/// modifications and untracked files are never clobbered. With `force`, the
+/// index and working tree are reset to `new` instead.
+pub fn checkout_tree(old: Option<&str>, new: &str, force: bool) -> Result<(), GitError> {
+    let old = match old {
+        Some(tree) => tree_snapshot(tree, true)?,
+        None => Snapshot::new(),
+    };
+    update_tree(&old, &tree_snapshot(new, true)?, force, "checkout")
+}
+/// Like [`checkout_tree`], but between two snapshots; `action` names the
+/// operation in the messages listing files that stand in the way.
+pub fn update_tree(
+    old: &Snapshot,
+    new: &Snapshot,
+    force: bool,
+    action: &str,
+) -> Result<(), GitError> {
+    let advice = match action {
+        "checkout" => "switch branches",
+        action => action,
+    };
+    let mut index = Index::load()?;
+    if !force && index.entries().iter().any(|entry| entry.stage() > 0) {
+        return Err(GitError::any(
+            "you need to resolve your current index first",
+        ));
+    }
+    let current = index_snapshot(&index);
+    let paths: BTreeSet<String> = old
+        .keys()
+        .chain(new.keys())
+        .chain(index.entries().iter().map(|entry| entry.path()))
+        .cloned()
+        .collect();
+    let mut ignore = Ignore::load()?;
+    let (mut updates, mut dirty, mut untracked) = (Vec::new(), Vec::new(), Vec::new());
+    for path in &paths {
+        let (from, to, staged) = (old.get(path), new.get(path), current.get(path));
+        if force {
+            let unmerged = staged.is_none() && index.contains(path);
+            if staged != to || unmerged || !is_clean(&index, path)? {
+                updates.push((path, to));
+            }
+        } else if from == to || staged == to {
+            continue;
+        } else if staged == from && is_clean(&index, path)? {
+            updates.push((path, to));
+        } else if staged.is_none() && from.is_none() {
+            let (_, hash) = to.expect("path is in one of the trees");
+            match in_the_way(&index, &mut ignore, path, hash)? {
+                true => untracked.push(path),
+                false => updates.push((path, to)),
+            }
+        } else {
+            dirty.push(path);
+        }
+    }
+    if !dirty.is_empty() {
+        return Err(aborting(
+            &format!("Your local changes to the following files would be overwritten by {action}:"),
+            &dirty,
+            &format!("Please commit your changes or stash them before you {advice}."),
+        ));
+    }
+    if !untracked.is_empty() {
+        return Err(aborting(
+            &format!(
+                "The following untracked working tree files would be overwritten by {action}:"
+            ),
+            &untracked,
+            &format!("Please move or remove them before you {advice}."),
+        ));
+    }
+    for (path, _) in updates.iter().filter(|(_, to)| to.is_none()) {
+        index.remove(path);
+        remove_file(path)?;
+    }
+    for (path, to) in &updates {
+        if let Some((mode, hash)) = to {
+            checkout_entry(&mut index, path, mode, hash)?;
+        }
+    }
+    index.write()
+}
+/// Stages every entry of `tree` with the stat data of the files a fresh
+/// checkout of it just wrote.
+pub fn stage_tree(tree: &str) -> Result<(), GitError> {
+    let mut index = Index::default();
+    for entry in Index::from_tree(tree)?.entries() {
+        let mut entry = entry.clone();
+        entry.refresh_stat()?;
+        index.add(entry);
+    }
+    index.write()
+}
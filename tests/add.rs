@@ -0,0 +1,24 @@
+mod common;
+use common::{fresh_repo, git, Repo};
+use std::fs;
+/// The tree of `a.txt` and `sub/b.txt`, as `git write-tree` records it.
+const TREE: &str = "972b5b8f25e6b64dc9a3033af8cb531ff783879a\n";
+fn repo_with_files(name: &str) -> Repo {
+    let dir = fresh_repo(name);
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), "a\n").unwrap();
+    fs::write(dir.join("sub/b.txt"), "b\n").unwrap();
+    dir
+}
+#[test]
+fn add_dot_stages_files_at_the_root() {
+    let dir = repo_with_files("add-dot");
+    git(&dir, &["add", "."]);
+    assert_eq!(git(&dir, &["write-tree"]), TREE);
+}
+#[test]
+fn add_all_stages_files_at_the_root() {
+    let dir = repo_with_files("add-all");
+    git(&dir, &["add", "-A"]);
+    assert_eq!(git(&dir, &["write-tree"]), TREE);
+}
@@ -0,0 +1,45 @@
+mod common;
+use common::{commit_files, fresh_repo, git, run};
+#[test]
+fn branch_rename_refuses_a_name_that_clashes_with_another_branch() {
+    let dir = fresh_repo("branch-rename-clash");
+    commit_files(&dir, &[("a", "a\n")], "base");
+    git(&dir, &["branch", "foo"]);
+    git(&dir, &["branch", "bar"]);
+    let foo = git(&dir, &["rev-parse", "foo"]);
+    let log = git(&dir, &["reflog", "show", "foo"]);
+    assert!(!log.is_empty());
+
+    for (new, message) in [
+        (
+            "bar/x",
+            "'refs/heads/bar' exists; cannot create 'refs/heads/bar/x'",
+        ),
+        (
+            "main/x",
+            "'refs/heads/main' exists; cannot create 'refs/heads/main/x'",
+        ),
+    ] {
+        let output = run(&dir, &["branch", "-m", "foo", new]);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message));
+        assert_eq!(git(&dir, &["rev-parse", "foo"]), foo);
+        assert_eq!(git(&dir, &["reflog", "show", "foo"]), log);
+    }
+    git(&dir, &["branch", "baz/x"]);
+    let output = run(&dir, &["branch", "-m", "foo", "baz"]);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("'refs/heads/baz/x' exists; cannot create 'refs/heads/baz'"));
+    assert_eq!(git(&dir, &["rev-parse", "foo"]), foo);
+
+    git(&dir, &["branch", "-m", "foo", "foo/x"]);
+    assert_eq!(git(&dir, &["rev-parse", "foo/x"]), foo);
+    let short = &foo[..7];
+    assert_eq!(
+        git(&dir, &["reflog", "show", "foo/x"]),
+        format!(
+            "{short} foo/x@{{0}}: Branch: renamed refs/heads/foo to refs/heads/foo/x\n\
+             {short} foo/x@{{1}}: branch: Created from HEAD\n"
+        )
+    );
+}
@@ -0,0 +1,96 @@
+mod common;
+use common::{commit_files, fresh_repo, git, git_fails, run_with_input};
+use std::fs;
+#[test]
+fn cat_file_reports_type_size_existence_and_pretty_trees() {
+    let dir = fresh_repo("cat-file-modes");
+    let commit = commit_files(&dir, &[("f", "1\n"), ("d/g", "22\n")], "first");
+    let tree = git(&dir, &["rev-parse", "HEAD^{tree}"]);
+    let blob = git(&dir, &["hash-object", "f"]);
+    let subtree = git(&dir, &["rev-parse", "HEAD:d"]);
+    assert_eq!(git(&dir, &["cat-file", "-t", &commit]), "commit\n");
+    assert_eq!(git(&dir, &["cat-file", "-t", "HEAD:d"]), "tree\n");
+    assert_eq!(git(&dir, &["cat-file", "-s", "HEAD:d/g"]), "3\n");
+    assert_eq!(git(&dir, &["cat-file", "-e", "HEAD:f"]), "");
+    assert_eq!(git_fails(&dir, &["cat-file", "-e", &"0".repeat(40)], 1), "");
+    assert_eq!(
+        git(&dir, &["cat-file", "-p", tree.trim()]),
+        format!(
+            "040000 tree {}\td\n100644 blob {}\tf\n",
+            subtree.trim(),
+            blob.trim()
+        )
+    );
+    assert_eq!(git(&dir, &["cat-file", "blob", "HEAD:f"]), "1\n");
+    let stderr = git_fails(&dir, &["cat-file", "-t", "nope"], 1);
+    assert!(stderr.contains("Not a valid object name nope"));
+}
+#[test]
+fn cat_file_batch_check_fills_in_each_atom() {
+    let dir = fresh_repo("cat-file-batch-check");
+    commit_files(&dir, &[("f", "1\n")], "first");
+    let blob = git(&dir, &["hash-object", "f"]);
+    let blob = blob.trim();
+    let disk = fs::metadata(dir.join(".git/objects").join(&blob[..2]).join(&blob[2..]))
+        .unwrap()
+        .len();
+    let output = run_with_input(
+        &dir,
+        &[
+            "cat-file",
+            "--batch-check=%(objectname) %(objecttype) %(objectsize) \
+             %(objectsize:disk) %(deltabase) [%(rest)]",
+        ],
+        b"HEAD:f  the rest\nnope\n",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{blob} blob 2 {disk} {} [the rest]\nnope missing\n",
+            "0".repeat(40)
+        )
+    );
+    let output = run_with_input(&dir, &["cat-file", "--batch-check"], b"HEAD:f extra\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "HEAD:f extra missing\n"
+    );
+    let stderr = git_fails(&dir, &["cat-file", "--batch-check=%(bogus)"], 1);
+    assert!(stderr.contains("unknown format element: bogus"));
+}
+#[test]
+fn cat_file_batch_shows_contents_and_every_object() {
+    let dir = fresh_repo("cat-file-batch");
+    let commit = commit_files(&dir, &[("f", "1\n")], "first");
+    let blob = git(&dir, &["hash-object", "f"]);
+    let blob = blob.trim();
+    let tree = git(&dir, &["rev-parse", "HEAD^{tree}"]);
+    let output = run_with_input(&dir, &["cat-file", "--batch", "--buffer"], b"HEAD:f\n");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{blob} blob 2\n1\n\n")
+    );
+    let mut hashes = [blob, commit.as_str(), tree.trim()];
+    hashes.sort();
+    assert_eq!(
+        git(
+            &dir,
+            &[
+                "cat-file",
+                "--batch-all-objects",
+                "--batch-check=%(objectname)"
+            ]
+        ),
+        format!("{}\n", hashes.join("\n"))
+    );
+    for args in [
+        &["cat-file", "--buffer", "-t", "HEAD"][..],
+        &["cat-file", "--batch-all-objects"],
+        &["cat-file", "--batch", "--batch-check"],
+        &["cat-file", "--batch", "HEAD"],
+    ] {
+        git_fails(&dir, args, 1);
+    }
+    let stderr = git_fails(&dir, &["cat-file", "--buffer", "-t", "HEAD"], 1);
+    assert!(stderr.contains("'--buffer' requires a batch mode"));
+}
@@ -0,0 +1,70 @@
+mod common;
+use common::{commit_files, fresh_repo, git, git_fails, write_files, Repo};
+use std::fs;
+/// `main` and `side` forked after `base`, with `side` changing `f` twice.
+fn forked(name: &str) -> (Repo, Vec<String>) {
+    let dir = fresh_repo(name);
+    commit_files(&dir, &[("f", "0\n")], "base");
+    let mut config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    config.push_str("[core]\n\teditor = :\n");
+    fs::write(dir.join(".git/config"), config).unwrap();
+    git(&dir, &["checkout", "-q", "-b", "side"]);
+    let picks = vec![
+        commit_files(&dir, &[("f", "1\n")], "one"),
+        commit_files(&dir, &[("g", "1\n")], "two"),
+    ];
+    git(&dir, &["checkout", "-q", "main"]);
+    (dir, picks)
+}
+#[test]
+fn cherry_pick_applies_a_range_and_records_the_origin() {
+    let (dir, picks) = forked("cherry-pick-range");
+    git(&dir, &["cherry-pick", "-x", "main..side"]);
+    assert_eq!(git(&dir, &["log", "--format=%s", "-2"]), "two\none\n");
+    assert_eq!(
+        git(&dir, &["log", "-1", "--format=%b"]),
+        format!("(cherry picked from commit {})\n\n", picks[1])
+    );
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "1\n");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+}
+#[test]
+fn cherry_pick_stops_at_a_conflict_until_it_is_resolved() {
+    let (dir, picks) = forked("cherry-pick-conflict");
+    commit_files(&dir, &[("f", "main\n")], "main");
+    let stderr = git_fails(&dir, &["cherry-pick", &picks[0], &picks[1]], 1);
+    assert!(stderr.contains("could not apply"), "{stderr}");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "UU f\n");
+    let stderr = git_fails(&dir, &["cherry-pick", "--continue"], 1);
+    assert!(stderr.contains("Committing is not possible because you have unmerged files."));
+
+    write_files(&dir, &[("f", "resolved\n")]);
+    git(&dir, &["cherry-pick", "--continue"]);
+    assert_eq!(git(&dir, &["log", "--format=%s", "-3"]), "two\none\nmain\n");
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "resolved\n");
+    assert!(dir.join("g").exists());
+}
+#[test]
+fn revert_undoes_a_commit_and_abort_restores_head() {
+    let (dir, picks) = forked("revert");
+    git(&dir, &["cherry-pick", "side"]);
+    git(&dir, &["revert", "HEAD"]);
+    assert!(!dir.join("g").exists());
+    assert_eq!(
+        git(&dir, &["log", "-1", "--format=%B"]),
+        format!(
+            "Revert \"two\"\n\nThis reverts commit {}.\n\n",
+            git(&dir, &["rev-parse", "HEAD~"]).trim()
+        )
+    );
+
+    let head = git(&dir, &["rev-parse", "HEAD"]);
+    commit_files(&dir, &[("f", "main\n")], "main");
+    let before = git(&dir, &["rev-parse", "HEAD"]);
+    git_fails(&dir, &["cherry-pick", &picks[0]], 1);
+    git(&dir, &["cherry-pick", "--abort"]);
+    assert_eq!(git(&dir, &["rev-parse", "HEAD"]), before);
+    assert_ne!(before, head);
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "main\n");
+}
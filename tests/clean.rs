@@ -0,0 +1,67 @@
+mod common;
+use common::{commit_files, fresh_repo, git, Repo};
+use std::fs;
+/// Tracked `t` and `dir/tracked`, untracked `u`, `dir/u` and `new/x`, and
+/// the ignored `a.log`, `build/out`, `dir/b.log` and `new/c.log`.
+fn untidy(name: &str) -> Repo {
+    let dir = fresh_repo(name);
+    let tracked = [
+        (".gitignore", "*.log\nbuild/\n"),
+        ("t", "\n"),
+        ("dir/tracked", "\n"),
+    ];
+    commit_files(&dir, &tracked, "base");
+    for path in [
+        "u",
+        "dir/u",
+        "new/x",
+        "a.log",
+        "build/out",
+        "dir/b.log",
+        "new/c.log",
+    ] {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "\n").unwrap();
+    }
+    dir
+}
+fn would_remove(paths: &[&str]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("Would remove {path}\n"))
+        .collect()
+}
+#[test]
+fn dry_runs_list_what_each_mode_removes() {
+    let dir = untidy("clean-dry-run");
+    for (flags, paths) in [
+        ("-n", &["dir/u", "u"][..]),
+        ("-nd", &["dir/u", "new/x", "u"]),
+        ("-nx", &["a.log", "dir/b.log", "dir/u", "u"]),
+        (
+            "-ndx",
+            &["a.log", "build/", "dir/b.log", "dir/u", "new/", "u"],
+        ),
+        ("-nX", &["a.log", "dir/b.log", "new/c.log"]),
+        ("-ndX", &["a.log", "build/", "dir/b.log", "new/c.log"]),
+    ] {
+        assert_eq!(git(&dir, &["clean", flags]), would_remove(paths), "{flags}");
+    }
+    assert!(dir.join("u").exists() && dir.join("build/out").exists());
+}
+#[test]
+fn clean_removes_files_and_limits_itself_to_pathspecs() {
+    let dir = untidy("clean-force");
+    let output = git(&dir, &["clean", "-f"]);
+    assert_eq!(output, "Removing dir/u\nRemoving u\n");
+    assert!(!dir.join("u").exists() && !dir.join("dir/u").exists());
+    assert!(dir.join("new/x").exists() && dir.join("a.log").exists());
+
+    let output = git(&dir, &["clean", "-fdx", "dir", "new"]);
+    assert_eq!(output, "Removing dir/b.log\nRemoving new/\n");
+    assert!(!dir.join("new").exists());
+    assert!(dir.join("a.log").exists() && dir.join("build/out").exists());
+    assert!(dir.join("dir/tracked").exists() && dir.join("t").exists());
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+}
@@ -0,0 +1,117 @@
+mod common;
+use common::{commit_files, fresh_repo, git, git_fails};
+use std::fs;
+#[test]
+fn commit_tree_records_each_parent_once_in_order() {
+    let dir = fresh_repo("commit-tree-parents");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    let second = commit_files(&dir, &[("f", "2\n")], "second");
+    let tree = git(&dir, &["write-tree"]);
+    let output = common::run(
+        &dir,
+        &[
+            "commit-tree",
+            tree.trim(),
+            "-p",
+            &second,
+            &format!("-p{first}"),
+            "-p",
+            "HEAD",
+            "-m",
+            "merge",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("error: duplicate parent {second} ignored\n")
+    );
+    let merge = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        git(&dir, &["cat-file", "-p", merge.trim()]),
+        format!(
+            "tree {}\nparent {second}\nparent {first}\n\
+             author A <a@example.com> 1700000000 +0000\n\
+             committer A <a@example.com> 1700000000 +0000\n\nmerge\n",
+            tree.trim()
+        )
+    );
+    git_fails(&dir, &["commit-tree", tree.trim(), "-p", "nope"], 1);
+    git_fails(&dir, &["commit-tree", tree.trim(), "-p", tree.trim()], 1);
+}
+#[test]
+fn commit_tree_joins_message_paragraphs_and_files() {
+    let dir = fresh_repo("commit-tree-message");
+    commit_files(&dir, &[("f", "1\n")], "first");
+    fs::write(dir.join(".git/body"), "body line\n\n").unwrap();
+    let tree = git(&dir, &["write-tree"]);
+    let hash = git(
+        &dir,
+        &[
+            "commit-tree",
+            tree.trim(),
+            "-m",
+            "subject",
+            "-F",
+            ".git/body",
+            "-mtrailer: x",
+        ],
+    );
+    let contents = git(&dir, &["cat-file", "-p", hash.trim()]);
+    assert!(contents.ends_with("\n\nsubject\n\nbody line\n\n\ntrailer: x\n"));
+    let stderr = git_fails(&dir, &["commit-tree", tree.trim(), "-F", "missing"], 1);
+    assert!(stderr.contains("could not open 'missing' for reading"));
+    git_fails(
+        &dir,
+        &["commit-tree", tree.trim(), tree.trim(), "-m", "x"],
+        1,
+    );
+}
+#[test]
+fn commit_tree_signs_with_the_configured_program_and_key() {
+    let dir = fresh_repo("commit-tree-sign");
+    commit_files(&dir, &[("f", "1\n")], "first");
+    let gpg = dir.join(".git/fake-gpg");
+    fs::write(
+        &gpg,
+        "#!/bin/sh\n\
+         echo \"$@\" >\"$(dirname \"$0\")/gpg-args\"\n\
+         cat >/dev/null\n\
+         echo '[GNUPG:] SIG_CREATED D 1 8 00 1700000000 KEY' >&2\n\
+         printf -- '-----BEGIN PGP SIGNATURE-----\\n\\nsig\\n-----END PGP SIGNATURE-----\\n'\n",
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&gpg, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let mut config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    config.push_str(&format!("[gpg]\n\tprogram = {}\n", gpg.display()));
+    fs::write(dir.join(".git/config"), config).unwrap();
+
+    let tree = git(&dir, &["write-tree"]);
+    let hash = git(&dir, &["commit-tree", tree.trim(), "-m", "signed", "-S"]);
+    let contents = git(&dir, &["cat-file", "-p", hash.trim()]);
+    assert!(contents.contains(
+        "committer A <a@example.com> 1700000000 +0000\n\
+         gpgsig -----BEGIN PGP SIGNATURE-----\n \n sig\n -----END PGP SIGNATURE-----\n\nsigned\n"
+    ));
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/gpg-args")).unwrap(),
+        "--status-fd=2 -bsau A <a@example.com>\n"
+    );
+    git(
+        &dir,
+        &["commit-tree", tree.trim(), "-m", "signed", "--gpg-sign=KEY"],
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/gpg-args")).unwrap(),
+        "--status-fd=2 -bsau KEY\n"
+    );
+    let hash = git(
+        &dir,
+        &["commit-tree", tree.trim(), "-S", "--no-gpg-sign", "-m", "x"],
+    );
+    assert!(!git(&dir, &["cat-file", "-p", hash.trim()]).contains("gpgsig"));
+}
@@ -0,0 +1,115 @@
+#![allow(dead_code)]
+use std::fs;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+/// The binary, set to run in `dir` with a fixed identity and dates.
+fn command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_codecrafters-git"));
+    command
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "A")
+        .env("GIT_AUTHOR_EMAIL", "a@example.com")
+        .env("GIT_AUTHOR_DATE", "@1700000000 +0000")
+        .env("GIT_COMMITTER_NAME", "A")
+        .env("GIT_COMMITTER_EMAIL", "a@example.com")
+        .env("GIT_COMMITTER_DATE", "@1700000000 +0000");
+    command
+}
+/// Runs the binary in `dir` with a fixed identity and dates.
+pub fn run(dir: &Path, args: &[&str]) -> Output {
+    command(dir, args).output().expect("failed to run git")
+}
+/// Runs the binary in `dir` like [`run`], feeding it `input` on standard
+/// input.
+pub fn run_with_input(dir: &Path, args: &[&str], input: &[u8]) -> Output {
+    let mut child = command(dir, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run git");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().expect("failed to run git")
+}
+/// Runs the binary in `dir`, expecting it to succeed, and returns its output.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = run(dir, args);
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+/// Runs the binary in `dir`, expecting it to fail with `code`, and returns
+/// its standard error.
+pub fn git_fails(dir: &Path, args: &[&str], code: i32) -> String {
+    let output = run(dir, args);
+    assert_eq!(
+        output.status.code(),
+        Some(code),
+        "git {args:?}: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    String::from_utf8(output.stderr).unwrap()
+}
+/// A scratch repository that is removed when dropped, so failing tests
+/// clean up after themselves too.
+pub struct Repo(PathBuf);
+impl Deref for Repo {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+impl Drop for Repo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+/// A freshly initialized repository in a scratch directory named after the
+/// test.
+pub fn fresh_repo(name: &str) -> Repo {
+    let dir = std::env::temp_dir().join(format!("git-rust-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    git(&dir, &["init", "-q", "-b", "main"]);
+    Repo(dir)
+}
+/// Writes `files`, creating their directories, and stages them.
+pub fn write_files(dir: &Path, files: &[(&str, &str)]) {
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    let paths: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+    git(dir, &[&["add", "--"], paths.as_slice()].concat());
+}
+/// Records the index as a commit on the current branch with `write-tree` and
+/// `commit-tree`, writing the loose ref itself, and returns the commit.
+pub fn commit(dir: &Path, message: &str) -> String {
+    let head = fs::read_to_string(dir.join(".git/HEAD")).unwrap();
+    let ref_path = match head.trim_end().strip_prefix("ref: ") {
+        Some(refname) => dir.join(".git").join(refname),
+        None => dir.join(".git/HEAD"),
+    };
+    let parent = fs::read_to_string(&ref_path).unwrap_or_default();
+    let tree = git(dir, &["write-tree"]);
+    let mut args = vec!["commit-tree", tree.trim(), "-m", message];
+    if !parent.is_empty() {
+        args.extend(["-p", parent.trim()]);
+    }
+    let hash = git(dir, &args).trim_end().to_string();
+    fs::create_dir_all(ref_path.parent().unwrap()).unwrap();
+    fs::write(ref_path, format!("{hash}\n")).unwrap();
+    hash
+}
+/// Writes and stages `files` and commits them, returning the new commit.
+pub fn commit_files(dir: &Path, files: &[(&str, &str)], message: &str) -> String {
+    write_files(dir, files);
+    commit(dir, message)
+}
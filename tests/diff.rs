@@ -0,0 +1,98 @@
+mod common;
+use common::{commit, commit_files, fresh_repo, git, run};
+use std::fs;
+fn numbers(from: usize, to: usize) -> String {
+    (from..=to).map(|n| format!("{n}\n")).collect()
+}
+#[test]
+fn rename_detection_gives_up_past_the_rename_limit() {
+    let dir = fresh_repo("diff-rename-limit");
+    for i in 1..=3 {
+        fs::write(dir.join(format!("f{i}")), numbers(i, 100)).unwrap();
+    }
+    git(&dir, &["add", "."]);
+    commit(&dir, "files");
+    for i in 1..=2 {
+        let mut contents = numbers(i, 100);
+        contents.push_str("x\n");
+        fs::remove_file(dir.join(format!("f{i}"))).unwrap();
+        fs::write(dir.join(format!("g{i}")), contents).unwrap();
+    }
+    git(&dir, &["add", "-A"]);
+    let renamed = "R099\tf1\tg1\nR099\tf2\tg2\n";
+    assert_eq!(git(&dir, &["diff", "--cached", "--name-status"]), renamed);
+    assert_eq!(
+        git(&dir, &["diff", "--cached", "--name-status", "-l2"]),
+        renamed
+    );
+    let output = run(&dir, &["diff", "--cached", "--name-status", "-l1"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "D\tf1\nD\tf2\nA\tg1\nA\tg2\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "warning: exhaustive rename detection was skipped due to too many files.\n\
+         warning: you may want to set your diff.renameLimit variable to at least 2 and retry the command.\n"
+    );
+    let config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    fs::write(
+        dir.join(".git/config"),
+        format!("{config}[diff]\n\trenameLimit = 1\n"),
+    )
+    .unwrap();
+    assert_eq!(
+        git(&dir, &["diff", "--cached", "--name-status", "-l0"]),
+        renamed
+    );
+    let output = run(&dir, &["diff", "--cached", "--name-status", "-C", "-C"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "D\tf1\nD\tf2\nA\tg1\nA\tg2\n"
+    );
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("diff.renameLimit variable to at least 3 "));
+}
+#[test]
+fn diff_tree_only_diffs_merges_against_each_parent_with_m() {
+    let dir = fresh_repo("diff-tree-merge");
+    let base = commit_files(&dir, &[("f", "f\n")], "base");
+    let side = commit_files(&dir, &[("g", "g\n")], "side");
+    fs::write(dir.join(".git/refs/heads/main"), &base).unwrap();
+    fs::remove_file(dir.join("g")).unwrap();
+    git(&dir, &["add", "-A"]);
+    let main = commit_files(&dir, &[("h", "h\n")], "main");
+    fs::write(dir.join("g"), "g\n").unwrap();
+    git(&dir, &["add", "g"]);
+    let tree = git(&dir, &["write-tree"]);
+    let merge = git(
+        &dir,
+        &[
+            "commit-tree",
+            tree.trim(),
+            "-p",
+            &main,
+            "-p",
+            &side,
+            "-m",
+            "merge",
+        ],
+    );
+    let merge = merge.trim();
+    assert_eq!(git(&dir, &["diff-tree", merge]), "");
+    assert_eq!(
+        git(&dir, &["diff-tree", "-m", "--name-only", merge]),
+        format!("{merge}\ng\n{merge}\nh\n")
+    );
+    assert_eq!(
+        git(&dir, &["diff-tree", "--name-only", &main]),
+        format!("{main}\nh\n")
+    );
+    let empty = git(
+        &dir,
+        &["commit-tree", tree.trim(), "-p", merge, "-m", "empty"],
+    );
+    assert_eq!(git(&dir, &["diff-tree", empty.trim()]), "");
+}
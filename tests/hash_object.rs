@@ -0,0 +1,141 @@
+mod common;
+use common::{fresh_repo, git, git_fails, run_with_input};
+use std::fs;
+use std::path::Path;
+/// The name `contents` gets as a blob stored as is.
+fn blob(dir: &Path, contents: &[u8]) -> Vec<u8> {
+    run_with_input(dir, &["hash-object", "--stdin"], contents).stdout
+}
+fn hash_file(dir: &Path, args: &[&str]) -> Vec<u8> {
+    git(dir, &[&["hash-object"], args].concat()).into_bytes()
+}
+#[test]
+fn hash_object_cleans_files_as_their_attributes_ask() {
+    let dir = fresh_repo("hash-object-attributes");
+    let mut config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    config.push_str(
+        "[filter \"upper\"]\n\tclean = tr a-z A-Z\n\
+         [filter \"broken\"]\n\tclean = false\n\trequired = true\n",
+    );
+    fs::write(dir.join(".git/config"), config).unwrap();
+    fs::write(
+        dir.join(".gitattributes"),
+        "*.up filter=upper\n*.txt eol=lf\nid.c ident\n*.bad filter=broken\n",
+    )
+    .unwrap();
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("sub/.gitattributes"), "*.txt -text\n").unwrap();
+    fs::write(dir.join("a.up"), "shout\n").unwrap();
+    fs::write(dir.join("a.txt"), "one\r\ntwo\r\n").unwrap();
+    fs::write(dir.join("sub/b.txt"), "one\r\ntwo\r\n").unwrap();
+    fs::write(dir.join("id.c"), "/* $Id: 1234 $ */\n").unwrap();
+    fs::write(dir.join("x.bad"), "x\n").unwrap();
+
+    assert_eq!(hash_file(&dir, &["a.up"]), blob(&dir, b"SHOUT\n"));
+    assert_eq!(hash_file(&dir, &["a.txt"]), blob(&dir, b"one\ntwo\n"));
+    assert_eq!(
+        hash_file(&dir, &["sub/b.txt"]),
+        blob(&dir, b"one\r\ntwo\r\n")
+    );
+    assert_eq!(hash_file(&dir, &["id.c"]), blob(&dir, b"/* $Id$ */\n"));
+    assert_eq!(
+        hash_file(&dir, &["--path", "b.up", "a.txt"]),
+        blob(&dir, b"ONE\r\nTWO\r\n")
+    );
+    assert_eq!(
+        hash_file(&dir, &["--no-filters", "a.up"]),
+        blob(&dir, b"shout\n")
+    );
+    let stderr = git_fails(&dir, &["hash-object", "x.bad"], 1);
+    assert!(stderr.contains("x.bad: clean filter 'broken' failed"));
+}
+#[test]
+fn hash_object_normalises_crlf_text_under_autocrlf() {
+    let dir = fresh_repo("hash-object-autocrlf");
+    let mut config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    config.push_str("[core]\n\tautocrlf = input\n");
+    fs::write(dir.join(".git/config"), config).unwrap();
+    fs::write(dir.join("text"), "a\r\nb\r\n").unwrap();
+    fs::write(dir.join("binary"), "a\0\r\n").unwrap();
+    assert_eq!(hash_file(&dir, &["text"]), blob(&dir, b"a\nb\n"));
+    assert_eq!(hash_file(&dir, &["binary"]), blob(&dir, b"a\0\r\n"));
+}
+#[test]
+fn hash_object_checks_commits_and_tags_unless_told_otherwise() {
+    let dir = fresh_repo("hash-object-fsck");
+    let tree = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+    let ident = "A <a@example.com> 1700000000 +0000";
+    let commit = format!("tree {tree}\nauthor {ident}\ncommitter {ident}\n\nm\n");
+    let output = run_with_input(
+        &dir,
+        &["hash-object", "-t", "commit", "--stdin"],
+        commit.as_bytes(),
+    );
+    assert!(output.status.success());
+    for (kind, data, error) in [
+        (
+            "commit",
+            format!("tree {tree}\ncommitter {ident}\n\nm\n"),
+            "missingAuthor: invalid format - expected 'author' line",
+        ),
+        (
+            "commit",
+            format!("tree {tree}\nauthor {ident}\n\nm\n"),
+            "missingCommitter: invalid format - expected 'committer' line",
+        ),
+        (
+            "tag",
+            format!("object {tree}\ntype tree\ntag v1\n\nm\n"),
+            "missingTaggerEntry: invalid format - expected 'tagger' line",
+        ),
+    ] {
+        let output = run_with_input(
+            &dir,
+            &["hash-object", "-t", kind, "--stdin"],
+            data.as_bytes(),
+        );
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains(&format!("error: object fails fsck: {error}\n")),
+            "{stderr}"
+        );
+        let output = run_with_input(
+            &dir,
+            &["hash-object", "-t", kind, "--literally", "--stdin"],
+            data.as_bytes(),
+        );
+        assert!(output.status.success());
+    }
+}
+#[test]
+fn hash_object_literally_takes_any_type_and_skips_filters() {
+    let dir = fresh_repo("hash-object-literally");
+    let output = run_with_input(
+        &dir,
+        &["hash-object", "-t", "bogus", "--literally", "-w", "--stdin"],
+        b"hi\n",
+    );
+    // printf 'bogus 3\0hi\n' | sha1sum
+    let hash = "097ca4930d4b1ebaeecad514f3434d7a1dbd5f78";
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{hash}\n")
+    );
+    assert!(dir
+        .join(".git/objects")
+        .join(&hash[..2])
+        .join(&hash[2..])
+        .exists());
+    let output = run_with_input(&dir, &["hash-object", "-t", "bogus", "--stdin"], b"hi\n");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("invalid object type \"bogus\""));
+
+    fs::write(dir.join(".gitattributes"), "*.txt eol=lf\n").unwrap();
+    fs::write(dir.join("a.txt"), "a\r\n").unwrap();
+    assert_eq!(
+        hash_file(&dir, &["--literally", "a.txt"]),
+        blob(&dir, b"a\r\n")
+    );
+}
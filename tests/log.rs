@@ -0,0 +1,52 @@
+mod common;
+use common::{commit_files, fresh_repo, git, run};
+fn three_commits(name: &str) -> (common::Repo, Vec<String>) {
+    let dir = fresh_repo(name);
+    let hashes = vec![
+        commit_files(&dir, &[("a", "a\n")], "first"),
+        commit_files(&dir, &[("b/c", "c\n")], "second"),
+        commit_files(&dir, &[("a", "aa\n")], "third"),
+    ];
+    (dir, hashes)
+}
+#[test]
+fn log_format_separates_and_tformat_terminates_entries() {
+    let (dir, _) = three_commits("log-format");
+    assert_eq!(
+        git(&dir, &["log", "--format=format:%s"]),
+        "third\nsecond\nfirst"
+    );
+    assert_eq!(
+        git(&dir, &["log", "--format=tformat:%s"]),
+        "third\nsecond\nfirst\n"
+    );
+    assert_eq!(git(&dir, &["log", "--format=%s"]), "third\nsecond\nfirst\n");
+    assert_eq!(
+        git(&dir, &["log", "--format=format:%s", "--name-only", "-2"]),
+        "third\na\n\nsecond\nb/c\n"
+    );
+    assert_eq!(
+        git(&dir, &["log", "--format=tformat:%s", "--name-only", "-2"]),
+        "third\n\na\nsecond\n\nb/c\n"
+    );
+}
+#[test]
+fn log_limits_history_to_paths() {
+    let (dir, hashes) = three_commits("log-paths");
+    let short = |hash: &String| hash[..7].to_string();
+    assert_eq!(
+        git(&dir, &["log", "--oneline", "--", "a"]),
+        format!("{} third\n{} first\n", short(&hashes[2]), short(&hashes[0]))
+    );
+    assert_eq!(
+        git(&dir, &["log", "--oneline", "--", "b"]),
+        format!("{} second\n", short(&hashes[1]))
+    );
+    assert_eq!(git(&dir, &["log", "--oneline", "--", "missing"]), "");
+}
+#[test]
+fn log_format_writes_raw_bytes() {
+    let (dir, _) = three_commits("log-bytes");
+    let output = run(&dir, &["log", "-1", "--format=%xff%x41%s"]);
+    assert_eq!(output.stdout, b"\xffAthird\n");
+}
@@ -0,0 +1,77 @@
+mod common;
+use common::{commit, commit_files, fresh_repo, git, run};
+use std::fs;
+#[test]
+fn merge_moves_a_file_out_of_the_way_of_a_directory() {
+    let dir = fresh_repo("merge-df");
+    commit_files(&dir, &[("keep", "base\n")], "base");
+    git(&dir, &["checkout", "-q", "-b", "side"]);
+    commit_files(&dir, &[("a/b", "ab\n")], "side");
+    git(&dir, &["checkout", "-q", "main"]);
+    commit_files(&dir, &[("a", "fa\n")], "main");
+
+    let output = run(&dir, &["merge", "side"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(
+        "CONFLICT (file/directory): directory in the way of a from HEAD; moving it to a~HEAD instead."
+    ));
+    assert_eq!(fs::read_to_string(dir.join("a~HEAD")).unwrap(), "fa\n");
+    assert_eq!(fs::read_to_string(dir.join("a/b")).unwrap(), "ab\n");
+    assert_eq!(
+        git(&dir, &["status", "--porcelain"]),
+        "D  a\nA  a/b\nAU a~HEAD\n"
+    );
+
+    git(&dir, &["merge", "--abort"]);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "fa\n");
+    assert!(!dir.join("a~HEAD").exists());
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+}
+#[test]
+fn merge_keeps_both_files_when_a_rename_meets_an_added_file() {
+    let numbers: String = (1..=20).map(|n| format!("{n}\n")).collect();
+    for ours_renames in [true, false] {
+        let dir = fresh_repo(&format!("merge-rename-add-{ours_renames}"));
+        commit_files(&dir, &[("a", &numbers)], "base");
+        git(&dir, &["branch", "side"]);
+        let (renamer, adder) = match ours_renames {
+            true => ("main", "side"),
+            false => ("side", "main"),
+        };
+        git(&dir, &["checkout", "-q", adder]);
+        commit_files(&dir, &[("b", "other\n")], "add b");
+        git(&dir, &["checkout", "-q", renamer]);
+        fs::rename(dir.join("a"), dir.join("b")).unwrap();
+        git(&dir, &["add", "-A"]);
+        commit(&dir, "rename a to b");
+        git(&dir, &["checkout", "-q", "main"]);
+
+        let output = run(&dir, &["merge", "side"]);
+        assert_eq!(output.status.code(), Some(1));
+        let (renamed_in, added_in) = match ours_renames {
+            true => ("HEAD", "side"),
+            false => ("side", "HEAD"),
+        };
+        assert!(String::from_utf8_lossy(&output.stdout).contains(&format!(
+            "CONFLICT (rename/add): Rename a->b in {renamed_in}.  Added b in {added_in}"
+        )));
+        let (ours, theirs) = match ours_renames {
+            true => (numbers.as_str(), "other\n"),
+            false => ("other\n", numbers.as_str()),
+        };
+        assert_eq!(
+            fs::read_to_string(dir.join("b")).unwrap(),
+            format!("<<<<<<< HEAD\n{ours}=======\n{theirs}>>>>>>> side\n")
+        );
+        let status = match ours_renames {
+            true => "AA b\n",
+            false => "D  a\nAA b\n",
+        };
+        assert_eq!(git(&dir, &["status", "--porcelain"]), status);
+        for (stage, contents) in [(":2:b", ours), (":3:b", theirs)] {
+            fs::write(dir.join("expected"), contents).unwrap();
+            let blob = git(&dir, &["hash-object", "expected"]);
+            assert_eq!(git(&dir, &["rev-parse", stage]), blob);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+mod common;
+use common::{commit, commit_files, fresh_repo, git, git_fails, write_files};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+/// Points `sequence.editor` at a script that saves each todo list to
+/// `.git/todo-seen` and then runs `sed` with `script` on it, and leaves
+/// commit messages as they are.
+fn set_sequence_editor(dir: &Path, script: &str) {
+    let editor = dir.join(".git/sequence-editor");
+    let todo = dir.join(".git/todo-seen");
+    let body = format!(
+        "#!/bin/sh\ncp \"$1\" '{}'\nsed -i -e '{script}' \"$1\"\n",
+        todo.display()
+    );
+    fs::write(&editor, body).unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    let mut config = fs::read_to_string(dir.join(".git/config")).unwrap();
+    config.push_str(&format!(
+        "[sequence]\n\teditor = {}\n[core]\n\teditor = :\n",
+        editor.display()
+    ));
+    fs::write(dir.join(".git/config"), config).unwrap();
+}
+/// The todo lines the sequence editor was last given, without the help.
+fn todo_seen(dir: &Path) -> String {
+    fs::read_to_string(dir.join(".git/todo-seen"))
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.is_empty())
+        .map(|line| format!("{line}\n"))
+        .collect()
+}
+fn subjects(dir: &Path) -> String {
+    git(dir, &["log", "--format=%s"])
+}
+#[test]
+fn interactive_rebase_follows_the_edited_todo() {
+    let dir = fresh_repo("rebase-todo");
+    commit_files(&dir, &[("base", "0\n")], "base");
+    let mut hashes = Vec::new();
+    for name in ["a", "b", "c"] {
+        hashes.push(commit_files(&dir, &[(name, "1\n")], name));
+    }
+    set_sequence_editor(&dir, "2s/^pick/drop/");
+    git(&dir, &["rebase", "-q", "-i", "HEAD~3"]);
+    let expected: String = ["a", "b", "c"]
+        .iter()
+        .zip(&hashes)
+        .map(|(subject, hash)| format!("pick {} {subject}\n", &hash[..7]))
+        .collect();
+    assert_eq!(todo_seen(&dir), expected);
+    assert_eq!(subjects(&dir), "c\na\nbase\n");
+    assert!(!dir.join("b").exists());
+    assert_eq!(git(&dir, &["symbolic-ref", "HEAD"]), "refs/heads/main\n");
+}
+#[test]
+fn autosquash_turns_amend_commits_into_fixups_that_replace_the_message() {
+    let dir = fresh_repo("rebase-autosquash");
+    commit_files(&dir, &[("base", "0\n")], "base");
+    let target = commit_files(&dir, &[("g", "1\n")], "add g");
+    let other = commit_files(&dir, &[("h", "1\n")], "add h");
+    let amend = commit_files(
+        &dir,
+        &[("g", "2\n")],
+        "amend! add g\n\nbetter message\n\nbody",
+    );
+    let fixup = commit_files(&dir, &[("g", "3\n")], "fixup! add g");
+    set_sequence_editor(&dir, "");
+    git(&dir, &["rebase", "-q", "-i", "--autosquash", "HEAD~4"]);
+    assert_eq!(
+        todo_seen(&dir),
+        format!(
+            "pick {} add g\nfixup -C {} amend! add g\nfixup {} fixup! add g\npick {} add h\n",
+            &target[..7],
+            &amend[..7],
+            &fixup[..7],
+            &other[..7]
+        )
+    );
+    assert_eq!(subjects(&dir), "add h\nbetter message\nbase\n");
+    assert_eq!(
+        git(&dir, &["log", "-1", "--format=%B", "HEAD~"]),
+        "better message\n\nbody\n\n"
+    );
+    assert_eq!(fs::read_to_string(dir.join("g")).unwrap(), "3\n");
+}
+#[test]
+fn continue_at_an_edit_amends_staged_changes_unless_head_moved() {
+    let dir = fresh_repo("rebase-edit");
+    commit_files(&dir, &[("base", "0\n")], "base");
+    commit_files(&dir, &[("a", "1\n")], "a");
+    set_sequence_editor(&dir, "1s/^pick/edit/");
+    git(&dir, &["rebase", "-q", "-i", "HEAD~1"]);
+    write_files(&dir, &[("a", "2\n")]);
+    git(&dir, &["rebase", "--continue"]);
+    assert_eq!(subjects(&dir), "a\nbase\n");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+    assert_eq!(
+        git(&dir, &["rev-parse", "HEAD:a"]),
+        git(&dir, &["rev-parse", ":a"])
+    );
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "2\n");
+
+    git(&dir, &["rebase", "-q", "-i", "HEAD~1"]);
+    commit_files(&dir, &[("b", "1\n")], "b");
+    write_files(&dir, &[("b", "2\n")]);
+    let stderr = git_fails(&dir, &["rebase", "--continue"], 1);
+    assert_eq!(
+        stderr,
+        "error: \nYou have uncommitted changes in your working tree. Please, commit them\nfirst and then run 'git rebase --continue' again.\n"
+    );
+    commit(&dir, "b again");
+    git(&dir, &["rebase", "--continue"]);
+    assert_eq!(subjects(&dir), "b again\nb\na\nbase\n");
+}
@@ -0,0 +1,35 @@
+mod common;
+use common::{commit_files, fresh_repo, git};
+#[test]
+fn reflog_records_updates_and_resolves_entries() {
+    let dir = fresh_repo("reflog-updates");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    let second = commit_files(&dir, &[("f", "2\n")], "second");
+    git(
+        &dir,
+        &["update-ref", "-m", "start", "refs/heads/topic", &first],
+    );
+    git(
+        &dir,
+        &["update-ref", "-m", "advance", "refs/heads/topic", &second],
+    );
+    git(
+        &dir,
+        &["update-ref", "-m", "rewind", "refs/heads/topic", &first],
+    );
+    assert_eq!(
+        git(&dir, &["reflog", "show", "topic"]),
+        format!(
+            "{a} topic@{{0}}: rewind\n{b} topic@{{1}}: advance\n{a} topic@{{2}}: start\n",
+            a = &first[..7],
+            b = &second[..7]
+        )
+    );
+    assert_eq!(
+        git(&dir, &["rev-parse", "topic@{1}"]),
+        format!("{second}\n")
+    );
+    assert_eq!(git(&dir, &["reflog", "list"]), "refs/heads/topic\n");
+    git(&dir, &["update-ref", "-d", "refs/heads/topic"]);
+    assert_eq!(git(&dir, &["reflog", "list"]), "");
+}
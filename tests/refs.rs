@@ -0,0 +1,101 @@
+mod common;
+use common::{commit_files, fresh_repo, git, git_fails, run};
+use std::fs;
+#[test]
+fn update_ref_checks_the_old_value_and_deletes() {
+    let dir = fresh_repo("refs-update");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    let second = commit_files(&dir, &[("f", "2\n")], "second");
+    git(&dir, &["update-ref", "refs/heads/topic", &first]);
+    assert_eq!(git(&dir, &["rev-parse", "topic"]), format!("{first}\n"));
+    let stderr = git_fails(
+        &dir,
+        &["update-ref", "refs/heads/topic", &second, &second],
+        1,
+    );
+    assert!(stderr.contains(&format!(
+        "cannot lock ref 'refs/heads/topic': is at {first} but expected {second}"
+    )));
+    git(&dir, &["update-ref", "refs/heads/topic", &second, &first]);
+    assert_eq!(git(&dir, &["rev-parse", "topic"]), format!("{second}\n"));
+    git(&dir, &["update-ref", "-d", "refs/heads/topic"]);
+    assert!(!run(&dir, &["rev-parse", "-q", "--verify", "topic"])
+        .status
+        .success());
+}
+#[test]
+fn update_ref_leaves_a_held_lock_alone() {
+    let dir = fresh_repo("refs-lock");
+    let hash = commit_files(&dir, &[("f", "f\n")], "base");
+    let lock = dir.join(".git/refs/heads/held.lock");
+    fs::write(&lock, "").unwrap();
+    let stderr = git_fails(&dir, &["update-ref", "refs/heads/held", &hash], 1);
+    assert!(stderr.contains("held.lock': File exists"));
+    assert!(lock.exists());
+    fs::remove_file(&lock).unwrap();
+    git(&dir, &["update-ref", "refs/heads/held", &hash]);
+    assert!(!lock.exists());
+}
+#[test]
+fn packed_refs_resolve_and_block_clashing_names() {
+    let dir = fresh_repo("refs-packed");
+    let hash = commit_files(&dir, &[("f", "f\n")], "base");
+    fs::write(
+        dir.join(".git/packed-refs"),
+        format!("# pack-refs with: peeled fully-peeled sorted \n{hash} refs/heads/foo/bar\n"),
+    )
+    .unwrap();
+    assert_eq!(git(&dir, &["rev-parse", "foo/bar"]), format!("{hash}\n"));
+    assert_eq!(
+        git(&dir, &["show-ref"]),
+        format!("{hash} refs/heads/foo/bar\n{hash} refs/heads/main\n")
+    );
+    let stderr = git_fails(&dir, &["update-ref", "refs/heads/foo", &hash], 1);
+    assert!(stderr.contains(
+        "cannot lock ref 'refs/heads/foo': 'refs/heads/foo/bar' exists; cannot create 'refs/heads/foo'"
+    ));
+    assert!(!dir.join(".git/refs/heads/foo").exists());
+    git(&dir, &["update-ref", "-d", "refs/heads/foo/bar"]);
+    git(&dir, &["update-ref", "refs/heads/foo", &hash]);
+    assert_eq!(git(&dir, &["show-ref", "--heads"]).lines().count(), 2);
+}
+#[test]
+fn for_each_ref_formats_sorts_and_writes_raw_bytes() {
+    let dir = fresh_repo("refs-for-each");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    let second = commit_files(&dir, &[("f", "2\n")], "second");
+    git(&dir, &["update-ref", "refs/heads/old", &first]);
+    git(&dir, &["update-ref", "refs/tags/v1", &first]);
+    assert_eq!(
+        git(
+            &dir,
+            &[
+                "for-each-ref",
+                "--format=%(refname:short) %(objectname:short) %(subject)",
+                "refs/heads"
+            ]
+        ),
+        format!("main {} second\nold {} first\n", &second[..7], &first[..7])
+    );
+    assert_eq!(
+        git(
+            &dir,
+            &[
+                "for-each-ref",
+                "--sort=-refname",
+                "--count=2",
+                "--format=%(refname)"
+            ]
+        ),
+        "refs/tags/v1\nrefs/heads/old\n"
+    );
+    let output = run(
+        &dir,
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%ff%09%%",
+            "refs/tags",
+        ],
+    );
+    assert_eq!(output.stdout, b"v1\xff\t%\n");
+}
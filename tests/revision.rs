@@ -0,0 +1,35 @@
+mod common;
+use common::{commit_files, fresh_repo, git, run};
+use std::fs;
+#[test]
+fn message_search_matches_a_regex_and_supports_negation() {
+    let dir = fresh_repo("revision-message-search");
+    let mut hashes = Vec::new();
+    for (i, message) in ["fix parser", "!bang", "fxx lexer"].into_iter().enumerate() {
+        let hash = commit_files(&dir, &[("f", &format!("{i}\n"))], message);
+        hashes.push(hash + "\n");
+    }
+    assert_eq!(git(&dir, &["rev-parse", ":/^fix"]), hashes[0]);
+    assert_eq!(git(&dir, &["rev-parse", ":/f.x l"]), hashes[2]);
+    assert_eq!(git(&dir, &["rev-parse", ":/!!b"]), hashes[1]);
+    assert_eq!(git(&dir, &["rev-parse", ":/!-f"]), hashes[1]);
+    for spec in [":/fix.*lexer", ":/!bang", ":/(", ":/parser$"] {
+        assert!(!run(&dir, &["rev-parse", spec]).status.success(), "{spec}");
+    }
+}
+#[test]
+fn abbreviated_hashes_resolve_objects_in_packs() {
+    let dir = fresh_repo("revision-packed-abbrev");
+    let loose = commit_files(&dir, &[("f", "f\n")], "loose");
+    let packed = "abcdef0123456789abcdef0123456789abcdef01";
+    let mut idx = b"\xfftOc\0\0\0\x02".to_vec();
+    for byte in 0..=255u32 {
+        idx.extend(u32::from(byte >= 0xab).to_be_bytes());
+    }
+    idx.extend(hex::decode(packed).unwrap());
+    fs::create_dir_all(dir.join(".git/objects/pack")).unwrap();
+    fs::write(dir.join(".git/objects/pack/pack-test.idx"), idx).unwrap();
+    assert_eq!(git(&dir, &["rev-parse", "abcdef0"]), format!("{packed}\n"));
+    assert_eq!(git(&dir, &["rev-parse", &loose[..7]]), format!("{loose}\n"));
+    assert!(!run(&dir, &["rev-parse", "abcdef1"]).status.success());
+}
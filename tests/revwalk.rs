@@ -0,0 +1,74 @@
+mod common;
+use common::{commit_files, fresh_repo, git, run};
+#[test]
+fn merge_base_finds_both_bases_of_a_criss_cross_merge() {
+    let dir = fresh_repo("revwalk-criss-cross");
+    let base = commit_files(&dir, &[("base", "0\n")], "base");
+    git(&dir, &["branch", "b"]);
+    let a1 = commit_files(&dir, &[("a", "1\n")], "a1");
+    git(&dir, &["branch", "a1"]);
+    git(&dir, &["checkout", "-q", "b"]);
+    let b1 = commit_files(&dir, &[("b", "1\n")], "b1");
+    git(&dir, &["checkout", "-q", "main"]);
+    git(&dir, &["merge", "-q", "-m", "m1", "b"]);
+    git(&dir, &["checkout", "-q", "b"]);
+    git(&dir, &["merge", "-q", "-m", "m2", "a1"]);
+
+    let mut bases: Vec<String> = git(&dir, &["merge-base", "--all", "main", "b"])
+        .lines()
+        .map(String::from)
+        .collect();
+    bases.sort();
+    let mut expected = vec![a1.clone(), b1.clone()];
+    expected.sort();
+    assert_eq!(bases, expected);
+    assert_eq!(git(&dir, &["merge-base", "a1", "b"]), format!("{a1}\n"));
+    assert_eq!(git(&dir, &["merge-base", "a1", &b1]), format!("{base}\n"));
+}
+#[test]
+fn merge_base_checks_ancestry_and_drops_redundant_commits() {
+    let dir = fresh_repo("revwalk-ancestry");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    let second = commit_files(&dir, &[("f", "2\n")], "second");
+    git(&dir, &["checkout", "-q", "-b", "side", &first]);
+    let side = commit_files(&dir, &[("g", "1\n")], "side");
+
+    git(&dir, &["merge-base", "--is-ancestor", &first, &second]);
+    git(&dir, &["merge-base", "--is-ancestor", &second, &second]);
+    let output = run(&dir, &["merge-base", "--is-ancestor", &second, &side]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = run(&dir, &["merge-base", "--is-ancestor", &second, &first]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let independent = git(
+        &dir,
+        &["merge-base", "--independent", &first, &side, &second, &side],
+    );
+    assert_eq!(independent, format!("{side}\n{second}\n"));
+}
+#[test]
+fn rev_list_stops_at_hidden_history() {
+    let dir = fresh_repo("revwalk-hide");
+    let mut hashes = Vec::new();
+    for i in 0..5 {
+        hashes.push(commit_files(&dir, &[("f", &format!("{i}\n"))], "main"));
+    }
+    git(&dir, &["checkout", "-q", "-b", "side", &hashes[2]]);
+    commit_files(&dir, &[("g", "1\n")], "side");
+    git(&dir, &["checkout", "-q", "main"]);
+    git(&dir, &["merge", "-q", "-m", "merge side", "side"]);
+    let merge = git(&dir, &["rev-parse", "HEAD"]);
+
+    assert_eq!(
+        git(&dir, &["rev-list", "main", "^side"]),
+        format!("{merge}{}\n{}\n", hashes[4], hashes[3])
+    );
+    assert_eq!(git(&dir, &["rev-list", "side", "^main"]), "");
+    assert_eq!(
+        git(
+            &dir,
+            &["rev-list", "--count", "main", &format!("^{}", hashes[3])]
+        ),
+        "3\n"
+    );
+}
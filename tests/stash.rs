@@ -0,0 +1,80 @@
+mod common;
+use common::{commit_files, fresh_repo, git, run, write_files, Repo};
+use std::fs;
+#[test]
+fn stash_refuses_to_save_unmerged_paths() {
+    let dir = fresh_repo("stash-unmerged");
+    commit_files(&dir, &[("f", "base\n")], "base");
+    git(&dir, &["checkout", "-q", "-b", "side"]);
+    commit_files(&dir, &[("f", "side\n")], "side");
+    git(&dir, &["checkout", "-q", "main"]);
+    commit_files(&dir, &[("f", "main\n")], "main");
+    assert_eq!(run(&dir, &["merge", "side"]).status.code(), Some(1));
+
+    let output = run(&dir, &["stash"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "f: needs merge\n");
+    assert_eq!(git(&dir, &["stash", "list"]), "");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "UU f\n");
+}
+/// A repository at `base` with `f` modified, `g` modified and staged and
+/// the new file `n` staged.
+fn dirty(name: &str) -> (Repo, String) {
+    let dir = fresh_repo(name);
+    let base = commit_files(&dir, &[("f", "0\n"), ("g", "0\n")], "base");
+    write_files(&dir, &[("g", "1\n"), ("n", "new\n")]);
+    fs::write(dir.join("f"), "1\n").unwrap();
+    (dir, base)
+}
+#[test]
+fn push_and_pop_round_trip_the_changes() {
+    let (dir, base) = dirty("stash-pop");
+    git(&dir, &["stash"]);
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+    assert!(!dir.join("n").exists());
+    assert_eq!(
+        git(&dir, &["stash", "list"]),
+        format!("stash@{{0}}: WIP on main: {} base\n", &base[..7])
+    );
+
+    git(&dir, &["stash", "pop"]);
+    assert_eq!(git(&dir, &["status", "--porcelain"]), " M f\n M g\nA  n\n");
+    assert_eq!(fs::read_to_string(dir.join("g")).unwrap(), "1\n");
+    assert_eq!(git(&dir, &["stash", "list"]), "");
+}
+#[test]
+fn apply_with_index_restores_staged_changes_and_keeps_the_stash() {
+    let (dir, _) = dirty("stash-apply-index");
+    git(&dir, &["stash", "push", "-m", "saved"]);
+    git(&dir, &["stash", "apply", "--index"]);
+    assert_eq!(git(&dir, &["status", "--porcelain"]), " M f\nM  g\nA  n\n");
+    assert_eq!(git(&dir, &["stash", "list"]), "stash@{0}: On main: saved\n");
+    git(&dir, &["stash", "drop"]);
+    assert_eq!(git(&dir, &["stash", "list"]), "");
+}
+#[test]
+fn include_untracked_stashes_and_restores_untracked_files() {
+    let (dir, _) = dirty("stash-untracked");
+    fs::write(dir.join("untracked"), "u\n").unwrap();
+    git(&dir, &["stash", "-u"]);
+    assert!(!dir.join("untracked").exists());
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+    git(&dir, &["stash", "pop", "--index"]);
+    assert_eq!(fs::read_to_string(dir.join("untracked")).unwrap(), "u\n");
+    assert_eq!(
+        git(&dir, &["status", "--porcelain"]),
+        " M f\nM  g\nA  n\n?? untracked\n"
+    );
+}
+#[test]
+fn stash_branch_applies_the_stash_where_it_was_made() {
+    let (dir, base) = dirty("stash-branch");
+    git(&dir, &["stash"]);
+    commit_files(&dir, &[("f", "2\n")], "next");
+    git(&dir, &["stash", "branch", "fix"]);
+    assert_eq!(git(&dir, &["symbolic-ref", "HEAD"]), "refs/heads/fix\n");
+    assert_eq!(git(&dir, &["rev-parse", "HEAD"]), base + "\n");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), " M f\nM  g\nA  n\n");
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "1\n");
+    assert_eq!(git(&dir, &["stash", "list"]), "");
+}
@@ -0,0 +1,25 @@
+mod common;
+use common::{commit_files, fresh_repo, git};
+use std::fs;
+#[test]
+fn status_collapses_untracked_directories() {
+    let dir = fresh_repo("status-untracked");
+    commit_files(&dir, &[("a/t", "t\n"), (".gitignore", "*.log\n")], "base");
+    for path in ["a/b/c/u", "a/u", "d/x", "d.txt", "e/x.log"] {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "x\n").unwrap();
+    }
+    assert_eq!(
+        git(&dir, &["status", "--porcelain"]),
+        "?? a/b/\n?? a/u\n?? d.txt\n?? d/\n"
+    );
+}
+#[test]
+fn status_names_a_detached_head() {
+    let dir = fresh_repo("status-detached");
+    let hash = commit_files(&dir, &[("f", "f\n")], "base");
+    fs::write(dir.join(".git/HEAD"), format!("{hash}\n")).unwrap();
+    let status = git(&dir, &["status"]);
+    assert!(status.starts_with(&format!("HEAD detached at {}\n", &hash[..7])));
+}
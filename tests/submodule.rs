@@ -0,0 +1,65 @@
+mod common;
+use common::{commit, commit_files, fresh_repo, git, git_fails, Repo};
+use std::fs;
+/// A superproject whose `sub` submodule has `f` at "1" in its only commit.
+fn superproject(name: &str) -> (Repo, String) {
+    let dir = fresh_repo(name);
+    let sub = dir.join("sub");
+    fs::create_dir(&sub).unwrap();
+    git(&sub, &["init", "-q", "-b", "main"]);
+    let hash = commit_files(&sub, &[("f", "1\n")], "sub");
+    let url = sub.to_str().unwrap();
+    let gitmodules = format!("[submodule \"sub\"]\n\tpath = sub\n\turl = {url}\n");
+    fs::write(dir.join(".gitmodules"), gitmodules).unwrap();
+    git(&dir, &["add", ".gitmodules", "sub"]);
+    commit(&dir, "super");
+    (dir, hash)
+}
+#[test]
+fn local_clone_copies_branches_and_tags() {
+    let dir = fresh_repo("clone-tags");
+    let hash = commit_files(&dir, &[("f", "1\n")], "first");
+    git(&dir, &["update-ref", "refs/tags/v1", &hash]);
+    git(&dir, &["clone", dir.to_str().unwrap(), "copy"]);
+    let copy = dir.join("copy");
+    let refs = git(&copy, &["show-ref"]);
+    assert_eq!(
+        refs,
+        format!(
+            "{hash} refs/heads/main\n{hash} refs/remotes/origin/HEAD\n\
+             {hash} refs/remotes/origin/main\n{hash} refs/tags/v1\n"
+        )
+    );
+}
+#[test]
+fn recursive_clone_checks_out_submodules() {
+    let (dir, hash) = superproject("submodule-clone");
+    git(&dir, &["clone", "--recurse-submodules", ".", "copy"]);
+    let copy = dir.join("copy");
+    assert_eq!(fs::read_to_string(copy.join("sub/f")).unwrap(), "1\n");
+    assert_eq!(
+        git(&copy, &["submodule", "status"]),
+        format!(" {hash} sub (heads/main)\n")
+    );
+}
+#[test]
+fn submodule_update_reports_why_a_checkout_failed() {
+    let (dir, hash) = superproject("submodule-update");
+    git(&dir, &["clone", "--recurse-submodules", ".", "copy"]);
+    let sub = dir.join("copy/sub");
+    commit_files(&sub, &[("f", "2\n")], "ahead");
+    fs::write(sub.join("f"), "local\n").unwrap();
+    let stderr = git_fails(&dir.join("copy"), &["submodule", "update"], 1);
+    assert!(
+        stderr.contains("Your local changes to the following files would be overwritten"),
+        "{stderr}"
+    );
+    fs::write(sub.join("f"), "2\n").unwrap();
+    let object = sub.join(".git/objects").join(&hash[..2]).join(&hash[2..]);
+    fs::remove_file(object).unwrap();
+    let stderr = git_fails(&dir.join("copy"), &["submodule", "update"], 1);
+    assert!(
+        stderr.contains("Unable to find current revision in submodule path 'sub'"),
+        "{stderr}"
+    );
+}
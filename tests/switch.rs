@@ -0,0 +1,79 @@
+mod common;
+use common::{commit_files, fresh_repo, git, git_fails, Repo};
+use std::fs;
+/// `main` with `f` at "2", and `side` one commit behind with `f` at "1".
+fn two_branches(name: &str) -> Repo {
+    let dir = fresh_repo(name);
+    commit_files(&dir, &[("f", "1\n")], "first");
+    git(&dir, &["branch", "side"]);
+    commit_files(&dir, &[("f", "2\n")], "second");
+    dir
+}
+#[test]
+fn switch_keeps_local_changes_that_a_switch_would_overwrite() {
+    let dir = two_branches("switch-local-changes");
+    fs::write(dir.join("f"), "local\n").unwrap();
+    let stderr = git_fails(&dir, &["switch", "side"], 1);
+    assert!(stderr.contains(
+        "Your local changes to the following files would be overwritten by checkout:\n\tf\n"
+    ));
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "local\n");
+    git(&dir, &["switch", "-f", "side"]);
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "1\n");
+    assert_eq!(git(&dir, &["symbolic-ref", "HEAD"]), "refs/heads/side\n");
+    git(&dir, &["switch", "-"]);
+    assert_eq!(git(&dir, &["symbolic-ref", "HEAD"]), "refs/heads/main\n");
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "2\n");
+}
+#[test]
+fn switch_and_checkout_validate_a_new_branch_before_touching_the_tree() {
+    let dir = two_branches("switch-bad-name");
+    let main = git(&dir, &["rev-parse", "main"]);
+    for args in [
+        &["switch", "-C", "bad..name", "side"][..],
+        &["checkout", "-B", "bad..name", "side"],
+        &["checkout", "-B", "main/x", "side"],
+        &["switch", "-c", "main", "side"],
+    ] {
+        git_fails(&dir, args, 1);
+        assert_eq!(
+            fs::read_to_string(dir.join("f")).unwrap(),
+            "2\n",
+            "{args:?}"
+        );
+        assert_eq!(git(&dir, &["status", "--porcelain"]), "", "{args:?}");
+        assert_eq!(git(&dir, &["rev-parse", "HEAD"]), main, "{args:?}");
+    }
+    git(&dir, &["checkout", "-B", "main", "side"]);
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "1\n");
+    assert_eq!(
+        git(&dir, &["rev-parse", "main"]),
+        git(&dir, &["rev-parse", "side"])
+    );
+}
+#[test]
+fn checkout_detaches_at_a_commit() {
+    let dir = two_branches("checkout-detach");
+    let side = git(&dir, &["rev-parse", "side"]);
+    git(&dir, &["checkout", "-q", side.trim()]);
+    assert_eq!(git(&dir, &["rev-parse", "HEAD"]), side);
+    assert!(git_fails(&dir, &["symbolic-ref", "HEAD"], 1).contains("not a symbolic ref"));
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "1\n");
+}
+#[test]
+fn restore_and_checkout_paths_take_the_index_or_a_source() {
+    let dir = two_branches("restore-paths");
+    fs::write(dir.join("f"), "local\n").unwrap();
+    git(&dir, &["restore", "f"]);
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "2\n");
+    fs::write(dir.join("f"), "staged\n").unwrap();
+    git(&dir, &["add", "f"]);
+    git(&dir, &["restore", "--staged", "f"]);
+    assert_eq!(git(&dir, &["status", "--porcelain"]), " M f\n");
+    git(&dir, &["restore", "--source=side", "f"]);
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "1\n");
+    git(&dir, &["checkout", "--", "f"]);
+    assert_eq!(fs::read_to_string(dir.join("f")).unwrap(), "2\n");
+    git(&dir, &["checkout", "side", "--", "f"]);
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "M  f\n");
+}
@@ -0,0 +1,120 @@
+mod common;
+use common::{commit_files, fresh_repo, git, git_fails, run_with_input};
+#[test]
+fn tag_creates_updates_and_deletes_tags() {
+    let dir = fresh_repo("tag-lifecycle");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    let second = commit_files(&dir, &[("f", "2\n")], "second");
+    git(&dir, &["tag", "light", &first]);
+    assert_eq!(git(&dir, &["rev-parse", "light"]), format!("{first}\n"));
+
+    git(&dir, &["tag", "-a", "v1", "-m", "release"]);
+    let tag = git(&dir, &["rev-parse", "v1"]);
+    assert_eq!(git(&dir, &["cat-file", "-t", tag.trim()]), "tag\n");
+    assert_eq!(
+        git(&dir, &["cat-file", "-p", tag.trim()]),
+        format!(
+            "object {second}\ntype commit\ntag v1\n\
+             tagger A <a@example.com> 1700000000 +0000\n\nrelease\n"
+        )
+    );
+    assert_eq!(
+        git(&dir, &["rev-parse", "v1^{commit}"]),
+        format!("{second}\n")
+    );
+    assert_eq!(git(&dir, &["tag"]), "light\nv1\n");
+
+    let stderr = git_fails(&dir, &["tag", "light"], 1);
+    assert!(stderr.contains("tag 'light' already exists"));
+    assert_eq!(
+        git(&dir, &["tag", "-f", "light"]),
+        format!("Updated tag 'light' (was {})\n", &first[..7])
+    );
+    assert_eq!(git(&dir, &["rev-parse", "light"]), format!("{second}\n"));
+}
+#[test]
+fn tag_delete_reports_each_tag_and_fails_on_missing_ones() {
+    let dir = fresh_repo("tag-delete");
+    let first = commit_files(&dir, &[("f", "1\n")], "first");
+    git(&dir, &["tag", "a"]);
+    git(&dir, &["tag", "b"]);
+    let output = common::run(&dir, &["tag", "-d", "a", "missing", "b"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "Deleted tag 'a' (was {short})\nDeleted tag 'b' (was {short})\n",
+            short = &first[..7]
+        )
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: tag 'missing' not found.\n"
+    );
+    assert_eq!(git(&dir, &["tag"]), "");
+}
+/// A tag for `object` of `kind`, with `message` appended as is.
+fn tag_input(object: &str, kind: &str, message: &[u8]) -> Vec<u8> {
+    let mut input = format!(
+        "object {object}\ntype {kind}\ntag v1\n\
+         tagger A <a@example.com> 1700000000 +0000\n\n"
+    )
+    .into_bytes();
+    input.extend_from_slice(message);
+    input
+}
+#[test]
+fn mktag_writes_tags_whose_messages_are_not_utf8() {
+    let dir = fresh_repo("mktag-bytes");
+    let commit = commit_files(&dir, &[("f", "1\n")], "first");
+    let input = tag_input(&commit, "commit", b"caf\xe9 in latin-1\n");
+    let output = run_with_input(&dir, &["mktag"], &input);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let tag = String::from_utf8(output.stdout).unwrap();
+    let contents = common::run(&dir, &["cat-file", "tag", tag.trim()]);
+    assert_eq!(contents.stdout, input);
+    let hashed = run_with_input(&dir, &["hash-object", "-t", "tag", "--stdin"], &input);
+    assert_eq!(hashed.stdout, tag.as_bytes());
+}
+#[test]
+fn mktag_rejects_tags_that_do_not_pass_fsck() {
+    let dir = fresh_repo("mktag-fsck");
+    let commit = commit_files(&dir, &[("f", "1\n")], "first");
+    let tree = git(&dir, &["write-tree"]);
+    let cases: [(Vec<u8>, &str); 4] = [
+        (
+            b"object 1234\ntype commit\ntag v1\n\n".to_vec(),
+            "error: tag input does not pass fsck: badObjectSha1: \
+             invalid 'object' line format - bad sha1\n",
+        ),
+        (
+            tag_input(&commit, "bogus", b""),
+            "error: tag input does not pass fsck: badType: invalid 'type' value\n",
+        ),
+        (
+            format!("object {commit}\ntype commit\ntag v1\n\nno tagger\n").into_bytes(),
+            "error: tag input does not pass fsck: missingTaggerEntry: \
+             invalid format - expected 'tagger' line\n",
+        ),
+        (
+            format!("object {commit}\ntype commit\ntag v1\ntagger A <a@example.com> 1 +0000")
+                .into_bytes(),
+            "error: tag input does not pass fsck: unterminatedHeader: unterminated header\n",
+        ),
+    ];
+    for (input, error) in cases {
+        let output = run_with_input(&dir, &["mktag"], &input);
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.starts_with(error), "{stderr}");
+    }
+    let output = run_with_input(&dir, &["mktag"], &tag_input(tree.trim(), "commit", b""));
+    assert!(String::from_utf8(output.stderr).unwrap().contains(&format!(
+        "object '{}' tagged as 'commit', but is a 'tree' type",
+        tree.trim()
+    )));
+}
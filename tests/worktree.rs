@@ -0,0 +1,32 @@
+mod common;
+use common::{commit, commit_files, fresh_repo, git};
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::Path;
+#[test]
+fn switching_restores_executable_bits_and_symlinks() {
+    let dir = fresh_repo("worktree-modes");
+    commit_files(&dir, &[("f", "plain\n")], "first");
+    git(&dir, &["branch", "plain"]);
+    fs::write(dir.join("run.sh"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    symlink("f", dir.join("link")).unwrap();
+    git(&dir, &["add", "run.sh", "link"]);
+    commit(&dir, "modes");
+    let tree = git(&dir, &["ls-tree", "HEAD"]);
+    assert!(
+        tree.contains("100755 blob") && tree.contains("120000 blob"),
+        "{tree}"
+    );
+    git(&dir, &["switch", "-q", "plain"]);
+    assert!(fs::symlink_metadata(dir.join("link")).is_err());
+    assert!(!dir.join("run.sh").exists());
+    git(&dir, &["switch", "-q", "main"]);
+    let mode = fs::metadata(dir.join("run.sh"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o111, 0o111);
+    assert_eq!(fs::read_link(dir.join("link")).unwrap(), Path::new("f"));
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+}